@@ -24,6 +24,8 @@ pub fn chores_from_rows(
             chore_spontaneous_work_enable: row.get("chore_spontaneous_work_enable").unwrap(),
             chore_created_ts: row.get("chore_created_ts").unwrap(),
             chore_archived_ts: row.get("chore_archived_ts").unwrap(),
+            chore_max_workers: row.get("chore_max_workers").unwrap(),
+            chore_token_budget: row.get("chore_token_budget").unwrap(),
         });
     }
     chores
@@ -46,7 +48,7 @@ pub fn chore_events_from_rows(
     events
 }
 
-fn chore_set_lowlevel(
+pub fn chore_set_lowlevel(
     tx: &rusqlite::Transaction,
     chore: &Chore,
 ) -> Result<usize, String> {
@@ -55,7 +57,9 @@ fn chore_set_lowlevel(
             chore_title = ?2,
             chore_spontaneous_work_enable = ?3,
             chore_created_ts = ?4,
-            chore_archived_ts = ?5
+            chore_archived_ts = ?5,
+            chore_max_workers = ?6,
+            chore_token_budget = ?7
         WHERE chore_id = ?1",
         params![
             chore.chore_id,
@@ -63,6 +67,8 @@ fn chore_set_lowlevel(
             chore.chore_spontaneous_work_enable,
             chore.chore_created_ts,
             chore.chore_archived_ts,
+            chore.chore_max_workers,
+            chore.chore_token_budget,
         ],
     ).map_err(|e| e.to_string())?;
     if updated_rows == 0 {
@@ -72,14 +78,18 @@ fn chore_set_lowlevel(
                 chore_title,
                 chore_spontaneous_work_enable,
                 chore_created_ts,
-                chore_archived_ts
-            ) VALUES (?1, ?2, ?3, ?4, ?5)",
+                chore_archived_ts,
+                chore_max_workers,
+                chore_token_budget
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 chore.chore_id,
                 chore.chore_title,
                 chore.chore_spontaneous_work_enable,
                 chore.chore_created_ts,
                 chore.chore_archived_ts,
+                chore.chore_max_workers,
+                chore.chore_token_budget,
             ],
         ).map_err(|e| e.to_string())
     } else {
@@ -87,7 +97,7 @@ fn chore_set_lowlevel(
     }
 }
 
-fn chore_event_set_lowlevel(
+pub fn chore_event_set_lowlevel(
     tx: &rusqlite::Transaction,
     cevent: &ChoreEvent,
 ) -> Result<usize, String> {
@@ -447,6 +457,8 @@ fn _chore_get_with_quicksearch(
                 chore_spontaneous_work_enable: row.get("chore_spontaneous_work_enable").unwrap(),
                 chore_created_ts: row.get("chore_created_ts").unwrap(),
                 chore_archived_ts: row.get("chore_archived_ts").unwrap(),
+                chore_max_workers: row.get("chore_max_workers").unwrap(),
+                chore_token_budget: row.get("chore_token_budget").unwrap(),
             };
             chores.push(chore);
             chore_map.insert(chore_id.clone(), true);
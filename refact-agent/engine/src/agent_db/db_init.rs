@@ -49,5 +49,6 @@ pub async fn chore_db_init(
         db.lock().lite.clone()
     };
     crate::agent_db::db_schema_20241102::create_tables_20241102(&*lite_arc.lock(), reset_memory).expect("Failed to create tables");
+    crate::agent_db::db_schema_20250810::add_chore_limits_20250810(&*lite_arc.lock()).expect("Failed to add chore limits");
     db
 }
@@ -0,0 +1,21 @@
+use rusqlite::Connection;
+
+
+// Per-chore limits for autonomous work, 0 means no limit
+pub fn add_chore_limits_20250810(conn: &Connection) -> Result<(), String> {
+    for (column, definition) in [
+        ("chore_max_workers", "INT NOT NULL DEFAULT 0"),
+        ("chore_token_budget", "INT NOT NULL DEFAULT 0"),
+    ] {
+        let exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('chores') WHERE name = ?1",
+            [column],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        if exists == 0 {
+            conn.execute(&format!("ALTER TABLE chores ADD COLUMN {} {}", column, definition), [])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
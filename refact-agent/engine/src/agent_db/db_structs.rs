@@ -11,6 +11,8 @@ pub struct Chore {
     pub chore_spontaneous_work_enable: bool,
     pub chore_created_ts: f64,
     pub chore_archived_ts: f64,
+    pub chore_max_workers: i64,     // cthreads worked on at the same time, 0 means no limit
+    pub chore_token_budget: i64,    // prompt+completion tokens for all cthreads, 0 means no limit
 }
 
 #[derive(Serialize, Deserialize, Default)]
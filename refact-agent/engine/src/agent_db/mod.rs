@@ -11,6 +11,7 @@ pub mod db_cmessage;
 pub mod db_cthread;
 pub mod db_init;
 pub mod db_schema_20241102;
+pub mod db_schema_20250810;
 pub mod db_structs;
 
 pub fn chore_pubub_push(
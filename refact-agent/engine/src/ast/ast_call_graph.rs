@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+
+use crate::ast::ast_structs::{AstDB, AstDefinition};
+use crate::ast::ast_db::{definition_by_official_path, definitions, derived_classes, doc_defs, doc_usages, usages};
+use crate::global_context::GlobalContext;
+
+// The graph is built from the same records as definition() and references() use:
+//   callers of X  -- u|X ⚡ caller records, see usages()
+//   callees of X  -- usages inside X's lines, see doc_usages()
+//   derived classes -- classes|X ⚡ child records, see derived_classes()
+
+const MAX_NODES: usize = 500;
+const USAGES_PER_SYMBOL: usize = 200;
+const MAX_TEXT_EDGES: usize = 200;
+
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CallGraphDirection {
+    Callers,
+    Callees,
+    #[default]
+    Both,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CallGraphNode {
+    pub symbol: String,   // official path without the hex prefix
+    pub file_name: String,
+    pub line1: usize,
+    pub line2: usize,
+    pub depth: usize,     // 0 for the symbols the graph started from
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CallGraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: String,     // "uses" or "derives"
+    pub line: usize,      // 1-based, in the file of `from`
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct CallGraph {
+    pub roots: Vec<String>,
+    pub nodes: IndexMap<String, CallGraphNode>,  // key is the full official path
+    pub edges: Vec<CallGraphEdge>,
+    pub files: Vec<String>,
+    pub truncated: bool,
+}
+
+impl CallGraph {
+    fn add_root(&mut self, def: &AstDefinition) {
+        self.add_node(def, 0);
+        self.roots.push(def.path());
+    }
+
+    /// Returns true if the node wasn't there before
+    fn add_node(&mut self, def: &AstDefinition, depth: usize) -> bool {
+        if let Some(node) = self.nodes.get_mut(&def.path()) {
+            node.depth = node.depth.min(depth);
+            return false;
+        }
+        if self.nodes.len() >= MAX_NODES {
+            self.truncated = true;
+            return false;
+        }
+        self.nodes.insert(def.path(), CallGraphNode {
+            symbol: def.path_drop0(),
+            file_name: def.cpath.clone(),
+            line1: def.full_line1(),
+            line2: def.full_line2(),
+            depth,
+        });
+        true
+    }
+
+    fn add_edge(&mut self, from: String, to: String, kind: &str, line: usize) {
+        let edge = CallGraphEdge { from, to, kind: kind.to_string(), line };
+        if self.nodes.contains_key(&edge.from) && self.nodes.contains_key(&edge.to) && !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        let files: HashSet<String> = self.nodes.values().map(|n| n.file_name.clone()).collect();
+        self.files = files.into_iter().collect();
+        self.files.sort();
+        self
+    }
+
+    fn symbol_of(&self, path: &str) -> String {
+        self.nodes.get(path).map(|n| n.symbol.clone()).unwrap_or(path.to_string())
+    }
+
+    pub fn to_dot(&self) -> String {
+        fn quoted(s: &str) -> String {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        let mut dot = "digraph call_graph {\n  rankdir=LR;\n  node [shape=box];\n".to_string();
+        for (path, node) in self.nodes.iter() {
+            let file_name = std::path::Path::new(&node.file_name).file_name()
+                .map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+            let label = format!("{}\\n{}:{}", node.symbol.replace('"', "\\\""), file_name, node.line1);
+            let style = if node.depth == 0 { ", style=bold" } else { "" };
+            dot.push_str(&format!("  {} [label=\"{}\"{}];\n", quoted(path), label, style));
+        }
+        for edge in self.edges.iter() {
+            let style = if edge.kind == "derives" { ", style=dashed" } else { "" };
+            dot.push_str(&format!("  {} -> {} [label=\"{}\"{}];\n", quoted(&edge.from), quoted(&edge.to), edge.line, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} symbols in {} files", self.nodes.len(), self.files.len());
+        if self.truncated {
+            text.push_str(&format!(", stopped at {} symbols", MAX_NODES));
+        }
+        text.push_str("\n\n");
+        if !self.edges.is_empty() {
+            text.push_str("Edges, user -> used:\n");
+            for edge in self.edges.iter().take(MAX_TEXT_EDGES) {
+                let file_name = self.nodes.get(&edge.from).map(|n| n.file_name.as_str()).unwrap_or_default();
+                let verb = if edge.kind == "derives" { " derives from " } else { " -> " };
+                text.push_str(&format!("{}{}{} at {}:{}\n", self.symbol_of(&edge.from), verb, self.symbol_of(&edge.to), file_name, edge.line));
+            }
+            if self.edges.len() > MAX_TEXT_EDGES {
+                text.push_str(&format!("...and {} more\n", self.edges.len() - MAX_TEXT_EDGES));
+            }
+            text.push('\n');
+        }
+        text.push_str("Files:\n");
+        for file_name in self.files.iter() {
+            text.push_str(&format!("{}\n", file_name));
+        }
+        text
+    }
+}
+
+async fn callees(
+    ast_index: Arc<AMutex<AstDB>>,
+    def: &AstDefinition,
+    doc_usages_cache: &mut HashMap<String, Vec<(usize, String)>>,
+) -> Vec<(Arc<AstDefinition>, usize)> {
+    if !doc_usages_cache.contains_key(&def.cpath) {
+        let doc_usages = doc_usages(ast_index.clone(), &def.cpath).await;
+        doc_usages_cache.insert(def.cpath.clone(), doc_usages);
+    }
+    let def_path = def.path();
+    let nested_prefix = format!("{}::", def_path);
+    let mut result = Vec::new();
+    for (uline, resolved) in doc_usages_cache[&def.cpath].iter() {
+        // uline starts from 0
+        if *uline + 1 < def.full_line1() || *uline + 1 > def.full_line2() {
+            continue;
+        }
+        if *resolved == def_path || resolved.starts_with(&nested_prefix) {
+            continue;  // local variables and nested functions are not interesting
+        }
+        if let Some(callee) = definition_by_official_path(ast_index.clone(), resolved).await {
+            result.push((callee, *uline));
+        }
+    }
+    result
+}
+
+async fn expand(
+    graph: &mut CallGraph,
+    ast_index: Arc<AMutex<AstDB>>,
+    start: Vec<(Arc<AstDefinition>, usize)>,
+    max_depth: usize,
+    direction: CallGraphDirection,
+) {
+    let mut queue: VecDeque<(Arc<AstDefinition>, usize, bool)> = VecDeque::new();
+    for (def, depth) in start {
+        if direction != CallGraphDirection::Callees {
+            queue.push_back((def.clone(), depth, true));
+        }
+        if direction != CallGraphDirection::Callers {
+            queue.push_back((def, depth, false));
+        }
+    }
+    let mut expanded: HashSet<(String, bool)> = HashSet::new();
+    let mut doc_usages_cache = HashMap::new();
+    while let Some((def, depth, towards_callers)) = queue.pop_front() {
+        if depth >= max_depth || graph.truncated || !expanded.insert((def.path(), towards_callers)) {
+            continue;
+        }
+        let neighbours = if towards_callers {
+            usages(ast_index.clone(), def.path(), USAGES_PER_SYMBOL).await
+        } else {
+            callees(ast_index.clone(), &def, &mut doc_usages_cache).await
+        };
+        for (other, uline) in neighbours {
+            graph.add_node(&other, depth + 1);
+            if towards_callers {
+                graph.add_edge(other.path(), def.path(), "uses", uline + 1);
+            } else {
+                graph.add_edge(def.path(), other.path(), "uses", uline + 1);
+            }
+            if graph.nodes.contains_key(&other.path()) {
+                queue.push_back((other, depth + 1, towards_callers));
+            }
+        }
+    }
+}
+
+pub async fn symbol_call_graph(
+    ast_index: Arc<AMutex<AstDB>>,
+    defs: Vec<Arc<AstDefinition>>,
+    depth: usize,
+    direction: CallGraphDirection,
+) -> CallGraph {
+    let mut graph = CallGraph::default();
+    for def in defs.iter() {
+        graph.add_root(def);
+    }
+    let start = defs.into_iter().map(|d| (d, 0)).collect();
+    expand(&mut graph, ast_index, start, depth, direction).await;
+    graph.finish()
+}
+
+/// What might break if the file changes: the symbols in it, everything that uses them up to `depth`,
+/// and all classes derived from the classes in the file, whatever the depth
+pub async fn file_impact(
+    ast_index: Arc<AMutex<AstDB>>,
+    cpath: &String,
+    depth: usize,
+) -> CallGraph {
+    let mut graph = CallGraph::default();
+    let mut start: Vec<(Arc<AstDefinition>, usize)> = Vec::new();
+    for def in doc_defs(ast_index.clone(), cpath).await {
+        graph.add_root(&def);
+        start.push((def, 0));
+    }
+    let mut i = 0;
+    while i < start.len() && !graph.truncated {
+        let (klass, klass_depth) = start[i].clone();
+        i += 1;
+        if klass.this_is_a_class.is_empty() {
+            continue;
+        }
+        for child_path in derived_classes(ast_index.clone(), &klass.this_is_a_class).await {
+            let Some(child) = definition_by_official_path(ast_index.clone(), &child_path).await else { continue };
+            let is_new = graph.add_node(&child, klass_depth);
+            graph.add_edge(child.path(), klass.path(), "derives", child.full_line1());
+            if is_new {
+                start.push((child, klass_depth));
+            }
+        }
+    }
+    expand(&mut graph, ast_index, start, depth, CallGraphDirection::Callers).await;
+    graph.finish()
+}
+
+/// Either `symbol` or `file_name` should be set, for a file it's always the callers direction
+pub async fn call_graph_for_query(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    symbol: &str,
+    file_name: &str,
+    depth: usize,
+    direction: CallGraphDirection,
+) -> Result<CallGraph, String> {
+    let ast_service = gcx.read().await.ast_service.clone()
+        .ok_or("ast module is turned off".to_string())?;
+    let ast_index = ast_service.lock().await.ast_index.clone();
+    crate::ast::ast_indexer_thread::ast_indexer_block_until_finished(ast_service.clone(), 20_000, true).await;
+    if !file_name.is_empty() {
+        let candidates = crate::files_correction::correct_to_nearest_filename(gcx.clone(), &file_name.to_string(), false, 1).await;
+        if candidates.len() != 1 {
+            return Err(format!("file {:?} not found or ambiguous, candidates {:?}", file_name, candidates));
+        }
+        return Ok(file_impact(ast_index, &candidates[0], depth).await);
+    }
+    let symbol = symbol.replace('.', "::");
+    if symbol.is_empty() {
+        return Err("either symbol or file name is required".to_string());
+    }
+    let defs = definitions(ast_index.clone(), &symbol).await;
+    if defs.is_empty() {
+        return Err(format!("no definitions with name `{}` found", symbol));
+    }
+    Ok(symbol_call_graph(ast_index, defs, depth, direction).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ast_db::{ast_index_init, connect_usages, connect_usages_look_if_relink_needed, doc_add, flush_sled_batch};
+    use crate::ast::ast_structs::AstErrorStats;
+
+    #[tokio::test]
+    async fn test_call_graph_py() {
+        let library_path = "src/ast/alt_testsuite/py_goat_library.py".to_string();
+        let main_path = "src/ast/alt_testsuite/py_goat_main.py".to_string();
+        let ast_index = ast_index_init("".to_string(), 10, false).await;
+        let mut errstats = AstErrorStats::default();
+        for path in [&library_path, &main_path] {
+            let text = std::fs::read_to_string(path).unwrap();
+            doc_add(ast_index.clone(), path, &text, &mut errstats).await.unwrap();
+        }
+        let mut ucx = connect_usages_look_if_relink_needed(ast_index.clone()).await;
+        while connect_usages(ast_index.clone(), &mut ucx).await {}
+        flush_sled_batch(ast_index.clone(), 0).await;
+
+        let defs = definitions(ast_index.clone(), "Animal::age").await;
+        let graph = symbol_call_graph(ast_index.clone(), defs, 2, CallGraphDirection::Callers).await;
+        assert_eq!(graph.roots.len(), 1);
+        assert!(graph.edges.iter().all(|e| e.kind == "uses"));
+        assert!(graph.edges.iter().any(|e| e.to == graph.roots[0]));
+        assert!(graph.to_dot().starts_with("digraph call_graph {"));
+
+        let impact = file_impact(ast_index.clone(), &library_path, 3).await;
+        assert!(impact.files.contains(&main_path), "{}", impact.to_text());
+        assert!(impact.edges.iter().any(|e| e.kind == "derives"), "{}", impact.to_text());
+    }
+}
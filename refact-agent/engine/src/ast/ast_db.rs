@@ -9,7 +9,7 @@ use sled::Db;
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::ast::ast_structs::{AstDB, AstDefinition, AstCounters, AstErrorStats};
+use crate::ast::ast_structs::{AstDB, AstDefinition, AstCounters, AstErrorStats, AstUsage};
 use crate::ast::ast_parse_anything::{parse_anything_and_add_file_path, filesystem_path_to_double_colon_path};
 use crate::fuzzy_search::fuzzy_search;
 
@@ -46,6 +46,11 @@ use crate::fuzzy_search::fuzzy_search;
 //           ^^^^^^^^^^^ derived from                               ^^^^^^^^^^ serialized value, klass
 //                         ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ full path of klass, makes those keys additive
 //
+// Files that have unresolved usages mentioning a class, to re-link only them when the class hierarchy changes:
+//   class-users|cpp🔎Goat ⚡ alt_testsuite::cpp_goat_main 👉 src/ast/alt_testsuite/cpp_goat_main.cpp
+//               ^^^^^^^^^ klass from targets_for_guesswork
+//                           ^^^^^^^^^^^^^^^^^^^^^^^^^^^^ file_global_path    ^^^^^^^^^^^^^^^^^^^^^^^^^ cpath in value
+//
 // Per doc records:
 //   doc-cpath|alt_testsuite::cpp_goat_library 👉 src/ast/alt_testsuite/cpp_goat_library.h
 //             ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ file_global_path (means path up to the global scope of the file)
@@ -54,6 +59,7 @@ use crate::fuzzy_search::fuzzy_search;
 // Other keys:
 //   counters|defs: 42
 //   counters|usages: 100
+//   schema|version: 2
 //
 //
 // Read tests below, the show what this index can do!
@@ -70,6 +76,8 @@ macro_rules! debug_print {
 }
 
 const CACHE_CAPACITY_BYTES: u64 = 256 * 1024 * 1024;  // 256M cache
+const SCHEMA_VERSION_KEY: &[u8] = b"schema|version";
+const SCHEMA_VERSION: i32 = 2;  // 2: class-users| keys
 
 pub async fn ast_index_init(ast_permanent: String, ast_max_files: usize, want_perf_report: bool) -> Arc<AMutex<AstDB>>
 {
@@ -91,6 +99,7 @@ pub async fn ast_index_init(ast_permanent: String, ast_max_files: usize, want_pe
         move || config.open().unwrap()
     ).await.unwrap());
     db.clear().unwrap();
+    db.insert(SCHEMA_VERSION_KEY, serde_cbor::to_vec(&SCHEMA_VERSION).unwrap()).unwrap();
     tracing::info!("/starting AST");
     let ast_index = AstDB {
         sleddb: db,
@@ -181,6 +190,7 @@ pub async fn doc_add(
     let mut added_defs: i32 = 0;
     let mut added_usages: i32 = 0;
     let mut unresolved_usages: i32 = 0;
+    let mut class_users_keys: HashSet<String> = HashSet::new();
     for definition in defs.iter() {
         assert!(definition.cpath == *cpath);
         let serialized = serde_cbor::to_vec(&definition).unwrap();
@@ -205,6 +215,9 @@ pub async fn doc_add(
                 continue;
             } else {
                 unresolved_usages += 1;
+                for klass in _classes_in_guesswork(usage) {
+                    class_users_keys.insert(format!("class-users|{} ⚡ {}", klass, file_global_path.join("::")));
+                }
             }
             added_usages += 1;
         }
@@ -215,6 +228,9 @@ pub async fn doc_add(
         }
         added_defs += 1;
     }
+    for class_users_key in class_users_keys {
+        batch.insert(class_users_key.as_bytes(), cpath.as_bytes());
+    }
     if unresolved_usages > 0 {
         let resolve_todo_key = format!("resolve-todo|{}", file_global_path.join("::"));
         batch.insert(resolve_todo_key.as_bytes(), cpath.as_bytes());
@@ -259,6 +275,11 @@ pub async fn doc_remove(ast_index: Arc<AMutex<AstDB>>, cpath: &String)
                     batch.remove(homeless_key.as_bytes());
                     debug_print!("        homeless {}", homeless_key);
                     continue;
+                } else {
+                    for klass in _classes_in_guesswork(usage) {
+                        let class_users_key = format!("class-users|{} ⚡ {}", klass, file_global_path.join("::"));
+                        batch.remove(class_users_key.as_bytes());
+                    }
                 }
                 deleted_usages += 1;
             }
@@ -341,9 +362,18 @@ pub struct ConnectUsageContext {
     pub usages_connected: usize,
     pub usages_not_found: usize,
     pub usages_ambiguous: usize,
+    pub classes_changed: usize,
+    pub files_requeued: usize,  // because classes they use have changed
+    pub files_relinked: usize,
     pub t0: Instant,
 }
 
+impl ConnectUsageContext {
+    pub fn usages_relinked(&self) -> usize {
+        self.usages_homeless + self.usages_connected + self.usages_not_found + self.usages_ambiguous
+    }
+}
+
 pub async fn connect_usages(ast_index: Arc<AMutex<AstDB>>, ucx: &mut ConnectUsageContext) -> bool
 {
     let db = ast_index.lock().await.sleddb.clone();
@@ -369,6 +399,7 @@ pub async fn connect_usages(ast_index: Arc<AMutex<AstDB>>, ucx: &mut ConnectUsag
             let tmp = _connect_usages_helper(&db, ucx, &def, &mut batch).await;
             resolved_usages.extend(tmp);
         }
+        ucx.files_relinked += 1;
         batch.insert(
             format!("doc-resolved|{}", global_file_path).as_bytes(),
             serde_cbor::to_vec(&resolved_usages).unwrap().as_slice(),
@@ -379,7 +410,7 @@ pub async fn connect_usages(ast_index: Arc<AMutex<AstDB>>, ucx: &mut ConnectUsag
     false
 }
 
-pub async fn connect_usages_look_if_full_reset_needed(ast_index: Arc<AMutex<AstDB>>) -> ConnectUsageContext
+pub async fn connect_usages_look_if_relink_needed(ast_index: Arc<AMutex<AstDB>>) -> ConnectUsageContext
 {
     flush_sled_batch(ast_index.clone(), 0).await;
     let db = ast_index.lock().await.sleddb.clone();
@@ -390,35 +421,57 @@ pub async fn connect_usages_look_if_full_reset_needed(ast_index: Arc<AMutex<AstD
     };
 
     let new_derived_from_map = _derived_from(&db).await;
+    let changed_classes = _changed_classes(&existing_hierarchy, &new_derived_from_map);
     let mut batch = sled::Batch::default();
+    let mut requeued: HashSet<String> = HashSet::new();
 
-    if existing_hierarchy.is_empty() {
-        let serialized_hierarchy = serde_cbor::to_vec(&new_derived_from_map).unwrap();
-        batch.insert(class_hierarchy_key, serialized_hierarchy.as_slice());
-        // first run, do nothing because all the definitions are already in the todo list
+    let schema_version = db.get(SCHEMA_VERSION_KEY).unwrap().map(|v| serde_cbor::from_slice::<i32>(&v).unwrap_or(0)).unwrap_or(0);
+    if schema_version < SCHEMA_VERSION {
+        // The db was written by an older version that had no class-users| keys, the changed classes below
+        // would find no files to re-link. Write those keys for every doc and re-link everything, once.
+        let mut iter = db.scan_prefix("doc-cpath|");
+        while let Some(Ok((key, value))) = iter.next() {
+            let key_string = String::from_utf8(key.to_vec()).unwrap();
+            let file_global_path = key_string.strip_prefix("doc-cpath|").unwrap().to_string();
+            let cpath = String::from_utf8(value.to_vec()).unwrap();
+            for def in doc_defs(ast_index.clone(), &cpath).await {
+                for usage in def.usages.iter().filter(|u| u.resolved_as.is_empty()) {
+                    for klass in _classes_in_guesswork(usage) {
+                        batch.insert(format!("class-users|{} ⚡ {}", klass, file_global_path).as_bytes(), cpath.as_bytes());
+                    }
+                }
+            }
+            batch.insert(format!("resolve-todo|{}", file_global_path).as_bytes(), cpath.as_bytes());
+            requeued.insert(file_global_path);
+        }
+        batch.insert(SCHEMA_VERSION_KEY, serde_cbor::to_vec(&SCHEMA_VERSION).unwrap());
+        tracing::info!("AST db schema version {} => {}, {} files need their usages reconnected", schema_version, SCHEMA_VERSION, requeued.len());
+    }
 
-    } else if new_derived_from_map != existing_hierarchy {
-        // XXX first branch is not covered by tests (simple enough to work and not break?)
-        tracing::info!(" * * * class hierarchy changed {} classes => {} classes, all usages need to be reconnected * * *", existing_hierarchy.len(), new_derived_from_map.len());
+    if !changed_classes.is_empty() {
         let serialized_hierarchy = serde_cbor::to_vec(&new_derived_from_map).unwrap();
         batch.insert(class_hierarchy_key, serialized_hierarchy.as_slice());
 
-        let mut iter = db.scan_prefix("doc-cpath|");
-        let mut cnt = 0;
-        while let Some(Ok((key, value))) = iter.next() {
-            let key_string = String::from_utf8(key.to_vec()).unwrap();
-            if let Some(file_global_path) = key_string.strip_prefix("doc-cpath|") {
-                let cpath = String::from_utf8(value.to_vec()).unwrap();
-                let resolve_todo_key = format!("resolve-todo|{}", file_global_path);
-                batch.insert(resolve_todo_key.as_bytes(), cpath.as_bytes());
-                cnt += 1;
+        // Only the files that try to resolve something through a changed class need to be re-linked,
+        // on the first run those files are already in the todo list, adding them again changes nothing
+        for klass in changed_classes.iter() {
+            let mut iter = db.scan_prefix(format!("class-users|{} ⚡ ", klass));
+            while let Some(Ok((key, value))) = iter.next() {
+                let key_string = String::from_utf8(key.to_vec()).unwrap();
+                if let Some((_, file_global_path)) = key_string.split_once(" ⚡ ") {
+                    if requeued.insert(file_global_path.to_string()) {
+                        let resolve_todo_key = format!("resolve-todo|{}", file_global_path);
+                        batch.insert(resolve_todo_key.as_bytes(), value);
+                    }
+                }
             }
         }
-        tracing::info!("added {} items to resolve-todo", cnt);
+        tracing::info!("class hierarchy changed {} classes => {} classes, {} classes differ, {} files need their usages reconnected",
+            existing_hierarchy.len(), new_derived_from_map.len(), changed_classes.len(), requeued.len());
     }
 
     if let Err(e) = db.apply_batch(batch) {
-        tracing::error!("connect_usages_look_if_full_reset_needed() failed to apply batch: {:?}", e);
+        tracing::error!("connect_usages_look_if_relink_needed() failed to apply batch: {:?}", e);
     }
 
     ConnectUsageContext {
@@ -428,10 +481,40 @@ pub async fn connect_usages_look_if_full_reset_needed(ast_index: Arc<AMutex<AstD
         usages_connected: 0,
         usages_not_found: 0,
         usages_ambiguous: 0,
+        classes_changed: changed_classes.len(),
+        files_requeued: requeued.len(),
+        files_relinked: 0,
         t0: Instant::now(),
     }
 }
 
+fn _changed_classes(
+    old_map: &IndexMap<String, Vec<String>>,
+    new_map: &IndexMap<String, Vec<String>>,
+) -> Vec<String> {
+    // The maps are transitive, so a new base class somewhere up the tree changes every class derived from it
+    let mut changed: Vec<String> = new_map.iter()
+        .filter(|(klass, ancestors)| old_map.get(*klass) != Some(*ancestors))
+        .map(|(klass, _)| klass.clone())
+        .collect();
+    changed.extend(old_map.keys().filter(|klass| !new_map.contains_key(*klass)).cloned());
+    changed
+}
+
+fn _classes_in_guesswork(usage: &AstUsage) -> Vec<String> {
+    // ?::cpp🔎Goat::self_review => ["cpp🔎Goat"]
+    let mut classes = Vec::new();
+    for target in usage.targets_for_guesswork.iter().filter(|t| t.starts_with("?::")) {
+        for cap in MAGNIFYING_GLASS_RE.captures_iter(target) {
+            let klass = cap.get(0).unwrap().as_str().to_string();
+            if !classes.contains(&klass) {
+                classes.push(klass);
+            }
+        }
+    }
+    classes
+}
+
 lazy_static! {
     static ref MAGNIFYING_GLASS_RE: Regex = Regex::new(r"(\w+)🔎(\w+)").unwrap();
 }
@@ -470,6 +553,15 @@ async fn _connect_usages_helper(
     //   resolve-cleanup/official_path     -- value contains all the "u|RESOLVED ⚡ official_path" in a list
     //
     let official_path = definition.official_path.join("::");
+    let cleanup_key = format!("resolve-cleanup|{}", official_path);
+    // Links from the previous pass might not be true anymore, new ones are inserted into the same batch and win
+    if let Ok(Some(cleanup_value)) = db.get(cleanup_key.as_bytes()) {
+        if let Ok(old_ulinks) = serde_cbor::from_slice::<Vec<String>>(&cleanup_value) {
+            for ulink in old_ulinks {
+                batch.remove(ulink.as_bytes());
+            }
+        }
+    }
     let mut result = Vec::<(usize, String)>::new();
     let mut all_saved_ulinks = Vec::<String>::new();
     for (uindex, usage) in definition.usages.iter().enumerate() {
@@ -577,7 +669,6 @@ async fn _connect_usages_helper(
             break;  // the next thing from targets_for_guesswork is a worse query, keep this one and exit
         }
     } // for usages
-    let cleanup_key = format!("resolve-cleanup|{}", definition.official_path.join("::"));
     let cleanup_value = serde_cbor::to_vec(&all_saved_ulinks).unwrap();
     batch.insert(cleanup_key.as_bytes(), cleanup_value.as_slice());
     result
@@ -714,6 +805,33 @@ pub async fn definitions(ast_index: Arc<AMutex<AstDB>>, double_colon_path: &str)
     defs
 }
 
+pub async fn definition_by_official_path(ast_index: Arc<AMutex<AstDB>>, full_official_path: &str) -> Option<Arc<AstDefinition>>
+{
+    let db = ast_index.lock().await.sleddb.clone();
+    let d_key = format!("d|{}", full_official_path);
+    match db.get(d_key.as_bytes()) {
+        Ok(Some(d_value)) => serde_cbor::from_slice::<AstDefinition>(&d_value).ok().map(Arc::new),
+        _ => None,
+    }
+}
+
+pub async fn derived_classes(ast_index: Arc<AMutex<AstDB>>, klass: &str) -> Vec<String>
+{
+    // classes|cpp🔎Animal ⚡ alt_testsuite::cpp_goat_library::Goat 👉 "cpp🔎Goat"
+    // derived_classes("cpp🔎Animal") returns ["alt_testsuite::cpp_goat_library::Goat"], only direct children
+    let db = ast_index.lock().await.sleddb.clone();
+    let t_prefix = format!("classes|{} ⚡ ", klass);
+    let mut result = Vec::new();
+    let mut iter = db.scan_prefix(&t_prefix);
+    while let Some(Ok((key, _))) = iter.next() {
+        let key_string = String::from_utf8(key.to_vec()).unwrap();
+        if let Some(child_path) = key_string.strip_prefix(&t_prefix) {
+            result.push(child_path.trim().to_string());
+        }
+    }
+    result
+}
+
 #[allow(dead_code)]
 pub async fn type_hierarchy(ast_index: Arc<AMutex<AstDB>>, language: String, subtree_of: String) -> String
 {
@@ -897,7 +1015,7 @@ mod tests {
             println!("(E) {}:{} {}", error.err_cpath, error.err_line, error.err_message);
         }
 
-        let mut ucx: ConnectUsageContext = connect_usages_look_if_full_reset_needed(ast_index.clone()).await;
+        let mut ucx: ConnectUsageContext = connect_usages_look_if_relink_needed(ast_index.clone()).await;
         loop {
             let did_anything = connect_usages(ast_index.clone(), &mut ucx).await;
             if !did_anything {
@@ -949,6 +1067,39 @@ mod tests {
         println!("goat_usage:\n{}", goat_usage_str);
         assert!(goat_usage.len() == 1 || goat_usage.len() == 2);  // derived from generates usages (new style: py) or not (old style)
 
+        // Nothing changed, nothing to re-link
+        let ucx_again = connect_usages_look_if_relink_needed(ast_index.clone()).await;
+        assert_eq!(ucx_again.classes_changed, 0);
+        assert_eq!(ucx_again.files_requeued, 0);
+
+        // A db from before class-users| existed gets those keys back and is re-linked in full, once
+        {
+            let db = ast_index.lock().await.sleddb.clone();
+            let class_users_keys: Vec<_> = db.scan_prefix("class-users|").keys().filter_map(|k| k.ok()).collect();
+            assert!(!class_users_keys.is_empty());
+            for key in class_users_keys.iter() {
+                db.remove(key).unwrap();
+            }
+            db.remove(SCHEMA_VERSION_KEY).unwrap();
+            let mut ucx_upgrade = connect_usages_look_if_relink_needed(ast_index.clone()).await;
+            assert_eq!(ucx_upgrade.files_requeued, 2);
+            assert_eq!(db.scan_prefix("class-users|").count(), class_users_keys.len());
+            while connect_usages(ast_index.clone(), &mut ucx_upgrade).await {}
+            flush_sled_batch(ast_index.clone(), 0).await;
+            assert_eq!(usages(ast_index.clone(), animalage_def0.path(), 100).await.len(), 5);
+            assert_eq!(connect_usages_look_if_relink_needed(ast_index.clone()).await.files_requeued, 0);
+        }
+
+        // Re-adding the library with the same hierarchy re-links the library only, and links are replaced, not duplicated
+        doc_remove(ast_index.clone(), &library_file_path.to_string()).await;
+        doc_add(ast_index.clone(), &library_file_path.to_string(), &library_text, &mut AstErrorStats::default()).await.unwrap();
+        let mut ucx_readd = connect_usages_look_if_relink_needed(ast_index.clone()).await;
+        while connect_usages(ast_index.clone(), &mut ucx_readd).await {}
+        flush_sled_batch(ast_index.clone(), 0).await;
+        assert_eq!(ucx_readd.classes_changed, 0);
+        assert!(ucx_readd.files_relinked <= 1, "files_relinked={}", ucx_readd.files_relinked);
+        assert_eq!(usages(ast_index.clone(), animalage_def0.path(), 100).await.len(), 5);
+
         doc_remove(ast_index.clone(), &library_file_path.to_string()).await;
         doc_remove(ast_index.clone(), &main_file_path.to_string()).await;
         flush_sled_batch(ast_index.clone(), 0).await;
@@ -958,7 +1109,7 @@ mod tests {
         assert_eq!(counters.counter_defs, 0);
         assert_eq!(counters.counter_usages, 0);
         assert_eq!(counters.counter_docs, 0);
-        assert_eq!(dblen, 3 + 1); // 3 counters and 1 class hierarchy
+        assert_eq!(dblen, 3 + 1 + 1); // 3 counters, 1 class hierarchy and the schema version
 
         let db = ast_index.lock().await.sleddb.clone();
         drop(ast_index);
@@ -970,6 +1121,24 @@ mod tests {
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     }
 
+    #[test]
+    fn test_changed_classes() {
+        let old_map: IndexMap<String, Vec<String>> = IndexMap::from([
+            ("cpp🔎Goat".to_string(), vec!["cpp🔎Animal".to_string()]),
+            ("cpp🔎Animal".to_string(), vec![]),
+            ("cpp🔎Sheep".to_string(), vec!["cpp🔎Animal".to_string()]),
+        ]);
+        let new_map: IndexMap<String, Vec<String>> = IndexMap::from([
+            ("cpp🔎Goat".to_string(), vec!["cpp🔎Animal".to_string(), "cpp🔎Creature".to_string()]),
+            ("cpp🔎Animal".to_string(), vec!["cpp🔎Creature".to_string()]),
+            ("cpp🔎Creature".to_string(), vec![]),
+        ]);
+        let mut changed = _changed_classes(&old_map, &new_map);
+        changed.sort();
+        assert_eq!(changed, vec!["cpp🔎Animal", "cpp🔎Creature", "cpp🔎Goat", "cpp🔎Sheep"]);
+        assert!(_changed_classes(&new_map, &new_map).is_empty());
+    }
+
     #[tokio::test]
     async fn test_ast_db_cpp() {
         init_tracing();
@@ -9,7 +9,7 @@ use crate::files_in_workspace::Document;
 use crate::global_context::GlobalContext;
 
 use crate::ast::ast_structs::{AstDB, AstStatus, AstCounters, AstErrorStats};
-use crate::ast::ast_db::{ast_index_init, fetch_counters, doc_add, doc_remove, flush_sled_batch, ConnectUsageContext, connect_usages, connect_usages_look_if_full_reset_needed};
+use crate::ast::ast_db::{ast_index_init, fetch_counters, doc_add, doc_remove, flush_sled_batch, ConnectUsageContext, connect_usages, connect_usages_look_if_relink_needed};
 
 
 pub struct AstIndexService {
@@ -200,7 +200,7 @@ async fn ast_indexer_thread(
         }
 
         // Connect usages, unless we have files in the todo
-        let mut usagecx: ConnectUsageContext = connect_usages_look_if_full_reset_needed(ast_index.clone()).await;
+        let mut usagecx: ConnectUsageContext = connect_usages_look_if_relink_needed(ast_index.clone()).await;
         loop {
             todo_count = ast_service.lock().await.ast_todo.len();
             if todo_count > 0 {
@@ -226,7 +226,7 @@ async fn ast_indexer_thread(
             }
             info!("AST connection graph errors:\n{}", error_messages);
         }
-        if usagecx.usages_connected + usagecx.usages_not_found + usagecx.usages_ambiguous + usagecx.usages_homeless > 0 {
+        if usagecx.usages_relinked() > 0 {
             info!("AST connection graph stats: homeless={}, connected={}, not_found={}, ambiguous={} in {:.3}s",
                 usagecx.usages_homeless,
                 usagecx.usages_connected,
@@ -236,6 +236,21 @@ async fn ast_indexer_thread(
             );
         }
 
+        if usagecx.files_relinked > 0 || usagecx.classes_changed > 0 {
+            info!("AST linking pass: classes_changed={}, files_requeued={}, files_relinked={}",
+                usagecx.classes_changed,
+                usagecx.files_requeued,
+                usagecx.files_relinked,
+            );
+            let mut status_locked = ast_status.lock().await;
+            status_locked.link_passes_total += 1;
+            status_locked.link_last_classes_changed = usagecx.classes_changed;
+            status_locked.link_last_files_requeued = usagecx.files_requeued;
+            status_locked.link_last_files_relinked = usagecx.files_relinked;
+            status_locked.link_last_usages_relinked = usagecx.usages_relinked();
+            status_locked.link_last_seconds = usagecx.t0.elapsed().as_secs_f32();
+        }
+
         if todo_count > 0 {
             info!("stopped processing links because there's a file to parse");
             continue;
@@ -311,7 +326,13 @@ pub async fn ast_service_init(ast_permanent: String, ast_max_files: usize) -> Ar
         ast_index_files_total: 0,
         ast_index_symbols_total: 0,
         ast_index_usages_total: 0,
-        ast_max_files_hit: false
+        ast_max_files_hit: false,
+        link_passes_total: 0,
+        link_last_classes_changed: 0,
+        link_last_files_requeued: 0,
+        link_last_files_relinked: 0,
+        link_last_usages_relinked: 0,
+        link_last_seconds: 0.0,
     }));
     let ast_service = AstIndexService {
         ast_sleeping_point: Arc::new(ANotify::new()),
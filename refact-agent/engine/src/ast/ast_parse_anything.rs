@@ -133,6 +133,7 @@ fn _name_to_usage(
         resolved_as: "".to_string(),
         debug_hint: "n2p".to_string(),
         uline,
+        name_range: None,
     };
     let mut node_guid = start_node_guid.unwrap();
     let mut look_here: Vec<AstSymbolInstanceArc> = Vec::new();
@@ -305,6 +306,7 @@ fn _usage_or_typeof_caller_colon_colon_usage(
             resolved_as: "".to_string(),
             debug_hint: "caller".to_string(),
             uline,
+            name_range: None,
         };
         let caller_node = caller.read();
         let typeof_caller = _typeof(pcx, caller_node.guid().clone(), caller_node.name().to_string(), errors);
@@ -332,6 +334,20 @@ fn _usage_or_typeof_caller_colon_colon_usage(
     }
 }
 
+// The name is after the caller if there's one: my_object.something_inside
+fn _name_range(pcx: &ParseContext, symbol: &dyn AstSymbolInstance) -> (usize, usize, usize) {
+    let range = symbol.full_range();
+    let mut start = (range.start_point.row, range.start_point.column);
+    if let Some(caller) = symbol.get_caller_guid().as_ref().and_then(|guid| pcx.map.get(guid)) {
+        let caller_end = caller.read().full_range().end_point;
+        if (caller_end.row, caller_end.column) > start && caller_end <= range.end_point {
+            start = (caller_end.row, caller_end.column);
+        }
+    }
+    let col2 = if range.end_point.row == start.0 { range.end_point.column } else { usize::MAX };
+    (start.0, start.1, col2)
+}
+
 pub fn parse_anything(
     cpath: &str,
     text: &str,
@@ -462,9 +478,11 @@ pub fn parse_anything(
                 if usage.is_none() {
                     continue;
                 }
+                let mut usage = usage.unwrap();
+                usage.name_range = Some(_name_range(&pcx, function_call));
                 let my_parent = _go_to_parent_until_declaration(&pcx.map, symbol_arc.clone(), errors);
                 if let Some(my_parent_def) = pcx.definitions.get_mut(&my_parent) {
-                    my_parent_def.usages.push(usage.unwrap());
+                    my_parent_def.usages.push(usage);
                 }
             }
             SymbolType::VariableUsage => {
@@ -479,9 +497,11 @@ pub fn parse_anything(
                 if usage.is_none() {
                     continue;
                 }
+                let mut usage = usage.unwrap();
+                usage.name_range = Some(_name_range(&pcx, variable_usage));
                 let my_parent = _go_to_parent_until_declaration(&pcx.map, symbol_arc.clone(), errors);
                 if let Some(my_parent_def) = pcx.definitions.get_mut(&my_parent) {
-                    my_parent_def.usages.push(usage.unwrap());
+                    my_parent_def.usages.push(usage);
                 }
             }
         }
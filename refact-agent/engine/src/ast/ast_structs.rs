@@ -14,6 +14,8 @@ pub struct AstUsage {
     pub resolved_as: String,
     pub debug_hint: String,
     pub uline: usize,     // starts from 0, TODO make it start from 1
+    #[serde(default)]
+    pub name_range: Option<(usize, usize, usize)>,  // row from 0 and byte columns col1..col2 to look for the name in, renames change only this
 }
 
 #[derive(Serialize, Deserialize)]
@@ -77,6 +79,13 @@ pub struct AstStatus {
     pub ast_index_symbols_total: i32,
     pub ast_index_usages_total: i32,
     pub ast_max_files_hit: bool,
+    // How much work the last pass of linking usages did, the hierarchy changes re-link only the affected files
+    pub link_passes_total: usize,
+    pub link_last_classes_changed: usize,
+    pub link_last_files_requeued: usize,
+    pub link_last_files_relinked: usize,
+    pub link_last_usages_relinked: usize,
+    pub link_last_seconds: f32,
 }
 
 pub struct AstCounters {
@@ -14,6 +14,7 @@ pub mod ast_structs;
 pub mod ast_parse_anything;
 pub mod ast_indexer_thread;
 pub mod ast_db;
+pub mod ast_call_graph;
 
 #[cfg(feature="vecdb")]
 pub mod file_splitter;
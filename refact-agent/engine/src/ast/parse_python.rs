@@ -51,6 +51,11 @@ fn py_trivial(potential_usage: &str) -> Option<String> {
     }
 }
 
+fn py_name_range(node: &Node) -> (usize, usize, usize) {
+    let range = node.range();
+    (range.start_point.row, range.start_point.column, range.end_point.column)
+}
+
 fn py_simple_resolve(cx: &mut ContextPy, path: &Vec<String>, look_for: &String, uline: usize) -> AstUsage
 {
     if let Some(t) = py_trivial(look_for) {
@@ -59,6 +64,7 @@ fn py_simple_resolve(cx: &mut ContextPy, path: &Vec<String>, look_for: &String,
             targets_for_guesswork: vec![],
             debug_hint: format!("trivial"),
             uline,
+            name_range: None,
         };
     }
     let mut current_path = path.clone();
@@ -73,6 +79,7 @@ fn py_simple_resolve(cx: &mut ContextPy, path: &Vec<String>, look_for: &String,
                 targets_for_guesswork: vec![],
                 debug_hint: format!("go_up"),
                 uline,
+                name_range: None,
             };
         }
         if let Some(an_alias) = cx.ap.alias.get(&hypothtical_str) {
@@ -81,6 +88,7 @@ fn py_simple_resolve(cx: &mut ContextPy, path: &Vec<String>, look_for: &String,
                 targets_for_guesswork: vec![],
                 debug_hint: format!("alias"),
                 uline,
+                name_range: None,
             };
         }
         current_path.pop();
@@ -90,6 +98,7 @@ fn py_simple_resolve(cx: &mut ContextPy, path: &Vec<String>, look_for: &String,
         targets_for_guesswork: vec![format!("?::{}", look_for)],
         debug_hint: format!("go_up_fail"),
         uline,
+        name_range: None,
     };
 }
 
@@ -198,7 +207,8 @@ fn py_resolve_dotted_creating_usages<'a>(cx: &mut ContextPy, node: &Node<'a>, pa
     // debug!(cx, "DOTTED {}", cx.ap.recursive_print_with_red_brackets(&node));
     match node.kind() {
         "identifier" => {
-            let u = py_simple_resolve(cx, path, &node_text, node.range().start_point.row);
+            let mut u = py_simple_resolve(cx, path, &node_text, node.range().start_point.row);
+            u.name_range = Some(py_name_range(node));
             // debug!(cx, "DOTTED GO_UP {:?}", u);
             if u.resolved_as.is_empty() && allow_creation {
                 return Some(AstUsage {
@@ -206,6 +216,7 @@ fn py_resolve_dotted_creating_usages<'a>(cx: &mut ContextPy, node: &Node<'a>, pa
                     resolved_as: format!("{}::{}", path.join("::"), node_text),
                     debug_hint: format!("local_var_create"),
                     uline: node.range().start_point.row,
+                    name_range: Some(py_name_range(node)),
                 });
             }
             if !u.resolved_as.ends_with("::self") && !u.debug_hint.ends_with("trivial") {
@@ -224,6 +235,7 @@ fn py_resolve_dotted_creating_usages<'a>(cx: &mut ContextPy, node: &Node<'a>, pa
                 resolved_as: attrib_path.clone(),
                 debug_hint: format!("attr"),
                 uline: attrib.range().start_point.row,
+                name_range: Some(py_name_range(&attrib)),
             };
             // debug!(cx, "DOTTED_ATTR {:?}", u);
             if let Some(_existing_attr) = cx.ap.things.get(&attrib_path) {
@@ -145,6 +145,8 @@ impl From<Language> for LanguageId {
             Self::TypeScript
         } else if value == tree_sitter_typescript::language_tsx() {
             Self::TypeScriptReact
+        } else if value == tree_sitter_go::language() {
+            Self::Go
         } else {
             Self::Unknown
         }
@@ -2,6 +2,7 @@ use std::fmt::Display;
 use std::path::PathBuf;
 
 use tracing::error;
+use tree_sitter::Language;
 
 use crate::ast::treesitter::ast_instance_structs::AstSymbolInstanceArc;
 use crate::ast::treesitter::language_id::LanguageId;
@@ -16,6 +17,7 @@ mod java;
 mod cpp;
 mod ts;
 mod js;
+mod go;
 
 
 #[derive(Debug, PartialEq, Eq)]
@@ -65,6 +67,10 @@ pub(crate) fn get_ast_parser(language_id: LanguageId) -> Result<Box<dyn AstLangu
             let parser = ts::TSParser::new()?; //quick fix untill we have a dedicated parser for TypeScriptReact
             Ok(Box::new(parser))
         }
+        LanguageId::Go => {
+            let parser = go::GoParser::new()?;
+            Ok(Box::new(parser))
+        }
         other => Err(ParserError {
             message: "Unsupported language id: ".to_string() + &other.to_string()
         }),
@@ -84,6 +90,20 @@ pub fn get_ast_parser_by_filename(filename: &PathBuf) -> Result<(Box<dyn AstLang
     }
 }
 
+pub fn get_tree_sitter_language(language_id: LanguageId) -> Option<Language> {
+    match language_id {
+        LanguageId::Cpp => Some(tree_sitter_cpp::language()),
+        LanguageId::Python => Some(tree_sitter_python::language()),
+        LanguageId::Java => Some(tree_sitter_java::language()),
+        LanguageId::JavaScript => Some(tree_sitter_javascript::language()),
+        LanguageId::Rust => Some(tree_sitter_rust::language()),
+        LanguageId::TypeScript => Some(tree_sitter_typescript::language_typescript()),
+        LanguageId::TypeScriptReact => Some(tree_sitter_typescript::language_tsx()),
+        LanguageId::Go => Some(tree_sitter_go::language()),
+        _ => None,
+    }
+}
+
 pub fn get_language_id_by_filename(filename: &PathBuf) -> Option<LanguageId> {
     let suffix = filename.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
     match suffix.as_str() {
@@ -95,6 +115,7 @@ pub fn get_language_id_by_filename(filename: &PathBuf) -> Option<LanguageId> {
         "rs" => Some(LanguageId::Rust),
         "ts" => Some(LanguageId::TypeScript),
         "tsx" => Some(LanguageId::TypeScriptReact),
+        "go" => Some(LanguageId::Go),
         _ => None
     }
 }
@@ -0,0 +1,847 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::string::ToString;
+use std::sync::Arc;
+
+#[cfg(test)]
+use itertools::Itertools;
+
+use parking_lot::RwLock;
+use similar::DiffableStr;
+use tree_sitter::{Node, Parser, Range};
+use tree_sitter_go::language;
+use uuid::Uuid;
+
+use crate::ast::treesitter::ast_instance_structs::{AstSymbolFields, AstSymbolInstanceArc, ClassFieldDeclaration, CommentDefinition, FunctionArg, FunctionCall, FunctionDeclaration, ImportDeclaration, ImportType, StructDeclaration, TypeAlias, TypeDef, VariableDefinition, VariableUsage};
+use crate::ast::treesitter::language_id::LanguageId;
+use crate::ast::treesitter::parsers::{AstLanguageParser, internal_error, ParserError};
+use crate::ast::treesitter::parsers::utils::{CandidateInfo, get_guid};
+use crate::ast::treesitter::structs::SymbolType;
+
+pub(crate) struct GoParser {
+    pub parser: Parser,
+}
+
+static GO_KEYWORDS: [&str; 25] = [
+    "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "for",
+    "func", "go", "goto", "if", "import", "interface", "map", "package", "range", "return",
+    "select", "struct", "switch", "type", "var",
+];
+
+static GO_POD_TYPES: [&str; 19] = [
+    "bool", "string", "byte", "rune", "int", "int8", "int16", "int32", "int64", "uint",
+    "uint8", "uint16", "uint32", "uint64", "uintptr", "float32", "float64", "complex64", "complex128",
+];
+
+pub fn parse_type(parent: &Node, code: &str) -> Option<TypeDef> {
+    let kind = parent.kind();
+    let text = code.slice(parent.byte_range()).to_string();
+    match kind {
+        "type_identifier" | "identifier" => {
+            if GO_POD_TYPES.contains(&text.as_str()) {
+                return Some(TypeDef {
+                    name: None,
+                    inference_info: Some(text),
+                    inference_info_guid: None,
+                    is_pod: true,
+                    namespace: "".to_string(),
+                    guid: None,
+                    nested_types: vec![],
+                });
+            }
+            return Some(TypeDef {
+                name: Some(text),
+                inference_info: None,
+                inference_info_guid: None,
+                is_pod: false,
+                namespace: "".to_string(),
+                guid: None,
+                nested_types: vec![],
+            });
+        }
+        "pointer_type" | "parenthesized_type" => {
+            // *Point is still a Point as far as linking goes
+            if let Some(child) = parent.named_child(0) {
+                return parse_type(&child, code);
+            }
+        }
+        "qualified_type" => {
+            let mut decl = TypeDef::default();
+            if let Some(name) = parent.child_by_field_name("name") {
+                decl.name = Some(code.slice(name.byte_range()).to_string());
+            }
+            if let Some(package) = parent.child_by_field_name("package") {
+                decl.namespace = code.slice(package.byte_range()).to_string();
+            }
+            return Some(decl);
+        }
+        "generic_type" => {
+            let mut decl = TypeDef::default();
+            if let Some(type_node) = parent.child_by_field_name("type") {
+                if let Some(dtype) = parse_type(&type_node, code) {
+                    decl.name = dtype.name;
+                    decl.namespace = dtype.namespace;
+                }
+            }
+            if let Some(type_arguments) = parent.child_by_field_name("type_arguments") {
+                for i in 0..type_arguments.child_count() {
+                    let child = type_arguments.child(i).unwrap();
+                    if let Some(t) = parse_type(&child, code) {
+                        decl.nested_types.push(t);
+                    }
+                }
+            }
+            return Some(decl);
+        }
+        "slice_type" | "array_type" => {
+            let mut decl = TypeDef {
+                name: Some("[]".to_string()),
+                ..Default::default()
+            };
+            if let Some(element) = parent.child_by_field_name("element") {
+                if let Some(dtype) = parse_type(&element, code) {
+                    decl.nested_types.push(dtype);
+                }
+            }
+            return Some(decl);
+        }
+        "map_type" => {
+            let mut decl = TypeDef {
+                name: Some("map".to_string()),
+                ..Default::default()
+            };
+            for field in ["key", "value"] {
+                if let Some(child) = parent.child_by_field_name(field) {
+                    if let Some(dtype) = parse_type(&child, code) {
+                        decl.nested_types.push(dtype);
+                    }
+                }
+            }
+            return Some(decl);
+        }
+        "channel_type" => {
+            let mut decl = TypeDef {
+                name: Some("chan".to_string()),
+                ..Default::default()
+            };
+            if let Some(value) = parent.child_by_field_name("value") {
+                if let Some(dtype) = parse_type(&value, code) {
+                    decl.nested_types.push(dtype);
+                }
+            }
+            return Some(decl);
+        }
+        "function_type" | "struct_type" | "interface_type" => {
+            return Some(TypeDef {
+                inference_info: Some(text),
+                ..Default::default()
+            });
+        }
+        &_ => {}
+    }
+    None
+}
+
+fn parse_function_args(parent: &Node, code: &str) -> Vec<FunctionArg> {
+    let mut args = vec![];
+    for i in 0..parent.child_count() {
+        let child = parent.child(i).unwrap();
+        match child.kind() {
+            "parameter_declaration" | "variadic_parameter_declaration" => {
+                let type_ = child.child_by_field_name("type").and_then(|t| parse_type(&t, code));
+                let mut cursor = child.walk();
+                let names = child.children_by_field_name("name", &mut cursor)
+                    .map(|n| code.slice(n.byte_range()).to_string())
+                    .collect::<Vec<_>>();
+                if names.is_empty() {
+                    // func(int, string) has no names, only types
+                    args.push(FunctionArg { name: "".to_string(), type_ });
+                    continue;
+                }
+                for name in names {
+                    args.push(FunctionArg { name, type_: type_.clone() });
+                }
+            }
+            &_ => {}
+        }
+    }
+    args
+}
+
+fn receiver_type_name(receiver: &Node, code: &str) -> Option<String> {
+    for i in 0..receiver.child_count() {
+        let child = receiver.child(i).unwrap();
+        if child.kind() == "parameter_declaration" {
+            let type_node = child.child_by_field_name("type")?;
+            return parse_type(&type_node, code).and_then(|t| t.name);
+        }
+    }
+    None
+}
+
+
+impl GoParser {
+    pub fn new() -> Result<GoParser, ParserError> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&language())
+            .map_err(internal_error)?;
+        Ok(GoParser { parser })
+    }
+
+    fn parse_type_declaration<'a>(
+        &mut self,
+        info: &CandidateInfo<'a>,
+        code: &str,
+        candidates: &mut VecDeque<CandidateInfo<'a>>,
+    ) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &info.parent_guid));
+
+        let specs = (0..info.node.child_count())
+            .map(|i| info.node.child(i).unwrap())
+            .filter(|x| ["type_spec", "type_alias"].contains(&x.kind()))
+            .collect::<Vec<_>>();
+        // `type Point struct {...}` should include the `type` keyword, grouped `type (...)` can't
+        let single_spec = specs.len() == 1;
+        for spec in specs {
+            let full_range = if single_spec { info.node.range() } else { spec.range() };
+            let type_node = spec.child_by_field_name("type");
+            match type_node.map(|x| x.kind()) {
+                Some("struct_type") | Some("interface_type") if spec.kind() == "type_spec" => {
+                    symbols.extend(self.parse_struct_declaration(info, &spec, &type_node.unwrap(), full_range, code, candidates));
+                }
+                _ => {
+                    let mut type_alias = TypeAlias::default();
+                    type_alias.ast_fields.language = info.ast_fields.language;
+                    type_alias.ast_fields.full_range = full_range;
+                    type_alias.ast_fields.file_path = info.ast_fields.file_path.clone();
+                    type_alias.ast_fields.parent_guid = Some(info.parent_guid.clone());
+                    type_alias.ast_fields.guid = get_guid();
+                    type_alias.ast_fields.is_error = info.ast_fields.is_error;
+                    if let Some(name) = spec.child_by_field_name("name") {
+                        type_alias.ast_fields.name = code.slice(name.byte_range()).to_string();
+                    }
+                    if let Some(type_node) = type_node {
+                        symbols.extend(self.find_error_usages(&type_node, code, &info.ast_fields.file_path, &info.parent_guid));
+                        if let Some(dtype) = parse_type(&type_node, code) {
+                            type_alias.types.push(dtype);
+                        }
+                    }
+                    symbols.push(Arc::new(RwLock::new(Box::new(type_alias))));
+                }
+            }
+        }
+        symbols
+    }
+
+    fn parse_struct_declaration<'a>(
+        &mut self,
+        info: &CandidateInfo<'a>,
+        spec: &Node<'a>,
+        type_node: &Node<'a>,
+        full_range: Range,
+        code: &str,
+        candidates: &mut VecDeque<CandidateInfo<'a>>,
+    ) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = StructDeclaration::default();
+
+        decl.ast_fields.language = info.ast_fields.language;
+        decl.ast_fields.full_range = full_range;
+        decl.ast_fields.declaration_range = full_range;
+        decl.ast_fields.definition_range = full_range;
+        decl.ast_fields.file_path = info.ast_fields.file_path.clone();
+        decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
+        decl.ast_fields.guid = get_guid();
+        decl.ast_fields.is_error = info.ast_fields.is_error;
+
+        symbols.extend(self.find_error_usages(spec, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+        symbols.extend(self.find_error_usages(type_node, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+
+        if let Some(name_node) = spec.child_by_field_name("name") {
+            decl.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+        }
+        if let Some(type_parameters) = spec.child_by_field_name("type_parameters") {
+            for i in 0..type_parameters.child_count() {
+                let child = type_parameters.child(i).unwrap();
+                let mut cursor = child.walk();
+                for name in child.children_by_field_name("name", &mut cursor) {
+                    if let Some(dtype) = parse_type(&name, code) {
+                        decl.template_types.push(dtype);
+                    }
+                }
+            }
+        }
+
+        // struct_type wraps field_declaration_list, interface_type has its elements directly inside
+        let body = if type_node.kind() == "struct_type" {
+            (0..type_node.child_count())
+                .map(|i| type_node.child(i).unwrap())
+                .find(|x| x.kind() == "field_declaration_list")
+        } else {
+            Some(*type_node)
+        };
+        if let Some(body) = body {
+            for i in 0..body.child_count() {
+                let child = body.child(i).unwrap();
+                match child.kind() {
+                    "{" => {
+                        decl.ast_fields.definition_range = Range {
+                            start_byte: child.start_byte(),
+                            end_byte: body.end_byte(),
+                            start_point: child.start_position(),
+                            end_point: body.end_position(),
+                        };
+                        decl.ast_fields.declaration_range = Range {
+                            start_byte: decl.ast_fields.full_range.start_byte,
+                            end_byte: child.start_byte(),
+                            start_point: decl.ast_fields.full_range.start_point,
+                            end_point: child.start_position(),
+                        };
+                    }
+                    // embedded struct: `type Circle struct { Shape; Radius float64 }`
+                    "field_declaration" if child.child_by_field_name("name").is_none() => {
+                        if let Some(dtype) = child.child_by_field_name("type").and_then(|t| parse_type(&t, code)) {
+                            decl.inherited_types.push(dtype);
+                        }
+                    }
+                    // embedded interface: `type ReadWriter interface { Reader; Writer }`
+                    "type_elem" | "constraint_elem" => {
+                        for j in 0..child.child_count() {
+                            if let Some(dtype) = parse_type(&child.child(j).unwrap(), code) {
+                                decl.inherited_types.push(dtype);
+                            }
+                        }
+                    }
+                    &_ => {
+                        candidates.push_back(CandidateInfo {
+                            ast_fields: decl.ast_fields.clone(),
+                            node: child,
+                            parent_guid: decl.ast_fields.guid.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    fn parse_variable_definition<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &info.parent_guid));
+
+        // var_spec / const_spec have name+type+value, short_var_declaration has left+right
+        let (names, values) = if info.node.kind() == "short_var_declaration" {
+            let mut names = vec![];
+            if let Some(left) = info.node.child_by_field_name("left") {
+                for i in 0..left.child_count() {
+                    let child = left.child(i).unwrap();
+                    if child.kind() == "identifier" {
+                        names.push(child);
+                    }
+                }
+            }
+            (names, info.node.child_by_field_name("right"))
+        } else {
+            let mut cursor = info.node.walk();
+            let names = info.node.children_by_field_name("name", &mut cursor).collect::<Vec<_>>();
+            (names, info.node.child_by_field_name("value"))
+        };
+
+        let mut type_ = TypeDef::default();
+        if let Some(type_node) = info.node.child_by_field_name("type") {
+            symbols.extend(self.find_error_usages(&type_node, code, &info.ast_fields.file_path, &info.parent_guid));
+            if let Some(dtype) = parse_type(&type_node, code) {
+                type_ = dtype;
+            }
+        }
+        let value_nodes = values.map(|values| {
+            (0..values.child_count())
+                .map(|i| values.child(i).unwrap())
+                .filter(|x| x.kind() != ",")
+                .collect::<Vec<_>>()
+        }).unwrap_or_default();
+
+        for (idx, name) in names.iter().enumerate() {
+            let name = code.slice(name.byte_range()).to_string();
+            if name == "_" {
+                continue;
+            }
+            let mut decl = VariableDefinition::default();
+            decl.ast_fields.language = info.ast_fields.language;
+            decl.ast_fields.full_range = info.node.range();
+            decl.ast_fields.file_path = info.ast_fields.file_path.clone();
+            decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
+            decl.ast_fields.guid = get_guid();
+            decl.ast_fields.is_error = info.ast_fields.is_error;
+            decl.ast_fields.name = name;
+            decl.type_ = type_.clone();
+            // `a, b := f()` has one value for several names, nothing to infer from
+            if value_nodes.len() == names.len() {
+                decl.type_.inference_info = Some(code.slice(value_nodes[idx].byte_range()).to_string());
+            }
+            symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        }
+        for value in value_nodes {
+            symbols.extend(self.find_error_usages(&value, code, &info.ast_fields.file_path, &info.parent_guid));
+            candidates.push_back(CandidateInfo {
+                ast_fields: info.ast_fields.clone(),
+                node: value,
+                parent_guid: info.parent_guid.clone(),
+            });
+        }
+
+        symbols
+    }
+
+    fn parse_field_declaration<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, _candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        let mut dtype = TypeDef::default();
+        if let Some(type_node) = info.node.child_by_field_name("type") {
+            symbols.extend(self.find_error_usages(&type_node, code, &info.ast_fields.file_path, &info.parent_guid));
+            if let Some(type_) = parse_type(&type_node, code) {
+                dtype = type_;
+            }
+        }
+
+        symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &info.parent_guid));
+
+        let mut cursor = info.node.walk();
+        for name in info.node.children_by_field_name("name", &mut cursor) {
+            let mut decl = ClassFieldDeclaration::default();
+            decl.ast_fields.language = info.ast_fields.language;
+            decl.ast_fields.full_range = info.node.range();
+            decl.ast_fields.declaration_range = info.node.range();
+            decl.ast_fields.file_path = info.ast_fields.file_path.clone();
+            decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
+            decl.ast_fields.guid = get_guid();
+            decl.ast_fields.is_error = info.ast_fields.is_error;
+            decl.ast_fields.name = code.slice(name.byte_range()).to_string();
+            decl.type_ = dtype.clone();
+            symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        }
+        symbols
+    }
+
+    fn parse_usages_<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        let kind = info.node.kind();
+        #[cfg(test)]
+        #[allow(unused)]
+            let text = code.slice(info.node.byte_range());
+        match kind {
+            "type_declaration" => {
+                symbols.extend(self.parse_type_declaration(info, code, candidates));
+            }
+            "var_spec" | "const_spec" | "short_var_declaration" => {
+                symbols.extend(self.parse_variable_definition(info, code, candidates));
+            }
+            "function_declaration" | "method_declaration" | "method_elem" | "method_spec" => {
+                symbols.extend(self.parse_function_declaration(info, code, candidates));
+            }
+            "call_expression" | "composite_literal" => {
+                symbols.extend(self.parse_call_expression(info, code, candidates));
+            }
+            "field_declaration" => {
+                symbols.extend(self.parse_field_declaration(info, code, candidates));
+            }
+            "identifier" => {
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = code.slice(info.node.byte_range()).to_string();
+                usage.ast_fields.language = info.ast_fields.language;
+                usage.ast_fields.full_range = info.node.range();
+                usage.ast_fields.file_path = info.ast_fields.file_path.clone();
+                usage.ast_fields.parent_guid = Some(info.parent_guid.clone());
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.is_error = info.ast_fields.is_error;
+                if let Some(caller_guid) = info.ast_fields.caller_guid.clone() {
+                    usage.ast_fields.guid = caller_guid;
+                }
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            "selector_expression" => {
+                let operand = info.node.child_by_field_name("operand").unwrap();
+                let field = info.node.child_by_field_name("field").unwrap();
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = code.slice(field.byte_range()).to_string();
+                usage.ast_fields.language = info.ast_fields.language;
+                usage.ast_fields.full_range = info.node.range();
+                usage.ast_fields.file_path = info.ast_fields.file_path.clone();
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.parent_guid = Some(info.parent_guid.clone());
+                usage.ast_fields.caller_guid = Some(get_guid());
+                usage.ast_fields.is_error = info.ast_fields.is_error;
+                if let Some(caller_guid) = info.ast_fields.caller_guid.clone() {
+                    usage.ast_fields.guid = caller_guid;
+                }
+                candidates.push_back(CandidateInfo {
+                    ast_fields: usage.ast_fields.clone(),
+                    node: operand,
+                    parent_guid: info.parent_guid.clone(),
+                });
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            "comment" => {
+                let mut def = CommentDefinition::default();
+                def.ast_fields.language = info.ast_fields.language;
+                def.ast_fields.full_range = info.node.range();
+                def.ast_fields.file_path = info.ast_fields.file_path.clone();
+                def.ast_fields.parent_guid = Some(info.parent_guid.clone());
+                def.ast_fields.guid = get_guid();
+                def.ast_fields.is_error = info.ast_fields.is_error;
+                symbols.push(Arc::new(RwLock::new(Box::new(def))));
+            }
+            "import_spec" => {
+                let mut def = ImportDeclaration::default();
+                def.ast_fields.language = info.ast_fields.language;
+                def.ast_fields.full_range = info.node.range();
+                def.ast_fields.file_path = info.ast_fields.file_path.clone();
+                if let Some(path) = info.node.child_by_field_name("path") {
+                    let path = code.slice(path.byte_range()).trim_matches(|c| c == '"' || c == '`').to_string();
+                    def.path_components = path.split("/").map(|x| x.to_string()).collect();
+                    // standard library packages never have a domain in the first element
+                    if let Some(first) = def.path_components.first() {
+                        if first.contains(".") {
+                            def.import_type = ImportType::Library;
+                        } else {
+                            def.import_type = ImportType::System;
+                        }
+                    }
+                }
+                if let Some(name) = info.node.child_by_field_name("name") {
+                    def.alias = Some(code.slice(name.byte_range()).to_string());
+                }
+                def.ast_fields.parent_guid = Some(info.parent_guid.clone());
+                def.ast_fields.guid = get_guid();
+                symbols.push(Arc::new(RwLock::new(Box::new(def))));
+            }
+            "ERROR" => {
+                let mut ast = info.ast_fields.clone();
+                ast.is_error = true;
+
+                for i in 0..info.node.child_count() {
+                    let child = info.node.child(i).unwrap();
+                    candidates.push_back(CandidateInfo {
+                        ast_fields: ast.clone(),
+                        node: child,
+                        parent_guid: info.parent_guid.clone(),
+                    });
+                }
+            }
+            "package_clause" => {}
+            _ => {
+                for i in 0..info.node.child_count() {
+                    let child = info.node.child(i).unwrap();
+                    candidates.push_back(CandidateInfo {
+                        ast_fields: info.ast_fields.clone(),
+                        node: child,
+                        parent_guid: info.parent_guid.clone(),
+                    })
+                }
+            }
+        }
+        symbols
+    }
+
+    fn find_error_usages(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        for i in 0..parent.child_count() {
+            let child = parent.child(i).unwrap();
+            if child.kind() == "ERROR" {
+                symbols.extend(self.parse_error_usages(&child, code, path, parent_guid));
+            }
+        }
+        symbols
+    }
+
+    fn parse_error_usages(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        match parent.kind() {
+            "identifier" => {
+                let name = code.slice(parent.byte_range()).to_string();
+                if GO_KEYWORDS.contains(&name.as_str()) {
+                    return symbols;
+                }
+
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = name;
+                usage.ast_fields.language = LanguageId::Go;
+                usage.ast_fields.full_range = parent.range();
+                usage.ast_fields.file_path = path.clone();
+                usage.ast_fields.parent_guid = Some(parent_guid.clone());
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.is_error = true;
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            "selector_expression" => {
+                let operand = parent.child_by_field_name("operand").unwrap();
+                let usages = self.parse_error_usages(&operand, code, path, parent_guid);
+                let field = parent.child_by_field_name("field").unwrap();
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = code.slice(field.byte_range()).to_string();
+                usage.ast_fields.language = LanguageId::Go;
+                usage.ast_fields.full_range = parent.range();
+                usage.ast_fields.file_path = path.clone();
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.parent_guid = Some(parent_guid.clone());
+                usage.ast_fields.is_error = true;
+                if let Some(last) = usages.last() {
+                    usage.ast_fields.caller_guid = last.read().fields().parent_guid.clone();
+                }
+                symbols.extend(usages);
+                if !GO_KEYWORDS.contains(&usage.ast_fields.name.as_str()) {
+                    symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+                }
+            }
+            &_ => {
+                for i in 0..parent.child_count() {
+                    let child = parent.child(i).unwrap();
+                    symbols.extend(self.parse_error_usages(&child, code, path, parent_guid));
+                }
+            }
+        }
+
+        symbols
+    }
+
+    pub fn parse_function_declaration<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = FunctionDeclaration::default();
+        decl.ast_fields.language = info.ast_fields.language;
+        decl.ast_fields.full_range = info.node.range();
+        decl.ast_fields.declaration_range = info.node.range();
+        decl.ast_fields.definition_range = info.node.range();
+        decl.ast_fields.file_path = info.ast_fields.file_path.clone();
+        decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
+        decl.ast_fields.is_error = info.ast_fields.is_error;
+        decl.ast_fields.guid = get_guid();
+
+        symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+
+        if let Some(name_node) = info.node.child_by_field_name("name") {
+            decl.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+        }
+        // parse_() moves methods under their receiver type using this namespace
+        if let Some(receiver) = info.node.child_by_field_name("receiver") {
+            symbols.extend(self.find_error_usages(&receiver, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+            if let Some(receiver_type) = receiver_type_name(&receiver, code) {
+                decl.ast_fields.namespace = receiver_type;
+            }
+        }
+        if let Some(type_parameters) = info.node.child_by_field_name("type_parameters") {
+            for i in 0..type_parameters.child_count() {
+                let child = type_parameters.child(i).unwrap();
+                let mut cursor = child.walk();
+                for name in child.children_by_field_name("name", &mut cursor) {
+                    if let Some(dtype) = parse_type(&name, code) {
+                        decl.template_types.push(dtype);
+                    }
+                }
+            }
+        }
+
+        if let Some(parameters_node) = info.node.child_by_field_name("parameters") {
+            symbols.extend(self.find_error_usages(&parameters_node, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+            decl.ast_fields.declaration_range = Range {
+                start_byte: decl.ast_fields.full_range.start_byte,
+                end_byte: parameters_node.end_byte(),
+                start_point: decl.ast_fields.full_range.start_point,
+                end_point: parameters_node.end_position(),
+            };
+            decl.args = parse_function_args(&parameters_node, code);
+        }
+        if let Some(result) = info.node.child_by_field_name("result") {
+            symbols.extend(self.find_error_usages(&result, code, &info.ast_fields.file_path, &decl.ast_fields.guid));
+            decl.return_type = if result.kind() == "parameter_list" {
+                // (int, error) or named results, keep them as text
+                Some(TypeDef {
+                    inference_info: Some(code.slice(result.byte_range()).to_string()),
+                    ..Default::default()
+                })
+            } else {
+                parse_type(&result, code)
+            };
+        }
+
+        if let Some(body_node) = info.node.child_by_field_name("body") {
+            decl.ast_fields.definition_range = body_node.range();
+            decl.ast_fields.declaration_range = Range {
+                start_byte: decl.ast_fields.full_range.start_byte,
+                end_byte: decl.ast_fields.definition_range.start_byte,
+                start_point: decl.ast_fields.full_range.start_point,
+                end_point: decl.ast_fields.definition_range.start_point,
+            };
+            candidates.push_back(CandidateInfo {
+                ast_fields: decl.ast_fields.clone(),
+                node: body_node,
+                parent_guid: decl.ast_fields.guid.clone(),
+            });
+        } else {
+            decl.ast_fields.declaration_range = decl.ast_fields.full_range;
+        }
+
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    pub fn parse_call_expression<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = FunctionCall::default();
+        decl.ast_fields.language = info.ast_fields.language;
+        decl.ast_fields.full_range = info.node.range();
+        decl.ast_fields.file_path = info.ast_fields.file_path.clone();
+        decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
+        decl.ast_fields.guid = get_guid();
+        decl.ast_fields.is_error = info.ast_fields.is_error;
+        if let Some(caller_guid) = info.ast_fields.caller_guid.clone() {
+            decl.ast_fields.guid = caller_guid;
+        }
+        decl.ast_fields.caller_guid = Some(get_guid());
+
+        symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &info.parent_guid));
+
+        let arguments = if info.node.kind() == "composite_literal" {
+            // Point{X: 1, Y: 2} constructs a Point, treat it like a call
+            if let Some(type_) = info.node.child_by_field_name("type") {
+                symbols.extend(self.find_error_usages(&type_, code, &info.ast_fields.file_path, &info.parent_guid));
+                match parse_type(&type_, code) {
+                    Some(dtype) if dtype.name.is_some() => {
+                        decl.ast_fields.name = dtype.name.unwrap();
+                        decl.ast_fields.namespace = dtype.namespace;
+                    }
+                    _ => {
+                        decl.ast_fields.name = code.slice(type_.byte_range()).to_string();
+                    }
+                }
+            }
+            info.node.child_by_field_name("body")
+        } else {
+            if let Some(function) = info.node.child_by_field_name("function") {
+                match function.kind() {
+                    "selector_expression" => {
+                        if let Some(field) = function.child_by_field_name("field") {
+                            decl.ast_fields.name = code.slice(field.byte_range()).to_string();
+                        }
+                        if let Some(operand) = function.child_by_field_name("operand") {
+                            candidates.push_back(CandidateInfo {
+                                ast_fields: decl.ast_fields.clone(),
+                                node: operand,
+                                parent_guid: info.parent_guid.clone(),
+                            });
+                        }
+                    }
+                    "identifier" => {
+                        decl.ast_fields.name = code.slice(function.byte_range()).to_string();
+                    }
+                    &_ => {
+                        // func literals, (*T).Method and friends
+                        decl.ast_fields.name = code.slice(function.byte_range()).to_string();
+                        let mut new_ast_fields = info.ast_fields.clone();
+                        new_ast_fields.caller_guid = None;
+                        candidates.push_back(CandidateInfo {
+                            ast_fields: new_ast_fields,
+                            node: function,
+                            parent_guid: info.parent_guid.clone(),
+                        });
+                    }
+                }
+            }
+            if let Some(type_arguments) = info.node.child_by_field_name("type_arguments") {
+                for i in 0..type_arguments.child_count() {
+                    if let Some(dtype) = parse_type(&type_arguments.child(i).unwrap(), code) {
+                        decl.template_types.push(dtype);
+                    }
+                }
+            }
+            info.node.child_by_field_name("arguments")
+        };
+        if let Some(arguments) = arguments {
+            symbols.extend(self.find_error_usages(&arguments, code, &info.ast_fields.file_path,
+                                                  &info.parent_guid));
+            let mut new_ast_fields = info.ast_fields.clone();
+            new_ast_fields.caller_guid = None;
+            for i in 0..arguments.child_count() {
+                let child = arguments.child(i).unwrap();
+                candidates.push_back(CandidateInfo {
+                    ast_fields: new_ast_fields.clone(),
+                    node: child,
+                    parent_guid: info.parent_guid.clone(),
+                });
+            }
+        }
+
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    fn parse_(&mut self, parent: &Node, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut ast_fields = AstSymbolFields::default();
+        ast_fields.file_path = path.clone();
+        ast_fields.is_error = false;
+        ast_fields.language = LanguageId::Go;
+
+        let root_guid = get_guid();
+        let mut candidates = VecDeque::from(vec![CandidateInfo {
+            ast_fields,
+            node: parent.clone(),
+            parent_guid: root_guid.clone(),
+        }]);
+        while let Some(candidate) = candidates.pop_front() {
+            let symbols_l = self.parse_usages_(&candidate, code, &mut candidates);
+            symbols.extend(symbols_l);
+        }
+
+        // Methods live outside of the type body, attach them to the type if it's declared in this file,
+        // so paths become Point::Move like in other languages
+        let top_level_types = symbols.iter()
+            .filter(|s| {
+                let s = s.read();
+                s.symbol_type() == SymbolType::StructDeclaration && *s.parent_guid() == Some(root_guid)
+            })
+            .map(|s| (s.read().name().to_string(), s.read().guid().clone()))
+            .collect::<HashMap<_, _>>();
+        for symbol in symbols.iter_mut() {
+            let mut sym = symbol.write();
+            if sym.symbol_type() != SymbolType::FunctionDeclaration || *sym.parent_guid() != Some(root_guid) {
+                continue;
+            }
+            if let Some(type_guid) = top_level_types.get(sym.namespace()) {
+                sym.fields_mut().parent_guid = Some(type_guid.clone());
+            }
+        }
+
+        let guid_to_symbol_map = symbols.iter()
+            .map(|s| (s.clone().read().guid().clone(), s.clone())).collect::<HashMap<_, _>>();
+        for symbol in symbols.iter_mut() {
+            let guid = symbol.read().guid().clone();
+            if let Some(parent_guid) = symbol.read().parent_guid() {
+                if let Some(parent) = guid_to_symbol_map.get(parent_guid) {
+                    parent.write().fields_mut().childs_guid.push(guid);
+                }
+            }
+        }
+
+        #[cfg(test)]
+        for symbol in symbols.iter_mut() {
+            let mut sym = symbol.write();
+            sym.fields_mut().childs_guid = sym.fields_mut().childs_guid.iter()
+                .sorted_by_key(|x| {
+                    guid_to_symbol_map.get(*x).unwrap().read().full_range().start_byte
+                }).map(|x| x.clone()).collect();
+        }
+
+        symbols
+    }
+}
+
+impl AstLanguageParser for GoParser {
+    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
+        let tree = self.parser.parse(code, None).unwrap();
+        let symbols = self.parse_(&tree.root_node(), code, path);
+        symbols
+    }
+}
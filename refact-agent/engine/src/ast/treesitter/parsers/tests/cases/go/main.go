@@ -0,0 +1,24 @@
+package main
+
+import (
+	"fmt"
+	"os"
+
+	"github.com/acme/shapes"
+)
+
+// Square reuses the center of shapes.Point
+type Square struct {
+	shapes.Point
+	Side float64
+}
+
+func (s Square) Area() float64 {
+	return s.Side * s.Side
+}
+
+func main() {
+	sq := Square{Side: 2}
+	fmt.Println(sq.Area())
+	os.Exit(0)
+}
@@ -0,0 +1,30 @@
+package shapes
+
+import "math"
+
+// Shape is anything with an area
+type Shape interface {
+	Area() float64
+}
+
+// Point is a location on a plane
+type Point struct {
+	X float64
+	Y float64
+}
+
+// Distance returns the distance to another point
+func (p *Point) Distance(other Point) float64 {
+	return math.Hypot(other.X-p.X, other.Y-p.Y)
+}
+
+// Circle embeds Point as its center
+type Circle struct {
+	Point
+	Radius float64
+}
+
+// Area returns the area of the circle
+func (c Circle) Area() float64 {
+	return math.Pi * c.Radius * c.Radius
+}
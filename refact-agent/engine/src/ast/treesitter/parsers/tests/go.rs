@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::canonicalize;
+    use std::path::PathBuf;
+
+    use crate::ast::treesitter::ast_instance_structs::{ImportDeclaration, ImportType, StructDeclaration};
+    use crate::ast::treesitter::language_id::LanguageId;
+    use crate::ast::treesitter::parsers::AstLanguageParser;
+    use crate::ast::treesitter::parsers::go::GoParser;
+    use crate::ast::treesitter::parsers::tests::{base_declaration_formatter_test, base_skeletonizer_test};
+    use crate::ast::treesitter::structs::SymbolType;
+
+    const MAIN_GO_CODE: &str = include_str!("cases/go/main.go");
+
+    const POINT_GO_CODE: &str = include_str!("cases/go/point.go");
+    const POINT_GO_SKELETON: &str = include_str!("cases/go/point.go.skeleton");
+    const POINT_GO_DECLS: &str = include_str!("cases/go/point.go.decl_json");
+
+    #[test]
+    fn parser_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let path = PathBuf::from("file:///main.go");
+        let symbols = parser.parse(MAIN_GO_CODE, &path);
+
+        let find = |name: &str, symbol_type: SymbolType| {
+            symbols.iter().find(|s| s.read().name() == name && s.read().symbol_type() == symbol_type)
+                .expect(name).clone()
+        };
+
+        let square = find("Square", SymbolType::StructDeclaration);
+        let square = square.read();
+        let inherited = &square.as_any().downcast_ref::<StructDeclaration>().unwrap().inherited_types;
+        assert_eq!(inherited.len(), 1);
+        assert_eq!(inherited[0].name, Some("Point".to_string()));
+        assert_eq!(inherited[0].namespace, "shapes");
+        assert_eq!(square.childs_guid().len(), 2);  // Side and the Area method
+
+        let area = find("Area", SymbolType::FunctionDeclaration);
+        assert_eq!(area.read().parent_guid(), &Some(square.guid().clone()));
+        assert_eq!(area.read().namespace(), "Square");
+
+        let main = find("main", SymbolType::FunctionDeclaration);
+        assert_eq!(find("sq", SymbolType::VariableDefinition).read().parent_guid(), &Some(main.read().guid().clone()));
+        for call in ["Square", "Println", "Area", "Exit"] {
+            find(call, SymbolType::FunctionCall);
+        }
+
+        let imports = symbols.iter()
+            .filter_map(|s| s.read().as_any().downcast_ref::<ImportDeclaration>()
+                .map(|x| (x.path_components.join("/"), x.import_type.clone())))
+            .collect::<Vec<_>>();
+        assert_eq!(imports.len(), 3);
+        assert!(imports.contains(&("fmt".to_string(), ImportType::System)));
+        assert!(imports.contains(&("github.com/acme/shapes".to_string(), ImportType::Library)));
+    }
+
+    #[test]
+    fn skeletonizer_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/go/point.go");
+        assert!(file.exists());
+
+        base_skeletonizer_test(&LanguageId::Go, &mut parser, &file, POINT_GO_CODE, POINT_GO_SKELETON);
+    }
+
+    #[test]
+    fn declaration_formatter_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/go/point.go");
+        assert!(file.exists());
+        base_declaration_formatter_test(&LanguageId::Go, &mut parser, &file, POINT_GO_CODE, POINT_GO_DECLS);
+    }
+}
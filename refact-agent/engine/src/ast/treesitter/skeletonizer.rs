@@ -62,7 +62,8 @@ pub trait SkeletonFormatter {
             .map(|x| x.replace("\r", "")
                 .replace("\t", "    ").to_string())
             .collect::<Vec<_>>();
-        let indent_n = content.iter().map(|x| {
+        // measure the indent after tabs are expanded, otherwise a tab counts as one space
+        let indent_n = lines.iter().map(|x| {
             if x.is_empty() {
                 return usize::MAX;
             } else {
@@ -6,8 +6,9 @@ use tokio::sync::mpsc;
 use async_trait::async_trait;
 use tokio::sync::Mutex as AMutex;
 use tokio::sync::RwLock as ARwLock;
+use tokio_util::sync::CancellationToken;
 
-use crate::call_validation::{ChatMessage, ContextFile, ContextEnum, SubchatParameters, PostprocessSettings};
+use crate::call_validation::{ChatMessage, ChatMode, ContextFile, ContextEnum, SubchatParameters, PostprocessSettings};
 use crate::global_context::GlobalContext;
 
 use crate::at_commands::at_file::AtFile;
@@ -15,6 +16,7 @@ use crate::at_commands::at_ast_definition::AtAstDefinition;
 use crate::at_commands::at_ast_reference::AtAstReference;
 use crate::at_commands::at_tree::AtTree;
 use crate::at_commands::at_web::AtWeb;
+use crate::at_commands::at_mcp_resource::AtMCPResource;
 use crate::at_commands::execute_at::AtCommandMember;
 
 
@@ -31,6 +33,9 @@ pub struct AtCommandsContext {
     pub chat_id: String,
     pub current_model: String,
     pub should_execute_remotely: bool,
+    pub cancel_token: CancellationToken,  // stops running tools, see tools_limits::chat_cancel()
+    pub chat_mode: ChatMode,  // tool_policy.yaml rules can depend on it
+    pub autonomous: bool,  // no human to confirm anything, ask_user decisions become deny
 
     pub at_commands: HashMap<String, Arc<AMutex<Box<dyn AtCommand + Send>>>>,  // a copy from static constant
     pub subchat_tool_parameters: IndexMap<String, SubchatParameters>,
@@ -63,6 +68,9 @@ impl AtCommandsContext {
             chat_id,
             current_model: "".to_string(),
             should_execute_remotely,
+            cancel_token: CancellationToken::new(),
+            chat_mode: ChatMode::default(),
+            autonomous: false,
 
             at_commands: at_commands_dict(global_context.clone()).await,
             subchat_tool_parameters: IndexMap::new(),
@@ -100,6 +108,7 @@ pub async fn at_commands_dict(gcx: Arc<ARwLock<GlobalContext>>) -> HashMap<Strin
         // ("@diff".to_string(), Arc::new(AMutex::new(Box::new(AtDiff::new()) as Box<dyn AtCommand + Send>))),
         // ("@diff-rev".to_string(), Arc::new(AMutex::new(Box::new(AtDiffRev::new()) as Box<dyn AtCommand + Send>))),
         ("@web".to_string(), Arc::new(AMutex::new(Box::new(AtWeb::new()) as Box<dyn AtCommand + Send>))),
+        ("@mcp-resource".to_string(), Arc::new(AMutex::new(Box::new(AtMCPResource::new()) as Box<dyn AtCommand + Send>))),
         #[cfg(feature="vecdb")]
         ("@search".to_string(), Arc::new(AMutex::new(Box::new(crate::at_commands::at_search::AtSearch::new()) as Box<dyn AtCommand + Send>))),
     ]);
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::sync::Mutex as AMutex;
+
+use crate::at_commands::at_commands::{AtCommand, AtCommandsContext, AtParam};
+use crate::at_commands::execute_at::{AtCommandMember, correct_at_arg};
+use crate::call_validation::{ChatMessage, ContextEnum, ContextFile};
+use crate::integrations::integr_mcp::mcp_sessions_with_clients;
+
+
+const RESOURCE_MAX_CHARS: usize = 100_000;
+
+fn truncate_resource_content(context_file: &mut ContextFile, max_chars: usize) {
+    if context_file.file_content.len() <= max_chars {
+        return;
+    }
+    let mut cut_at = max_chars;
+    while !context_file.file_content.is_char_boundary(cut_at) {
+        cut_at -= 1;
+    }
+    context_file.file_content.truncate(cut_at);
+    context_file.file_content.push_str("\n...truncated\n");
+    context_file.line2 = context_file.file_content.lines().count();
+}
+
+pub struct AtMCPResource {
+    pub params: Vec<Arc<AMutex<dyn AtParam>>>,
+}
+
+impl AtMCPResource {
+    pub fn new() -> Self {
+        AtMCPResource {
+            params: vec![
+                Arc::new(AMutex::new(AtParamMCPResource::new()))
+            ],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AtParamMCPResource {}
+
+impl AtParamMCPResource {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl AtParam for AtParamMCPResource {
+    async fn is_value_valid(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        value: &String,
+    ) -> bool {
+        let gcx = ccx.lock().await.global_context.clone();
+        mcp_sessions_with_clients(gcx).await.iter()
+            .any(|(_, _, resources, _)| resources.iter().any(|r| &r.uri == value))
+    }
+
+    async fn param_completion(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        value: &String,
+    ) -> Vec<String> {
+        let (gcx, top_n) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.top_n)
+        };
+        let value_lower = value.to_lowercase();
+        let mut candidates = vec![];
+        for (_, _, resources, _) in mcp_sessions_with_clients(gcx).await {
+            for resource in resources {
+                if resource.uri.to_lowercase().contains(&value_lower) || resource.name.to_lowercase().contains(&value_lower) {
+                    candidates.push(resource.uri);
+                }
+            }
+        }
+        // exact prefix matches first, then the rest in the order servers listed them
+        candidates.sort_by_key(|uri| !uri.to_lowercase().starts_with(&value_lower));
+        candidates.dedup();
+        candidates.truncate(top_n);
+        candidates
+    }
+
+    fn param_completion_valid(&self) -> bool {true}
+}
+
+#[async_trait]
+impl AtCommand for AtMCPResource {
+    fn params(&self) -> &Vec<Arc<AMutex<dyn AtParam>>> {
+        &self.params
+    }
+
+    async fn at_execute(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        cmd: &mut AtCommandMember,
+        args: &mut Vec<AtCommandMember>,
+    ) -> Result<(Vec<ContextEnum>, String), String> {
+        let mut arg0 = match args.iter().find(|x| !x.text.trim().is_empty()) {
+            Some(x) => x.clone(),
+            None => {
+                cmd.ok = false; cmd.reason = Some("no resource provided".to_string());
+                args.clear();
+                if ccx.lock().await.is_preview {
+                    return Ok((vec![], "".to_string()));
+                }
+                return Err("Cannot execute @mcp-resource: no resource provided".to_string());
+            }
+        };
+        correct_at_arg(ccx.clone(), self.params[0].clone(), &mut arg0).await;
+        args.clear();
+        args.push(arg0.clone());
+
+        if !arg0.ok {
+            return Err(format!("arg0 is incorrect: {:?}. Reason: {:?}", arg0.text, arg0.reason));
+        }
+
+        let gcx = ccx.lock().await.global_context.clone();
+        let client = mcp_sessions_with_clients(gcx).await.into_iter()
+            .find(|(_, _, resources, _)| resources.iter().any(|r| r.uri == arg0.text))
+            .map(|(_, client, _, _)| client)
+            .ok_or(format!("no MCP server provides {:?}", arg0.text))?;
+        let mut context_files = client.lock().await.read_resource(&arg0.text).await
+            .map_err(|e| format!("Failed to read MCP resource {}: {}", arg0.text, e))?;
+
+        // Resources don't exist on disk, postprocess_context_files() would drop them, so this is the final form
+        for context_file in context_files.iter_mut() {
+            truncate_resource_content(context_file, RESOURCE_MAX_CHARS);
+        }
+        let json_vec = context_files.iter().map(|p| json!(p)).collect::<Vec<_>>();
+        let message = ChatMessage::new(
+            "context_file".to_string(),
+            serde_json::to_string(&json_vec).unwrap_or("".to_string()),
+        );
+
+        tracing::info!("executed @mcp-resource {}", arg0.text);
+        let replacement_text = if cmd.pos1 == 0 { "".to_string() } else { arg0.text.clone() };
+        Ok((vec![ContextEnum::ChatMessage(message)], replacement_text))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_resource_content() {
+        let mut context_file = ContextFile {
+            file_name: "docs://readme".to_string(),
+            file_content: "line one\nплюс\n".repeat(3),
+            line1: 1,
+            line2: 6,
+            symbols: vec![],
+            gradient_type: -1,
+            usefulness: 100.0,
+        };
+        truncate_resource_content(&mut context_file, 1000);
+        assert_eq!(context_file.line2, 6);
+
+        // 12 bytes is in the middle of the 2-byte "л", the cut moves back to a char boundary
+        truncate_resource_content(&mut context_file, 12);
+        assert_eq!(context_file.file_content, "line one\nп\n...truncated\n");
+        assert_eq!(context_file.line2, 3);
+    }
+}
@@ -9,7 +9,7 @@ use crate::at_commands::execute_at::AtCommandMember;
 use crate::call_validation::{ContextEnum, ContextFile};
 use crate::caps::get_custom_embedding_api_key;
 use crate::vecdb;
-use crate::vecdb::vdb_structs::VecdbSearch;
+use crate::vecdb::vdb_structs::{SearchMode, VecdbSearch};
 
 
 pub fn text_on_clip(query: &String, from_tool_call: bool) -> String {
@@ -63,6 +63,7 @@ pub async fn execute_at_search(
     ccx: Arc<AMutex<AtCommandsContext>>,
     query: &String,
     vecdb_scope_filter_mb: Option<String>,
+    mode: SearchMode,
 ) -> Result<Vec<ContextFile>, String> {
     let (gcx, top_n) = {
         let ccx_locked = ccx.lock().await;
@@ -80,7 +81,7 @@ pub async fn execute_at_search(
         Some(ref db) => {
             let top_n_twice_as_big = top_n * 2;  // top_n will be cut at postprocessing stage, and we really care about top_n files, not pieces
             // TODO: this code sucks, release lock, don't hold anything during the search
-            let search_result = db.vecdb_search(query.clone(), top_n_twice_as_big, vecdb_scope_filter_mb, &api_key).await?;
+            let search_result = db.vecdb_search(query.clone(), top_n_twice_as_big, vecdb_scope_filter_mb, &api_key, mode).await?;
             let results = search_result.results.clone();
             return Ok(results2message(&results));
         }
@@ -104,7 +105,18 @@ impl AtCommand for AtSearch {
         let args1 = args.iter().map(|x|x.clone()).collect::<Vec<_>>();
         info!("execute @search {:?}", args1.iter().map(|x|x.text.clone()).collect::<Vec<_>>());
 
-        let query = args.iter().map(|x|x.text.clone()).collect::<Vec<_>>().join(" ");
+        // @search --lexical E0432
+        let mut mode = SearchMode::default();
+        let mut query_words = vec![];
+        for arg in args.iter() {
+            match arg.text.as_str() {
+                "--semantic" => mode = SearchMode::Semantic,
+                "--lexical" => mode = SearchMode::Lexical,
+                "--hybrid" => mode = SearchMode::Hybrid,
+                _ => query_words.push(arg.text.clone()),
+            }
+        }
+        let query = query_words.join(" ");
         if query.trim().is_empty() {
             if ccx.lock().await.is_preview {
                 return Ok((vec![], "".to_string()));
@@ -112,7 +124,7 @@ impl AtCommand for AtSearch {
             return Err("Cannot execute search: query is empty.".to_string());
         }
 
-        let vector_of_context_file = execute_at_search(ccx.clone(), &query, None).await?;
+        let vector_of_context_file = execute_at_search(ccx.clone(), &query, None, mode).await?;
         let text = text_on_clip(&query, false);
         Ok((vec_context_file_to_context_tools(vector_of_context_file), text))
     }
@@ -5,6 +5,7 @@ pub mod at_commands;
 pub mod at_file;
 pub mod at_web;
 pub mod at_tree;
+pub mod at_mcp_resource;
 
 #[cfg(feature="vecdb")]
 pub mod at_search;
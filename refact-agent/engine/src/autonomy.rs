@@ -2,12 +2,13 @@ use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::sync::{RwLock as ARwLock, Mutex as AMutex};
 use indexmap::IndexSet;
+use rusqlite::OptionalExtension;
 
 use crate::global_context::GlobalContext;
 use crate::agent_db::db_structs::{CThread, CMessage};
 use crate::agent_db::chore_pubsub_sleeping_procedure;
 use crate::agent_db::db_cthread::CThreadSubscription;
-use crate::call_validation::{ChatContent, ChatMessage, ChatUsage};
+use crate::call_validation::{ChatContent, ChatMessage, ChatMode, ChatUsage};
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::subchat::subchat_single;
 
@@ -58,7 +59,8 @@ pub async fn look_for_a_job(
             might_work_on_cthread_id.remove(&deleted_id);
         }
 
-        while let Some(cthread_id) = might_work_on_cthread_id.iter().next().cloned() {
+        // busy ones (another worker, or the chore has enough workers) stay in the set for the next round
+        for cthread_id in might_work_on_cthread_id.clone().into_iter() {
             match look_if_the_job_for_me(gcx.clone(), &worker_name, &cthread_id).await {
                 Ok(lock_success) => {
                     if lock_success {
@@ -118,6 +120,14 @@ async fn look_if_the_job_for_me(
             return Ok(true);  // true means don't come back to it again
         }
 
+        if let Some(limits) = chore_limits_for_cthread(&tx, &cthread_rec)? {
+            let workers_n = chore_workers_busy(&tx, &limits.chore_id, now)?;
+            if limits.chore_max_workers > 0 && workers_n >= limits.chore_max_workers {
+                tracing::info!("{} {} chore {} already has {} workers", worker_name, cthread_id, limits.chore_id, workers_n);
+                return Ok(false);
+            }
+        }
+
         cthread_rec.cthread_locked_by = worker_name.clone();
         cthread_rec.cthread_locked_ts = now;
         crate::agent_db::db_cthread::cthread_set_lowlevel(&tx, &cthread_rec)?;
@@ -126,21 +136,8 @@ async fn look_if_the_job_for_me(
     };
 
     tracing::info!("{} {} autonomous work start", worker_name, cthread_id);
-    let mut apply_json: serde_json::Value;
-
-    match do_the_job(gcx, worker_name, &cthread_rec, &cmessages).await {
-        Ok(result) => {
-            apply_json = result;
-        }
-        Err(e) => {
-            apply_json = serde_json::json!({
-                "cthread_error": format!("{}", e),
-            });
-        }
-    }
-    apply_json["cthread_id"] = serde_json::json!(cthread_id);
-    apply_json["cthread_locked_by"] = serde_json::json!("");
-    apply_json["cthread_locked_ts"] = serde_json::json!(0);
+    let job_result = do_the_job(gcx, worker_name, &cthread_rec, &cmessages).await;
+    let apply_json = job_result_to_apply_json(job_result, cthread_id);
     tracing::info!("{} {} /autonomous work\n{}", worker_name, cthread_id, apply_json);
     crate::agent_db::db_cthread::cthread_apply_json(cdb, apply_json)?;
 
@@ -186,11 +183,7 @@ async fn do_the_job(
     let message_info_str = message_info.join(", ");
     tracing::info!("{} started work on {}\n[{}]", worker_name, cthread_rec.cthread_id, message_info_str);
 
-    // TODO: make something similar to the `subchat` with chat `wrapping` logic
-    // wrap_up_depth: usize,
-    // wrap_up_tokens_cnt: usize,
-    // wrap_up_prompt: &str,
-    // wrap_up_n: usize,
+    let max_steps = gcx.read().await.cmdline.autonomy_max_steps;
     let mut usage = ChatUsage { ..Default::default() };
     let ccx: Arc<AMutex<AtCommandsContext>> = Arc::new(AMutex::new(AtCommandsContext::new(
         gcx.clone(),
@@ -201,58 +194,204 @@ async fn do_the_job(
         cthread_rec.cthread_id.clone(),
         false,
     ).await));
-    let log_prefix = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
-    let chat_response_msgs = subchat_single(
-        ccx.clone(),
-        cthread_rec.cthread_model.as_str(),
-        messages,
-        None,
-        None,
-        false,
-        Some(cthread_rec.cthread_temperature as f32),
-        Some(cthread_rec.cthread_max_new_tokens),
-        cthread_rec.cthread_n,
-        None,
-        true,
-        Some(&mut usage),
-        Some(cthread_rec.cthread_id.clone()),
-        Some(format!("{log_prefix}-chore-job")),
-    ).await.map_err(|e| format!("Error: {}", e))?;
-
-    let choice0: Vec<ChatMessage> = chat_response_msgs[0].clone();
     {
-        let mut lite_locked = lite.lock();
-        let tx = lite_locked.transaction().map_err(|e| e.to_string())?;
-        for (i, chat_message) in choice0.iter().enumerate() {
-            let mut cmessage_usage_prompt = 0;
-            let mut cmessage_usage_completion = 0;
-            if let Some(u) = &chat_message.usage {
-                cmessage_usage_prompt = u.prompt_tokens as i32;
-                cmessage_usage_completion = u.completion_tokens as i32;
+        let mut ccx_locked = ccx.lock().await;
+        ccx_locked.chat_mode = chat_mode_from_toolset(&cthread_rec.cthread_toolset);
+        ccx_locked.autonomous = true;
+    }
+    let log_prefix = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut messages = messages;
+    let mut next_cmessage_num = cmessages.len() as i32;
+    let mut step_n = 0;
+    loop {
+        // stop conditions are checked against the database, a human or another worker might have changed things
+        let fresh_rec = crate::agent_db::db_cthread::cthread_get(cdb.clone(), cthread_rec.cthread_id.clone())?;
+        let stop_json = stop_condition(&lite.lock(), &fresh_rec, step_n, max_steps)?;
+        if let Some(stop_json) = stop_json {
+            tracing::info!("{} {} stopped after {} steps: {}", worker_name, cthread_rec.cthread_id, step_n, stop_json);
+            return Ok(stop_json);
+        }
+        // the lock shouldn't go stale while the job is still running
+        crate::agent_db::db_cthread::cthread_apply_json(cdb.clone(), serde_json::json!({
+            "cthread_id": cthread_rec.cthread_id,
+            "cthread_locked_by": worker_name,
+            "cthread_locked_ts": SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs_f64(),
+        }))?;
+
+        // if the last message has tool calls, the scratchpad runs the tools first, tool results come back together with the answer
+        let chat_response_msgs = subchat_single(
+            ccx.clone(),
+            cthread_rec.cthread_model.as_str(),
+            messages.clone(),
+            None,
+            None,
+            false,
+            Some(cthread_rec.cthread_temperature as f32),
+            Some(cthread_rec.cthread_max_new_tokens),
+            cthread_rec.cthread_n,
+            None,
+            true,
+            Some(&mut usage),
+            Some(cthread_rec.cthread_id.clone()),
+            Some(format!("{log_prefix}-chore-job")),
+        ).await.map_err(|e| format!("Error: {}", e))?;
+        step_n += 1;
+
+        let choice0: Vec<ChatMessage> = chat_response_msgs[0].clone();
+        let new_messages = choice0.get(messages.len()..).unwrap_or(&[]).to_vec();
+        {
+            let mut lite_locked = lite.lock();
+            let tx = lite_locked.transaction().map_err(|e| e.to_string())?;
+            for chat_message in new_messages.iter() {
+                let mut cmessage_usage_prompt = 0;
+                let mut cmessage_usage_completion = 0;
+                if let Some(u) = &chat_message.usage {
+                    cmessage_usage_prompt = u.prompt_tokens as i32;
+                    cmessage_usage_completion = u.completion_tokens as i32;
+                }
+                let cmessage = CMessage {
+                    cmessage_belongs_to_cthread_id: cthread_rec.cthread_id.clone(),
+                    cmessage_alt: 0,
+                    cmessage_num: next_cmessage_num,
+                    cmessage_prev_alt: 0,
+                    cmessage_usage_model: cthread_rec.cthread_model.clone(),
+                    cmessage_usage_prompt,
+                    cmessage_usage_completion,
+                    cmessage_json: serde_json::to_string(chat_message).map_err(|e| format!("{}", e))?,
+                };
+                crate::agent_db::db_cmessage::cmessage_set(&tx, cmessage);
+                next_cmessage_num += 1;
             }
-            let cmessage = CMessage {
-                cmessage_belongs_to_cthread_id: cthread_rec.cthread_id.clone(),
-                cmessage_alt: 0,
-                cmessage_num: (cmessages.len() as i32) + (i as i32),
-                cmessage_prev_alt: 0,
-                cmessage_usage_model: cthread_rec.cthread_model.clone(),
-                cmessage_usage_prompt,
-                cmessage_usage_completion,
-                cmessage_json: serde_json::to_string(chat_message).map_err(|e| format!("{}", e))?,
-            };
-            crate::agent_db::db_cmessage::cmessage_set(&tx, cmessage);
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+        chore_sleeping_point.notify_waiters();
+        messages = choice0;
+
+        let model_wants_tools = messages.last().map_or(false, |m| {
+            m.role == "assistant" && m.tool_calls.as_ref().map_or(false, |calls| !calls.is_empty())
+        });
+        if new_messages.is_empty() || !model_wants_tools {
+            break;
         }
-        tx.commit().map_err(|e| e.to_string())?;
     }
-    chore_sleeping_point.notify_waiters();
+    tracing::info!("{} {} finished in {} steps, usage {:?}", worker_name, cthread_rec.cthread_id, step_n, usage);
     Ok(serde_json::json!({}))
 }
 
+fn job_result_to_apply_json(
+    job_result: Result<serde_json::Value, String>,
+    cthread_id: &String,
+) -> serde_json::Value {
+    let mut apply_json = match job_result {
+        Ok(result) => result,
+        Err(e) => serde_json::json!({
+            "cthread_error": format!("{}", e),
+        }),
+    };
+    apply_json["cthread_id"] = serde_json::json!(cthread_id);
+    apply_json["cthread_locked_by"] = serde_json::json!("");
+    apply_json["cthread_locked_ts"] = serde_json::json!(0);
+    apply_json
+}
+
+// cthread_toolset is quick/explore/agent, tool_policy.yaml rules see it as the chat mode
+fn chat_mode_from_toolset(toolset: &str) -> ChatMode {
+    match toolset.to_lowercase().as_str() {
+        "quick" | "no_tools" => ChatMode::NO_TOOLS,
+        "explore" => ChatMode::EXPLORE,
+        "configure" => ChatMode::CONFIGURE,
+        "project_summary" => ChatMode::PROJECT_SUMMARY,
+        _ => ChatMode::AGENT,
+    }
+}
+
+// Some(apply_json) means the job must stop before the next model call
+fn stop_condition(
+    conn: &rusqlite::Connection,
+    fresh_rec: &CThread,
+    step_n: usize,
+    max_steps: usize,
+) -> Result<Option<serde_json::Value>, String> {
+    if fresh_rec.cthread_error == "pause" {
+        return Ok(Some(serde_json::json!({})));
+    }
+    if let Some(limits) = chore_limits_for_cthread(conn, fresh_rec)? {
+        let spent = chore_tokens_spent(conn, &limits.chore_id)?;
+        if limits.chore_token_budget > 0 && spent >= limits.chore_token_budget {
+            return Ok(Some(serde_json::json!({
+                "cthread_error": format!("chore token budget exhausted: {} of {} tokens used", spent, limits.chore_token_budget),
+            })));
+        }
+    }
+    if step_n >= max_steps {
+        return Ok(Some(serde_json::json!({
+            "cthread_error": format!("stopped after {} steps, increase --autonomy-max-steps to let it go further", step_n),
+        })));
+    }
+    Ok(None)
+}
+
+struct ChoreLimits {
+    chore_id: String,
+    chore_max_workers: i64,
+    chore_token_budget: i64,
+}
+
+fn chore_limits_for_cthread(
+    conn: &rusqlite::Connection,
+    cthread_rec: &CThread,
+) -> Result<Option<ChoreLimits>, String> {
+    let chore_event_id = match &cthread_rec.cthread_belongs_to_chore_event_id {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    conn.query_row(
+        "SELECT c.chore_id, c.chore_max_workers, c.chore_token_budget
+         FROM chores c JOIN chore_events e ON e.chore_event_belongs_to_chore_id = c.chore_id
+         WHERE e.chore_event_id = ?1",
+        rusqlite::params![chore_event_id],
+        |row| Ok(ChoreLimits {
+            chore_id: row.get(0)?,
+            chore_max_workers: row.get(1)?,
+            chore_token_budget: row.get(2)?,
+        }),
+    ).optional().map_err(|e| e.to_string())
+}
+
+fn chore_workers_busy(
+    conn: &rusqlite::Connection,
+    chore_id: &String,
+    now: f64,
+) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM cthreads t JOIN chore_events e ON t.cthread_belongs_to_chore_event_id = e.chore_event_id
+         WHERE e.chore_event_belongs_to_chore_id = ?1 AND t.cthread_locked_by != '' AND t.cthread_locked_ts + ?2 > ?3",
+        rusqlite::params![chore_id, LOCK_TOO_OLD_SEC, now],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())
+}
+
+fn chore_tokens_spent(
+    conn: &rusqlite::Connection,
+    chore_id: &String,
+) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(m.cmessage_usage_prompt + m.cmessage_usage_completion), 0)
+         FROM cmessages m
+         JOIN cthreads t ON m.cmessage_belongs_to_cthread_id = t.cthread_id
+         JOIN chore_events e ON t.cthread_belongs_to_chore_event_id = e.chore_event_id
+         WHERE e.chore_event_belongs_to_chore_id = ?1",
+        rusqlite::params![chore_id],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())
+}
+
 pub async fn look_for_a_job_start_tasks(
     gcx: Arc<ARwLock<GlobalContext>>,
 ) -> Vec<tokio::task::JoinHandle<()>> {
+    let workers_n = gcx.read().await.cmdline.autonomy_workers;
     let mut handles = Vec::new();
-    for n in 0..1 {
+    for n in 0..workers_n {
         let handle = tokio::spawn(look_for_a_job(
             gcx.clone(),
             n,
@@ -261,3 +400,100 @@ pub async fn look_for_a_job_start_tasks(
     }
     handles
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent_db::db_structs::{Chore, ChoreEvent};
+
+    fn chore_db_in_memory(chore_token_budget: i64, spent_per_message: i32) -> rusqlite::Connection {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::agent_db::db_schema_20241102::create_tables_20241102(&conn, false).unwrap();
+        crate::agent_db::db_schema_20250810::add_chore_limits_20250810(&conn).unwrap();
+        let tx = conn.transaction().unwrap();
+        crate::agent_db::db_chore::chore_set_lowlevel(&tx, &Chore {
+            chore_id: "chore1".to_string(),
+            chore_title: "test chore".to_string(),
+            chore_max_workers: 1,
+            chore_token_budget,
+            ..Default::default()
+        }).unwrap();
+        crate::agent_db::db_chore::chore_event_set_lowlevel(&tx, &ChoreEvent {
+            chore_event_id: "event1".to_string(),
+            chore_event_belongs_to_chore_id: "chore1".to_string(),
+            ..Default::default()
+        }).unwrap();
+        crate::agent_db::db_cthread::cthread_set_lowlevel(&tx, &test_cthread()).unwrap();
+        for cmessage_num in 0..2 {
+            crate::agent_db::db_cmessage::cmessage_set(&tx, CMessage {
+                cmessage_belongs_to_cthread_id: "thread1".to_string(),
+                cmessage_num,
+                cmessage_usage_prompt: spent_per_message,
+                cmessage_usage_completion: spent_per_message,
+                cmessage_json: "{\"role\": \"user\", \"content\": \"hi\"}".to_string(),
+                ..Default::default()
+            });
+        }
+        tx.commit().unwrap();
+        conn
+    }
+
+    fn test_cthread() -> CThread {
+        CThread {
+            cthread_id: "thread1".to_string(),
+            cthread_belongs_to_chore_event_id: Some("event1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_chat_mode_from_toolset() {
+        assert_eq!(chat_mode_from_toolset("explore"), ChatMode::EXPLORE);
+        assert_eq!(chat_mode_from_toolset("quick"), ChatMode::NO_TOOLS);
+        assert_eq!(chat_mode_from_toolset("agent"), ChatMode::AGENT);
+        assert_eq!(chat_mode_from_toolset(""), ChatMode::AGENT);
+    }
+
+    #[test]
+    fn test_stop_conditions() {
+        let conn = chore_db_in_memory(1000, 100);
+        let mut rec = test_cthread();
+        assert_eq!(stop_condition(&conn, &rec, 0, 30).unwrap(), None);
+
+        let stop_json = stop_condition(&conn, &rec, 30, 30).unwrap().unwrap();
+        assert!(stop_json["cthread_error"].as_str().unwrap().starts_with("stopped after 30 steps"));
+
+        rec.cthread_error = "pause".to_string();
+        assert_eq!(stop_condition(&conn, &rec, 30, 30).unwrap(), Some(serde_json::json!({})));
+
+        let conn = chore_db_in_memory(400, 100);
+        let stop_json = stop_condition(&conn, &test_cthread(), 0, 30).unwrap().unwrap();
+        assert_eq!(stop_json["cthread_error"], "chore token budget exhausted: 400 of 400 tokens used");
+
+        // 0 means no budget, and a cthread outside of any chore has no limits at all
+        let mut conn = chore_db_in_memory(0, 100);
+        assert_eq!(stop_condition(&conn, &test_cthread(), 0, 30).unwrap(), None);
+
+        // a stale lock doesn't occupy a worker slot of the chore
+        assert_eq!(chore_workers_busy(&conn, &"chore1".to_string(), 1000.0).unwrap(), 0);
+        let tx = conn.transaction().unwrap();
+        let locked_rec = CThread { cthread_locked_by: "aworker-1-0".to_string(), cthread_locked_ts: 1000.0, ..test_cthread() };
+        crate::agent_db::db_cthread::cthread_set_lowlevel(&tx, &locked_rec).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(chore_workers_busy(&conn, &"chore1".to_string(), 1000.0).unwrap(), 1);
+        assert_eq!(chore_workers_busy(&conn, &"chore1".to_string(), 1000.0 + LOCK_TOO_OLD_SEC).unwrap(), 0);
+        let free_rec = CThread { cthread_belongs_to_chore_event_id: None, ..test_cthread() };
+        assert!(chore_limits_for_cthread(&conn, &free_rec).unwrap().is_none());
+
+        let apply_json = job_result_to_apply_json(Err("Error: model is down".to_string()), &"thread1".to_string());
+        assert_eq!(apply_json, serde_json::json!({
+            "cthread_error": "Error: model is down",
+            "cthread_id": "thread1",
+            "cthread_locked_by": "",
+            "cthread_locked_ts": 0,
+        }));
+        let apply_json = job_result_to_apply_json(Ok(stop_json), &"thread1".to_string());
+        assert_eq!(apply_json["cthread_error"], "chore token budget exhausted: 400 of 400 tokens used");
+        assert_eq!(apply_json["cthread_locked_by"], "");
+    }
+}
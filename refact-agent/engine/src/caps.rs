@@ -45,6 +45,24 @@ pub struct ModelRecord {
     pub supports_boost_reasoning: bool,
     #[serde(default)]
     pub default_temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ModelProvider>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>,  // tried in order when the provider answers 429 or 5xx
+}
+
+// Where a single model lives, overrides chat_*/completion_* fields of the caps for that model.
+// Empty fields fall back to the caps-level values.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ModelProvider {
+    #[serde(default)]
+    pub endpoint: String,
+    #[serde(default)]
+    pub endpoint_style: String,
+    #[serde(default)]
+    pub apikey: String,  // "$ENV_VAR" works the same way as in chat_apikey
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -211,6 +229,11 @@ fn load_caps_from_buf(
 
     _inherit_r1_from_r0(&mut r1, &r0);
     apply_models_dict_patch(&mut r1);
+    for rec in r1.code_completion_models.values_mut().chain(r1.code_chat_models.values_mut()) {
+        if let Some(provider) = rec.provider.as_mut() {
+            provider.endpoint = relative_to_full_url(&caps_url, &provider.endpoint)?;
+        }
+    }
     r1.endpoint_template = relative_to_full_url(&caps_url, &r1.endpoint_template)?;
     r1.endpoint_chat_passthrough = relative_to_full_url(&caps_url, &r1.endpoint_chat_passthrough)?;
     if r1.endpoint_chat_passthrough.is_empty() {
@@ -423,6 +446,12 @@ fn apply_models_dict_patch(caps: &mut CodeAssistantCaps) {
         if rec_patched.supports_tools {
             rec.supports_tools = rec_patched.supports_tools;
         }
+        if rec_patched.provider.is_some() {
+            rec.provider = rec_patched.provider.clone();
+        }
+        if !rec_patched.fallbacks.is_empty() {
+            rec.fallbacks = rec_patched.fallbacks.clone();
+        }
     }
 
     for (model, rec_patched) in caps.models_dict_patch.iter() {
@@ -435,6 +464,16 @@ fn apply_models_dict_patch(caps: &mut CodeAssistantCaps) {
     }
 }
 
+// Known models replace what caps say about a model, except where the model is served from and what replaces it
+fn _keep_provider_and_fallbacks(known: &ModelRecord, from_caps: Option<&ModelRecord>) -> ModelRecord {
+    let mut rec = known.clone();
+    if let Some(from_caps) = from_caps {
+        rec.provider = from_caps.provider.clone();
+        rec.fallbacks = from_caps.fallbacks.clone();
+    }
+    rec
+}
+
 fn _inherit_r1_from_r0(
     r1: &mut CodeAssistantCaps,
     r0: &ModelsOnly,
@@ -445,13 +484,15 @@ fn _inherit_r1_from_r0(
 
         for (rec_name, rec) in r0.code_completion_models.iter() {
             if rec_name == &k_stripped || rec.similar_models.contains(&k_stripped) {
-                r1.code_completion_models.insert(k.to_string(), rec.clone());
+                let rec = _keep_provider_and_fallbacks(rec, r1.code_completion_models.get(k));
+                r1.code_completion_models.insert(k.to_string(), rec);
             }
         }
 
         for (rec_name, rec) in r0.code_chat_models.iter() {
             if rec_name == &k_stripped || rec.similar_models.contains(&k_stripped) {
-                r1.code_chat_models.insert(k.to_string(), rec.clone());
+                let rec = _keep_provider_and_fallbacks(rec, r1.code_chat_models.get(k));
+                r1.code_chat_models.insert(k.to_string(), rec);
             }
         }
     }
@@ -523,6 +564,18 @@ pub fn which_scratchpad_to_use<'a>(
     }
 }
 
+pub fn model_record_any_kind(
+    caps: &CodeAssistantCaps,
+    model: &str,
+) -> Option<ModelRecord> {
+    let no_finetune = strip_model_from_finetune(&model.to_string());
+    caps.code_chat_models.get(model)
+        .or_else(|| caps.code_completion_models.get(model))
+        .or_else(|| caps.code_chat_models.get(&no_finetune))
+        .or_else(|| caps.code_completion_models.get(&no_finetune))
+        .cloned()
+}
+
 pub async fn get_model_record(
     gcx: Arc<ARwLock<GlobalContext>>,
     model: &str,
@@ -559,6 +612,16 @@ embedding_size: 1536
 # completion_apikey: "hf_..."    # or use $HF_TOKEN if you have it in global environment variables
 # completion_model: bigcode/starcoder2-3b
 
+# Any model can live at its own provider, and fall back to other models when its provider answers 429 or 5xx
+# (list it in running_models too):
+# code_chat_models:
+#   claude-3-7-sonnet-20250219:
+#     provider:
+#       endpoint: "https://api.anthropic.com/v1/messages"
+#       endpoint_style: "anthropic"
+#       apikey: "$ANTHROPIC_API_KEY"
+#     fallbacks: [gpt-4o]
+
 running_models:   # all models mentioned in *_model are automatically running, but you can add more
   - gpt-4o-mini
   - gpt-4o
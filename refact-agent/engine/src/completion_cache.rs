@@ -1,10 +1,15 @@
 use crate::call_validation::CodeCompletionPost;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::Mutex as StdMutex;
 use std::sync::RwLock as StdRwLock;
 use std::collections::HashMap;
 
 use ropey::Rope;
-// use tracing::info;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
 
 const CACHE_ENTRIES: usize = 500;
 const CACHE_KEY_CHARS: usize = 5000;  // max memory CACHE_KEY_CHARS * CACHE_ENTRIES = 2500000 = 2.5M
@@ -42,46 +47,280 @@ impl CompletionSaveToCache {
 pub struct CompletionCache {
     pub map: HashMap<(String, String), serde_json::Value>,
     pub in_added_order: Vec<(String, String)>,
+    pub disk: Option<CompletionCacheDisk>,
+    pub hits: u64,
+    pub misses: u64,
 }
 
 impl CompletionCache {
     pub fn new(
     ) -> Self {
-        Self { map: HashMap::new(), in_added_order: Vec::new() }
+        Self { map: HashMap::new(), in_added_order: Vec::new(), disk: None, hits: 0, misses: 0 }
     }
+
+    pub fn status(&self) -> CompletionCacheStatus {
+        CompletionCacheStatus {
+            hits: self.hits,
+            misses: self.misses,
+            memory_entries: self.map.len(),
+            disk_enabled: self.disk.is_some(),
+            disk_entries: self.disk.as_ref().map(|d| d.entries).unwrap_or(0),
+            disk_bytes: self.disk.as_ref().map(|d| d.bytes).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CompletionCacheStatus {
+    pub hits: u64,
+    pub misses: u64,
+    pub memory_entries: usize,
+    pub disk_enabled: bool,
+    pub disk_entries: usize,
+    pub disk_bytes: usize,
+}
+
+// Survives restarts: the in-memory map is the hot layer, misses there look here.
+// Keys are hashed, entries are evicted least recently used first when over max_bytes,
+// expire after ttl_seconds, and an entry made by another model is a miss (and gets deleted).
+#[derive(Debug)]
+pub struct CompletionCacheDisk {
+    conn: StdMutex<rusqlite::Connection>,  // Connection is not Sync
+    max_bytes: usize,
+    ttl_seconds: u64,
+    entries: usize,
+    bytes: usize,
+}
+
+fn now_ts() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64
 }
 
+fn disk_key(key: &(String, String)) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(key.0.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(key.1.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+impl CompletionCacheDisk {
+    pub fn open(path: &Path, max_bytes: usize, ttl_seconds: u64) -> Result<Self, String> {
+        let conn = rusqlite::Connection::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+        conn.execute_batch("
+            PRAGMA journal_mode=WAL;
+            PRAGMA synchronous=NORMAL;
+            CREATE TABLE IF NOT EXISTS completion_cache (
+                key_hash TEXT PRIMARY KEY,
+                model TEXT NOT NULL,
+                value TEXT NOT NULL,
+                bytes INTEGER NOT NULL,
+                created_ts INTEGER NOT NULL,
+                last_used_ts INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS completion_cache_last_used ON completion_cache(last_used_ts);
+        ").map_err(|e| format!("completion cache schema: {}", e))?;
+        conn.execute("DELETE FROM completion_cache WHERE created_ts < ?1", params![now_ts() - ttl_seconds as i64])
+            .map_err(|e| format!("completion cache cleanup: {}", e))?;
+        let (entries, bytes): (i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(bytes), 0) FROM completion_cache", [], |row| Ok((row.get(0)?, row.get(1)?))
+        ).map_err(|e| format!("completion cache size: {}", e))?;
+        let mut disk = CompletionCacheDisk {
+            conn: StdMutex::new(conn),
+            max_bytes,
+            ttl_seconds,
+            entries: entries as usize,
+            bytes: bytes as usize,
+        };
+        disk.evict()?;
+        info!("completion cache {}: {} entries, {} bytes", path.display(), disk.entries, disk.bytes);
+        Ok(disk)
+    }
+
+    fn get(&mut self, key: &(String, String), model: &str) -> Result<Option<serde_json::Value>, String> {
+        let key_hash = disk_key(key);
+        let now = now_ts();
+        let conn = self.conn.get_mut().unwrap();
+        let row: Option<(String, String, i64, i64)> = conn.query_row(
+            "SELECT model, value, bytes, created_ts FROM completion_cache WHERE key_hash = ?1",
+            params![key_hash],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        ).optional().map_err(|e| e.to_string())?;
+        let (row_model, value, bytes, created_ts) = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        if row_model != model || created_ts + (self.ttl_seconds as i64) < now {
+            conn.execute("DELETE FROM completion_cache WHERE key_hash = ?1", params![key_hash]).map_err(|e| e.to_string())?;
+            self.entries = self.entries.saturating_sub(1);
+            self.bytes = self.bytes.saturating_sub(bytes as usize);
+            return Ok(None);
+        }
+        conn.execute("UPDATE completion_cache SET last_used_ts = ?1 WHERE key_hash = ?2", params![now, key_hash]).map_err(|e| e.to_string())?;
+        serde_json::from_str(&value).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn put_many(&mut self, items: &[((String, String), String, serde_json::Value)]) -> Result<(), String> {
+        let now = now_ts();
+        let conn = self.conn.get_mut().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for (key, model, value) in items {
+            let value_str = value.to_string();
+            let bytes = value_str.len() + 64;  // plus the key hash
+            // first completion wins, the same as in memory
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO completion_cache (key_hash, model, value, bytes, created_ts, last_used_ts) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                params![disk_key(key), model, value_str, bytes as i64, now],
+            ).map_err(|e| e.to_string())?;
+            if inserted > 0 {
+                self.entries += 1;
+                self.bytes += bytes;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        self.evict()
+    }
+
+    fn evict(&mut self) -> Result<(), String> {
+        if self.bytes <= self.max_bytes {
+            return Ok(());
+        }
+        let target = self.max_bytes / 10 * 9;  // some room, so it doesn't happen on every put
+        let conn = self.conn.get_mut().unwrap();
+        let mut victims: Vec<(String, usize)> = vec![];
+        {
+            let mut stmt = conn.prepare("SELECT key_hash, bytes FROM completion_cache ORDER BY last_used_ts ASC")
+                .map_err(|e| e.to_string())?;
+            let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+            let mut bytes_left = self.bytes;
+            while bytes_left > target {
+                let row = match rows.next().map_err(|e| e.to_string())? {
+                    Some(row) => row,
+                    None => break,
+                };
+                let bytes: i64 = row.get(1).map_err(|e| e.to_string())?;
+                victims.push((row.get(0).map_err(|e| e.to_string())?, bytes as usize));
+                bytes_left = bytes_left.saturating_sub(bytes as usize);
+            }
+        }
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for (key_hash, bytes) in victims.iter() {
+            tx.execute("DELETE FROM completion_cache WHERE key_hash = ?1", params![key_hash]).map_err(|e| e.to_string())?;
+            self.entries = self.entries.saturating_sub(1);
+            self.bytes = self.bytes.saturating_sub(*bytes);
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+// Might read sqlite, call it from spawn_blocking in async code
 pub fn cache_get(
     cache: Arc<StdRwLock<CompletionCache>>,
     key: (String, String),
+    model: &str,
 ) -> Option<serde_json::Value> {
-    let cache_locked = cache.write().unwrap();
+    let mut cache_locked = cache.write().unwrap();
     if let Some(value) = cache_locked.map.get(&key) {
-        return Some(value.clone());
+        if value.get("model").and_then(|m| m.as_str()) == Some(model) {
+            let value = value.clone();
+            cache_locked.hits += 1;
+            return Some(value);
+        }
     }
+    let from_disk = match cache_locked.disk.as_mut().map(|disk| disk.get(&key, model)) {
+        Some(Ok(value_mb)) => value_mb,
+        Some(Err(e)) => {
+            warn!("completion cache read: {}", e);
+            None
+        }
+        None => None,
+    };
+    if let Some(value) = from_disk {
+        cache_locked.hits += 1;
+        _memory_put(&mut cache_locked, key, value.clone());
+        return Some(value);
+    }
+    cache_locked.misses += 1;
     None
 }
 
-pub fn cache_put(
-    cache: Arc<StdRwLock<CompletionCache>>,
-    new_key: (String, String),
+fn _cut_key(key: (String, String)) -> (String, String) {
+    let mut new_key_copy = key;
+    let k0_chars = new_key_copy.0.chars();
+    if k0_chars.clone().count() > CACHE_KEY_CHARS {
+        new_key_copy.0 = k0_chars.clone().skip(k0_chars.count() - CACHE_KEY_CHARS).collect();
+    }
+    new_key_copy
+}
+
+fn _memory_put(
+    cache_locked: &mut CompletionCache,
+    new_key_copy: (String, String),
     value: serde_json::Value,
 ) {
-    let mut cache_locked = cache.write().unwrap();
     while cache_locked.in_added_order.len() > CACHE_ENTRIES {
         let old_key = cache_locked.in_added_order.remove(0);
         cache_locked.map.remove(&old_key);
     }
     // info!("cache put: {:?} = {:?}", new_key, value);
-    let mut new_key_copy = new_key.clone();
-    let k0_chars = new_key_copy.0.chars();
-    if k0_chars.clone().count() > CACHE_KEY_CHARS {
-        new_key_copy.0 = k0_chars.clone().skip(k0_chars.count() - CACHE_KEY_CHARS).collect();
+    let other_model = cache_locked.map.get(&new_key_copy).map(|old| old.get("model") != value.get("model")).unwrap_or(false);
+    if other_model {
+        cache_locked.map.insert(new_key_copy.clone(), value);
+    } else {
+        cache_locked.map.entry(new_key_copy.clone()).or_insert(value);
     }
-    cache_locked.map.entry(new_key_copy.clone()).or_insert(value);
     cache_locked.in_added_order.push(new_key_copy.clone());
 }
 
+pub fn cache_put(
+    cache: Arc<StdRwLock<CompletionCache>>,
+    new_key: (String, String),
+    value: serde_json::Value,
+) {
+    cache_put_many(cache, vec![(new_key, value)]);
+}
+
+pub fn cache_put_many(
+    cache: Arc<StdRwLock<CompletionCache>>,
+    items: Vec<((String, String), serde_json::Value)>,
+) {
+    let for_disk = {
+        let mut cache_locked = cache.write().unwrap();
+        let mut for_disk = vec![];
+        for (new_key, value) in items {
+            let new_key_copy = _cut_key(new_key);
+            if cache_locked.disk.is_some() {
+                let model = value.get("model").and_then(|m| m.as_str()).unwrap_or("").to_string();
+                for_disk.push((new_key_copy.clone(), model, value.clone()));
+            }
+            _memory_put(&mut cache_locked, new_key_copy, value);
+        }
+        for_disk
+    };
+    if for_disk.is_empty() {
+        return;
+    }
+    // sqlite calls block, keep them off the async runtime threads (this runs from Drop inside request handlers)
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(move || _disk_put_many(cache, for_disk));
+        }
+        Err(_) => _disk_put_many(cache, for_disk),
+    }
+}
+
+fn _disk_put_many(
+    cache: Arc<StdRwLock<CompletionCache>>,
+    for_disk: Vec<((String, String), String, serde_json::Value)>,
+) {
+    let mut cache_locked = cache.write().unwrap();
+    if let Some(disk) = cache_locked.disk.as_mut() {
+        if let Err(e) = disk.put_many(&for_disk) {
+            warn!("completion cache write: {}", e);
+        }
+    }
+}
+
 pub fn cache_key_from_post(
     post: &CodeCompletionPost,
 ) -> (String, String) {
@@ -152,13 +391,14 @@ impl Drop for CompletionSaveToCache {
         } else {
             believe_chars += 1;
         }
+        let mut items = vec![];
         for char_num in 0..believe_chars {
             let code_completion_ahead: String = self.completion0_text.chars().skip(char_num).collect();
             let cache_key_ahead: (String, String) = (
                 self.cache_key.0.clone() + &self.completion0_text.chars().take(char_num).collect::<String>(),
                 self.cache_key.1.clone()
             );
-            cache_put(self.cache_arc.clone(), cache_key_ahead, serde_json::json!(
+            items.push((cache_key_ahead, serde_json::json!(
                 {
                     "choices": [{
                         "index": 0,
@@ -169,7 +409,56 @@ impl Drop for CompletionSaveToCache {
                     "cached": true,
                     "snippet_telemetry_id": self.completion0_snippet_telemetry_id,
                 }
-            ));
+            )));
+        }
+        cache_put_many(self.cache_arc.clone(), items);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(s: &str) -> (String, String) {
+        (s.to_string(), "singleline".to_string())
+    }
+
+    #[test]
+    fn test_disk_cache_survives_restart_and_checks_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("completion_cache.sqlite");
+        let mut disk = CompletionCacheDisk::open(&path, 1024 * 1024, 3600).unwrap();
+        disk.put_many(&[(key("def f("), "starcoder2".to_string(), json!({"model": "starcoder2", "choices": []}))]).unwrap();
+        drop(disk);
+
+        let mut disk = CompletionCacheDisk::open(&path, 1024 * 1024, 3600).unwrap();
+        assert_eq!(disk.entries, 1);
+        assert!(disk.get(&key("def f("), "starcoder2").unwrap().is_some());
+        assert!(disk.get(&key("def g("), "starcoder2").unwrap().is_none());
+        // another model: a miss, and the stale entry is gone
+        assert!(disk.get(&key("def f("), "qwen2.5-coder").unwrap().is_none());
+        assert_eq!(disk.entries, 0);
+        assert_eq!(disk.bytes, 0);
+    }
+
+    #[test]
+    fn test_disk_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let value = json!({"model": "m", "code_completion": "x".repeat(900)});
+        let entry_bytes = value.to_string().len() + 64;
+        let mut disk = CompletionCacheDisk::open(&dir.path().join("c.sqlite"), entry_bytes * 3, 3600).unwrap();
+        disk.put_many(&[(key("a"), "m".to_string(), value.clone()), (key("b"), "m".to_string(), value.clone())]).unwrap();
+        {
+            // "b" is the oldest, "a" the most recently used one, timestamps have a second resolution
+            let conn = disk.conn.get_mut().unwrap();
+            conn.execute("UPDATE completion_cache SET last_used_ts = last_used_ts - 10 WHERE key_hash = ?1", params![disk_key(&key("b"))]).unwrap();
+            conn.execute("UPDATE completion_cache SET last_used_ts = last_used_ts + 10 WHERE key_hash = ?1", params![disk_key(&key("a"))]).unwrap();
         }
+        disk.put_many(&[(key("c"), "m".to_string(), value.clone()), (key("d"), "m".to_string(), value.clone())]).unwrap();
+        assert!(disk.bytes <= entry_bytes * 3);
+        assert!(disk.get(&key("b"), "m").unwrap().is_none());
+        assert!(disk.get(&key("a"), "m").unwrap().is_some());
     }
 }
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::dashboard::structs::RHData;
+use crate::telemetry::utils::{read_file, telemetry_local_history_dir};
+
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DashboardFilter {
+    #[serde(default)]
+    pub date_from: Option<String>,  // "YYYY-MM-DD", inclusive
+    #[serde(default)]
+    pub date_to: Option<String>,    // "YYYY-MM-DD", inclusive
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalHistoryLine {
+    records: Vec<Value>,
+    ts_start: i64,
+    ts_end: i64,
+}
+
+fn parse_date(date: &str, time: NaiveTime) -> Result<i64, String> {
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|e| format!("cannot parse date {:?}, expected YYYY-MM-DD: {}", date, e))?;
+    Ok(DateTime::<Utc>::from_naive_utc_and_offset(date.and_time(time), Utc).timestamp())
+}
+
+impl DashboardFilter {
+    pub fn apply(&self, records: Vec<RHData>) -> Result<Vec<RHData>, String> {
+        let ts_from = match &self.date_from {
+            Some(d) if !d.is_empty() => Some(parse_date(d, NaiveTime::from_hms_opt(0, 0, 0).unwrap())?),
+            _ => None,
+        };
+        let ts_to = match &self.date_to {
+            Some(d) if !d.is_empty() => Some(parse_date(d, NaiveTime::from_hms_opt(23, 59, 59).unwrap())?),
+            _ => None,
+        };
+        let project = self.project.clone().filter(|p| !p.is_empty());
+        Ok(records.into_iter()
+            .filter(|r| ts_from.map_or(true, |ts| r.ts_end >= ts))
+            .filter(|r| ts_to.map_or(true, |ts| r.ts_end <= ts))
+            // records from the server don't know their project, a project filter can't tell them apart
+            .filter(|r| r.project.is_empty() || project.as_ref().map_or(true, |p| &r.project == p))
+            .collect())
+    }
+}
+
+fn parse_local_history(text: &str) -> Vec<RHData> {
+    let mut result = vec![];
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let parsed: LocalHistoryLine = match serde_json::from_str(line) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!("skipping bad local telemetry line: {}", e);
+                continue;
+            }
+        };
+        for rec in parsed.records {
+            result.push(RHData {
+                id: result.len() as i64,
+                tenant_name: String::new(),
+                ts_reported: parsed.ts_end,
+                ip: String::new(),
+                enduser_client_version: String::new(),
+                completions_cnt: rec["completions_cnt"].as_i64().unwrap_or(0),
+                file_extension: rec["file_extension"].as_str().unwrap_or("").to_string(),
+                human_characters: rec["human_characters"].as_i64().unwrap_or(0),
+                model: rec["model"].as_str().unwrap_or("").to_string(),
+                robot_characters: rec["robot_characters"].as_i64().unwrap_or(0),
+                teletype: "robot_human".to_string(),
+                ts_start: parsed.ts_start,
+                ts_end: parsed.ts_end,
+                project: rec["project"].as_str().unwrap_or("").to_string(),
+            });
+        }
+    }
+    result
+}
+
+pub async fn load_local_rh_records(cache_dir: &PathBuf) -> Vec<RHData> {
+    let dir = telemetry_local_history_dir(cache_dir).await;
+    let mut paths = vec![];
+    if let Ok(mut entries) = tokio::fs::read_dir(&dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.to_string_lossy().ends_with("-rh.jsonl") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    let mut records = vec![];
+    for path in paths {
+        match read_file(path.clone()).await {
+            Ok(text) => records.extend(parse_local_history(&text)),
+            Err(e) => warn!("cannot read {}: {}", path.display(), e),
+        }
+    }
+    records
+}
+
+pub fn local_projects(records: &Vec<RHData>) -> Vec<String> {
+    let mut projects = records.iter()
+        .map(|r| r.project.clone())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>();
+    projects.sort();
+    projects.dedup();
+    projects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_history_filter() {
+        // 2025-03-01 12:00 UTC and 2025-03-05 12:00 UTC
+        let text = "\
+{\"records\":[{\"file_extension\":\"rs\",\"model\":\"m\",\"project\":\"alpha\",\"human_characters\":10,\"robot_characters\":5,\"completions_cnt\":1}],\"ts_start\":1740830400,\"ts_end\":1740830400}
+not json
+{\"records\":[{\"file_extension\":\"py\",\"model\":\"m\",\"project\":\"beta\",\"human_characters\":3,\"robot_characters\":7,\"completions_cnt\":2}],\"ts_start\":1741176000,\"ts_end\":1741176000}
+";
+        let records = parse_local_history(text);
+        assert_eq!(records.len(), 2);
+        assert_eq!(local_projects(&records), vec!["alpha".to_string(), "beta".to_string()]);
+
+        let filter = DashboardFilter { project: Some("beta".to_string()), ..Default::default() };
+        let filtered = filter.apply(parse_local_history(text)).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].file_extension, "py");
+
+        let filter = DashboardFilter { date_from: Some("2025-03-01".to_string()), date_to: Some("2025-03-01".to_string()), project: None };
+        let filtered = filter.apply(parse_local_history(text)).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].project, "alpha");
+
+        let filter = DashboardFilter { date_from: Some("March".to_string()), ..Default::default() };
+        assert!(filter.apply(parse_local_history(text)).is_err());
+
+        let mut from_server = parse_local_history(text);
+        from_server.iter_mut().for_each(|r| r.project = String::new());
+        let filter = DashboardFilter { project: Some("beta".to_string()), ..Default::default() };
+        assert_eq!(filter.apply(from_server).unwrap().len(), 2);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod dashboard;
+pub mod local_history;
 pub mod structs;
 mod utils;
@@ -48,6 +48,8 @@ pub struct RHData {
     pub teletype: String,
     pub ts_start: i64,
     pub ts_end: i64,
+    #[serde(default)]
+    pub project: String,  // only in local history, the server doesn't know it
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
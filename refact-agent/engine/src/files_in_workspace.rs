@@ -516,7 +516,7 @@ pub fn is_path_to_enqueue_valid(path: &PathBuf) -> Result<(), String> {
     Ok(())
 }
 
-async fn enqueue_some_docs(
+pub async fn enqueue_some_docs(
     gcx: Arc<ARwLock<GlobalContext>>,
     paths: &Vec<String>,
     force: bool,
@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::USER_AGENT;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest_eventsource::EventSource;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::call_validation::{ChatMeta, SamplingParameters};
+use crate::forward_to_openai_endpoint::insert_extra_headers;
+use crate::scratchpads::scratchpad_utils::parse_image_b64_from_image_url_openai;
+
+
+// Native Anthropic Messages API, chat_endpoint_style: "anthropic". The passthrough scratchpad still produces
+// OpenAI-style messages, here they become Messages API blocks, and answers are turned back into OpenAI-style
+// choices/deltas, so the rest of restream, subchats and the UI don't need to know.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub async fn forward_to_anthropic_endpoint(
+    save_url: &mut String,
+    bearer: String,
+    extra_headers: &HashMap<String, String>,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_chat_passthrough: &String,
+    sampling_parameters: &SamplingParameters,
+    is_metadata_supported: bool,
+    meta: Option<ChatMeta>
+) -> Result<Value, String> {
+    let url = endpoint_chat_passthrough.clone();
+    save_url.clone_from(&url);
+    let data = anthropic_request_body(model_name, prompt, sampling_parameters, false, meta)?;
+    // When cancelling requests, coroutine ususally gets aborted here on the following line.
+    let req = client.post(&url)
+        .headers(anthropic_headers(&bearer, extra_headers, is_metadata_supported))
+        .body(data.to_string())
+        .send()
+        .await;
+    let resp = req.map_err(|e| format!("{}", e))?;
+    let status_code = resp.status().as_u16();
+    let response_txt = resp.text().await.map_err(|e|
+        format!("reading from socket {}: {}", url, e)
+    )?;
+    // Errors are {"type": "error", "error": {...}}, passed up as they are, restream reports the "error" field
+    if status_code != 200 && status_code != 400 {
+        return Err(format!("{} status={} text {}", url, status_code, response_txt));
+    }
+    if status_code != 200 {
+        info!("forward_to_anthropic_endpoint: {} {}\n{}", url, status_code, response_txt);
+    }
+    let parsed_json: Value = match serde_json::from_str(&response_txt) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Failed to parse JSON response: {}\n{}", e, response_txt)),
+    };
+    if parsed_json.get("error").is_some() {
+        return Ok(parsed_json);
+    }
+    Ok(anthropic_response_to_openai(&parsed_json))
+}
+
+pub async fn forward_to_anthropic_endpoint_streaming(
+    save_url: &mut String,
+    bearer: String,
+    extra_headers: &HashMap<String, String>,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_chat_passthrough: &String,
+    sampling_parameters: &SamplingParameters,
+    is_metadata_supported: bool,
+    meta: Option<ChatMeta>
+) -> Result<EventSource, String> {
+    let url = endpoint_chat_passthrough.clone();
+    save_url.clone_from(&url);
+    let data = anthropic_request_body(model_name, prompt, sampling_parameters, true, meta)?;
+    let builder = client.post(&url)
+        .headers(anthropic_headers(&bearer, extra_headers, is_metadata_supported))
+        .body(data.to_string());
+    let event_source: EventSource = EventSource::new(builder).map_err(|e|
+        format!("can't stream from {}: {}", url, e)
+    )?;
+    Ok(event_source)
+}
+
+fn anthropic_headers(bearer: &str, extra_headers: &HashMap<String, String>, is_metadata_supported: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
+    if !bearer.is_empty() {
+        if let Ok(api_key) = HeaderValue::from_str(bearer) {
+            headers.insert("x-api-key", api_key);
+        }
+    }
+    headers.insert("anthropic-version", HeaderValue::from_static(ANTHROPIC_VERSION));
+    insert_extra_headers(&mut headers, extra_headers);
+    if is_metadata_supported {
+        headers.insert(USER_AGENT, HeaderValue::from_str(format!("refact-lsp {}", crate::version::build_info::PKG_VERSION).as_str()).unwrap());
+    }
+    headers
+}
+
+fn anthropic_request_body(
+    model_name: &str,
+    prompt: &str,
+    sampling_parameters: &SamplingParameters,
+    stream: bool,
+    meta: Option<ChatMeta>,
+) -> Result<Value, String> {
+    if !prompt.starts_with("PASSTHROUGH ") {
+        return Err("anthropic endpoint style works only with chat models that use the passthrough scratchpad".to_string());
+    }
+    let big_json: Value = serde_json::from_str(&prompt[12..])
+        .map_err(|e| format!("passthrough prompt is not a valid json: {}", e))?;
+    let openai_messages = big_json.get("messages").and_then(|x| x.as_array()).cloned().unwrap_or_default();
+    let (mut system, mut messages) = openai_messages_to_anthropic(&openai_messages);
+
+    let mut data = json!({
+        "model": model_name,
+        "stream": stream,
+        "max_tokens": sampling_parameters.max_new_tokens,
+    });
+
+    // Cache breakpoints: tools and system prompt rarely change, the last message makes the next turn a cache hit
+    let mut tools = big_json.get("tools").and_then(|x| x.as_array()).map(|x| openai_tools_to_anthropic(x)).unwrap_or_default();
+    if let Some(last_tool) = tools.last_mut() {
+        last_tool["cache_control"] = json!({"type": "ephemeral"});
+    }
+    if let Some(last_system_block) = system.last_mut() {
+        last_system_block["cache_control"] = json!({"type": "ephemeral"});
+    }
+    if let Some(last_block) = messages.last_mut()
+        .and_then(|m| m.get_mut("content"))
+        .and_then(|c| c.as_array_mut())
+        .and_then(|c| c.last_mut()) {
+        last_block["cache_control"] = json!({"type": "ephemeral"});
+    }
+
+    if !system.is_empty() {
+        data["system"] = Value::Array(system);
+    }
+    data["messages"] = Value::Array(messages);
+    if !tools.is_empty() {
+        data["tools"] = Value::Array(tools);
+        if let Some(tool_choice) = big_json.get("tool_choice").and_then(openai_tool_choice_to_anthropic) {
+            data["tool_choice"] = tool_choice;
+        }
+    }
+    let stop_sequences = sampling_parameters.stop.iter().filter(|s| !s.trim().is_empty()).cloned().collect::<Vec<_>>();
+    if !stop_sequences.is_empty() {
+        data["stop_sequences"] = json!(stop_sequences);
+    }
+    if let Some(thinking) = sampling_parameters.thinking.clone() {
+        data["thinking"] = thinking;  // temperature must stay unset when thinking
+    } else if let Some(temperature) = sampling_parameters.temperature {
+        data["temperature"] = json!(temperature);
+    }
+    if let Some(meta) = meta.filter(|m| !m.chat_id.is_empty()) {
+        data["metadata"] = json!({"user_id": meta.chat_id});  // the only metadata field the API accepts
+    }
+    Ok(data)
+}
+
+fn text_block(text: &str) -> Value {
+    json!({"type": "text", "text": text})
+}
+
+fn openai_content_to_anthropic_blocks(content: &Value) -> Vec<Value> {
+    match content {
+        Value::String(s) if !s.is_empty() => vec![text_block(s)],
+        Value::Array(elements) => elements.iter().filter_map(|el| {
+            match el.get("type").and_then(|t| t.as_str()) {
+                Some("text") => el.get("text").and_then(|t| t.as_str()).filter(|t| !t.is_empty()).map(text_block),
+                Some("image_url") => {
+                    let url = el.get("image_url").and_then(|u| u.get("url")).and_then(|u| u.as_str()).unwrap_or("");
+                    match parse_image_b64_from_image_url_openai(url) {
+                        Some((media_type, _, data)) => Some(json!({
+                            "type": "image",
+                            "source": {"type": "base64", "media_type": media_type, "data": data},
+                        })),
+                        None => {
+                            warn!("anthropic: skipping image that is not a base64 data url");
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            }
+        }).collect(),
+        _ => vec![],
+    }
+}
+
+// Messages API wants strictly alternating user/assistant turns with non-empty content,
+// tool results are user turns made of tool_result blocks, system goes into a separate field
+pub fn openai_messages_to_anthropic(messages: &[Value]) -> (Vec<Value>, Vec<Value>) {
+    let mut system: Vec<Value> = vec![];
+    let mut result: Vec<Value> = vec![];
+    for msg in messages {
+        let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("");
+        let content = msg.get("content").cloned().unwrap_or(Value::Null);
+        let (anthropic_role, blocks) = match role {
+            "system" => {
+                system.extend(openai_content_to_anthropic_blocks(&content));
+                continue;
+            }
+            "user" => ("user", openai_content_to_anthropic_blocks(&content)),
+            "assistant" => {
+                let mut blocks = vec![];
+                if let Some(thinking_blocks) = msg.get("thinking_blocks").and_then(|x| x.as_array()) {
+                    // must go back exactly as they came, signature included, or the API rejects the turn
+                    blocks.extend(thinking_blocks.iter().cloned());
+                }
+                blocks.extend(openai_content_to_anthropic_blocks(&content));
+                if let Some(tool_calls) = msg.get("tool_calls").and_then(|x| x.as_array()) {
+                    for tool_call in tool_calls {
+                        let arguments = tool_call.get("function").and_then(|f| f.get("arguments")).and_then(|a| a.as_str()).unwrap_or("");
+                        let input = serde_json::from_str::<Value>(arguments).ok().filter(|x| x.is_object()).unwrap_or(json!({}));
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": tool_call.get("id").cloned().unwrap_or(json!("")),
+                            "name": tool_call.get("function").and_then(|f| f.get("name")).cloned().unwrap_or(json!("")),
+                            "input": input,
+                        }));
+                    }
+                }
+                ("assistant", blocks)
+            }
+            "tool" => {
+                let text = match &content {
+                    Value::String(s) => s.clone(),
+                    other => openai_content_to_anthropic_blocks(other).iter()
+                        .filter_map(|b| b.get("text").and_then(|t| t.as_str()).map(|t| t.to_string()))
+                        .collect::<Vec<_>>().join("\n"),
+                };
+                ("user", vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": msg.get("tool_call_id").cloned().unwrap_or(json!("")),
+                    "content": if text.is_empty() { "(empty)".to_string() } else { text },
+                })])
+            }
+            _ => {
+                warn!("anthropic: unknown role {:?}, skipped", role);
+                continue;
+            }
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        match result.last_mut() {
+            Some(last) if last["role"] == anthropic_role => {
+                // tool_result blocks have to come first in a user turn
+                let last_content = last["content"].as_array_mut().unwrap();
+                if anthropic_role == "user" && blocks.iter().any(|b| b["type"] == "tool_result") {
+                    let pos = last_content.iter().take_while(|b| b["type"] == "tool_result").count();
+                    for (i, block) in blocks.into_iter().enumerate() {
+                        last_content.insert(pos + i, block);
+                    }
+                } else {
+                    last_content.extend(blocks);
+                }
+            }
+            _ => result.push(json!({"role": anthropic_role, "content": blocks})),
+        }
+    }
+    (system, result)
+}
+
+fn openai_tools_to_anthropic(tools: &[Value]) -> Vec<Value> {
+    tools.iter().filter_map(|tool| {
+        let function = tool.get("function")?;
+        Some(json!({
+            "name": function.get("name")?.clone(),
+            "description": function.get("description").cloned().unwrap_or(json!("")),
+            "input_schema": function.get("parameters").cloned().unwrap_or(json!({"type": "object", "properties": {}})),
+        }))
+    }).collect()
+}
+
+fn openai_tool_choice_to_anthropic(tool_choice: &Value) -> Option<Value> {
+    match tool_choice {
+        Value::String(s) => match s.as_str() {
+            "auto" => Some(json!({"type": "auto"})),
+            "required" => Some(json!({"type": "any"})),
+            "none" => Some(json!({"type": "none"})),
+            _ => None,
+        },
+        Value::Object(_) => tool_choice.get("function").and_then(|f| f.get("name"))
+            .map(|name| json!({"type": "tool", "name": name})),
+        _ => None,
+    }
+}
+
+fn stop_reason_to_finish_reason(stop_reason: &Value) -> Value {
+    match stop_reason.as_str() {
+        Some("end_turn") | Some("stop_sequence") => json!("stop"),
+        Some("max_tokens") => json!("length"),
+        Some("tool_use") => json!("tool_calls"),
+        Some(other) => json!(other),
+        None => Value::Null,
+    }
+}
+
+fn anthropic_usage_to_openai(usage: &Value) -> Value {
+    let get = |field: &str| usage.get(field).and_then(|x| x.as_u64()).unwrap_or(0);
+    let cache_creation = get("cache_creation_input_tokens");
+    let cache_read = get("cache_read_input_tokens");
+    let prompt_tokens = get("input_tokens") + cache_creation + cache_read;
+    let completion_tokens = get("output_tokens");
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+        "cache_creation_input_tokens": cache_creation,
+        "cache_read_input_tokens": cache_read,
+    })
+}
+
+pub fn anthropic_response_to_openai(resp: &Value) -> Value {
+    let mut text = String::new();
+    let mut tool_calls = vec![];
+    let mut thinking_blocks = vec![];
+    for block in resp.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default() {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => text.push_str(block.get("text").and_then(|t| t.as_str()).unwrap_or("")),
+            Some("tool_use") => tool_calls.push(json!({
+                "id": block.get("id").cloned().unwrap_or(json!("")),
+                "type": "function",
+                "function": {
+                    "name": block.get("name").cloned().unwrap_or(json!("")),
+                    "arguments": block.get("input").map(|i| i.to_string()).unwrap_or("{}".to_string()),
+                },
+            })),
+            Some("thinking") | Some("redacted_thinking") => thinking_blocks.push(block.clone()),
+            _ => {}
+        }
+    }
+    let mut message = json!({
+        "role": "assistant",
+        "content": text,
+        "tool_calls": if tool_calls.is_empty() { Value::Null } else { Value::Array(tool_calls) },
+    });
+    if !thinking_blocks.is_empty() {
+        message["thinking_blocks"] = Value::Array(thinking_blocks);
+    }
+    json!({
+        "id": resp.get("id").cloned().unwrap_or(Value::Null),
+        "object": "chat.completion",
+        "model": resp.get("model").cloned().unwrap_or(Value::Null),
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": stop_reason_to_finish_reason(resp.get("stop_reason").unwrap_or(&Value::Null)),
+        }],
+        "usage": anthropic_usage_to_openai(resp.get("usage").unwrap_or(&json!({}))),
+    })
+}
+
+// Turns Messages API stream events into OpenAI-style chunks, one event at a time
+#[derive(Default)]
+pub struct AnthropicStreamConverter {
+    pub finished: bool,
+    model: String,
+    usage: Value,
+    tool_call_index: HashMap<u64, usize>,  // content block index -> tool_calls index
+}
+
+impl AnthropicStreamConverter {
+    pub fn new() -> Self {
+        AnthropicStreamConverter { usage: json!({}), ..Default::default() }
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Value) -> Value {
+        json!({
+            "object": "chat.completion.chunk",
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        })
+    }
+
+    // None means nothing to send for this event
+    pub fn event_to_openai_chunk(&mut self, event: &Value) -> Option<Value> {
+        let block_index = event.get("index").and_then(|x| x.as_u64()).unwrap_or(0);
+        match event.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+            "message_start" => {
+                let message = event.get("message").cloned().unwrap_or(json!({}));
+                self.model = message.get("model").and_then(|m| m.as_str()).unwrap_or("").to_string();
+                self.usage = message.get("usage").cloned().unwrap_or(json!({}));
+                Some(self.chunk(json!({"role": "assistant", "content": ""}), Value::Null))
+            }
+            "content_block_start" => {
+                let block = event.get("content_block").cloned().unwrap_or(json!({}));
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("tool_use") => {
+                        let tool_index = self.tool_call_index.len();
+                        self.tool_call_index.insert(block_index, tool_index);
+                        Some(self.chunk(json!({"tool_calls": [{
+                            "index": tool_index,
+                            "id": block.get("id").cloned().unwrap_or(json!("")),
+                            "type": "function",
+                            "function": {"name": block.get("name").cloned().unwrap_or(json!("")), "arguments": ""},
+                        }]}), Value::Null))
+                    }
+                    Some("text") => {
+                        let text = block.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                        if text.is_empty() { None } else { Some(self.chunk(json!({"content": text}), Value::Null)) }
+                    }
+                    Some("redacted_thinking") => Some(self.chunk(json!({"thinking_blocks": [block.clone()], "reasoning_content": ""}), Value::Null)),
+                    _ => None,
+                }
+            }
+            "content_block_delta" => {
+                let delta = event.get("delta").cloned().unwrap_or(json!({}));
+                match delta.get("type").and_then(|t| t.as_str()) {
+                    Some("text_delta") => Some(self.chunk(json!({"content": delta.get("text").cloned().unwrap_or(json!(""))}), Value::Null)),
+                    Some("input_json_delta") => {
+                        let tool_index = *self.tool_call_index.get(&block_index)?;
+                        Some(self.chunk(json!({"tool_calls": [{
+                            "index": tool_index,
+                            "function": {"arguments": delta.get("partial_json").cloned().unwrap_or(json!(""))},
+                        }]}), Value::Null))
+                    }
+                    Some("thinking_delta") => {
+                        let thinking = delta.get("thinking").cloned().unwrap_or(json!(""));
+                        Some(self.chunk(json!({
+                            "thinking_blocks": [{"type": "thinking", "thinking": thinking, "signature": null}],
+                            "reasoning_content": thinking,
+                        }), Value::Null))
+                    }
+                    Some("signature_delta") => Some(self.chunk(json!({
+                        "thinking_blocks": [{"type": "thinking", "thinking": null, "signature": delta.get("signature").cloned().unwrap_or(json!(""))}],
+                        "reasoning_content": "",
+                    }), Value::Null)),
+                    _ => None,
+                }
+            }
+            "message_delta" => {
+                if let Some(output_tokens) = event.get("usage").and_then(|u| u.get("output_tokens")) {
+                    self.usage["output_tokens"] = output_tokens.clone();
+                }
+                let finish_reason = stop_reason_to_finish_reason(event.get("delta").and_then(|d| d.get("stop_reason")).unwrap_or(&Value::Null));
+                let mut chunk = self.chunk(json!({}), finish_reason);
+                chunk["usage"] = anthropic_usage_to_openai(&self.usage);
+                Some(chunk)
+            }
+            "message_stop" => {
+                self.finished = true;
+                None
+            }
+            "error" => Some(json!({"error": event.get("error").cloned().unwrap_or_else(|| event.clone())})),
+            _ => None,  // ping, content_block_stop
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_messages_to_anthropic() {
+        let messages = vec![
+            json!({"role": "system", "content": "you are a helpful assistant"}),
+            json!({"role": "user", "content": "list files"}),
+            json!({"role": "assistant", "content": "", "thinking_blocks": [{"type": "thinking", "thinking": "hmm", "signature": "sig"}],
+                   "tool_calls": [{"id": "toolu_1", "type": "function", "function": {"name": "tree", "arguments": "{\"path\": \"src\"}"}}]}),
+            json!({"role": "tool", "tool_call_id": "toolu_1", "content": "src/\n  main.rs"}),
+            json!({"role": "user", "content": [{"type": "text", "text": "and now?"}]}),
+        ];
+        let (system, converted) = openai_messages_to_anthropic(&messages);
+        assert_eq!(system, vec![json!({"type": "text", "text": "you are a helpful assistant"})]);
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1], json!({"role": "assistant", "content": [
+            {"type": "thinking", "thinking": "hmm", "signature": "sig"},
+            {"type": "tool_use", "id": "toolu_1", "name": "tree", "input": {"path": "src"}},
+        ]}));
+        // tool result and the next user message are one user turn, tool_result first
+        assert_eq!(converted[2], json!({"role": "user", "content": [
+            {"type": "tool_result", "tool_use_id": "toolu_1", "content": "src/\n  main.rs"},
+            {"type": "text", "text": "and now?"},
+        ]}));
+    }
+
+    #[test]
+    fn test_anthropic_response_to_openai() {
+        let resp = json!({
+            "id": "msg_1", "model": "claude-3-7-sonnet-20250219", "stop_reason": "tool_use",
+            "content": [
+                {"type": "thinking", "thinking": "let me look", "signature": "sig"},
+                {"type": "text", "text": "Looking."},
+                {"type": "tool_use", "id": "toolu_1", "name": "cat", "input": {"paths": "a.py"}},
+            ],
+            "usage": {"input_tokens": 10, "cache_read_input_tokens": 90, "output_tokens": 5},
+        });
+        let openai = anthropic_response_to_openai(&resp);
+        let choice0 = &openai["choices"][0];
+        assert_eq!(choice0["finish_reason"], "tool_calls");
+        assert_eq!(choice0["message"]["content"], "Looking.");
+        assert_eq!(choice0["message"]["tool_calls"][0]["function"]["name"], "cat");
+        assert_eq!(choice0["message"]["tool_calls"][0]["function"]["arguments"], "{\"paths\":\"a.py\"}");
+        assert_eq!(choice0["message"]["thinking_blocks"][0]["signature"], "sig");
+        assert_eq!(openai["usage"]["prompt_tokens"], 100);
+        assert_eq!(openai["usage"]["total_tokens"], 105);
+    }
+
+    #[test]
+    fn test_stream_events_to_openai_chunks() {
+        let mut converter = AnthropicStreamConverter::new();
+        let events = vec![
+            json!({"type": "message_start", "message": {"model": "claude-3-7-sonnet", "usage": {"input_tokens": 7, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "tree", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"path\":"}}),
+            json!({"type": "ping"}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 12}}),
+            json!({"type": "message_stop"}),
+        ];
+        let chunks = events.iter().filter_map(|e| converter.event_to_openai_chunk(e)).collect::<Vec<_>>();
+        assert!(converter.finished);
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "Hi");
+        assert_eq!(chunks[2]["choices"][0]["delta"]["tool_calls"][0]["id"], "toolu_1");
+        assert_eq!(chunks[3]["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"], "{\"path\":");
+        assert_eq!(chunks[4]["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunks[4]["usage"]["completion_tokens"], 12);
+        assert_eq!(chunks[4]["usage"]["prompt_tokens"], 7);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use reqwest::header::AUTHORIZATION;
 use reqwest::header::CONTENT_TYPE;
 use reqwest::header::HeaderMap;
@@ -8,6 +9,7 @@ use serde_json::json;
 use tokio::sync::Mutex as AMutex;
 
 use crate::call_validation::{ChatMeta, SamplingParameters};
+use crate::forward_to_openai_endpoint::insert_extra_headers;
 
 // Idea: use USER_AGENT
 // let user_agent = format!("{NAME}/{VERSION}; rust/unknown; ide/{ide:?}");
@@ -16,6 +18,7 @@ use crate::call_validation::{ChatMeta, SamplingParameters};
 pub async fn forward_to_hf_style_endpoint(
     save_url: &mut String,
     bearer: String,
+    extra_headers: &HashMap<String, String>,
     model_name: &str,
     prompt: &str,
     client: &reqwest::Client,
@@ -30,6 +33,7 @@ pub async fn forward_to_hf_style_endpoint(
     if !bearer.is_empty() {
         headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
     }
+    insert_extra_headers(&mut headers, extra_headers);
     let params_string = serde_json::to_string(sampling_parameters).unwrap();
     let mut params_json = serde_json::from_str::<serde_json::Value>(&params_string).unwrap();
     params_json["return_full_text"] = serde_json::Value::Bool(false);
@@ -65,6 +69,7 @@ pub async fn forward_to_hf_style_endpoint(
 pub async fn forward_to_hf_style_endpoint_streaming(
     save_url: &mut String,
     bearer: String,
+    extra_headers: &HashMap<String, String>,
     model_name: &str,
     prompt: &str,
     client: &reqwest::Client,
@@ -79,6 +84,7 @@ pub async fn forward_to_hf_style_endpoint_streaming(
     if !bearer.is_empty() {
         headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
     }
+    insert_extra_headers(&mut headers, extra_headers);
     let params_string = serde_json::to_string(sampling_parameters).unwrap();
     let mut params_json = serde_json::from_str::<serde_json::Value>(&params_string).unwrap();
     params_json["return_full_text"] = serde_json::Value::Bool(false);
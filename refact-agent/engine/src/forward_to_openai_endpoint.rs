@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use reqwest::header::AUTHORIZATION;
 use reqwest::header::CONTENT_TYPE;
 use reqwest::header::USER_AGENT;
 use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
 use reqwest::header::HeaderValue;
 use reqwest_eventsource::EventSource;
 use serde_json::json;
@@ -15,6 +17,7 @@ use crate::scratchpads::chat_utils_limit_history::CompressionStrength;
 pub async fn forward_to_openai_style_endpoint(
     save_url: &mut String,
     bearer: String,
+    extra_headers: &HashMap<String, String>,
     model_name: &str,
     prompt: &str,
     client: &reqwest::Client,
@@ -32,6 +35,7 @@ pub async fn forward_to_openai_style_endpoint(
     if !bearer.is_empty() {
         headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
     }
+    insert_extra_headers(&mut headers, extra_headers);
     if is_metadata_supported {
         headers.insert(USER_AGENT, HeaderValue::from_str(format!("refact-lsp {}", crate::version::build_info::PKG_VERSION).as_str()).unwrap());
     }
@@ -95,6 +99,7 @@ pub async fn forward_to_openai_style_endpoint(
 pub async fn forward_to_openai_style_endpoint_streaming(
     save_url: &mut String,
     bearer: String,
+    extra_headers: &HashMap<String, String>,
     model_name: &str,
     prompt: &str,
     client: &reqwest::Client,
@@ -112,6 +117,7 @@ pub async fn forward_to_openai_style_endpoint_streaming(
     if !bearer.is_empty() {
         headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
     }
+    insert_extra_headers(&mut headers, extra_headers);
     if is_metadata_supported {
         headers.insert(USER_AGENT, HeaderValue::from_str(format!("refact-lsp {}", crate::version::build_info::PKG_VERSION).as_str()).unwrap());
     }
@@ -160,6 +166,16 @@ pub async fn forward_to_openai_style_endpoint_streaming(
     Ok(event_source)
 }
 
+// Provider-specific headers from caps, like organization ids or proxy auth; invalid ones are skipped
+pub fn insert_extra_headers(headers: &mut HeaderMap, extra_headers: &HashMap<String, String>) {
+    for (k, v) in extra_headers.iter() {
+        match (HeaderName::from_bytes(k.as_bytes()), HeaderValue::from_str(v)) {
+            (Ok(name), Ok(value)) => { headers.insert(name, value); }
+            _ => tracing::warn!("skipping invalid header {:?}", k),
+        }
+    }
+}
+
 // NOTE: questionable function, no idea why we need it
 fn passthrough_messages_to_json(
     data: &mut serde_json::Value,
@@ -1,18 +1,27 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use git2::{IndexAddOption, Oid, Repository};
 use tokio::sync::RwLock as ARwLock;
 use tokio::time::Instant;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use serde::{Serialize, Deserialize};
+use similar::{DiffTag, TextDiff};
 
 use crate::ast::chunk_utils::official_text_hashing_function;
 use crate::custom_error::MapErrToString;
 use crate::files_blocklist::reload_indexing_everywhere_if_needed;
-use crate::files_correction::{deserialize_path, get_active_workspace_folder, get_project_dirs, serialize_path};
+use crate::files_correction::{canonical_path, deserialize_path, get_active_workspace_folder, get_project_dirs, serialize_path};
+use crate::files_in_workspace::enqueue_some_docs;
 use crate::global_context::GlobalContext;
 use crate::git::{FileChange, FileChangeStatus, from_unix_glob_pattern_to_gitignore};
-use crate::git::operations::{checkout_head_and_branch_to_commit, commit, get_commit_datetime, get_diff_statuses, get_diff_statuses_index_to_commit, get_or_create_branch, stage_changes, open_or_init_repo};
+use crate::git::operations::{checkout_head_and_branch_to_commit, commit, get_commit_datetime, get_diff_statuses, get_diff_statuses_index_to_commit, get_or_create_branch, git_diff_as_string, git_diff_commit_to_commit, git_diff_to_file_changes, stage_changes, open_or_init_repo};
+
+const CHECKPOINT_BRANCH_PREFIX: &str = "refact-";
+const CHECKPOINT_COMMIT_MESSAGE_PREFIX: &str = "Auto commit for chat ";
+const CHECKPOINT_LABEL_TAG_PREFIX: &str = "refact-label-";
+const HUNK_CONTEXT_LINES: usize = 3;
+const HUNKS_MAX_FILE_SIZE: usize = 1_000_000;
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
 pub struct Checkpoint {
@@ -27,6 +36,45 @@ impl Checkpoint {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointInfo {
+    #[serde(flatten)]
+    pub checkpoint: Checkpoint,
+    pub chat_id: String,  // empty for the initial commit
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointHunk {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub relative_path: PathBuf,
+    pub index: usize,
+    pub diff: String,
+    /// Hash of the file as it was when the hunks were computed, pass it back in CheckpointRestoreSelection
+    pub current_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointRestoreSelection {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub relative_path: PathBuf,
+    /// Indices of CheckpointHunk from the preview, the whole file is restored if not set
+    #[serde(default)]
+    pub hunks: Option<Vec<usize>>,
+    /// CheckpointHunk::current_hash, required with hunks, the restore fails if the file has changed since the preview
+    #[serde(default)]
+    pub current_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CheckpointsPrunePolicy {
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    #[serde(default)]
+    pub max_chats: Option<usize>,
+}
+
 async fn open_shadow_repo_and_nested_repos(
     gcx: Arc<ARwLock<GlobalContext>>, workspace_folder: &Path, allow_init_main_repo: bool,
 ) -> Result<(Repository, Vec<Repository>, String), String> {
@@ -130,7 +178,7 @@ pub async fn create_workspace_checkpoint(
     }
 
     let checkpoint = {
-        let branch = get_or_create_branch(&repo, &format!("refact-{chat_id}"))?;
+        let branch = get_or_create_branch(&repo, &format!("{CHECKPOINT_BRANCH_PREFIX}{chat_id}"))?;
 
         let (_, mut file_changes) = get_diff_statuses(git2::StatusShow::Workdir, &repo, false)?;
 
@@ -139,7 +187,7 @@ pub async fn create_workspace_checkpoint(
         file_changes.extend(flatened_nested_file_changes);
 
         stage_changes(&repo, &file_changes)?;
-        let commit_oid = commit(&repo, &branch, &format!("Auto commit for chat {chat_id}"), "Refact Agent", "agent@refact.ai")?;
+        let commit_oid = commit(&repo, &branch, &format!("{CHECKPOINT_COMMIT_MESSAGE_PREFIX}{chat_id}"), "Refact Agent", "agent@refact.ai")?;
         
         for (nested_repo, changes) in nested_file_changes {
             stage_changes(&nested_repo, &changes)?;
@@ -153,9 +201,87 @@ pub async fn create_workspace_checkpoint(
     Ok((checkpoint, repo))
 }
 
+fn file_in_commit(repo: &Repository, commit: &git2::Commit, relative_path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let tree = commit.tree().map_err_with_prefix("Failed to get checkpoint tree:")?;
+    match tree.get_path(relative_path) {
+        Ok(entry) => {
+            let blob = entry.to_object(repo).and_then(|o| o.peel_to_blob())
+                .map_err_with_prefix(format!("Failed to read {} from checkpoint:", relative_path.display()))?;
+            Ok(Some(blob.content().to_vec()))
+        },
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to find {} in checkpoint: {}", relative_path.display(), e)),
+    }
+}
+
+fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+fn text_hunks(current: &str, target: &str) -> Vec<String> {
+    let diff = TextDiff::from_lines(current, target);
+    let mut unified_diff = diff.unified_diff();
+    unified_diff.context_radius(HUNK_CONTEXT_LINES);
+    unified_diff.iter_hunks().map(|hunk| hunk.to_string()).collect()
+}
+
+/// Hunk indices are the same as in text_hunks(), unselected hunks keep the current text
+fn apply_selected_hunks(current: &str, target: &str, selected: &[usize]) -> String {
+    let diff = TextDiff::from_lines(current, target);
+    let mut chosen_ops = HashSet::new();
+    for (i, group) in diff.grouped_ops(HUNK_CONTEXT_LINES).iter().enumerate() {
+        if !selected.contains(&i) {
+            continue;
+        }
+        for op in group.iter().filter(|op| op.tag() != DiffTag::Equal) {
+            chosen_ops.insert((op.old_range().start, op.new_range().start));
+        }
+    }
+    let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+    let mut result = String::with_capacity(current.len().max(target.len()));
+    for op in diff.ops() {
+        let take_target = op.tag() != DiffTag::Equal && chosen_ops.contains(&(op.old_range().start, op.new_range().start));
+        let lines = if take_target { &new_lines[op.new_range()] } else { &old_lines[op.old_range()] };
+        for line in lines {
+            result.push_str(line);
+        }
+    }
+    result
+}
+
+fn hunks_for_file_changes(repo: &Repository, commit: &git2::Commit, files_changed: &Vec<FileChange>) -> Vec<CheckpointHunk> {
+    let workdir = match repo.workdir() {
+        Some(workdir) => workdir,
+        None => return vec![],
+    };
+    let mut result = Vec::new();
+    for change in files_changed {
+        let current = std::fs::read(workdir.join(&change.relative_path)).unwrap_or_default();
+        let target = match file_in_commit(repo, commit, &change.relative_path) {
+            Ok(target) => target.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("{e}");
+                continue;
+            }
+        };
+        if current.len() > HUNKS_MAX_FILE_SIZE || target.len() > HUNKS_MAX_FILE_SIZE {
+            continue;
+        }
+        let current_hash = content_hash(&current);
+        // Binary files can be restored only as a whole
+        let (Ok(current), Ok(target)) = (String::from_utf8(current), String::from_utf8(target)) else {
+            continue;
+        };
+        for (index, diff) in text_hunks(&current, &target).into_iter().enumerate() {
+            result.push(CheckpointHunk { relative_path: change.relative_path.clone(), index, diff, current_hash: current_hash.clone() });
+        }
+    }
+    result
+}
+
 pub async fn preview_changes_for_workspace_checkpoint(
     gcx: Arc<ARwLock<GlobalContext>>, checkpoint_to_restore: &Checkpoint, chat_id: &str
-) -> Result<(Vec<FileChange>, DateTime<Utc>, Checkpoint), String> {
+) -> Result<(Vec<FileChange>, Vec<CheckpointHunk>, DateTime<Utc>, Checkpoint), String> {
     let (checkpoint_for_undo, repo) = create_workspace_checkpoint(gcx.clone(), Some(checkpoint_to_restore), chat_id).await?;
 
     let commit_to_restore_oid = Oid::from_str(&checkpoint_to_restore.commit_hash).map_err_to_string()?;
@@ -172,7 +298,10 @@ pub async fn preview_changes_for_workspace_checkpoint(
         };
     }
 
-    Ok((files_changed, reverted_to, checkpoint_for_undo))
+    let commit_to_restore = repo.find_commit(commit_to_restore_oid).map_err_to_string()?;
+    let hunks = hunks_for_file_changes(&repo, &commit_to_restore, &files_changed);
+
+    Ok((files_changed, hunks, reverted_to, checkpoint_for_undo))
 }
 
 pub async fn restore_workspace_checkpoint(
@@ -187,8 +316,14 @@ pub async fn restore_workspace_checkpoint(
     }
 
     let commit_to_restore_oid = Oid::from_str(&checkpoint_to_restore.commit_hash).map_err_to_string()?;
+    let files_to_reindex = match repo.head().ok().and_then(|head| head.target()) {
+        Some(head_oid) => git_diff_commit_to_commit(&repo, &head_oid, &commit_to_restore_oid)
+            .and_then(|diff| git_diff_to_file_changes(&repo, &diff, true))
+            .unwrap_or_default(),
+        None => vec![],
+    };
 
-    checkout_head_and_branch_to_commit(&repo, &format!("refact-{chat_id}"), &commit_to_restore_oid)?;
+    checkout_head_and_branch_to_commit(&repo, &format!("{CHECKPOINT_BRANCH_PREFIX}{chat_id}"), &commit_to_restore_oid)?;
     
     for nested_repo in &nested_repos {
         let reset_index_result = nested_repo.index()
@@ -208,9 +343,246 @@ pub async fn restore_workspace_checkpoint(
         }
     }
 
+    reindex_restored_files(gcx.clone(), files_to_reindex.into_iter().map(|c| c.absolute_path).collect()).await;
+    Ok(())
+}
+
+/// Hunk indices only make sense for the text they were computed from
+fn check_unchanged_since_preview(selected: &CheckpointRestoreSelection, current: &[u8]) -> Result<(), String> {
+    match (&selected.current_hash, &selected.hunks) {
+        (Some(expected_hash), _) if *expected_hash != content_hash(current) =>
+            Err(format!("{} has changed since the preview, preview the checkpoint again", selected.relative_path.display())),
+        (None, Some(_)) =>
+            Err(format!("current_hash from the preview is required to restore hunks of {}", selected.relative_path.display())),
+        _ => Ok(()),
+    }
+}
+
+/// Restores only the selected files or hunks, the shadow branch stays where it is, so the next
+/// checkpoint records the result as a regular change
+pub async fn restore_workspace_checkpoint_partially(
+    gcx: Arc<ARwLock<GlobalContext>>, checkpoint_to_restore: &Checkpoint, selection: &[CheckpointRestoreSelection],
+) -> Result<Vec<PathBuf>, String> {
+    let workspace_folder = get_active_workspace_folder(gcx.clone()).await
+        .ok_or_else(|| "No active workspace folder".to_string())?;
+    let (repo, _, workspace_folder_hash) =
+        open_shadow_repo_and_nested_repos(gcx.clone(), &workspace_folder, false).await?;
+    if checkpoint_to_restore.workspace_hash() != workspace_folder_hash {
+        return Err("Can not restore checkpoint for different workspace folder".to_string());
+    }
+    let workdir = repo.workdir().ok_or("Failed to get workdir.".to_string())?.to_path_buf();
+    let commit_to_restore_oid = Oid::from_str(&checkpoint_to_restore.commit_hash).map_err_to_string()?;
+    let commit_to_restore = repo.find_commit(commit_to_restore_oid).map_err_with_prefix("Failed to find checkpoint:")?;
+
+    // Compute everything first, so a bad selection doesn't leave the workspace half-restored
+    let mut new_contents: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+    for selected in selection {
+        if selected.relative_path.is_absolute() || selected.relative_path.components().any(|c| c == Component::ParentDir) {
+            return Err(format!("Bad path {}", selected.relative_path.display()));
+        }
+        let absolute_path = workdir.join(&selected.relative_path);
+        let target = file_in_commit(&repo, &commit_to_restore, &selected.relative_path)?;
+        let current = tokio::fs::read(&absolute_path).await.unwrap_or_default();
+        check_unchanged_since_preview(selected, &current)?;
+        let new_content = match &selected.hunks {
+            None => target,
+            Some(hunks) => {
+                let target_exists = target.is_some();
+                let (Ok(current), Ok(target)) = (String::from_utf8(current), String::from_utf8(target.unwrap_or_default())) else {
+                    return Err(format!("Can not restore hunks of a binary file {}", selected.relative_path.display()));
+                };
+                let result = apply_selected_hunks(&current, &target, hunks);
+                if result.is_empty() && !target_exists { None } else { Some(result.into_bytes()) }
+            }
+        };
+        new_contents.push((absolute_path, new_content));
+    }
+
+    let mut restored = Vec::new();
+    for (absolute_path, new_content) in new_contents {
+        match new_content {
+            Some(content) => {
+                if let Some(parent) = absolute_path.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err_with_prefix(format!("Failed to create {}:", parent.display()))?;
+                }
+                tokio::fs::write(&absolute_path, content).await
+                    .map_err_with_prefix(format!("Failed to write {}:", absolute_path.display()))?;
+            },
+            None => {
+                if absolute_path.exists() {
+                    tokio::fs::remove_file(&absolute_path).await
+                        .map_err_with_prefix(format!("Failed to remove {}:", absolute_path.display()))?;
+                }
+            },
+        }
+        restored.push(canonical_path(absolute_path.to_string_lossy()));
+    }
+
+    reindex_restored_files(gcx.clone(), restored.clone()).await;
+    Ok(restored)
+}
+
+async fn reindex_restored_files(gcx: Arc<ARwLock<GlobalContext>>, paths: Vec<PathBuf>) {
+    // The file watcher would get there too, but the chat may ask AST/VecDB about these files right away
+    let paths = paths.into_iter()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    if !paths.is_empty() {
+        enqueue_some_docs(gcx, &paths, false).await;
+    }
+}
+
+async fn open_shadow_repo_for_known_workspace(
+    gcx: Arc<ARwLock<GlobalContext>>, workspace_folder: &Path,
+) -> Result<Repository, String> {
+    // Checkpoints cover the files of nested repos too, they are committed flattened into the main shadow repo
+    if !get_project_dirs(gcx.clone()).await.iter().any(|dir| dir == workspace_folder) {
+        return Err(format!("{} is not a workspace folder", workspace_folder.display()));
+    }
+    let (repo, _, _) = open_shadow_repo_and_nested_repos(gcx, workspace_folder, false).await?;
+    Ok(repo)
+}
+
+fn checkpoint_labels(repo: &Repository) -> Result<HashMap<Oid, String>, String> {
+    let mut labels = HashMap::new();
+    let tag_names = repo.tag_names(Some(&format!("{CHECKPOINT_LABEL_TAG_PREFIX}*"))).map_err_to_string()?;
+    for tag_name in tag_names.iter().flatten() {
+        let tag = match repo.find_reference(&format!("refs/tags/{tag_name}")).and_then(|r| r.peel_to_tag()) {
+            Ok(tag) => tag,
+            Err(e) => {
+                tracing::warn!("Failed to read checkpoint label {tag_name}: {e}");
+                continue;
+            }
+        };
+        labels.insert(tag.target_id(), tag.message().unwrap_or_default().to_string());
+    }
+    Ok(labels)
+}
+
+pub async fn list_workspace_checkpoints(
+    gcx: Arc<ARwLock<GlobalContext>>, workspace_folder: &Path, chat_id: Option<&str>, limit: usize,
+) -> Result<Vec<CheckpointInfo>, String> {
+    let repo = open_shadow_repo_for_known_workspace(gcx.clone(), workspace_folder).await?;
+    let labels = checkpoint_labels(&repo)?;
+
+    let mut revwalk = repo.revwalk().map_err_to_string()?;
+    revwalk.set_sorting(git2::Sort::TIME).map_err_to_string()?;
+    revwalk.push_glob(&format!("refs/heads/{CHECKPOINT_BRANCH_PREFIX}*")).map_err_to_string()?;
+    for labeled_oid in labels.keys() {
+        revwalk.push(*labeled_oid).map_err_to_string()?;
+    }
+
+    let mut result = Vec::new();
+    for oid in revwalk {
+        let oid = oid.map_err_to_string()?;
+        let commit = repo.find_commit(oid).map_err_to_string()?;
+        let commit_chat_id = commit.message().unwrap_or_default()
+            .strip_prefix(CHECKPOINT_COMMIT_MESSAGE_PREFIX).unwrap_or_default().trim().to_string();
+        if chat_id.is_some_and(|chat_id| chat_id != commit_chat_id) {
+            continue;
+        }
+        result.push(CheckpointInfo {
+            checkpoint: Checkpoint { workspace_folder: workspace_folder.to_path_buf(), commit_hash: oid.to_string() },
+            chat_id: commit_chat_id,
+            label: labels.get(&oid).cloned(),
+            created_at: get_commit_datetime(&repo, &oid)?,
+        });
+        if result.len() >= limit {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+pub async fn label_workspace_checkpoint(
+    gcx: Arc<ARwLock<GlobalContext>>, checkpoint: &Checkpoint, label: &str,
+) -> Result<(), String> {
+    // A label is an annotated tag, it also keeps the checkpoint alive when its chat branch is pruned
+    let repo = open_shadow_repo_for_known_workspace(gcx.clone(), &checkpoint.workspace_folder).await?;
+    let oid = Oid::from_str(&checkpoint.commit_hash).map_err_to_string()?;
+    let commit = repo.find_commit(oid).map_err_with_prefix("Failed to find checkpoint:")?;
+    let tag_name = format!("{CHECKPOINT_LABEL_TAG_PREFIX}{oid}");
+
+    if label.trim().is_empty() {
+        return match repo.tag_delete(&tag_name) {
+            Err(e) if e.code() != git2::ErrorCode::NotFound => Err(format!("Failed to remove label: {e}")),
+            _ => Ok(()),
+        };
+    }
+    let signature = git2::Signature::now("Refact Agent", "agent@refact.ai").map_err_to_string()?;
+    repo.tag(&tag_name, commit.as_object(), &signature, label.trim(), true)
+        .map_err_with_prefix("Failed to label checkpoint:")?;
     Ok(())
 }
 
+pub async fn diff_workspace_checkpoints(
+    gcx: Arc<ARwLock<GlobalContext>>, from: &Checkpoint, to: &Checkpoint, max_diff_size: usize,
+) -> Result<(Vec<FileChange>, String), String> {
+    if from.workspace_hash() != to.workspace_hash() {
+        return Err("Can not diff checkpoints of different workspace folders".to_string());
+    }
+    let repo = open_shadow_repo_for_known_workspace(gcx.clone(), &from.workspace_folder).await?;
+    let from_oid = Oid::from_str(&from.commit_hash).map_err_to_string()?;
+    let to_oid = Oid::from_str(&to.commit_hash).map_err_to_string()?;
+
+    let diff = git_diff_commit_to_commit(&repo, &from_oid, &to_oid)?;
+    let files_changed = git_diff_to_file_changes(&repo, &diff, true)?;
+    let diff_text = git_diff_as_string(&diff, max_diff_size)?;
+    Ok((files_changed, diff_text))
+}
+
+pub fn prune_checkpoint_branches(repo: &Repository, policy: &CheckpointsPrunePolicy) -> Result<Vec<String>, String> {
+    let head_branch = repo.head().ok().and_then(|h| h.shorthand().map(|s| s.to_string()));
+    let labeled_oids = checkpoint_labels(repo)?.into_keys().collect::<Vec<_>>();
+    let mut chat_branches = Vec::new();
+    for branch in repo.branches(Some(git2::BranchType::Local)).map_err_to_string()? {
+        let (branch, _) = branch.map_err_to_string()?;
+        let name = match branch.name() {
+            Ok(Some(name)) if name.starts_with(CHECKPOINT_BRANCH_PREFIX) => name.to_string(),
+            _ => continue,
+        };
+        let tip = branch.get().peel_to_commit().ok();
+        // A chat with a labeled checkpoint is kept whole, not only the labeled commit
+        let has_label = tip.as_ref().is_some_and(|tip| labeled_oids.iter().any(|oid| {
+            *oid == tip.id() || repo.graph_descendant_of(tip.id(), *oid).unwrap_or(false)
+        }));
+        if has_label {
+            continue;
+        }
+        let tip_ts = tip.map(|c| c.time().seconds()).unwrap_or(0);
+        chat_branches.push((tip_ts, name, branch));
+    }
+    chat_branches.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let now = Utc::now().timestamp();
+    let mut removed = Vec::new();
+    for (i, (tip_ts, name, mut branch)) in chat_branches.into_iter().enumerate() {
+        let too_old = policy.max_age_days.is_some_and(|days| now - tip_ts > days as i64 * 24 * 3600);
+        let too_many = policy.max_chats.is_some_and(|max_chats| i >= max_chats);
+        if !too_old && !too_many {
+            continue;
+        }
+        // HEAD can't be deleted, the newest chat is never pruned anyway
+        if head_branch.as_deref() == Some(name.as_str()) {
+            continue;
+        }
+        match branch.delete() {
+            Ok(_) => removed.push(name.trim_start_matches(CHECKPOINT_BRANCH_PREFIX).to_string()),
+            Err(e) => tracing::warn!("Failed to prune checkpoint branch {name}: {e}"),
+        }
+    }
+    Ok(removed)
+}
+
+pub async fn prune_workspace_checkpoints(
+    gcx: Arc<ARwLock<GlobalContext>>, workspace_folder: &Path, policy: &CheckpointsPrunePolicy,
+) -> Result<Vec<String>, String> {
+    // Only chat branches without labeled checkpoints are removed
+    let repo = open_shadow_repo_for_known_workspace(gcx.clone(), workspace_folder).await?;
+    prune_checkpoint_branches(&repo, policy)
+}
+
 pub async fn init_shadow_repos_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> () {
     let workspace_folders = get_project_dirs(gcx.clone()).await;
 
@@ -261,4 +633,68 @@ pub async fn init_shadow_repos_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> ()
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_selected_hunks() {
+        let current = (1..=20).map(|i| format!("line {i}\n")).collect::<String>();
+        let target = current.replace("line 2\n", "line two\n").replace("line 18\n", "");
+        assert_eq!(text_hunks(&current, &target).len(), 2);
+
+        assert_eq!(apply_selected_hunks(&current, &target, &[]), current);
+        assert_eq!(apply_selected_hunks(&current, &target, &[0, 1]), target);
+        let only_first = apply_selected_hunks(&current, &target, &[0]);
+        assert!(only_first.contains("line two\n") && only_first.contains("line 18\n"));
+        let only_second = apply_selected_hunks(&current, &target, &[1]);
+        assert!(only_second.contains("line 2\n") && !only_second.contains("line 18\n"));
+
+        let selected = |hunks: Option<Vec<usize>>, current_hash: Option<String>| CheckpointRestoreSelection {
+            relative_path: PathBuf::from("a.txt"), hunks, current_hash,
+        };
+        let previewed_hash = Some(content_hash(current.as_bytes()));
+        assert!(check_unchanged_since_preview(&selected(Some(vec![0]), previewed_hash.clone()), current.as_bytes()).is_ok());
+        assert!(check_unchanged_since_preview(&selected(Some(vec![0]), previewed_hash.clone()), target.as_bytes()).is_err());
+        assert!(check_unchanged_since_preview(&selected(None, previewed_hash), target.as_bytes()).is_err());
+        assert!(check_unchanged_since_preview(&selected(Some(vec![0]), None), current.as_bytes()).is_err());
+        assert!(check_unchanged_since_preview(&selected(None, None), target.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_prune_keeps_head_and_other_branches() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let signature = git2::Signature::now("Refact Agent", "agent@refact.ai").unwrap();
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let initial = repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[]).unwrap();
+        let initial = repo.find_commit(initial).unwrap();
+        for name in ["refact-chat1", "refact-chat2", "refact-chat3", "user-branch"] {
+            repo.branch(name, &initial, false).unwrap();
+        }
+        repo.set_head("refs/heads/refact-chat2").unwrap();
+
+        let policy = CheckpointsPrunePolicy { max_age_days: None, max_chats: Some(0) };
+        let mut removed = prune_checkpoint_branches(&repo, &policy).unwrap();
+        removed.sort();
+        assert_eq!(removed, vec!["chat1".to_string(), "chat3".to_string()]);
+        assert!(repo.find_branch("refact-chat2", git2::BranchType::Local).is_ok());
+        assert!(repo.find_branch("user-branch", git2::BranchType::Local).is_ok());
+
+        let policy = CheckpointsPrunePolicy { max_age_days: Some(1), max_chats: None };
+        assert!(prune_checkpoint_branches(&repo, &policy).unwrap().is_empty());
+
+        // a label on any commit of a chat keeps the whole chat branch
+        let labeled = repo.commit(None, &signature, &signature, "Auto commit for chat chat4", &tree, &[&initial]).unwrap();
+        let labeled = repo.find_commit(labeled).unwrap();
+        let later = repo.commit(None, &signature, &signature, "Auto commit for chat chat4", &tree, &[&labeled]).unwrap();
+        repo.branch("refact-chat4", &repo.find_commit(later).unwrap(), false).unwrap();
+        repo.tag(&format!("{CHECKPOINT_LABEL_TAG_PREFIX}{}", labeled.id()), labeled.as_object(), &signature, "before refactoring", false).unwrap();
+        let policy = CheckpointsPrunePolicy { max_age_days: None, max_chats: Some(0) };
+        assert!(prune_checkpoint_branches(&repo, &policy).unwrap().is_empty());
+        assert!(repo.find_branch("refact-chat4", git2::BranchType::Local).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
 pub mod checkpoints;
 pub mod commit_info;
 pub mod operations;
+pub mod shadow_gc;
 
 use serde::{Serialize, Deserialize};
 use std::path::PathBuf;
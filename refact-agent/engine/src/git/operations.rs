@@ -244,7 +244,43 @@ pub fn git_diff_head_to_workdir<'repo>(repository: &'repo Repository) -> Result<
 
 pub fn git_diff_head_to_workdir_as_string(repository: &Repository, max_size: usize) -> Result<String, String> {
     let diff = git_diff_head_to_workdir(repository)?;
+    git_diff_as_string(&diff, max_size)
+}
+
+pub fn git_diff_commit_to_commit<'repo>(repository: &'repo Repository, from_oid: &Oid, to_oid: &Oid) -> Result<git2::Diff<'repo>, String> {
+    let from_tree = repository.find_commit(from_oid.clone()).and_then(|c| c.tree())
+        .map_err_with_prefix("Failed to get tree of the first commit:")?;
+    let to_tree = repository.find_commit(to_oid.clone()).and_then(|c| c.tree())
+        .map_err_with_prefix("Failed to get tree of the second commit:")?;
+    repository.diff_tree_to_tree(Some(&from_tree), Some(&to_tree), Some(&mut DiffOptions::new()))
+        .map_err_with_prefix("Failed to generate diff:")
+}
+
+pub fn git_diff_to_file_changes(repository: &Repository, diff: &git2::Diff, include_abs_paths: bool) -> Result<Vec<FileChange>, String> {
+    let repo_workdir = repository.workdir()
+        .ok_or("Failed to get workdir from repository".to_string())?;
+    let mut file_changes = Vec::new();
+    for delta in diff.deltas() {
+        let (status, path) = match delta.status() {
+            git2::Delta::Added => (FileChangeStatus::ADDED, delta.new_file().path()),
+            git2::Delta::Deleted => (FileChangeStatus::DELETED, delta.old_file().path()),
+            _ => (FileChangeStatus::MODIFIED, delta.new_file().path()),
+        };
+        let relative_path = match path {
+            Some(path) => path.to_path_buf(),
+            None => continue,
+        };
+        let absolute_path = if include_abs_paths {
+            canonical_path(repo_workdir.join(&relative_path).to_string_lossy())
+        } else {
+            PathBuf::new()
+        };
+        file_changes.push(FileChange { relative_path, absolute_path, status });
+    }
+    Ok(file_changes)
+}
 
+pub fn git_diff_as_string(diff: &git2::Diff, max_size: usize) -> Result<String, String> {
     let mut diff_str = String::new();
     diff.print(git2::DiffFormat::Patch, |_, _, line| {
         let line_content = std::str::from_utf8(line.content()).unwrap_or("");
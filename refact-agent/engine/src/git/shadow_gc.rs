@@ -0,0 +1,238 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock as ARwLock;
+use tokio::time::Instant;
+
+use crate::ast::chunk_utils::official_text_hashing_function;
+use crate::files_correction::get_project_dirs;
+use crate::git::checkpoints::{prune_checkpoint_branches, CheckpointsPrunePolicy};
+use crate::global_context::GlobalContext;
+use crate::yaml_configs::customization_loader::load_customization;
+
+const SHADOW_GIT_GC_FIRST_RUN_AFTER_SECONDS: u64 = 300;
+const SHADOW_GIT_GC_EVERY_SECONDS: u64 = 3600;
+// Loose objects younger than that may belong to a checkpoint being created right now
+const SHADOW_GIT_GC_PRUNE_EXPIRE: &str = "1.hour.ago";
+
+
+/// Zero means no limit, missing fields take the default
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CheckpointsRetention {
+    pub max_age_days: u64,
+    pub max_chats: usize,
+    pub max_total_size_mb: u64,
+}
+
+impl Default for CheckpointsRetention {
+    fn default() -> Self {
+        // the same as in customization_compiled_in.yaml
+        CheckpointsRetention { max_age_days: 30, max_chats: 200, max_total_size_mb: 2048 }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ShadowRepoSize {
+    #[serde(serialize_with = "crate::files_correction::serialize_path")]
+    pub git_dir: PathBuf,
+    /// Empty if the folder is not open in the IDE right now
+    #[serde(serialize_with = "crate::files_correction::serialize_path")]
+    pub workspace_folder: PathBuf,
+    pub nested: bool,
+    pub size_bytes: u64,
+    pub chats: usize,
+}
+
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path).into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn count_chats(git_dir: &Path) -> usize {
+    let repo = match git2::Repository::open(git_dir) {
+        Ok(repo) => repo,
+        Err(_) => return 0,
+    };
+    repo.branches(Some(git2::BranchType::Local))
+        .map(|branches| branches.filter_map(|b| b.ok())
+            .filter(|(b, _)| b.name().ok().flatten().is_some_and(|n| n.starts_with("refact-")))
+            .count())
+        .unwrap_or(0)
+}
+
+async fn known_workspace_hashes(gcx: Arc<ARwLock<GlobalContext>>) -> Vec<(String, PathBuf)> {
+    let vcs_roots = gcx.read().await.documents_state.workspace_vcs_roots.clone();
+    let mut folders = get_project_dirs(gcx.clone()).await;
+    folders.extend(vcs_roots.lock().unwrap().iter().cloned());
+    folders.into_iter()
+        .map(|f| (official_text_hashing_function(&f.to_string_lossy().to_string()), f))
+        .collect()
+}
+
+fn shadow_repos_sizes_in(cache_dir: &Path, known: &[(String, PathBuf)]) -> Vec<ShadowRepoSize> {
+    let shadow_git_dir = cache_dir.join("shadow_git");
+    let mut result = Vec::new();
+    for (dir, nested) in [(shadow_git_dir.clone(), false), (shadow_git_dir.join("nested"), true)] {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let git_dir = entry.path();
+            if !git_dir.is_dir() || (!nested && git_dir.file_name().is_some_and(|n| n == "nested")) {
+                continue;
+            }
+            let hash = git_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let workspace_folder = known.iter().find(|(h, _)| *h == hash).map(|(_, f)| f.clone()).unwrap_or_default();
+            result.push(ShadowRepoSize {
+                size_bytes: dir_size(&git_dir),
+                chats: if nested { 0 } else { count_chats(&git_dir) },
+                git_dir,
+                workspace_folder,
+                nested,
+            });
+        }
+    }
+    result.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes));
+    result
+}
+
+async fn shadow_repos_sizes_in_background(cache_dir: &Path, known: &[(String, PathBuf)]) -> Vec<ShadowRepoSize> {
+    let (cache_dir, known) = (cache_dir.to_path_buf(), known.to_vec());
+    tokio::task::spawn_blocking(move || shadow_repos_sizes_in(&cache_dir, &known)).await.unwrap_or_default()
+}
+
+pub async fn shadow_repos_sizes(gcx: Arc<ARwLock<GlobalContext>>) -> Vec<ShadowRepoSize> {
+    let cache_dir = gcx.read().await.cache_dir.clone();
+    let known = known_workspace_hashes(gcx.clone()).await;
+    shadow_repos_sizes_in_background(&cache_dir, &known).await
+}
+
+async fn git_gc(shadow_repo_dir: &Path) -> Result<(), String> {
+    // libgit2 can't repack, the git binary is needed for that
+    let git = which::which("git").map_err(|_| "git binary not found".to_string())?;
+    // Shadow repos are not bare, objects live in <hash>/.git
+    let git_dir = git2::Repository::open(shadow_repo_dir).map_err(|e| e.to_string())?.path().to_path_buf();
+    let prune_arg = format!("--prune={SHADOW_GIT_GC_PRUNE_EXPIRE}");
+    for args in [
+        vec!["reflog", "expire", "--expire=now", "--expire-unreachable=now", "--all"],
+        vec!["gc", "--quiet", prune_arg.as_str()],
+    ] {
+        let output = tokio::process::Command::new(&git)
+            .arg("--git-dir").arg(&git_dir)
+            .args(&args)
+            .output().await
+            .map_err(|e| format!("failed to run git {}: {}", args[0], e))?;
+        if !output.status.success() {
+            return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+        }
+    }
+    Ok(())
+}
+
+async fn prune_and_gc(git_dir: &Path, policy: &CheckpointsPrunePolicy, nested: bool) {
+    if !nested {
+        // Nested shadow repos only keep an index, there are no branches to prune
+        match git2::Repository::open(git_dir).map_err(|e| e.to_string()).and_then(|repo| prune_checkpoint_branches(&repo, policy)) {
+            Ok(pruned) if !pruned.is_empty() => tracing::info!("shadow git gc: pruned {} chats in {}", pruned.len(), git_dir.display()),
+            Ok(_) => {},
+            Err(e) => tracing::warn!("shadow git gc: failed to prune {}: {}", git_dir.display(), e),
+        }
+    }
+    if let Err(e) = git_gc(git_dir).await {
+        tracing::warn!("shadow git gc: {}: {}", git_dir.display(), e);
+    }
+}
+
+pub async fn enforce_checkpoints_retention(cache_dir: &Path, known: &[(String, PathBuf)], retention: &CheckpointsRetention) {
+    let t0 = Instant::now();
+    let mut policy = CheckpointsPrunePolicy {
+        max_age_days: (retention.max_age_days > 0).then_some(retention.max_age_days),
+        max_chats: (retention.max_chats > 0).then_some(retention.max_chats),
+    };
+    for repo in shadow_repos_sizes_in_background(cache_dir, known).await {
+        prune_and_gc(&repo.git_dir, &policy, repo.nested).await;
+    }
+
+    if retention.max_total_size_mb == 0 {
+        return;
+    }
+    // Over quota: keep fewer chats in the open folders, the current chat and labeled checkpoints are never pruned.
+    // Shadow repos of folders that are not open are left alone, they might hold labeled checkpoints.
+    let quota = retention.max_total_size_mb * 1024 * 1024;
+    let mut repos = shadow_repos_sizes_in_background(cache_dir, known).await;
+    let mut total: u64 = repos.iter().map(|r| r.size_bytes).sum();
+    let mut max_chats = repos.iter().map(|r| r.chats).max().unwrap_or(0);
+    while total > quota && max_chats > 1 {
+        max_chats /= 2;
+        policy.max_chats = Some(max_chats);
+        for repo in repos.iter().filter(|r| !r.workspace_folder.as_os_str().is_empty() && !r.nested) {
+            prune_and_gc(&repo.git_dir, &policy, false).await;
+        }
+        repos = shadow_repos_sizes_in_background(cache_dir, known).await;
+        let new_total = repos.iter().map(|r| r.size_bytes).sum();
+        if new_total >= total {
+            // nothing left to free this way, don't prune more chats for nothing
+            total = new_total;
+            break;
+        }
+        total = new_total;
+    }
+    if total > quota {
+        tracing::warn!("shadow git gc: still using {}MB, more than {}MB allowed", total / 1024 / 1024, retention.max_total_size_mb);
+    }
+    tracing::info!("shadow git gc finished in {:.2}s, {}MB used", t0.elapsed().as_secs_f64(), total / 1024 / 1024);
+}
+
+pub async fn shadow_git_gc_background_task(gcx: Arc<ARwLock<GlobalContext>>) {
+    tokio::time::sleep(tokio::time::Duration::from_secs(SHADOW_GIT_GC_FIRST_RUN_AFTER_SECONDS)).await;
+    loop {
+        let retention = {
+            let mut error_log = Vec::new();
+            load_customization(gcx.clone(), true, &mut error_log).await.checkpoints_retention.unwrap_or_default()
+        };
+        let cache_dir = gcx.read().await.cache_dir.clone();
+        let known = known_workspace_hashes(gcx.clone()).await;
+        enforce_checkpoints_retention(&cache_dir, &known, &retention).await;
+        tokio::time::sleep(tokio::time::Duration::from_secs(SHADOW_GIT_GC_EVERY_SECONDS)).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::operations::open_or_init_repo;
+
+    #[tokio::test]
+    async fn test_retention_gc_shrinks_shadow_repo() {
+        if which::which("git").is_err() {
+            return;
+        }
+        let cache_dir = tempfile::tempdir().unwrap();
+        let shadow_repo_dir = cache_dir.path().join("shadow_git").join("0123abcd");
+        let repo = open_or_init_repo(&shadow_repo_dir).unwrap();
+        let signature = git2::Signature::now("Refact Agent", "agent@refact.ai").unwrap();
+        let text = (0..5000).map(|i| format!("line {i} of a file that the agent edits a little every time\n")).collect::<String>();
+        let mut parent: Option<git2::Oid> = None;
+        for i in 0..30 {
+            let blob = repo.blob(format!("{text}version {i}\n").as_bytes()).unwrap();
+            let mut tree_builder = repo.treebuilder(None).unwrap();
+            tree_builder.insert("file.txt", blob, 0o100644).unwrap();
+            let tree = repo.find_tree(tree_builder.write().unwrap()).unwrap();
+            let parents = parent.map(|p| repo.find_commit(p).unwrap()).into_iter().collect::<Vec<_>>();
+            let commit = repo.commit(None, &signature, &signature, "Auto commit for chat chat1", &tree, &parents.iter().collect::<Vec<_>>()).unwrap();
+            parent = Some(commit);
+        }
+        repo.branch("refact-chat1", &repo.find_commit(parent.unwrap()).unwrap(), false).unwrap();
+
+        let size_before = dir_size(&shadow_repo_dir);
+        let retention = CheckpointsRetention { max_age_days: 0, max_chats: 0, max_total_size_mb: 0 };
+        enforce_checkpoints_retention(cache_dir.path(), &[], &retention).await;
+        let size_after = dir_size(&shadow_repo_dir);
+        assert!(size_after < size_before / 2, "{} bytes before gc, {} after", size_before, size_after);
+        assert!(git2::Repository::open(&shadow_repo_dir).unwrap().find_branch("refact-chat1", git2::BranchType::Local).is_ok());
+    }
+}
@@ -17,7 +17,7 @@ use tracing::{error, info};
 
 use crate::ast::ast_indexer_thread::AstIndexService;
 use crate::caps::CodeAssistantCaps;
-use crate::completion_cache::CompletionCache;
+use crate::completion_cache::{CompletionCache, CompletionCacheDisk};
 use crate::custom_error::ScratchError;
 use crate::files_in_workspace::DocumentsState;
 use crate::integrations::docker::docker_ssh_tunnel_utils::SshTunnel;
@@ -55,6 +55,13 @@ pub struct CommandLine {
     #[structopt(long, short="v", help="Makes DEBUG log level visible, instead of the default INFO.")]
     pub verbose: bool,
 
+    #[structopt(long, help="Keep code completion cache on disk in the cache dir, so it survives restarts.")]
+    pub completion_cache_disk: bool,
+    #[structopt(long, default_value="64", help="Size limit for the on-disk completion cache, megabytes, least recently used entries go first.")]
+    pub completion_cache_max_mb: usize,
+    #[structopt(long, default_value="72", help="On-disk completion cache entries older than this are not used, hours.")]
+    pub completion_cache_ttl_hours: u64,
+
     #[structopt(long, help="Use AST, for it to start working, give it a jsonl files list or LSP workspace folders.")]
     pub ast: bool,
     // #[structopt(long, help="Use AST light mode, could be useful for large projects and little memory. Less information gets stored.")]
@@ -76,6 +83,18 @@ pub struct CommandLine {
     #[cfg(feature="vecdb")]
     #[structopt(long, default_value="", help="Set VecDB storage path manually.")]
     pub vecdb_force_path: String,
+    #[cfg(feature="vecdb")]
+    #[structopt(long, default_value="", help="Keep vectors in a remote index shared by the team instead of the local sqlite file, for example http://vecdb.internal:8008")]
+    pub vecdb_remote_url: String,
+    #[cfg(feature="vecdb")]
+    #[structopt(long, default_value="", help="API key for --vecdb-remote-url, sent as a Bearer token.")]
+    pub vecdb_remote_api_key: String,
+    #[cfg(feature="vecdb")]
+    #[structopt(long, default_value="default", help="Collection in the remote index, everyone working on the same repo should use the same name.")]
+    pub vecdb_remote_collection: String,
+    #[cfg(feature="vecdb")]
+    #[structopt(long, help="Send code windows to --vecdb-remote-url too, for the server's keyword index. Without it only vectors leave the machine and keyword search uses a local index.")]
+    pub vecdb_remote_lexical: bool,
 
     #[structopt(long, short="f", default_value="", help="A path to jsonl file with {\"path\": ...} on each line, files will immediately go to VecDB and AST.")]
     pub files_jsonl_path: String,
@@ -93,6 +112,11 @@ pub struct CommandLine {
     #[structopt(long, help="A way to tell this binary it can run more tools without confirmation.")]
     pub inside_container: bool,
 
+    #[structopt(long, default_value="1", help="How many chat threads can be worked on autonomously at the same time.")]
+    pub autonomy_workers: usize,
+    #[structopt(long, default_value="30", help="Model calls in one autonomous job before it stops and waits for a human, tool calls count as steps.")]
+    pub autonomy_max_steps: usize,
+
     #[structopt(long, default_value="", help="Specify the integrations.yaml, this also disables the global integrations.d")]
     pub integrations_yaml: String,
 
@@ -174,6 +198,8 @@ pub struct GlobalContext {
     pub codelens_cache: Arc<AMutex<crate::http::routers::v1::code_lens::CodeLensCache>>,
     pub docker_ssh_tunnel: Arc<AMutex<Option<SshTunnel>>>,
     pub chore_db: Arc<ParkMutex<crate::agent_db::db_structs::ChoreDB>>,
+    pub chat_tool_runs: Arc<AMutex<crate::tools::tools_limits::ChatToolRuns>>,
+    pub tool_policy: Arc<crate::tools::tools_policy::ToolPolicy>,
 }
 
 pub type SharedGlobalContext = Arc<ARwLock<GlobalContext>>;  // TODO: remove this type alias, confusing
@@ -356,6 +382,18 @@ pub async fn create_global_context(
     }
     let http_client = http_client_builder.build().unwrap();
 
+    let mut completions_cache = CompletionCache::new();
+    if cmdline.completion_cache_disk {
+        match CompletionCacheDisk::open(
+            &cache_dir.join("completion_cache.sqlite"),
+            cmdline.completion_cache_max_mb * 1024 * 1024,
+            cmdline.completion_cache_ttl_hours * 3600,
+        ) {
+            Ok(disk) => completions_cache.disk = Some(disk),
+            Err(e) => error!("completion cache stays in memory only: {}", e),
+        }
+    }
+
     let mut workspace_dirs: Vec<PathBuf> = vec![];
     if !cmdline.workspace_folder.is_empty() {
         let path = crate::files_correction::canonical_path(&cmdline.workspace_folder);
@@ -374,7 +412,7 @@ pub async fn create_global_context(
         caps_last_attempted_ts: 0,
         tokenizer_map: HashMap::new(),
         tokenizer_download_lock: Arc::new(AMutex::<bool>::new(false)),
-        completions_cache: Arc::new(StdRwLock::new(CompletionCache::new())),
+        completions_cache: Arc::new(StdRwLock::new(completions_cache)),
         telemetry: Arc::new(StdRwLock::new(telemetry_structs::Storage::new())),
         #[cfg(feature="vecdb")]
         vec_db: Arc::new(AMutex::new(None)),
@@ -391,6 +429,8 @@ pub async fn create_global_context(
         codelens_cache: Arc::new(AMutex::new(crate::http::routers::v1::code_lens::CodeLensCache::default())),
         docker_ssh_tunnel: Arc::new(AMutex::new(None)),
         chore_db: crate::agent_db::db_init::chore_db_init(&config_dir, cmdline.reset_memory).await,
+        chat_tool_runs: Arc::new(AMutex::new(HashMap::new())),
+        tool_policy: Arc::new(crate::tools::tools_policy::ToolPolicy::default()),
     };
     let gcx = Arc::new(ARwLock::new(cx));
     crate::files_in_workspace::watcher_init(gcx.clone()).await;
@@ -14,17 +14,18 @@ use crate::custom_error::ScratchError;
 use crate::global_context::SharedGlobalContext;
 use crate::http::routers::v1::code_completion::{handle_v1_code_completion_web, handle_v1_code_completion_prompt};
 use crate::http::routers::v1::code_lens::handle_v1_code_lens;
-use crate::http::routers::v1::ast::{handle_v1_ast_file_dump, handle_v1_ast_file_symbols, handle_v1_ast_status};
+use crate::http::routers::v1::ast::{handle_v1_ast_call_graph, handle_v1_ast_file_dump, handle_v1_ast_file_symbols, handle_v1_ast_status};
 use crate::http::routers::v1::at_commands::{handle_v1_command_completion, handle_v1_command_preview, handle_v1_at_command_execute};
-use crate::http::routers::v1::at_tools::{handle_v1_tools, handle_v1_tools_check_if_confirmation_needed, handle_v1_tools_execute};
+use crate::http::routers::v1::at_tools::{handle_v1_audit_log, handle_v1_tools, handle_v1_tools_check_if_confirmation_needed, handle_v1_tools_execute};
 use crate::http::routers::v1::caps::handle_v1_caps;
 use crate::http::routers::v1::caps::handle_v1_ping;
-use crate::http::routers::v1::chat::{handle_v1_chat, handle_v1_chat_completions};
+use crate::http::routers::v1::chat::{handle_v1_chat, handle_v1_chat_cancel, handle_v1_chat_completions};
 use crate::http::routers::v1::chat_based_handlers::{handle_v1_commit_message_from_diff, handle_v1_trajectory_compress};
 use crate::http::routers::v1::chat_based_handlers::handle_v1_trajectory_save;
 use crate::http::routers::v1::dashboard::get_dashboard_plots;
 use crate::http::routers::v1::docker::{handle_v1_docker_container_action, handle_v1_docker_container_list};
-use crate::http::routers::v1::git::{handle_v1_git_commit, handle_v1_checkpoints_preview, handle_v1_checkpoints_restore};
+use crate::http::routers::v1::git::{handle_v1_git_commit, handle_v1_checkpoints_preview, handle_v1_checkpoints_restore,
+    handle_v1_checkpoints_list, handle_v1_checkpoints_label, handle_v1_checkpoints_diff, handle_v1_checkpoints_prune, handle_v1_shadow_git_sizes};
 use crate::http::routers::v1::graceful_shutdown::handle_v1_graceful_shutdown;
 use crate::http::routers::v1::snippet_accepted::handle_v1_snippet_accepted;
 use crate::http::routers::v1::telemetry_network::handle_v1_telemetry_network;
@@ -43,11 +44,11 @@ use crate::http::routers::v1::system_prompt::handle_v1_prepend_system_prompt_and
 use crate::http::routers::v1::vecdb::{handle_v1_vecdb_search, handle_v1_vecdb_status};
 #[cfg(feature="vecdb")]
 use crate::http::routers::v1::handlers_memdb::{handle_mem_add, handle_mem_erase, handle_mem_update_used, handle_mem_block_until_vectorized};
-use crate::http::routers::v1::v1_integrations::{handle_v1_integration_get, handle_v1_integration_icon, handle_v1_integration_save, handle_v1_integration_delete, handle_v1_integrations, handle_v1_integrations_filtered, handle_v1_integrations_mcp_logs};
+use crate::http::routers::v1::v1_integrations::{handle_v1_integration_get, handle_v1_integration_icon, handle_v1_integration_save, handle_v1_integration_delete, handle_v1_integrations, handle_v1_integrations_filtered, handle_v1_integrations_mcp_logs, handle_v1_integrations_mcp_prompts, handle_v1_integrations_mcp_prompt_get};
 use crate::agent_db::db_cthread::{handle_db_v1_cthread_update, handle_db_v1_cthreads_sub};
 use crate::agent_db::db_cmessage::{handle_db_v1_cmessages_update, handle_db_v1_cmessages_sub};
 use crate::agent_db::db_chore::{handle_db_v1_chore_update, handle_db_v1_chore_event_update, handle_db_v1_chores_sub};
-use crate::http::routers::v1::file_edit_tools::handle_v1_file_edit_tool_dry_run;
+use crate::http::routers::v1::file_edit_tools::{handle_v1_file_edit_tool_dry_run, handle_v1_rename_symbol};
 use crate::http::routers::v1::handlers_memdb::{handle_mem_sub, handle_mem_upd};
 use crate::http::utils::telemetry_wrapper;
 
@@ -92,6 +93,7 @@ pub fn make_v1_router() -> Router {
 
         .route("/chat", telemetry_post!(handle_v1_chat))
         .route("/chat/completions", telemetry_post!(handle_v1_chat_completions))  // standard
+        .route("/chat-cancel", telemetry_post!(handle_v1_chat_cancel))
 
         .route("/telemetry-network", telemetry_post!(handle_v1_telemetry_network))
         .route("/telemetry-chat", telemetry_post!(handle_v1_telemetry_chat))
@@ -102,6 +104,7 @@ pub fn make_v1_router() -> Router {
         .route("/tools", telemetry_get!(handle_v1_tools))
         .route("/tools-check-if-confirmation-needed", telemetry_post!(handle_v1_tools_check_if_confirmation_needed))
         .route("/tools-execute", telemetry_post!(handle_v1_tools_execute)) // because it works remotely
+        .route("/audit-log", telemetry_post!(handle_v1_audit_log))
 
         .route("/lsp-initialize", telemetry_post!(handle_v1_lsp_initialize))
         .route("/lsp-did-changed", telemetry_post!(handle_v1_lsp_did_change))
@@ -112,6 +115,7 @@ pub fn make_v1_router() -> Router {
         .route("/ast-file-symbols", telemetry_post!(handle_v1_ast_file_symbols))
         .route("/ast-file-dump", telemetry_post!(handle_v1_ast_file_dump))
         .route("/ast-status", telemetry_get!(handle_v1_ast_status))
+        .route("/ast-call-graph", telemetry_post!(handle_v1_ast_call_graph))
 
         .route("/rag-status", telemetry_get!(handle_v1_rag_status))
         .route("/config-path", telemetry_get!(handle_v1_config_path))
@@ -138,16 +142,24 @@ pub fn make_v1_router() -> Router {
         .route("/integration-delete", delete(handle_v1_integration_delete))
         .route("/integration-icon/:icon_name", get(handle_v1_integration_icon))
         .route("/integrations-mcp-logs", telemetry_post!(handle_v1_integrations_mcp_logs))
+        .route("/integrations-mcp-prompts", telemetry_post!(handle_v1_integrations_mcp_prompts))
+        .route("/integrations-mcp-prompt-get", telemetry_post!(handle_v1_integrations_mcp_prompt_get))
 
         .route("/docker-container-list", telemetry_post!(handle_v1_docker_container_list))
         .route("/docker-container-action", telemetry_post!(handle_v1_docker_container_action))
 
         .route("/checkpoints-preview", telemetry_post!(handle_v1_checkpoints_preview))
         .route("/checkpoints-restore", telemetry_post!(handle_v1_checkpoints_restore))
+        .route("/checkpoints-list", telemetry_post!(handle_v1_checkpoints_list))
+        .route("/checkpoints-label", telemetry_post!(handle_v1_checkpoints_label))
+        .route("/checkpoints-diff", telemetry_post!(handle_v1_checkpoints_diff))
+        .route("/checkpoints-prune", telemetry_post!(handle_v1_checkpoints_prune))
+        .route("/shadow-git-sizes", telemetry_get!(handle_v1_shadow_git_sizes))
 
         .route("/links", telemetry_post!(handle_v1_links))
 
         .route("/file_edit_tool_dry_run", telemetry_post!(handle_v1_file_edit_tool_dry_run))
+        .route("/rename-symbol", telemetry_post!(handle_v1_rename_symbol))
 
         // experimental
         .route("/get-dashboard-plots", telemetry_get!(get_dashboard_plots))
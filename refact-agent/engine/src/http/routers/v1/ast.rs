@@ -7,6 +7,7 @@ use url::Url;
 use serde_json::json;
 use uuid::Uuid;
 
+use crate::ast::ast_call_graph::{call_graph_for_query, CallGraphDirection};
 use crate::custom_error::ScratchError;
 use crate::files_in_workspace::{Document, get_file_text_from_memory_or_disk};
 use crate::global_context::SharedGlobalContext;
@@ -39,6 +40,24 @@ struct FileNameOnlyPost {
     file_name: String,
 }
 
+fn default_call_graph_depth() -> usize {
+    2
+}
+
+#[derive(Deserialize)]
+struct AstCallGraphPost {
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    file_name: String,  // impact of changing the file, instead of a symbol
+    #[serde(default = "default_call_graph_depth")]
+    depth: usize,
+    #[serde(default)]
+    direction: CallGraphDirection,
+    #[serde(default)]
+    format: String,  // "json" (default) or "dot"
+}
+
 
 pub async fn handle_v1_ast_file_dump(
     Extension(global_context): Extension<SharedGlobalContext>,
@@ -163,3 +182,33 @@ pub async fn handle_v1_ast_status(
         }
     }
 }
+
+pub async fn handle_v1_ast_call_graph(
+    Extension(global_context): Extension<SharedGlobalContext>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<AstCallGraphPost>(&body_bytes).map_err(|e| {
+        ScratchError::new(StatusCode::BAD_REQUEST, format!("JSON problem: {}", e))
+    })?;
+    if post.symbol.is_empty() == post.file_name.is_empty() {
+        return Err(ScratchError::new(StatusCode::BAD_REQUEST, "set either symbol or file_name".to_string()));
+    }
+    let graph = call_graph_for_query(global_context.clone(), &post.symbol, &post.file_name, post.depth.min(10), post.direction).await
+        .map_err(|e| ScratchError::new(StatusCode::NOT_FOUND, e))?;
+
+    if post.format == "dot" {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/vnd.graphviz")
+            .body(Body::from(graph.to_dot()))
+            .unwrap());
+    }
+    let json_string = serde_json::to_string_pretty(&graph).map_err(|e| {
+        ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("JSON serialization problem: {}", e))
+    })?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(json_string))
+        .unwrap())
+}
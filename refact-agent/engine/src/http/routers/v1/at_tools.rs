@@ -10,7 +10,7 @@ use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::cached_tokenizers;
-use crate::call_validation::{ChatMessage, ChatMeta, ChatToolCall, PostprocessSettings, SubchatParameters};
+use crate::call_validation::{ChatMessage, ChatMeta, ChatMode, ChatToolCall, PostprocessSettings, SubchatParameters};
 use crate::http::http_post_json;
 use crate::http::routers::v1::chat::CHAT_TOP_N;
 use crate::integrations::docker::docker_container_manager::docker_container_get_host_lsp_port_to_connect;
@@ -18,6 +18,9 @@ use crate::tools::tools_description::{tool_description_list_from_yaml, tools_mer
 use crate::custom_error::ScratchError;
 use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
 use crate::tools::tools_execute::run_tools;
+use crate::tools::tools_audit::{audit_log_query, AuditQuery};
+use crate::tools::tools_limits::chat_cancel_token;
+use crate::tools::tools_policy::check_tool_call;
 
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -44,6 +47,7 @@ struct PauseReason {
     rule: String,
     tool_call_id: String,
     integr_config_path: Option<String>,
+    explanation: String,  // which rule decided and why, see tools_policy.rs
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -56,6 +60,8 @@ pub struct ToolsExecutePost {
     pub model_name: String,
     pub chat_id: String,
     pub style: Option<String>,
+    #[serde(default)]
+    pub chat_mode: ChatMode,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -135,6 +141,7 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
     let ccx = Arc::new(AMutex::new(AtCommandsContext::new(
         gcx.clone(), 1000, 1, false, post.messages.clone(), "".to_string(), false
     ).await)); // used only for should_confirm
+    ccx.lock().await.chat_mode = post.meta.chat_mode;
 
     let all_tools = match tools_merged_and_filtered(gcx.clone(), true).await {
         Ok(tools) => tools,
@@ -167,12 +174,13 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
                         rule: format!("tool parsing problem: {}", e),
                         tool_call_id: tool_call.id.clone(),
                         integr_config_path: tool.has_config_path(),
+                        explanation: "arguments are not valid json".to_string(),
                     }
                 ]));
             }
         };
 
-        let should_confirm = tool.match_against_confirm_deny(ccx.clone(), &args).await
+        let should_confirm = check_tool_call(ccx.clone(), &**tool, &tool_call.function.name, &args, post.meta.chat_mode).await
             .map_err(|e| { ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, e)})?;
 
         match should_confirm.result {
@@ -183,6 +191,7 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
                     rule: should_confirm.rule.clone(),
                     tool_call_id: tool_call.id.clone(),
                     integr_config_path: tool.has_config_path(),
+                    explanation: should_confirm.explanation.clone(),
                 });
             },
             MatchConfirmDenyResult::CONFIRMATION => {
@@ -192,6 +201,7 @@ pub async fn handle_v1_tools_check_if_confirmation_needed(
                     rule: should_confirm.rule.clone(),
                     tool_call_id: tool_call.id.clone(),
                     integr_config_path: tool.has_config_path(),
+                    explanation: should_confirm.explanation.clone(),
                 });
             },
             _ => {},
@@ -223,6 +233,10 @@ pub async fn handle_v1_tools_execute(
     ).await;
     ccx.subchat_tool_parameters = tools_execute_post.subchat_tool_parameters.clone();
     ccx.postprocess_parameters = tools_execute_post.postprocess_parameters.clone();
+    ccx.chat_mode = tools_execute_post.chat_mode;
+    if !tools_execute_post.chat_id.is_empty() {
+        ccx.cancel_token = chat_cancel_token(gcx.clone(), &tools_execute_post.chat_id).await;
+    }
     let ccx_arc = Arc::new(AMutex::new(ccx));
 
     let mut at_tools = tools_merged_and_filtered(gcx.clone(), false).await.map_err(|e|{
@@ -246,4 +260,25 @@ pub async fn handle_v1_tools_execute(
         .body(Body::from(response_json))
         .unwrap()
     )
-}
\ No newline at end of file
+}
+
+pub async fn handle_v1_audit_log(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let query = if body_bytes.is_empty() {
+        AuditQuery::default()
+    } else {
+        serde_json::from_slice::<AuditQuery>(&body_bytes)
+            .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?
+    };
+
+    let entries = audit_log_query(gcx.clone(), &query).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::json!({ "entries": entries }).to_string()))
+        .unwrap())
+}
@@ -6,6 +6,7 @@ use tokio::sync::RwLock as ARwLock;
 use axum::Extension;
 use axum::response::Result;
 use hyper::{Body, Response, StatusCode};
+use serde::Deserialize;
 use serde_json::Value;
 
 use crate::call_validation::{ChatContent, ChatMessage, ChatPost, ChatMode};
@@ -15,6 +16,7 @@ use crate::at_commands::at_commands::AtCommandsContext;
 use crate::git::checkpoints::create_workspace_checkpoint;
 use crate::global_context::{is_metadata_supported, GlobalContext, SharedGlobalContext};
 use crate::integrations::docker::docker_container_manager::docker_container_check_status_or_start;
+use crate::tools::tools_limits::{chat_cancel, chat_cancel_token};
 
 
 pub fn available_tools_by_chat_mode(current_tools: Vec<Value>, chat_mode: &ChatMode) -> Vec<Value> {
@@ -99,6 +101,26 @@ pub async fn handle_v1_chat(
     _chat(gcx, &body_bytes, true).await
 }
 
+#[derive(Deserialize)]
+struct ChatCancelPost {
+    chat_id: String,
+}
+
+pub async fn handle_v1_chat_cancel(
+    // stops the tools running for this chat, they answer with a "cancelled" tool message
+    Extension(gcx): Extension<SharedGlobalContext>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<ChatCancelPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    let cancelled = chat_cancel(gcx.clone(), &post.chat_id).await;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::json!({"cancelled": cancelled}).to_string()))
+        .unwrap())
+}
+
 pub fn deserialize_messages_from_post(messages: &Vec<serde_json::Value>) -> Result<Vec<ChatMessage>, ScratchError> {
     let messages: Vec<ChatMessage> = messages.iter()
         .map(|x| serde_json::from_value(x.clone()))
@@ -290,6 +312,10 @@ async fn _chat(
     ).await;
     ccx.subchat_tool_parameters = chat_post.subchat_tool_parameters.clone();
     ccx.postprocess_parameters = chat_post.postprocess_parameters.clone();
+    ccx.chat_mode = chat_post.meta.chat_mode;
+    if !chat_post.meta.chat_id.is_empty() {
+        ccx.cancel_token = chat_cancel_token(gcx.clone(), &chat_post.meta.chat_id).await;
+    }
     let ccx_arc = Arc::new(AMutex::new(ccx));
 
     if chat_post.stream == Some(false) {
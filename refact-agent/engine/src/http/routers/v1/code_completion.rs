@@ -95,7 +95,11 @@ pub async fn handle_v1_code_completion(
     };
     if !code_completion_post.no_cache {
         let cache_key = completion_cache::cache_key_from_post(&code_completion_post);
-        let cached_maybe = completion_cache::cache_get(cache_arc.clone(), cache_key.clone());
+        let cached_maybe = {
+            let (cache_arc, cache_key, model) = (cache_arc.clone(), cache_key.clone(), code_completion_post.model.clone());
+            tokio::task::spawn_blocking(move || completion_cache::cache_get(cache_arc, cache_key, &model)).await
+                .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("completion cache: {}", e)))?
+        };
         if let Some(cached_json_value) = cached_maybe {
             // info!("cache hit for key {:?}", cache_key.clone());
             if !code_completion_post.stream {
@@ -10,6 +10,7 @@ use tracing::info;
 use tokio::io;
 use tokio::io::AsyncBufReadExt;
 use crate::dashboard::dashboard::records2plots;
+use crate::dashboard::local_history::{DashboardFilter, load_local_rh_records, local_projects};
 use crate::dashboard::structs::RHData;
 
 
@@ -22,6 +23,15 @@ struct RHResponse {
 #[derive(Debug, Serialize)]
 struct DashboardPlotsResponse {
     data: String,
+    projects: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct DashboardPlotsPost {
+    #[serde(flatten)]
+    filter: DashboardFilter,
+    #[serde(default)]
+    local: bool,
 }
 
 async fn fetch_data(
@@ -61,28 +71,40 @@ async fn fetch_data(
 
 pub async fn get_dashboard_plots(
     Extension(global_context): Extension<SharedGlobalContext>,
-    _: hyper::body::Bytes,
+    body_bytes: hyper::body::Bytes,
 ) -> axum::response::Result<Response<Body>, ScratchError> {
+    // Body is optional: {"date_from": "YYYY-MM-DD", "date_to": "YYYY-MM-DD", "project": "name", "local": true}
+    let post = if body_bytes.iter().all(|b| b.is_ascii_whitespace()) {
+        DashboardPlotsPost::default()
+    } else {
+        serde_json::from_slice::<DashboardPlotsPost>(&body_bytes)
+            .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?
+    };
 
     let caps = crate::global_context::try_load_caps_quickly_if_not_present(global_context.clone(), 0).await?;
-    let (http_client, api_key, url) = {
+    let (http_client, api_key, url, cache_dir) = {
         let gcx_locked = global_context.read().await;
-        (gcx_locked.http_client.clone(), gcx_locked.cmdline.api_key.clone(), caps.read().unwrap().telemetry_basic_retrieve_my_own.clone())
+        (gcx_locked.http_client.clone(), gcx_locked.cmdline.api_key.clone(), caps.read().unwrap().telemetry_basic_retrieve_my_own.clone(), gcx_locked.cache_dir.clone())
     };
-    if url.is_empty() {
-        return Err(ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, "Error: no url provided from caps".to_string()));
-    }
 
-    let mut records = match fetch_data(
-        &http_client,
-        &url,
-        &api_key
-    ).await {
-        Ok(res) => res,
-        Err(e) => {
-            return Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error fetching reports: {}", e)));
+    // Without a server to ask, plot the local history kept by the telemetry task
+    let records = if url.is_empty() || post.local {
+        load_local_rh_records(&cache_dir).await
+    } else {
+        match fetch_data(
+            &http_client,
+            &url,
+            &api_key
+        ).await {
+            Ok(res) => res,
+            Err(e) => {
+                return Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error fetching reports: {}", e)));
+            }
         }
     };
+    let projects = local_projects(&records);
+    let mut records = post.filter.apply(records)
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
 
     let plots = match records2plots(&mut records).await {
         Ok(plots) => plots,
@@ -90,7 +112,7 @@ pub async fn get_dashboard_plots(
             return Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error plotting reports: {}", e)));
         }
     };
-    let body = match serde_json::to_string_pretty(&DashboardPlotsResponse{data: plots.to_string()}) {
+    let body = match serde_json::to_string_pretty(&DashboardPlotsResponse{data: plots.to_string(), projects}) {
         Ok(res) => res,
         Err(e) => {
             return Err(ScratchError::new(StatusCode::NO_CONTENT, format!("Error serializing plots: {}", e)));
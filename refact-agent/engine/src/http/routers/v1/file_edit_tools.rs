@@ -2,7 +2,9 @@ use std::sync::Arc;
 
 use crate::call_validation::DiffChunk;
 use crate::custom_error::ScratchError;
+use crate::git::checkpoints::Checkpoint;
 use crate::global_context::GlobalContext;
+use crate::tools::file_edit::tool_rename_symbol::{apply_rename, parse_args as parse_rename_args, plan_rename, RenamePlan};
 use axum::http::{Response, StatusCode};
 use axum::Extension;
 use hyper::Body;
@@ -23,6 +25,24 @@ pub struct FileEditDryRunResponse {
     chunks: Vec<DiffChunk>,
 }
 
+#[derive(Deserialize)]
+pub struct RenameSymbolPost {
+    #[serde(flatten)]
+    pub tool_args: HashMap<String, serde_json::Value>,  // symbol, new_name, path
+    #[serde(default)]
+    pub apply: bool,
+    #[serde(default)]
+    pub chat_id: String,
+}
+
+#[derive(Serialize)]
+pub struct RenameSymbolResponse {
+    #[serde(flatten)]
+    plan: RenamePlan,
+    applied: bool,
+    checkpoint: Option<Checkpoint>,
+}
+
 pub async fn handle_v1_file_edit_tool_dry_run(
     Extension(global_context): Extension<Arc<ARwLock<GlobalContext>>>,
     body_bytes: hyper::body::Bytes,
@@ -61,6 +81,27 @@ pub async fn handle_v1_file_edit_tool_dry_run(
             .await
             .map_err(|x| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, x))?
         }
+        "replace_symbol" | "insert_after_symbol" => {
+            crate::tools::file_edit::tool_symbol_edit::tool_symbol_edit_exec(
+                global_context.clone(),
+                &post.tool_args,
+                post.tool_name == "insert_after_symbol",
+                true,
+            )
+            .await
+            .map_err(|x| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, x))?
+        }
+        "apply_patch" => {
+            // several files, only the chunks make sense
+            let chunks = crate::tools::file_edit::tool_apply_patch::tool_apply_patch_exec(
+                global_context.clone(),
+                &post.tool_args,
+                true,
+            )
+            .await
+            .map_err(|x| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, x))?;
+            ("".to_string(), "".to_string(), chunks)
+        }
         _ => {
             return Err(ScratchError::new(
                 StatusCode::BAD_REQUEST,
@@ -82,3 +123,40 @@ pub async fn handle_v1_file_edit_tool_dry_run(
         ))
         .unwrap())
 }
+
+pub async fn handle_v1_rename_symbol(
+    Extension(global_context): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> axum::response::Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<RenameSymbolPost>(&body_bytes).map_err(|e| {
+        ScratchError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("JSON problem: {}", e),
+        )
+    })?;
+    let (symbol, path, new_name) = parse_rename_args(global_context.clone(), &post.tool_args).await
+        .map_err(|x| ScratchError::new(StatusCode::BAD_REQUEST, x))?;
+    let plan = plan_rename(global_context.clone(), &symbol, &path, &new_name).await
+        .map_err(|x| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, x))?;
+
+    let mut checkpoint = None;
+    let applied = post.apply && !plan.chunks.is_empty();
+    if applied {
+        let chat_id = if post.chat_id.is_empty() { "rename-symbol".to_string() } else { post.chat_id.clone() };
+        checkpoint = Some(apply_rename(global_context.clone(), &plan, &chat_id).await
+            .map_err(|x| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, x))?);
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            serde_json::to_string_pretty(&RenameSymbolResponse {
+                plan,
+                applied,
+                checkpoint,
+            })
+            .unwrap(),
+        ))
+        .unwrap())
+}
@@ -14,8 +14,12 @@ use crate::files_correction::{deserialize_path, serialize_path};
 use crate::custom_error::ScratchError;
 use crate::git::{CommitInfo, FileChange};
 use crate::git::operations::{get_configured_author_email_and_name, stage_changes};
-use crate::git::checkpoints::{preview_changes_for_workspace_checkpoint, restore_workspace_checkpoint, Checkpoint};
+use crate::git::checkpoints::{preview_changes_for_workspace_checkpoint, restore_workspace_checkpoint, restore_workspace_checkpoint_partially,
+    Checkpoint, CheckpointHunk, CheckpointInfo, CheckpointRestoreSelection, CheckpointsPrunePolicy,
+    diff_workspace_checkpoints, label_workspace_checkpoint, list_workspace_checkpoints, prune_workspace_checkpoints};
+use crate::git::shadow_gc::shadow_repos_sizes;
 use crate::global_context::GlobalContext;
+use crate::yaml_configs::customization_loader::load_customization;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GitCommitPost {
@@ -33,6 +37,9 @@ pub struct GitError {
 pub struct CheckpointsPost {
     pub checkpoints: Vec<Checkpoint>,
     pub meta: ChatMeta,
+    /// Restore only these files or hunks, everything is restored if not set
+    #[serde(default)]
+    pub selection: Option<Vec<CheckpointRestoreSelection>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -48,8 +55,44 @@ pub struct CheckpointsPreviewResponse {
 pub struct CheckpointsRestoreResponse {
     pub success: bool, 
     pub error_log: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restored_files: Vec<PathBuf>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsListPost {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub workspace_folder: PathBuf,
+    #[serde(default)]
+    pub chat_id: Option<String>,
+    #[serde(default = "default_checkpoints_list_limit")]
+    pub limit: usize,
+}
+
+fn default_checkpoints_list_limit() -> usize { 100 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsLabelPost {
+    pub checkpoint: Checkpoint,
+    pub label: String,  // empty string removes the label
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsDiffPost {
+    pub from: Checkpoint,
+    pub to: Checkpoint,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointsPrunePost {
+    #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
+    pub workspace_folder: PathBuf,
+    #[serde(flatten)]
+    pub policy: CheckpointsPrunePolicy,
+}
+
+const CHECKPOINTS_DIFF_MAX_SIZE: usize = 200_000;
+
 fn serialize_datetime_utc<S: serde::Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
     serializer.serialize_str(&dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
 }
@@ -59,6 +102,8 @@ pub struct WorkspaceChanges {
     #[serde(serialize_with = "serialize_path", deserialize_with = "deserialize_path")]
     pub workspace_folder: PathBuf,
     pub files_changed: Vec<FileChange>,
+    #[serde(default)]
+    pub hunks: Vec<CheckpointHunk>,
 }
 
 pub async fn handle_v1_git_commit(
@@ -147,11 +192,12 @@ pub async fn handle_v1_checkpoints_preview(
     }
 
     let response = match preview_changes_for_workspace_checkpoint(gcx.clone(), &post.checkpoints.first().unwrap(), &post.meta.chat_id).await {
-        Ok((files_changed, reverted_to, checkpoint_for_undo)) => {
+        Ok((files_changed, hunks, reverted_to, checkpoint_for_undo)) => {
             CheckpointsPreviewResponse {
                 reverted_changes: vec![WorkspaceChanges {
                     workspace_folder: post.checkpoints.first().unwrap().workspace_folder.clone(),
                     files_changed,
+                    hunks,
                 }],
                 checkpoints_for_undo: vec![checkpoint_for_undo],
                 reverted_to,
@@ -187,11 +233,17 @@ pub async fn handle_v1_checkpoints_restore(
         return Err(ScratchError::new(StatusCode::NOT_IMPLEMENTED, "Multiple checkpoints to restore not implemented yet".to_string()));
     }
 
-    let response = match restore_workspace_checkpoint(gcx.clone(), &post.checkpoints.first().unwrap(), &post.meta.chat_id).await {
-        Ok(_) => {
+    let checkpoint = post.checkpoints.first().unwrap();
+    let restore_result = match &post.selection {
+        Some(selection) => restore_workspace_checkpoint_partially(gcx.clone(), checkpoint, selection).await,
+        None => restore_workspace_checkpoint(gcx.clone(), checkpoint, &post.meta.chat_id).await.map(|_| vec![]),
+    };
+    let response = match restore_result {
+        Ok(restored_files) => {
             CheckpointsRestoreResponse {
                 success: true,
                 error_log: vec![],
+                restored_files,
             }
         },
         Err(e) => {
@@ -207,4 +259,78 @@ pub async fn handle_v1_checkpoints_restore(
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&response).unwrap()))
         .unwrap())
-}
\ No newline at end of file
+}
+
+fn json_response(value: serde_json::Value) -> Result<Response<Body>, ScratchError> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(&value).unwrap()))
+        .unwrap())
+}
+
+pub async fn handle_v1_checkpoints_list(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsListPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+
+    let checkpoints: Vec<CheckpointInfo> = list_workspace_checkpoints(gcx.clone(), &post.workspace_folder, post.chat_id.as_deref(), post.limit).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    json_response(serde_json::json!({"checkpoints": checkpoints}))
+}
+
+pub async fn handle_v1_checkpoints_label(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsLabelPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+
+    label_workspace_checkpoint(gcx.clone(), &post.checkpoint, &post.label).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    json_response(serde_json::json!({"success": true}))
+}
+
+pub async fn handle_v1_checkpoints_diff(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsDiffPost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+
+    let (files_changed, diff) = diff_workspace_checkpoints(gcx.clone(), &post.from, &post.to, CHECKPOINTS_DIFF_MAX_SIZE).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    json_response(serde_json::json!({"files_changed": files_changed, "diff": diff}))
+}
+
+pub async fn handle_v1_checkpoints_prune(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<CheckpointsPrunePost>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+    if post.policy.max_age_days.is_none() && post.policy.max_chats.is_none() {
+        return Err(ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, "Set max_age_days or max_chats".to_string()));
+    }
+
+    let pruned_chats = prune_workspace_checkpoints(gcx.clone(), &post.workspace_folder, &post.policy).await
+        .map_err(|e| ScratchError::new(StatusCode::BAD_REQUEST, e))?;
+    json_response(serde_json::json!({"pruned_chats": pruned_chats}))
+}
+
+pub async fn handle_v1_shadow_git_sizes(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    _: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let repos = shadow_repos_sizes(gcx.clone()).await;
+    let total_bytes: u64 = repos.iter().map(|r| r.size_bytes).sum();
+    let retention = load_customization(gcx.clone(), true, &mut vec![]).await
+        .checkpoints_retention.unwrap_or_default();
+    json_response(serde_json::json!({
+        "repos": repos,
+        "total_bytes": total_bytes,
+        "retention": retention,
+    }))
+}
@@ -4,6 +4,7 @@ use hyper::{Body, Response, StatusCode};
 use serde::Serialize;
 
 use crate::ast::ast_structs::AstStatus;
+use crate::completion_cache::CompletionCacheStatus;
 use crate::custom_error::ScratchError;
 use crate::global_context::SharedGlobalContext;
 
@@ -15,16 +16,18 @@ struct RagStatus {
     vecdb: Option<crate::vecdb::vdb_structs::VecDbStatus>,
     vecdb_alive: String,
     vec_db_error: String,
+    completion_cache: CompletionCacheStatus,
 }
 
 pub async fn handle_v1_rag_status(
     Extension(gcx): Extension<SharedGlobalContext>,
     _: hyper::body::Bytes,
 ) -> Result<Response<Body>, ScratchError> {
-    let (vec_db_module, vec_db_error, ast_module) = {
+    let (vec_db_module, vec_db_error, ast_module, completions_cache) = {
         let gcx_locked = gcx.write().await;
-        (gcx_locked.vec_db.clone(), gcx_locked.vec_db_error.clone(), gcx_locked.ast_service.clone())
+        (gcx_locked.vec_db.clone(), gcx_locked.vec_db_error.clone(), gcx_locked.ast_service.clone(), gcx_locked.completions_cache.clone())
     };
+    let completion_cache_status = completions_cache.read().unwrap().status();
 
     #[cfg(feature="vecdb")]
     let (maybe_vecdb_status, vecdb_message) = match crate::vecdb::vdb_highlev::get_status(vec_db_module).await {
@@ -52,6 +55,7 @@ pub async fn handle_v1_rag_status(
         vecdb: maybe_vecdb_status,
         vecdb_alive: vecdb_message,
         vec_db_error,
+        completion_cache: completion_cache_status,
     };
 
     let json_string = serde_json::to_string_pretty(&status).map_err(|e| {
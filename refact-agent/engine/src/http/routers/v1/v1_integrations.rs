@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use axum::Extension;
@@ -12,7 +13,7 @@ use rust_embed::RustEmbed;
 use crate::custom_error::ScratchError;
 use crate::global_context::GlobalContext;
 use crate::integrations::setting_up_integrations::split_path_into_project_and_integration;
-use crate::integrations::integr_mcp::SessionMCP;
+use crate::integrations::integr_mcp::{SessionMCP, mcp_sessions_with_clients};
 
 
 pub async fn handle_v1_integrations(
@@ -223,3 +224,65 @@ pub async fn handle_v1_integrations_mcp_logs(
         }).to_string()))
         .unwrap())
 }
+
+pub async fn handle_v1_integrations_mcp_prompts(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    _: hyper::body::Bytes,
+) -> axum::response::Result<Response<Body>, ScratchError> {
+    let mut prompts = vec![];
+    for (config_path, _, _, session_prompts) in mcp_sessions_with_clients(gcx.clone()).await {
+        for prompt in session_prompts {
+            prompts.push(serde_json::json!({
+                "config_path": config_path,
+                "name": prompt.name,
+                "description": prompt.description,
+                "arguments": prompt.arguments,
+            }));
+        }
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::json!({
+            "prompts": prompts,
+        }).to_string()))
+        .unwrap())
+}
+
+#[derive(Deserialize)]
+pub struct IntegrationsMcpPromptGetRequest {
+    pub config_path: String,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+}
+
+pub async fn handle_v1_integrations_mcp_prompt_get(
+    Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
+    body_bytes: hyper::body::Bytes,
+) -> axum::response::Result<Response<Body>, ScratchError> {
+    let post = serde_json::from_slice::<IntegrationsMcpPromptGetRequest>(&body_bytes)
+        .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("JSON problem: {}", e)))?;
+
+    let (_, client, _, prompts) = mcp_sessions_with_clients(gcx.clone()).await.into_iter()
+        .find(|(config_path, _, _, _)| config_path == &post.config_path)
+        .ok_or(ScratchError::new(StatusCode::NOT_FOUND, format!("session {} not found or not running", post.config_path)))?;
+    let prompt = prompts.iter().find(|p| p.name == post.name)
+        .ok_or(ScratchError::new(StatusCode::NOT_FOUND, format!("prompt {} not found", post.name)))?;
+    let missing_args = prompt.missing_required_arguments(&post.arguments);
+    if !missing_args.is_empty() {
+        return Err(ScratchError::new(StatusCode::BAD_REQUEST, format!("missing required arguments: {}", missing_args.join(", "))));
+    }
+
+    let messages = client.lock().await.get_prompt(&post.name, &post.arguments).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("failed to get prompt {}: {}", post.name, e)))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::json!({
+            "messages": messages,
+        }).to_string()))
+        .unwrap())
+}
@@ -6,13 +6,15 @@ use serde::{Deserialize, Serialize};
 use crate::caps::get_custom_embedding_api_key;
 use crate::custom_error::ScratchError;
 use crate::global_context::SharedGlobalContext;
-use crate::vecdb::vdb_structs::VecdbSearch;
+use crate::vecdb::vdb_structs::{SearchMode, VecdbSearch};
 
 
 #[derive(Serialize, Deserialize, Clone)]
 struct VecDBPost {
     query: String,
     top_n: usize,
+    #[serde(default)]
+    mode: SearchMode,
 }
 
 const NO_VECDB: &str = "Vector db is not running, check if you have --vecdb parameter and a vectorization model is running on server side.";
@@ -30,7 +32,7 @@ pub async fn handle_v1_vecdb_search(
     let cx_locked = gcx.read().await;
 
     let search_res = match *cx_locked.vec_db.lock().await {
-        Some(ref db) => db.vecdb_search(post.query.to_string(), post.top_n, None, &api_key).await,
+        Some(ref db) => db.vecdb_search(post.query.to_string(), post.top_n, None, &api_key, post.mode).await,
         None => {
             return Err(ScratchError::new(
                 StatusCode::INTERNAL_SERVER_ERROR, NO_VECDB.to_string(),
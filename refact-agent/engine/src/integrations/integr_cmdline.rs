@@ -22,6 +22,7 @@ use crate::postprocessing::pp_command_output::{CmdlineOutputFilter, output_mini_
 use crate::integrations::integr_abstract::{IntegrationTrait, IntegrationCommon, IntegrationConfirmation};
 use crate::integrations::utils::{serialize_num_to_str, deserialize_str_to_num, serialize_opt_num_to_str, deserialize_str_to_opt_num};
 use crate::integrations::setting_up_integrations::YamlError;
+use crate::integrations::process_io_utils::output_killed_on_drop;
 
 
 #[derive(Deserialize, Serialize, Clone, Default)]
@@ -179,6 +180,7 @@ pub fn create_command_from_string(
     let shell = if cfg!(target_os = "windows") { "powershell.exe" } else { "sh" };
     let shell_arg = if cfg!(target_os = "windows") { "-Command" } else { "-c" };
     let mut cmd = Command::new(shell);
+    cmd.kill_on_drop(true);
 
     if command_workdir.is_empty() {
         if let Some(first_project_dir) = project_dirs.first() {
@@ -216,11 +218,10 @@ pub async fn execute_blocking_command(
     let command_future = async {
         let mut cmd = create_command_from_string(command, command_workdir, env_variables, project_dirs)?;
         let t0 = tokio::time::Instant::now();
-        let result = cmd
+        let result = output_killed_on_drop(cmd
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output()
-            .await;
+        ).await;
         let duration = t0.elapsed();
         info!("EXEC: /finished in {:?}", duration);
 
@@ -340,6 +341,10 @@ impl Tool for ToolCmdline {
     fn has_config_path(&self) -> Option<String> {
         Some(self.config_path.clone())
     }
+
+    fn own_timeout(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_secs(self.cfg.timeout.parse::<u64>().unwrap_or(10)))
+    }
 }
 
 pub const CMDLINE_INTEGRATION_SCHEMA: &str = r#"
@@ -390,3 +395,39 @@ smartlinks:
         content: |
           🔧 Please write %CURRENT_CONFIG% based on what you see in the project. Follow the plan in the system prompt.
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    fn process_is_gone(pid: &str) -> bool {
+        // a killed orphan may linger as a zombie if nobody reaps it, that counts as gone
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat.rsplit_once(')').map(|(_, rest)| rest.trim_start().starts_with('Z')).unwrap_or(false),
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_timed_out_command_kills_its_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pid_file = tmp.path().join("sleep.pid");
+        let cfg = CmdlineToolConfig { timeout: "1".to_string(), ..Default::default() };
+        let command = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let workdir = tmp.path().to_string_lossy().to_string();
+
+        let result = execute_blocking_command(&command, &cfg, &workdir, &HashMap::new(), vec![]).await;
+        assert!(result.unwrap_err().contains("timed out"));
+
+        let sleep_pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        for _ in 0..50 {
+            if process_is_gone(&sleep_pid) {
+                return;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+        panic!("sleep {} is still running after the command was cancelled", sleep_pid);
+    }
+}
@@ -0,0 +1,1122 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::SystemTime;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{watch, Mutex as AMutex, RwLock as ARwLock};
+use tokio::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ChatUsage, ContextEnum};
+use crate::files_correction::get_active_project_path;
+use crate::global_context::GlobalContext;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation, IntegrationTrait};
+use crate::integrations::process_io_utils::{first_n_chars, last_n_chars};
+use crate::integrations::sessions::{get_session_hashmap_key, IntegrationSession};
+use crate::tools::tools_description::{Tool, ToolDesc, ToolParam};
+
+
+const SESSION_TIMEOUT_AFTER_INACTIVITY: Duration = Duration::from_secs(30 * 60);
+const PROGRAM_OUTPUT_CAP: usize = 100_000;  // chars of program output kept until the model sees them
+const STACK_LEVELS: i64 = 20;
+const CODE_SECTION_LINES_AROUND: usize = 5;
+const MAX_WAIT_SECONDS: u64 = 120;
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_LAUNCH_ARGUMENTS: &str = r#"{"program": "%program%", "args": "%args%", "cwd": "%workdir%", "stopOnEntry": true}"#;
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct SettingsDap {
+    pub adapter_command: String,
+    #[serde(default)]
+    pub adapter_id: String,
+    #[serde(default)]
+    pub adapter_port: String,  // empty means the adapter talks over stdin/stdout
+    #[serde(default)]
+    pub launch_arguments: String,
+    #[serde(default)]
+    pub timeout: String,
+}
+
+#[derive(Default)]
+pub struct ToolDap {
+    pub common: IntegrationCommon,
+    pub name: String,
+    pub settings_dap: SettingsDap,
+    pub config_path: String,
+}
+
+enum DapWriter {
+    Stdio(ChildStdin),
+    Tcp(OwnedWriteHalf),
+}
+
+/// Everything the adapter has told us so far, read_dap_messages() keeps it up to date
+#[derive(Default)]
+struct DapState {
+    responses: HashMap<i64, Value>,
+    program_output: String,
+    thread_id: Option<i64>,
+    initialized: bool,
+    stopped_reason: Option<String>,  // Some while the program is paused
+    terminated: bool,
+    adapter_gone: bool,
+    exit_code: Option<i64>,
+}
+
+/// Shared by the session and the reader task, so waiting for the adapter doesn't need the session lock
+struct DapConnection {
+    writer: AMutex<DapWriter>,
+    seq: AtomicI64,
+    state: watch::Sender<DapState>,
+}
+
+pub struct DapSession {
+    process: Child,
+    conn: Arc<DapConnection>,
+    breakpoints: HashMap<String, Vec<i64>>,
+    function_breakpoints: Vec<String>,
+    workdir: PathBuf,
+    last_usage_ts: u64,
+}
+
+impl Drop for DapSession {
+    fn drop(&mut self) {
+        self.process.start_kill().map_err(|e| error!("Failed to kill debug adapter: {}", e)).ok();
+    }
+}
+
+impl IntegrationSession for DapSession
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_expired(&self) -> bool {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        self.last_usage_ts + SESSION_TIMEOUT_AFTER_INACTIVITY.as_secs() < current_time
+    }
+
+    fn try_stop(&mut self, self_arc: Arc<AMutex<Box<dyn IntegrationSession>>>) -> Box<dyn Future<Output = String> + Send> {
+        Box::new(async move {
+            disconnect_session(self_arc).await;
+            "".to_string()
+        })
+    }
+}
+
+#[async_trait]
+impl IntegrationTrait for ToolDap {
+    fn as_any(&self) -> &dyn Any { self }
+
+    async fn integr_settings_apply(&mut self, _gcx: Arc<ARwLock<GlobalContext>>, config_path: String, value: &serde_json::Value) -> Result<(), serde_json::Error> {
+        self.settings_dap = serde_json::from_value(value.clone())?;
+        self.common = serde_json::from_value(value.clone())?;
+        self.config_path = config_path;
+        Ok(())
+    }
+
+    fn integr_settings_as_json(&self) -> Value {
+        serde_json::to_value(&self.settings_dap).unwrap_or_default()
+    }
+
+    fn integr_common(&self) -> IntegrationCommon {
+        self.common.clone()
+    }
+
+    async fn integr_tools(&self, integr_name: &str) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
+        vec![Box::new(ToolDap {
+            common: self.common.clone(),
+            name: integr_name.to_string(),
+            settings_dap: self.settings_dap.clone(),
+            config_path: self.config_path.clone(),
+        })]
+    }
+
+    fn integr_schema(&self) -> &str { DAP_INTEGRATION_SCHEMA }
+}
+
+#[derive(Debug, PartialEq)]
+enum DapCommand {
+    Launch(Vec<String>),
+    Break(BreakpointSpec),
+    Clear(BreakpointSpec),
+    Continue,
+    Next,
+    Step,
+    Finish,
+    Pause,
+    Evaluate(String),
+    Stack,
+    Variables(usize),
+    Wait(u64),
+    Kill,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BreakpointSpec {
+    Line(String, i64),
+    Function(String),
+}
+
+fn parse_breakpoint_spec(spec: &str) -> Result<BreakpointSpec, String> {
+    if spec.is_empty() {
+        return Err("Breakpoint is missing, use `break path/to/file.rs:42` or `break function_name`".to_string());
+    }
+    if let Some((file, line)) = spec.rsplit_once(':') {
+        if let Ok(line) = line.parse::<i64>() {
+            return Ok(BreakpointSpec::Line(file.to_string(), line));
+        }
+    }
+    Ok(BreakpointSpec::Function(spec.to_string()))
+}
+
+fn parse_command(command: &str) -> Result<DapCommand, String> {
+    let command = command.trim();
+    let (verb, rest) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    let rest = rest.trim();
+    match verb {
+        "launch" => {
+            let program_args = shell_words::split(rest).map_err(|e| e.to_string())?;
+            if program_args.is_empty() {
+                return Err("Usage: launch path/to/program [args...]".to_string());
+            }
+            Ok(DapCommand::Launch(program_args))
+        }
+        "break" | "b" => Ok(DapCommand::Break(parse_breakpoint_spec(rest)?)),
+        "clear" => Ok(DapCommand::Clear(parse_breakpoint_spec(rest)?)),
+        "continue" | "c" => Ok(DapCommand::Continue),
+        "next" | "n" => Ok(DapCommand::Next),
+        "step" | "s" => Ok(DapCommand::Step),
+        "finish" | "out" => Ok(DapCommand::Finish),
+        "pause" => Ok(DapCommand::Pause),
+        "evaluate" | "print" | "p" => {
+            if rest.is_empty() {
+                return Err("Expression is missing, use `evaluate expression`".to_string());
+            }
+            Ok(DapCommand::Evaluate(rest.to_string()))
+        }
+        "stack" | "where" | "bt" => Ok(DapCommand::Stack),
+        "variables" | "locals" => {
+            let frame_n = if rest.is_empty() { 0 } else {
+                rest.parse::<usize>().map_err(|_| "Argument `frame_n` in `variables frame_n` is not a number".to_string())?
+            };
+            Ok(DapCommand::Variables(frame_n))
+        }
+        "wait" => {
+            let n_seconds = rest.parse::<u64>().map_err(|_| "Argument `n_seconds` in `wait n_seconds` is missing or not a number".to_string())?;
+            Ok(DapCommand::Wait(n_seconds.min(MAX_WAIT_SECONDS)))
+        }
+        "kill" | "quit" => Ok(DapCommand::Kill),
+        "" => Err("Command is empty".to_string()),
+        _ => Err(format!("Unknown command {:?}, use one of: launch, break, clear, continue, next, step, finish, pause, evaluate, stack, variables, wait, kill", verb)),
+    }
+}
+
+/// Takes complete "Content-Length: N\r\n\r\n{json}" messages from the front of the buffer
+fn parse_dap_frames(buf: &mut Vec<u8>) -> Vec<Value> {
+    let mut messages = vec![];
+    loop {
+        let Some(header_end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else { break };
+        let header = String::from_utf8_lossy(&buf[..header_end]).to_string();
+        let content_length = header.lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, v)| v.trim().parse::<usize>().ok());
+        let Some(content_length) = content_length else {
+            warn!("DAP: header without Content-Length: {:?}", header);
+            buf.drain(..header_end + 4);
+            continue;
+        };
+        let body_start = header_end + 4;
+        if buf.len() < body_start + content_length {
+            break;
+        }
+        match serde_json::from_slice::<Value>(&buf[body_start..body_start + content_length]) {
+            Ok(message) => messages.push(message),
+            Err(e) => warn!("DAP: cannot parse a message: {}", e),
+        }
+        buf.drain(..body_start + content_length);
+    }
+    messages
+}
+
+fn encode_dap_message(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+async fn read_dap_messages<R: AsyncRead + Unpin>(mut reader: R, conn: Arc<DapConnection>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => {
+                buf.extend_from_slice(&chunk[..n]);
+                for message in parse_dap_frames(&mut buf) {
+                    if let Err(e) = conn.handle_message(message).await {
+                        warn!("DAP: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("DAP: reading from the adapter failed: {}", e);
+                break;
+            }
+        }
+    }
+    conn.state.send_modify(|s| {
+        s.adapter_gone = true;
+        s.terminated = true;
+    });
+}
+
+/// Strings "%program%", "%workdir%" are replaced inside any string, a string that is exactly "%args%" becomes an array
+fn fill_launch_arguments(template: &Value, program: &str, args: &[String], workdir: &str) -> Value {
+    match template {
+        Value::String(s) if s == "%args%" => json!(args),
+        Value::String(s) => Value::String(s.replace("%program%", program).replace("%workdir%", workdir)),
+        Value::Array(items) => Value::Array(items.iter().map(|x| fill_launch_arguments(x, program, args, workdir)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), fill_launch_arguments(v, program, args, workdir))).collect()),
+        other => other.clone(),
+    }
+}
+
+impl DapState {
+    fn handle_event(&mut self, message: &Value) {
+        let body = &message["body"];
+        match message["event"].as_str().unwrap_or_default() {
+            "initialized" => self.initialized = true,
+            "output" => {
+                if body["category"].as_str() != Some("telemetry") {
+                    self.program_output.push_str(body["output"].as_str().unwrap_or_default());
+                    if self.program_output.len() > PROGRAM_OUTPUT_CAP {
+                        self.program_output = last_n_chars(&self.program_output, PROGRAM_OUTPUT_CAP);
+                    }
+                }
+            }
+            "stopped" => {
+                let mut reason = body["reason"].as_str().unwrap_or("paused").to_string();
+                if let Some(text) = body["text"].as_str().or(body["description"].as_str()) {
+                    reason = format!("{} ({})", reason, text);
+                }
+                self.stopped_reason = Some(reason);
+                if let Some(thread_id) = body["threadId"].as_i64() {
+                    self.thread_id = Some(thread_id);
+                }
+            }
+            "continued" => self.stopped_reason = None,
+            "exited" => self.exit_code = body["exitCode"].as_i64(),
+            "terminated" => {
+                self.terminated = true;
+                self.stopped_reason = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl DapConnection {
+    fn new(writer: DapWriter) -> Self {
+        DapConnection {
+            writer: AMutex::new(writer),
+            seq: AtomicI64::new(0),
+            state: watch::channel(DapState::default()).0,
+        }
+    }
+
+    async fn send_request(&self, command: &str, arguments: Value) -> Result<i64, String> {
+        let seq = self.seq.fetch_add(1, Ordering::SeqCst) + 1;
+        let message = json!({"seq": seq, "type": "request", "command": command, "arguments": arguments});
+        self.send(&message).await?;
+        Ok(seq)
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), String> {
+        let bytes = encode_dap_message(message);
+        let mut writer = self.writer.lock().await;
+        let result = match &mut *writer {
+            DapWriter::Stdio(w) => w.write_all(&bytes).await.and(w.flush().await),
+            DapWriter::Tcp(w) => w.write_all(&bytes).await.and(w.flush().await),
+        };
+        result.map_err(|e| format!("Failed to send to the debug adapter: {}", e))
+    }
+
+    async fn handle_message(&self, message: Value) -> Result<(), String> {
+        match message["type"].as_str().unwrap_or_default() {
+            "response" => {
+                if let Some(request_seq) = message["request_seq"].as_i64() {
+                    self.state.send_modify(|s| { s.responses.insert(request_seq, message); });
+                }
+            }
+            "event" => self.state.send_modify(|s| s.handle_event(&message)),
+            "request" => {
+                // reverse requests like runInTerminal or startDebugging, we didn't announce support for them
+                let reply = json!({
+                    "seq": self.seq.fetch_add(1, Ordering::SeqCst) + 1, "type": "response", "request_seq": message["seq"], "command": message["command"],
+                    "success": false, "message": "not supported",
+                });
+                self.send(&reply).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn wait_response(&self, command: &str, seq: i64, timeout: Duration) -> Result<Value, String> {
+        let mut state_rx = self.state.subscribe();
+        let _ = tokio::time::timeout(timeout, state_rx.wait_for(|s| s.responses.contains_key(&seq) || s.adapter_gone)).await;
+        let mut response = None;
+        self.state.send_if_modified(|s| {
+            response = s.responses.remove(&seq);
+            false
+        });
+        match response {
+            Some(response) if response["success"].as_bool() == Some(true) => Ok(response["body"].clone()),
+            Some(response) => {
+                let message = response["body"]["error"]["format"].as_str()
+                    .or(response["message"].as_str())
+                    .unwrap_or("unknown error");
+                Err(format!("`{}` failed: {}", command, message))
+            }
+            None if self.state.borrow().adapter_gone => Err(format!("The debug adapter exited while waiting for `{}`", command)),
+            None => Err(format!("The debug adapter didn't answer `{}` in {} seconds", command, timeout.as_secs())),
+        }
+    }
+
+    async fn request(&self, command: &str, arguments: Value, timeout: Duration) -> Result<Value, String> {
+        let seq = self.send_request(command, arguments).await?;
+        self.wait_response(command, seq, timeout).await
+    }
+
+    /// Waits for events until `condition` holds or the time runs out, returns whether it holds
+    async fn wait_until(&self, timeout: Duration, condition: fn(&DapState) -> bool) -> bool {
+        let mut state_rx = self.state.subscribe();
+        let _ = tokio::time::timeout(timeout, state_rx.wait_for(|s| condition(s) || s.adapter_gone)).await;
+        condition(&self.state.borrow())
+    }
+
+    async fn current_thread(&self, timeout: Duration) -> Result<i64, String> {
+        let known_thread_id = self.state.borrow().thread_id;
+        if let Some(thread_id) = known_thread_id {
+            return Ok(thread_id);
+        }
+        let body = self.request("threads", json!({}), timeout).await?;
+        let thread_id = body["threads"].as_array()
+            .and_then(|threads| threads.first())
+            .and_then(|t| t["id"].as_i64())
+            .ok_or("The debugged program has no threads")?;
+        self.state.send_if_modified(|s| {
+            s.thread_id = Some(thread_id);
+            false
+        });
+        Ok(thread_id)
+    }
+
+    async fn frames(&self, timeout: Duration) -> Result<Vec<Value>, String> {
+        let thread_id = self.current_thread(timeout).await?;
+        let body = self.request("stackTrace", json!({"threadId": thread_id, "startFrame": 0, "levels": STACK_LEVELS}), timeout).await?;
+        Ok(body["stackFrames"].as_array().cloned().unwrap_or_default())
+    }
+
+    async fn variables(&self, frame: &Value, timeout: Duration) -> Result<String, String> {
+        let body = self.request("scopes", json!({"frameId": frame["id"]}), timeout).await?;
+        let scopes = body["scopes"].as_array().cloned().unwrap_or_default();
+        let mut out = String::new();
+        for scope in scopes.iter().filter(|s| !s["expensive"].as_bool().unwrap_or(false)).take(2) {
+            let body = self.request("variables", json!({"variablesReference": scope["variablesReference"]}), timeout).await?;
+            out.push_str(&format!("{}:\n", scope["name"].as_str().unwrap_or("scope")));
+            for variable in body["variables"].as_array().cloned().unwrap_or_default() {
+                let type_suffix = variable["type"].as_str().filter(|t| !t.is_empty()).map(|t| format!(" ({})", t)).unwrap_or_default();
+                out.push_str(&format!(
+                    "  {} = {}{}\n",
+                    variable["name"].as_str().unwrap_or_default(),
+                    first_n_chars(variable["value"].as_str().unwrap_or_default(), 200),
+                    type_suffix,
+                ));
+            }
+        }
+        Ok(out)
+    }
+
+    fn take_program_output(&self) -> String {
+        let mut program_output = String::new();
+        self.state.send_if_modified(|s| {
+            program_output = std::mem::take(&mut s.program_output);
+            false
+        });
+        program_output
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.state.borrow().stopped_reason.is_some()
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.state.borrow().terminated
+    }
+
+    async fn set_breakpoints(&self, request: BreakpointsRequest, timeout: Duration) -> Result<String, String> {
+        let body = self.request(request.command, request.arguments, timeout).await?;
+        if !request.add {
+            return Ok(format!("Breakpoint at {} cleared", request.location));
+        }
+        let confirmed = body["breakpoints"].as_array()
+            .and_then(|bps| bps.iter().find(|bp| request.line.is_some() && bp["line"].as_i64() == request.line).or(bps.last()))
+            .cloned()
+            .unwrap_or_default();
+        Ok(describe_breakpoint(&request.location, &confirmed))
+    }
+
+    /// Where the program is now, in the same shape as pdb output
+    async fn describe_state(&self, command_output: &str, timeout: Duration) -> Result<String, String> {
+        let program_output = self.take_program_output();
+        let (terminated, exit_code, stopped_reason) = {
+            let state = self.state.borrow();
+            (state.terminated, state.exit_code, state.stopped_reason.clone())
+        };
+        if terminated {
+            let exit_code = exit_code.map(|c| format!(" with exit code {}", c)).unwrap_or_default();
+            return Ok(format!(
+                "Command output:\n{}\n{}The program has finished{}, use `launch` to start it again.\n",
+                command_output, format_program_output(&program_output), exit_code,
+            ));
+        }
+        let Some(stopped_reason) = stopped_reason else {
+            return Ok(format!(
+                "Command output:\n{}\n{}The program is running. Call the tool again with \"wait n_seconds\" to wait for a breakpoint, \"pause\" to stop it, or \"kill\" to end the session.\n",
+                command_output, format_program_output(&program_output),
+            ));
+        };
+        let frames = self.frames(timeout).await?;
+        let code_section = match frames.first() {
+            Some(frame) => code_section(frame).await,
+            None => "".to_string(),
+        };
+        let locals = match frames.first() {
+            Some(frame) => self.variables(frame, timeout).await.unwrap_or_else(|e| format!("{}\n", e)),
+            None => "".to_string(),
+        };
+        Ok(format!(
+            "Command output:\n{}\n{}Stopped: {}\nCurrent code section:\n{}\nStack trace:\n{}\nLocal variables:\n{}",
+            command_output,
+            format_program_output(&program_output),
+            stopped_reason,
+            code_section,
+            format_stack(&frames),
+            first_n_chars(&locals, 2000),
+        ))
+    }
+
+    /// Killing the adapter alone can leave the debuggee orphaned and paused, debugpy and js-debug run it
+    /// in a separate process, so ask the adapter to take it down first
+    async fn disconnect(&self) {
+        if self.state.borrow().adapter_gone {
+            return;
+        }
+        if let Err(e) = self.request("disconnect", json!({"terminateDebuggee": true}), DISCONNECT_TIMEOUT).await {
+            warn!("DAP: {}", e);
+        }
+    }
+
+    async fn resume(&self, command: &str, timeout: Duration) -> Result<(), String> {
+        if !self.is_stopped() {
+            return Err(format!("The program is not stopped, `{}` needs a stopped program, use \"pause\" or \"wait n_seconds\"", command));
+        }
+        let thread_id = self.current_thread(timeout).await?;
+        self.state.send_modify(|s| s.stopped_reason = None);
+        self.request(command, json!({"threadId": thread_id}), timeout).await?;
+        self.wait_until(timeout, |s| s.stopped_reason.is_some() || s.terminated).await;
+        Ok(())
+    }
+}
+
+/// The adapter replaces all breakpoints of a file, or all function breakpoints, with every request
+struct BreakpointsRequest {
+    location: String,
+    line: Option<i64>,
+    add: bool,
+    command: &'static str,
+    arguments: Value,
+}
+
+impl DapSession {
+    fn resolve_path(&self, file: &str) -> String {
+        let path = PathBuf::from(file);
+        let path = if path.is_relative() { self.workdir.join(path) } else { path };
+        crate::files_correction::canonical_path(path.to_string_lossy().to_string()).to_string_lossy().to_string()
+    }
+
+    fn breakpoints_request(&mut self, spec: BreakpointSpec, add: bool) -> BreakpointsRequest {
+        match spec {
+            BreakpointSpec::Line(file, line) => {
+                let path = self.resolve_path(&file);
+                let lines = self.breakpoints.entry(path.clone()).or_default();
+                lines.retain(|l| *l != line);
+                if add {
+                    lines.push(line);
+                }
+                BreakpointsRequest {
+                    location: format!("{}:{}", path, line),
+                    line: Some(line),
+                    add,
+                    command: "setBreakpoints",
+                    arguments: json!({
+                        "source": {"path": path},
+                        "breakpoints": lines.iter().map(|l| json!({"line": l})).collect::<Vec<_>>(),
+                    }),
+                }
+            }
+            BreakpointSpec::Function(name) => {
+                self.function_breakpoints.retain(|f| *f != name);
+                if add {
+                    self.function_breakpoints.push(name.clone());
+                }
+                BreakpointsRequest {
+                    location: name,
+                    line: None,
+                    add,
+                    command: "setFunctionBreakpoints",
+                    arguments: json!({
+                        "breakpoints": self.function_breakpoints.iter().map(|f| json!({"name": f})).collect::<Vec<_>>(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+async fn disconnect_session(session: Arc<AMutex<Box<dyn IntegrationSession>>>) {
+    let conn = {
+        let mut session_locked = session.lock().await;
+        match session_locked.as_any_mut().downcast_mut::<DapSession>() {
+            Some(session) => session.conn.clone(),
+            None => return,
+        }
+    };
+    conn.disconnect().await;
+}
+
+fn describe_breakpoint(location: &str, confirmed: &Value) -> String {
+    if confirmed["verified"].as_bool() == Some(false) {
+        let why = confirmed["message"].as_str().unwrap_or("the adapter couldn't map it to code yet, it might resolve after the module loads");
+        format!("Breakpoint at {} is not verified: {}", location, why)
+    } else {
+        let line = confirmed["line"].as_i64().map(|l| format!(", line {}", l)).unwrap_or_default();
+        format!("Breakpoint set at {}{}", location, line)
+    }
+}
+
+fn format_program_output(program_output: &str) -> String {
+    if program_output.is_empty() {
+        "".to_string()
+    } else {
+        format!("Program output:\n{}\n", last_n_chars(program_output, 5000))
+    }
+}
+
+fn format_stack(frames: &[Value]) -> String {
+    let mut out = String::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let path = frame["source"]["path"].as_str().or(frame["source"]["name"].as_str()).unwrap_or("<unknown>");
+        out.push_str(&format!(
+            "#{} {} at {}:{}\n",
+            i, frame["name"].as_str().unwrap_or_default(), path, frame["line"].as_i64().unwrap_or_default(),
+        ));
+    }
+    out
+}
+
+async fn code_section(frame: &Value) -> String {
+    let (Some(path), Some(line)) = (frame["source"]["path"].as_str(), frame["line"].as_u64()) else {
+        return "(no source)\n".to_string();
+    };
+    let Ok(text) = tokio::fs::read_to_string(path).await else {
+        return format!("(cannot read {})\n", path);
+    };
+    let line = line as usize;
+    let first = line.saturating_sub(CODE_SECTION_LINES_AROUND).max(1);
+    let mut out = String::new();
+    for (i, text_line) in text.lines().enumerate().skip(first - 1).take(2 * CODE_SECTION_LINES_AROUND + 1) {
+        let marker = if i + 1 == line { "->" } else { "  " };
+        out.push_str(&format!("{:>4} {} {}\n", i + 1, marker, text_line));
+    }
+    out
+}
+
+async fn start_dap_session(
+    settings: &SettingsDap,
+    program_args: &[String],
+    workdir: &Path,
+    timeout: Duration,
+) -> Result<(DapSession, String), String> {
+    let adapter_args = shell_words::split(&settings.adapter_command).map_err(|e| e.to_string())?;
+    if adapter_args.is_empty() {
+        return Err("`adapter_command` is empty in the integration config".to_string());
+    }
+    let launch_template: Value = serde_json::from_str(
+        if settings.launch_arguments.trim().is_empty() { DEFAULT_LAUNCH_ARGUMENTS } else { settings.launch_arguments.as_str() }
+    ).map_err(|e| format!("`launch_arguments` in the integration config is not valid json: {}", e))?;
+
+    info!("Starting debug adapter {:?} in {:?}", adapter_args, workdir);
+    let adapter_port = settings.adapter_port.trim();
+    let mut command = Command::new(&adapter_args[0]);
+    command.args(&adapter_args[1..])
+        .current_dir(workdir)
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    if adapter_port.is_empty() {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    } else {
+        command.stdin(Stdio::null()).stdout(Stdio::null());
+    }
+    let mut process = command.spawn().map_err(|e| format!("Failed to start {}: {}", adapter_args[0], e))?;
+
+    let conn = if adapter_port.is_empty() {
+        let stdout = process.stdout.take().ok_or("Failed to open stdout of the debug adapter")?;
+        let stdin = process.stdin.take().ok_or("Failed to open stdin of the debug adapter")?;
+        let conn = Arc::new(DapConnection::new(DapWriter::Stdio(stdin)));
+        tokio::spawn(read_dap_messages(stdout, conn.clone()));
+        conn
+    } else {
+        let port = adapter_port.parse::<u16>().map_err(|_| format!("`adapter_port` {:?} is not a port number", adapter_port))?;
+        let started = Instant::now();
+        let stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) if started.elapsed() < timeout => tokio::time::sleep(Duration::from_millis(200)).await,
+                Err(e) => return Err(format!("Cannot connect to the debug adapter on port {}: {}", port, e)),
+            }
+        };
+        let (read_half, write_half) = stream.into_split();
+        let conn = Arc::new(DapConnection::new(DapWriter::Tcp(write_half)));
+        tokio::spawn(read_dap_messages(read_half, conn.clone()));
+        conn
+    };
+
+    let session = DapSession {
+        process,
+        conn: conn.clone(),
+        breakpoints: HashMap::new(),
+        function_breakpoints: vec![],
+        workdir: workdir.to_path_buf(),
+        last_usage_ts: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(),
+    };
+
+    let adapter_id = if settings.adapter_id.is_empty() { adapter_args[0].clone() } else { settings.adapter_id.clone() };
+    conn.request("initialize", json!({
+        "clientID": "refact",
+        "adapterID": adapter_id,
+        "linesStartAt1": true,
+        "columnsStartAt1": true,
+        "pathFormat": "path",
+        "supportsRunInTerminalRequest": false,
+    }), timeout).await?;
+
+    let program = session.resolve_path(&program_args[0]);
+    let launch_arguments = fill_launch_arguments(&launch_template, &program, &program_args[1..], &workdir.to_string_lossy());
+    // some adapters (debugpy) answer `launch` only after configurationDone
+    let launch_seq = conn.send_request("launch", launch_arguments).await?;
+    if !conn.wait_until(timeout, |s| s.initialized || s.terminated).await {
+        warn!("DAP: no `initialized` event, sending configurationDone anyway");
+    }
+    if let Err(e) = conn.request("configurationDone", json!({}), timeout).await {
+        warn!("DAP: {}", e);
+    }
+    conn.wait_response("launch", launch_seq, timeout).await?;
+    conn.wait_until(timeout, |s| s.stopped_reason.is_some() || s.terminated).await;
+    let output = format!("Launched {} with {}", program, adapter_args[0]);
+    Ok((session, output))
+}
+
+#[async_trait]
+impl Tool for ToolDap {
+    fn as_any(&self) -> &dyn Any { self }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let (command, workdir_maybe) = parse_args(args)?;
+        let dap_command = parse_command(&command)?;
+        let timeout = Duration::from_secs(self.settings_dap.timeout.parse::<u64>().unwrap_or(10));
+
+        let (gcx, chat_id) = {
+            let ccx_lock = ccx.lock().await;
+            (ccx_lock.global_context.clone(), ccx_lock.chat_id.clone())
+        };
+        let session_hashmap_key = get_session_hashmap_key(&self.name, &chat_id);
+
+        if let DapCommand::Launch(program_args) = &dap_command {
+            let workdir = match workdir_maybe {
+                Some(workdir) => workdir,
+                None => get_active_project_path(gcx.clone()).await.ok_or("No working directory, set `workdir`")?,
+            };
+            let old_session = gcx.write().await.integration_sessions.remove(&session_hashmap_key);
+            if let Some(old_session) = old_session {
+                disconnect_session(old_session).await;
+            }
+            let (session, launch_output) = start_dap_session(&self.settings_dap, program_args, &workdir, timeout).await?;
+            let output = session.conn.describe_state(&launch_output, timeout).await?;
+            if !session.conn.is_terminated() {
+                let command_session: Box<dyn IntegrationSession> = Box::new(session);
+                gcx.write().await.integration_sessions.insert(session_hashmap_key, Arc::new(AMutex::new(command_session)));
+            }
+            return Ok(tool_answer(output, tool_call_id));
+        }
+
+        let command_session = {
+            let gcx_locked = gcx.read().await;
+            gcx_locked.integration_sessions.get(&session_hashmap_key)
+                .ok_or(format!("There is no active debugging session in this chat, start it with {}(\"launch path/to/program args\")", self.name))?
+                .clone()
+        };
+        let (conn, breakpoints_request) = {
+            let mut command_session_locked = command_session.lock().await;
+            let session = command_session_locked.as_any_mut().downcast_mut::<DapSession>()
+                .ok_or("Failed to downcast to DapSession")?;
+            session.last_usage_ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+            let breakpoints_request = match &dap_command {
+                DapCommand::Break(spec) => Some(session.breakpoints_request(spec.clone(), true)),
+                DapCommand::Clear(spec) => Some(session.breakpoints_request(spec.clone(), false)),
+                _ => None,
+            };
+            (session.conn.clone(), breakpoints_request)
+        };
+        // The session lock is released here, a long `wait` or `continue` doesn't block try_stop() or anyone else
+
+        let output = match dap_command {
+            DapCommand::Launch(_) => unreachable!(),
+            DapCommand::Kill => {
+                conn.disconnect().await;
+                gcx.write().await.integration_sessions.remove(&session_hashmap_key);
+                return Ok(tool_answer("Debugging session has been killed".to_string(), tool_call_id));
+            }
+            DapCommand::Break(_) | DapCommand::Clear(_) => conn.set_breakpoints(breakpoints_request.unwrap(), timeout).await?,
+            DapCommand::Continue => {
+                conn.resume("continue", timeout).await?;
+                conn.describe_state("continue", timeout).await?
+            }
+            DapCommand::Next => {
+                conn.resume("next", timeout).await?;
+                conn.describe_state("next", timeout).await?
+            }
+            DapCommand::Step => {
+                conn.resume("stepIn", timeout).await?;
+                conn.describe_state("step", timeout).await?
+            }
+            DapCommand::Finish => {
+                conn.resume("stepOut", timeout).await?;
+                conn.describe_state("finish", timeout).await?
+            }
+            DapCommand::Pause => {
+                let thread_id = conn.current_thread(timeout).await?;
+                conn.request("pause", json!({"threadId": thread_id}), timeout).await?;
+                conn.wait_until(timeout, |s| s.stopped_reason.is_some() || s.terminated).await;
+                conn.describe_state("pause", timeout).await?
+            }
+            DapCommand::Wait(n_seconds) => {
+                conn.wait_until(Duration::from_secs(n_seconds), |s| s.stopped_reason.is_some() || s.terminated).await;
+                conn.describe_state(&format!("waited up to {} seconds", n_seconds), timeout).await?
+            }
+            DapCommand::Evaluate(expression) => {
+                let frame_id = if conn.is_stopped() {
+                    conn.frames(timeout).await?.first().map(|f| f["id"].clone())
+                } else {
+                    None
+                };
+                let mut arguments = json!({"expression": expression, "context": "repl"});
+                if let Some(frame_id) = frame_id {
+                    arguments["frameId"] = frame_id;
+                }
+                let body = conn.request("evaluate", arguments, timeout).await?;
+                let type_suffix = body["type"].as_str().filter(|t| !t.is_empty()).map(|t| format!(" ({})", t)).unwrap_or_default();
+                format!("{} = {}{}\n{}", expression, last_n_chars(body["result"].as_str().unwrap_or_default(), 5000), type_suffix,
+                    format_program_output(&conn.take_program_output()))
+            }
+            DapCommand::Stack => {
+                let frames = conn.frames(timeout).await?;
+                format!("Stack trace:\n{}", format_stack(&frames))
+            }
+            DapCommand::Variables(frame_n) => {
+                let frames = conn.frames(timeout).await?;
+                let frame = frames.get(frame_n).ok_or(format!("There are only {} frames", frames.len()))?;
+                format!("Variables in frame #{}:\n{}", frame_n, first_n_chars(&conn.variables(frame, timeout).await?, 5000))
+            }
+        };
+        if conn.is_terminated() {
+            conn.disconnect().await;
+            gcx.write().await.integration_sessions.remove(&session_hashmap_key);
+        }
+        Ok(tool_answer(output, tool_call_id))
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let (command, _) = parse_args(args)?;
+        Ok(command.trim().to_string())
+    }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: self.name.clone(),
+            agentic: true,
+            experimental: false,
+            description: format!(
+                "Debugger ({}) for inspecting variables and exploring what the program really does. Executes one command at a time. \
+                Start with `launch path/to/program args`, the program stops at the entry point if the debugger supports it.",
+                if self.settings_dap.adapter_id.is_empty() { &self.settings_dap.adapter_command } else { &self.settings_dap.adapter_id },
+            ),
+            parameters: vec![
+                ToolParam {
+                    name: "command".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Examples: 'launch target/debug/app --flag', 'break src/main.rs:42', 'break function_name', 'clear src/main.rs:42', 'continue', 'next', 'step', 'finish', 'pause', 'evaluate x + 1', 'stack', 'variables 1', 'wait 30', 'kill'".to_string(),
+                },
+                ToolParam {
+                    name: "workdir".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Working directory for `launch`, relative paths in commands are resolved from it.".to_string(),
+                },
+            ],
+            parameters_required: vec!["command".to_string()],
+        }
+    }
+
+    fn tool_depends_on(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn usage(&mut self) -> &mut Option<ChatUsage> {
+        static mut DEFAULT_USAGE: Option<ChatUsage> = None;
+        #[allow(static_mut_refs)]
+        unsafe { &mut DEFAULT_USAGE }
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.integr_common().confirmation)
+    }
+
+    fn has_config_path(&self) -> Option<String> {
+        Some(self.config_path.clone())
+    }
+}
+
+fn parse_args(args: &HashMap<String, Value>) -> Result<(String, Option<PathBuf>), String> {
+    let command = match args.get("command") {
+        Some(Value::String(s)) => s.to_string(),
+        Some(v) => return Err(format!("argument `command` is not a string: {:?}", v)),
+        None => return Err("Missing argument `command`".to_string()),
+    };
+    let workdir_maybe = match args.get("workdir") {
+        Some(Value::String(s)) => {
+            if s.is_empty() {
+                None
+            } else {
+                let workdir = crate::files_correction::canonical_path(s);
+                if !workdir.exists() {
+                    return Err("Workdir doesn't exist".to_string());
+                } else {
+                    Some(workdir)
+                }
+            }
+        },
+        Some(v) => return Err(format!("argument `workdir` is not a string: {:?}", v)),
+        None => None
+    };
+    Ok((command, workdir_maybe))
+}
+
+fn tool_answer(output: String, tool_call_id: &String) -> (bool, Vec<ContextEnum>)
+{
+    (false, vec![
+        ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(output),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })
+    ])
+}
+
+pub const DAP_INTEGRATION_SCHEMA: &str = r#"
+fields:
+  adapter_command:
+    f_type: string_long
+    f_desc: "The debug adapter to start, it should speak the Debug Adapter Protocol on stdin/stdout, or on adapter_port if that is set."
+    f_placeholder: "python3 -m debugpy.adapter"
+    f_label: "Adapter Command"
+  adapter_id:
+    f_type: string_short
+    f_desc: "Adapter id sent in the initialize request, some adapters check it: debugpy, lldb-dap, go, pwa-node."
+    f_placeholder: "debugpy"
+  adapter_port:
+    f_type: string_short
+    f_desc: "Leave empty if the adapter talks over stdin/stdout. Set it for adapters that listen on a TCP port, like `dlv dap --listen=127.0.0.1:4711`."
+    f_placeholder: "4711"
+    f_extra: true
+  launch_arguments:
+    f_type: string_long
+    f_desc: "JSON for the launch request, specific to the adapter. %program% and %workdir% are replaced, \"%args%\" becomes the list of program arguments."
+    f_default: "{\"program\": \"%program%\", \"args\": \"%args%\", \"cwd\": \"%workdir%\", \"stopOnEntry\": true}"
+    f_extra: true
+  timeout:
+    f_type: string_short
+    f_desc: "How many seconds to wait for the adapter to answer, or for the program to stop after continue/next/step."
+    f_default: "10"
+    f_extra: true
+description: |
+  Debugging over the Debug Adapter Protocol, works with any language that has an adapter. Some examples:
+  Python: adapter_command "python3 -m debugpy.adapter", adapter_id "debugpy"
+  Rust, C, C++: adapter_command "lldb-dap", adapter_id "lldb-dap"
+  Go: adapter_command "dlv dap --listen=127.0.0.1:4711", adapter_port "4711", adapter_id "go", launch_arguments {"mode": "exec", "program": "%program%", "args": "%args%", "stopOnEntry": true}
+  JavaScript: adapter_command "node js-debug/src/dapDebugServer.js 8123", adapter_port "8123", adapter_id "pwa-node", launch_arguments {"type": "pwa-node", "program": "%program%", "args": "%args%", "cwd": "%workdir%", "stopOnEntry": true}
+available:
+  on_your_laptop_possible: true
+  when_isolated_possible: true
+confirmation:
+  ask_user_default: ["launch *"]
+  deny_default: []
+smartlinks:
+  - sl_label: "Test"
+    sl_chat:
+      - role: "user"
+        content: |
+          🔧 Test the tool that corresponds to %CURRENT_CONFIG%: start debugging a small program in the project, set a breakpoint, continue to it and inspect some variables.
+          If it doesn't work or the tool isn't available, go through the usual plan in the system prompt.
+    sl_enable_only_with_tool: true
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dap_frames_and_commands() {
+        let first = json!({"seq": 1, "type": "event", "event": "initialized"});
+        let second = json!({"seq": 2, "type": "response", "request_seq": 1, "success": true, "body": {"text": "ünicode"}});
+        let mut buf = encode_dap_message(&first);
+        let second_bytes = encode_dap_message(&second);
+        buf.extend_from_slice(&second_bytes[..10]);
+        assert_eq!(parse_dap_frames(&mut buf), vec![first]);
+        buf.extend_from_slice(&second_bytes[10..]);
+        assert_eq!(parse_dap_frames(&mut buf), vec![second]);
+        assert!(buf.is_empty());
+
+        assert_eq!(parse_command("launch ./app 'a b' c").unwrap(), DapCommand::Launch(vec!["./app".to_string(), "a b".to_string(), "c".to_string()]));
+        assert_eq!(parse_command("break src/main.rs:42").unwrap(), DapCommand::Break(BreakpointSpec::Line("src/main.rs".to_string(), 42)));
+        assert_eq!(parse_command("b my_module::run").unwrap(), DapCommand::Break(BreakpointSpec::Function("my_module::run".to_string())));
+        assert_eq!(parse_command("p x + y").unwrap(), DapCommand::Evaluate("x + y".to_string()));
+        assert_eq!(parse_command("variables").unwrap(), DapCommand::Variables(0));
+        assert!(parse_command("wait").is_err());
+        assert_eq!(parse_command("wait 100000").unwrap(), DapCommand::Wait(MAX_WAIT_SECONDS));
+        assert!(parse_command("jump 10").is_err());
+
+        let template: Value = serde_json::from_str(DEFAULT_LAUNCH_ARGUMENTS).unwrap();
+        let filled = fill_launch_arguments(&template, "/p/app", &["-v".to_string()], "/p");
+        assert_eq!(filled, json!({"program": "/p/app", "args": ["-v"], "cwd": "/p", "stopOnEntry": true}));
+    }
+
+    const STUB_ADAPTER: &str = r#"
+import json, sys
+
+def read_message():
+    headers = {}
+    while True:
+        line = sys.stdin.buffer.readline()
+        if not line:
+            sys.exit(0)
+        line = line.strip()
+        if not line:
+            break
+        key, value = line.decode().split(":", 1)
+        headers[key.strip().lower()] = value.strip()
+    return json.loads(sys.stdin.buffer.read(int(headers["content-length"])))
+
+seq = 0
+def send(message):
+    global seq
+    seq += 1
+    message["seq"] = seq
+    body = json.dumps(message).encode()
+    sys.stdout.buffer.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+    sys.stdout.buffer.flush()
+
+def respond(request, body={}):
+    send({"type": "response", "request_seq": request["seq"], "command": request["command"], "success": True, "body": body})
+
+launch = None
+while True:
+    request = read_message()
+    command = request["command"]
+    if command == "initialize":
+        respond(request, {"supportsConfigurationDoneRequest": True})
+        send({"type": "event", "event": "initialized"})
+    elif command == "launch":
+        launch = request  # like debugpy, the answer comes after configurationDone
+    elif command == "configurationDone":
+        respond(request)
+        respond(launch)
+        send({"type": "event", "event": "output", "body": {"category": "stdout", "output": "hello from the program\n"}})
+        send({"type": "event", "event": "stopped", "body": {"reason": "entry", "threadId": 7}})
+    elif command == "stackTrace" and request["arguments"]["threadId"] == 7:
+        respond(request, {"stackFrames": [{"id": 1, "name": "main", "line": 2, "source": {"path": launch["arguments"]["program"]}}]})
+    elif command == "scopes":
+        respond(request, {"scopes": [{"name": "Locals", "variablesReference": 11, "expensive": False}]})
+    elif command == "variables":
+        respond(request, {"variables": [{"name": "x", "value": "42", "type": "int"}]})
+    elif command == "disconnect":
+        respond(request)
+        send({"type": "event", "event": "terminated"})
+        sys.exit(0)
+    else:
+        send({"type": "response", "request_seq": request["seq"], "command": command, "success": False, "message": "unexpected"})
+"#;
+
+    #[tokio::test]
+    async fn test_dap_session_against_stub_adapter() {
+        if which::which("python3").is_err() {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let adapter_path = tmp.path().join("stub_adapter.py");
+        std::fs::write(&adapter_path, STUB_ADAPTER).unwrap();
+        std::fs::write(tmp.path().join("main.py"), "x = 42\nprint(x)\n").unwrap();
+        let settings = SettingsDap {
+            adapter_command: format!("python3 {}", adapter_path.display()),
+            adapter_id: "stub".to_string(),
+            ..Default::default()
+        };
+        let timeout = Duration::from_secs(10);
+
+        let (session, launch_output) = start_dap_session(&settings, &["main.py".to_string()], tmp.path(), timeout).await.unwrap();
+        assert!(launch_output.starts_with("Launched "), "{}", launch_output);
+        let conn = session.conn.clone();
+        let state = conn.describe_state(&launch_output, timeout).await.unwrap();
+        assert!(state.contains("hello from the program"), "{}", state);
+        assert!(state.contains("Stopped: entry"), "{}", state);
+        assert!(state.contains("->") && state.contains("print(x)"), "{}", state);
+        assert!(state.contains("#0 main at "), "{}", state);
+        assert!(state.contains("x = 42 (int)"), "{}", state);
+
+        conn.disconnect().await;
+        assert!(conn.wait_until(timeout, |s| s.terminated).await);
+        assert!(conn.wait_until(timeout, |s| s.adapter_gone).await);
+    }
+}
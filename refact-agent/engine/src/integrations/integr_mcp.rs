@@ -14,23 +14,109 @@ use mcp_client_rs::client::ClientBuilder;
 use crate::global_context::GlobalContext;
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::tools::tools_description::{Tool, ToolDesc, ToolParam};
-use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
+use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
 use crate::integrations::integr_abstract::{IntegrationTrait, IntegrationCommon, IntegrationConfirmation};
 use crate::integrations::sessions::IntegrationSession;
+use crate::integrations::mcp_http_transport::{MCPHttpClient, MCPHttpTransport};
 
 
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MCPTransport {
+    #[default]
+    #[serde(alias = "")]
+    Stdio,
+    Sse,
+    Http,
+}
+
 #[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
 pub struct SettingsMCP {
-    #[serde(rename = "command")]
+    #[serde(default)]
+    pub transport: MCPTransport,
+    #[serde(default, rename = "command")]
     pub mcp_command: String,
     #[serde(default, rename = "env")]
     pub mcp_env: HashMap<String, String>,
+    #[serde(flatten)]
+    pub http: SettingsMCPHttp,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
+pub struct SettingsMCPHttp {
+    #[serde(default, rename = "url")]
+    pub mcp_url: String,
+    #[serde(default, rename = "headers")]
+    pub mcp_headers: HashMap<String, String>,
+    #[serde(default, rename = "auth_token")]
+    pub mcp_auth_token: String,
+    #[serde(default, rename = "request_timeout")]
+    pub mcp_request_timeout: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MCPLaunchSettings {
+    Stdio(SettingsMCP),
+    Http(MCPHttpTransport, SettingsMCPHttp),
+}
+
+impl SettingsMCP {
+    pub fn launch_settings(&self) -> MCPLaunchSettings {
+        match self.transport {
+            MCPTransport::Stdio => MCPLaunchSettings::Stdio(self.clone()),
+            MCPTransport::Sse => MCPLaunchSettings::Http(MCPHttpTransport::Sse, self.http.clone()),
+            MCPTransport::Http => MCPLaunchSettings::Http(MCPHttpTransport::StreamableHttp, self.http.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MCPResource {
+    pub uri: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, rename = "mimeType")]
+    pub mime_type: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MCPPromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MCPPrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub arguments: Vec<MCPPromptArgument>,
+}
+
+impl MCPPrompt {
+    pub fn missing_required_arguments(&self, arguments: &HashMap<String, String>) -> Vec<String> {
+        self.arguments.iter()
+            .filter(|a| a.required && !arguments.contains_key(&a.name))
+            .map(|a| a.name.clone())
+            .collect()
+    }
+}
+
+pub enum MCPTransportClient {
+    Stdio(MCPClient),
+    Http(MCPHttpClient),
 }
 
 pub struct ToolMCP {
     pub common: IntegrationCommon,
     pub config_path: String,
-    pub mcp_client: Arc<AMutex<MCPClient>>,
+    pub mcp_client: Arc<AMutex<MCPTransportClient>>,
     pub mcp_tool: mcp_client_rs::Tool,
 }
 
@@ -45,9 +131,11 @@ pub struct IntegrationMCP {
 pub struct SessionMCP {
     pub debug_name: String,
     pub config_path: String,        // to check if expired or not
-    pub launched_cfg: SettingsMCP,  // a copy to compare against IntegrationMCP::cfg, to see if anything has changed
-    pub mcp_client: Option<Arc<AMutex<MCPClient>>>,
+    pub launched_cfg: MCPLaunchSettings,  // a copy to compare against IntegrationMCP::cfg, to see if anything has changed
+    pub mcp_client: Option<Arc<AMutex<MCPTransportClient>>>,
     pub mcp_tools: Vec<mcp_client_rs::Tool>,
+    pub mcp_resources: Vec<MCPResource>,
+    pub mcp_prompts: Vec<MCPPrompt>,
     pub startup_task_handles: Option<(Arc<AMutex<Option<JoinHandle<()>>>>, AbortHandle)>,
     pub logs: Arc<AMutex<Vec<String>>>,          // Store log messages
 }
@@ -88,6 +176,150 @@ impl IntegrationSession for SessionMCP {
     }
 }
 
+impl MCPTransportClient {
+    pub async fn list_tools(&mut self) -> Result<Vec<mcp_client_rs::Tool>, String> {
+        match self {
+            MCPTransportClient::Stdio(client) => client.list_tools().await
+                .map(|result| result.tools)
+                .map_err(|e| format!("{:?}", e)),
+            MCPTransportClient::Http(client) => client.list_tools().await,
+        }
+    }
+
+    pub async fn call_tool(&mut self, tool_name: &str, args: serde_json::Value) -> Result<String, String> {
+        match self {
+            MCPTransportClient::Stdio(client) => {
+                let result = client.call_tool(tool_name, args).await.map_err(|e| e.to_string())?;
+                if result.is_error {
+                    return Err(format!("Tool execution error: {:?}", result.content));
+                }
+                match result.content.get(0) {
+                    Some(mcp_client_rs::MessageContent::Text { text }) => Ok(text.clone()),
+                    _ => Err(format!("Unexpected tool output format: {:?}", result.content)),
+                }
+            }
+            MCPTransportClient::Http(client) => {
+                let result = client.call_tool(tool_name, args).await?;
+                let content = result.get("content").cloned().unwrap_or(serde_json::Value::Null);
+                if result.get("isError").and_then(|x| x.as_bool()).unwrap_or(false) {
+                    return Err(format!("Tool execution error: {}", content));
+                }
+                match content.get(0) {
+                    Some(first) if first.get("type").and_then(|t| t.as_str()) == Some("text") => {
+                        Ok(first.get("text").and_then(|t| t.as_str()).unwrap_or_default().to_string())
+                    }
+                    _ => Err(format!("Unexpected tool output format: {}", content)),
+                }
+            }
+        }
+    }
+
+    pub async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+        match self {
+            MCPTransportClient::Stdio(client) => client.request(method, Some(params)).await.map_err(|e| format!("{:?}", e)),
+            MCPTransportClient::Http(client) => client.request(method, params).await,
+        }
+    }
+
+    async fn request_all_pages(&mut self, method: &str, items_key: &str) -> Result<Vec<serde_json::Value>, String> {
+        let mut items = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => serde_json::json!({"cursor": c}),
+                None => serde_json::json!({}),
+            };
+            let result = self.request(method, params).await?;
+            if let Some(serde_json::Value::Array(page)) = result.get(items_key) {
+                items.extend(page.iter().cloned());
+            }
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(|c| c.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    pub async fn list_resources(&mut self) -> Result<Vec<MCPResource>, String> {
+        let items = self.request_all_pages("resources/list", "resources").await?;
+        Ok(items.into_iter().filter_map(|x| serde_json::from_value(x).ok()).collect())
+    }
+
+    pub async fn list_prompts(&mut self) -> Result<Vec<MCPPrompt>, String> {
+        let items = self.request_all_pages("prompts/list", "prompts").await?;
+        Ok(items.into_iter().filter_map(|x| serde_json::from_value(x).ok()).collect())
+    }
+
+    pub async fn read_resource(&mut self, uri: &str) -> Result<Vec<ContextFile>, String> {
+        let result = self.request("resources/read", serde_json::json!({"uri": uri})).await?;
+        let mut context_files = vec![];
+        for contents in result.get("contents").and_then(|c| c.as_array()).cloned().unwrap_or_default() {
+            let content_uri = contents.get("uri").and_then(|u| u.as_str()).unwrap_or(uri).to_string();
+            let text = match contents.get("text").and_then(|t| t.as_str()) {
+                Some(text) => text.to_string(),
+                None => {
+                    tracing::info!("MCP resource {} has binary contents, skipping", content_uri);
+                    continue;
+                }
+            };
+            let line2 = text.lines().count();
+            context_files.push(ContextFile {
+                file_name: content_uri,
+                file_content: text,
+                line1: 1,
+                line2,
+                symbols: vec![],
+                gradient_type: -1,
+                usefulness: 100.0,
+            });
+        }
+        if context_files.is_empty() {
+            return Err(format!("resource {} has no text contents", uri));
+        }
+        Ok(context_files)
+    }
+
+    pub async fn get_prompt(&mut self, name: &str, arguments: &HashMap<String, String>) -> Result<Vec<ChatMessage>, String> {
+        let result = self.request("prompts/get", serde_json::json!({"name": name, "arguments": arguments})).await?;
+        let mut messages = vec![];
+        for m in result.get("messages").and_then(|m| m.as_array()).cloned().unwrap_or_default() {
+            let role = m.get("role").and_then(|r| r.as_str()).unwrap_or("user").to_string();
+            let content = m.get("content").cloned().unwrap_or_default();
+            let text = match content.get("type").and_then(|t| t.as_str()) {
+                Some("text") => content.get("text").and_then(|t| t.as_str()).unwrap_or_default().to_string(),
+                Some("resource") => {
+                    let resource = content.get("resource").cloned().unwrap_or_default();
+                    match resource.get("text").and_then(|t| t.as_str()) {
+                        Some(text) => format!("{}:\n```\n{}\n```", resource.get("uri").and_then(|u| u.as_str()).unwrap_or_default(), text),
+                        None => continue,
+                    }
+                }
+                _ => {
+                    tracing::info!("MCP prompt {} contains unsupported content {:?}, skipping", name, content.get("type"));
+                    continue;
+                }
+            };
+            messages.push(ChatMessage::new(role, text));
+        }
+        Ok(messages)
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), String> {
+        match self {
+            MCPTransportClient::Stdio(client) => client.shutdown().await.map_err(|e| format!("{:?}", e)),
+            MCPTransportClient::Http(client) => client.shutdown().await,
+        }
+    }
+
+    pub async fn get_stderr(&mut self) -> Option<String> {
+        match self {
+            MCPTransportClient::Stdio(client) => client.get_stderr(None).await.ok(),
+            MCPTransportClient::Http(_) => None,  // remote server, nothing to read
+        }
+    }
+}
+
 async fn _add_log_entry(session_logs: Arc<AMutex<Vec<String>>>, entry: String) {
     let timestamp = chrono::Local::now().format("%H:%M:%S%.3f").to_string();
     let log_entry = format!("[{}] {}", timestamp, entry);
@@ -103,7 +335,7 @@ async fn _add_log_entry(session_logs: Arc<AMutex<Vec<String>>>, entry: String) {
 
 async fn _session_kill_process(
     debug_name: &str, 
-    mcp_client: Arc<AMutex<MCPClient>>, 
+    mcp_client: Arc<AMutex<MCPTransportClient>>, 
     session_logs: Arc<AMutex<Vec<String>>>,
 ) {
     tracing::info!("Stopping MCP Server for {}", debug_name);
@@ -125,11 +357,119 @@ async fn _session_kill_process(
     }
 }
 
+async fn _session_connect_stdio(
+    cfg: &SettingsMCP,
+    debug_name: &str,
+    logs: Arc<AMutex<Vec<String>>>,
+) -> Option<MCPTransportClient> {
+    let parsed_args = match shell_words::split(&cfg.mcp_command) {
+        Ok(args) => {
+            if args.is_empty() {
+                let error_msg = "Empty command".to_string();
+                tracing::info!("{error_msg} for {debug_name}");
+                _add_log_entry(logs.clone(), error_msg).await;
+                return None;
+            }
+            args
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to parse command: {}", e);
+            tracing::info!("{error_msg} for {debug_name}");
+            _add_log_entry(logs.clone(), error_msg).await;
+            return None;
+        }
+    };
+
+    let mut client_builder = ClientBuilder::new(&parsed_args[0]);
+    for arg in parsed_args.iter().skip(1) {
+        client_builder = client_builder.arg(arg);
+    }
+    for (key, value) in &cfg.mcp_env {
+        client_builder = client_builder.env(key, value);
+    }
+
+    let (mut client, imp, caps) = match client_builder.spawn().await {
+        Ok(r) => r,
+        Err(e) => {
+            let err_msg = format!("Failed to init process: {}", e);
+            tracing::error!("{err_msg} for {debug_name}");
+            _add_log_entry(logs.clone(), err_msg).await;
+            return None;
+        }
+    };
+    if let Err(e) = client.initialize(imp, caps).await {
+        let err_msg = format!("Failed to init server: {}", e);
+        tracing::error!("{err_msg} for {debug_name}");
+        _add_log_entry(logs.clone(), err_msg).await;
+        if let Ok(error_log) = client.get_stderr(None).await {
+            _add_log_entry(logs.clone(), error_log).await;
+        }
+        return None;
+    };
+
+    // let set_result = client.request(
+    //     "logging/setLevel",
+    //     Some(serde_json::json!({ "level": "debug" })),
+    // ).await;
+    // match set_result {
+    //     Ok(_) => {
+    //         tracing::info!("MCP START SESSION (2) set log level success");
+    //     }
+    //     Err(e) => {
+    //         tracing::info!("MCP START SESSION (2) failed to set log level: {:?}", e);
+    //     }
+    // }
+
+    Some(MCPTransportClient::Stdio(client))
+}
+
+async fn _session_connect_http(
+    http_client: reqwest::Client,
+    transport: MCPHttpTransport,
+    cfg: &SettingsMCPHttp,
+    debug_name: &str,
+    logs: Arc<AMutex<Vec<String>>>,
+) -> Option<MCPTransportClient> {
+    if cfg.mcp_url.trim().is_empty() {
+        let error_msg = "Empty url".to_string();
+        tracing::info!("{error_msg} for {debug_name}");
+        _add_log_entry(logs.clone(), error_msg).await;
+        return None;
+    }
+    _add_log_entry(logs.clone(), format!("Connecting to {} ({:?})", cfg.mcp_url, transport)).await;
+
+    let mut client = match MCPHttpClient::connect(
+        http_client,
+        transport,
+        cfg.mcp_url.trim(),
+        &cfg.mcp_headers,
+        &cfg.mcp_auth_token,
+        std::time::Duration::from_secs(cfg.mcp_request_timeout.parse::<u64>().unwrap_or(60).max(1)),
+    ).await {
+        Ok(client) => client,
+        Err(e) => {
+            let err_msg = format!("Failed to connect: {}", e);
+            tracing::error!("{err_msg} for {debug_name}");
+            _add_log_entry(logs.clone(), err_msg).await;
+            return None;
+        }
+    };
+    if let Err(e) = client.initialize().await {
+        let err_msg = format!("Failed to init server: {}", e);
+        tracing::error!("{err_msg} for {debug_name}");
+        _add_log_entry(logs.clone(), err_msg).await;
+        return None;
+    }
+
+    Some(MCPTransportClient::Http(client))
+}
+
 async fn _session_apply_settings(
     gcx: Arc<ARwLock<GlobalContext>>,
     config_path: String,
-    new_cfg: SettingsMCP,
+    new_cfg: MCPLaunchSettings,
 ) {
+    let http_client = gcx.read().await.http_client.clone();
     let session_key = format!("{}", config_path);
 
     let session_arc = {
@@ -142,6 +482,8 @@ async fn _session_apply_settings(
                 launched_cfg: new_cfg.clone(),
                 mcp_client: None,
                 mcp_tools: Vec::new(),
+                mcp_resources: Vec::new(),
+                mcp_prompts: Vec::new(),
                 startup_task_handles: None,
                 logs: Arc::new(AMutex::new(Vec::new())),
             })));
@@ -187,98 +529,67 @@ async fn _session_apply_settings(
                 _session_kill_process(&debug_name, mcp_client, logs.clone()).await;
             }
 
-            let parsed_args = match shell_words::split(&new_cfg_clone.mcp_command) {
-                Ok(args) => {
-                    if args.is_empty() {
-                        let error_msg = "Empty command".to_string();
-                        tracing::info!("{error_msg} for {debug_name}");
-                        _add_log_entry(logs.clone(), error_msg).await;
-                        return;
-                    }
-                    args
-                }
-                Err(e) => {
-                    let error_msg = format!("Failed to parse command: {}", e);
-                    tracing::info!("{error_msg} for {debug_name}");
-                    _add_log_entry(logs.clone(), error_msg).await;
-                    return;
-                }
-            };
-
-            let mut client_builder = ClientBuilder::new(&parsed_args[0]);
-            for arg in parsed_args.iter().skip(1) {
-                client_builder = client_builder.arg(arg);
-            }
-            for (key, value) in &new_cfg_clone.mcp_env {
-                client_builder = client_builder.env(key, value);
-            }
-
-            let (mut client, imp, caps) = match client_builder.spawn().await {
-                Ok(r) => r,
-                Err(e) => {
-                    let err_msg = format!("Failed to init process: {}", e);
-                    tracing::error!("{err_msg} for {debug_name}");
-                    _add_log_entry(logs.clone(), err_msg).await;
-                    return;
-                }
+            let client_maybe = match &new_cfg_clone {
+                MCPLaunchSettings::Stdio(cfg) => _session_connect_stdio(cfg, &debug_name, logs.clone()).await,
+                MCPLaunchSettings::Http(transport, cfg) => _session_connect_http(http_client, *transport, cfg, &debug_name, logs.clone()).await,
             };
-            if let Err(e) = client.initialize(imp, caps).await {
-                let err_msg = format!("Failed to init server: {}", e);
-                tracing::error!("{err_msg} for {debug_name}");
-                _add_log_entry(logs.clone(), err_msg).await;
-                if let Ok(error_log) = client.get_stderr(None).await {
-                    _add_log_entry(logs.clone(), error_log).await;
-                }
-                return;
+            let mut client = match client_maybe {
+                Some(client) => client,
+                None => return,
             };
 
-            // let set_result = client.request(
-            //     "logging/setLevel",
-            //     Some(serde_json::json!({ "level": "debug" })),
-            // ).await;
-            // match set_result {
-            //     Ok(_) => {
-            //         tracing::info!("MCP START SESSION (2) set log level success");
-            //     }
-            //     Err(e) => {
-            //         tracing::info!("MCP START SESSION (2) failed to set log level: {:?}", e);
-            //     }
-            // }
-
             tracing::info!("MCP START SESSION (2) {:?}", debug_name);
             _add_log_entry(logs.clone(), "Listing tools".to_string()).await;
             
             let tools_result = match client.list_tools().await {
                 Ok(result) => {
-                    let success_msg = format!("Successfully listed {} tools", result.tools.len());
+                    let success_msg = format!("Successfully listed {} tools", result.len());
                     tracing::info!("{} for {}", success_msg, debug_name);
                     result
                 },
                 Err(tools_error) => {
-                    let err_msg = format!("Failed to list tools: {:?}", tools_error);
+                    let err_msg = format!("Failed to list tools: {}", tools_error);
                     tracing::error!("{} for {}", err_msg, debug_name);
                     _add_log_entry(logs.clone(), err_msg).await;
-                    if let Ok(error_log) = client.get_stderr(None).await {
+                    if let Some(error_log) = client.get_stderr().await {
                         _add_log_entry(logs.clone(), error_log).await;
                     }
                     return;
                 }
             };
 
+            // resources and prompts are optional parts of the protocol, many servers only have tools
+            let resources_result = match client.list_resources().await {
+                Ok(result) => result,
+                Err(e) => {
+                    _add_log_entry(logs.clone(), format!("No resources: {}", e)).await;
+                    vec![]
+                }
+            };
+            let prompts_result = match client.list_prompts().await {
+                Ok(result) => result,
+                Err(e) => {
+                    _add_log_entry(logs.clone(), format!("No prompts: {}", e)).await;
+                    vec![]
+                }
+            };
+
             let new_mcp_client = Arc::new(AMutex::new(client));
             
-            let tools_len = {
+            let (tools_len, resources_len, prompts_len) = {
                 tracing::info!("MCP START SESSION (3) {:?}", debug_name);
                 let mut session_locked = session_arc_clone.lock().await;
                 let session_downcasted = session_locked.as_any_mut().downcast_mut::<SessionMCP>().unwrap();
 
                 session_downcasted.mcp_client = Some(new_mcp_client);
-                session_downcasted.mcp_tools = tools_result.tools;
+                session_downcasted.mcp_tools = tools_result;
+                session_downcasted.mcp_resources = resources_result;
+                session_downcasted.mcp_prompts = prompts_result;
 
-                session_downcasted.mcp_tools.len()
+                (session_downcasted.mcp_tools.len(), session_downcasted.mcp_resources.len(), session_downcasted.mcp_prompts.len())
             };
             
-            let setup_msg = format!("MCP session setup complete with {tools_len} tools");
+            let setup_msg = format!("MCP session setup complete with {tools_len} tools, {resources_len} resources, {prompts_len} prompts");
             tracing::info!("{} for {}", setup_msg, debug_name);
             _add_log_entry(logs.clone(), setup_msg).await;
         });
@@ -290,6 +601,25 @@ async fn _session_apply_settings(
     }
 }
 
+pub async fn mcp_sessions_with_clients(
+    gcx: Arc<ARwLock<GlobalContext>>,
+) -> Vec<(String, Arc<AMutex<MCPTransportClient>>, Vec<MCPResource>, Vec<MCPPrompt>)> {
+    let sessions = gcx.read().await.integration_sessions.iter()
+        .map(|(key, session)| (key.clone(), session.clone()))
+        .collect::<Vec<_>>();
+    let mut result = vec![];
+    for (key, session) in sessions {
+        let mut session_locked = session.lock().await;
+        if let Some(session_downcasted) = session_locked.as_any_mut().downcast_mut::<SessionMCP>() {
+            if let Some(client) = &session_downcasted.mcp_client {
+                result.push((key, client.clone(), session_downcasted.mcp_resources.clone(), session_downcasted.mcp_prompts.clone()));
+            }
+        }
+    }
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+    result
+}
+
 async fn _session_wait_startup_task(
     session_arc: Arc<AMutex<Box<dyn IntegrationSession>>>,
 ) {
@@ -307,6 +637,57 @@ async fn _session_wait_startup_task(
     }
 }
 
+async fn _session_tools(
+    gcx_option: Option<Weak<ARwLock<GlobalContext>>>,
+    config_path: &str,
+    common: &IntegrationCommon,
+) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
+    let session_key = format!("{}", config_path);
+    
+    let gcx = match gcx_option {
+        Some(gcx_weak) => match gcx_weak.upgrade() {
+            Some(gcx) => gcx,
+            None => {
+                tracing::error!("Error: System is shutting down");
+                return vec![];
+            }
+        },
+        None => {
+            tracing::error!("Error: MCP is not set up yet");
+            return vec![];
+        }
+    };
+    
+    let session_maybe = gcx.read().await.integration_sessions.get(&session_key).cloned();
+    let session = match session_maybe {
+        Some(session) => session,
+        None => {
+            tracing::error!("No session for {:?}, strange (1)", session_key);
+            return vec![];
+        }
+    };
+
+    let mut result: Vec<Box<dyn crate::tools::tools_description::Tool + Send>> = vec![];
+    {
+        let mut session_locked = session.lock().await;
+        let session_downcasted: &mut SessionMCP = session_locked.as_any_mut().downcast_mut::<SessionMCP>().unwrap();
+        if session_downcasted.mcp_client.is_none() {
+            tracing::error!("No mcp_client for {:?}, strange (2)", session_key);
+            return vec![];
+        }
+        for tool in session_downcasted.mcp_tools.iter() {
+            result.push(Box::new(ToolMCP {
+                common: common.clone(),
+                config_path: config_path.to_string(),
+                mcp_client: session_downcasted.mcp_client.clone().unwrap(),
+                mcp_tool: tool.clone(),
+            }));
+        }
+    }
+
+    result
+}
+
 #[async_trait]
 impl IntegrationTrait for IntegrationMCP {
     fn as_any(&self) -> &dyn std::any::Any {
@@ -318,7 +699,7 @@ impl IntegrationTrait for IntegrationMCP {
         self.cfg = serde_json::from_value(value.clone())?;
         self.common = serde_json::from_value(value.clone())?;
         self.config_path = config_path;
-        _session_apply_settings(gcx.clone(), self.config_path.clone(), self.cfg.clone()).await;  // possibly saves coroutine in session
+        _session_apply_settings(gcx.clone(), self.config_path.clone(), self.cfg.launch_settings()).await;  // possibly saves coroutine in session
         Ok(())
     }
 
@@ -331,50 +712,7 @@ impl IntegrationTrait for IntegrationMCP {
     }
 
     async fn integr_tools(&self, _integr_name: &str) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
-        let session_key = format!("{}", self.config_path);
-        
-        let gcx = match self.gcx_option.clone() {
-            Some(gcx_weak) => match gcx_weak.upgrade() {
-                Some(gcx) => gcx,
-                None => {
-                    tracing::error!("Error: System is shutting down");
-                    return vec![];
-                }
-            },
-            None => {
-                tracing::error!("Error: MCP is not set up yet");
-                return vec![];
-            }
-        };
-        
-        let session_maybe = gcx.read().await.integration_sessions.get(&session_key).cloned();
-        let session = match session_maybe {
-            Some(session) => session,
-            None => {
-                tracing::error!("No session for {:?}, strange (1)", session_key);
-                return vec![];
-            }
-        };
-
-        let mut result: Vec<Box<dyn crate::tools::tools_description::Tool + Send>> = vec![];
-        {
-            let mut session_locked = session.lock().await;
-            let session_downcasted: &mut SessionMCP = session_locked.as_any_mut().downcast_mut::<SessionMCP>().unwrap();
-            if session_downcasted.mcp_client.is_none() {
-                tracing::error!("No mcp_client for {:?}, strange (2)", session_key);
-                return vec![];
-            }
-            for tool in session_downcasted.mcp_tools.iter() {
-                result.push(Box::new(ToolMCP {
-                    common: self.common.clone(),
-                    config_path: self.config_path.clone(),
-                    mcp_client: session_downcasted.mcp_client.clone().unwrap(),
-                    mcp_tool: tool.clone(),
-                }));
-            }
-        }
-
-        result
+        _session_tools(self.gcx_option.clone(), &self.config_path, &self.common).await
     }
 
     fn integr_schema(&self) -> &str {
@@ -419,36 +757,23 @@ impl Tool for ToolMCP {
             let mut mcp_client_locked = self.mcp_client.lock().await;
             mcp_client_locked.call_tool(self.mcp_tool.name.as_str(), json_args).await
         };
-        
+
         let tool_output = match result_probably {
-            Ok(result) => {
-                if result.is_error {
-                    let error_msg = format!("Tool execution error: {:?}", result.content);
-                    _add_log_entry(session_logs.clone(), error_msg.clone()).await;
-                    return Err(error_msg);
-                }
-                
-                if let Some(mcp_client_rs::MessageContent::Text { text }) = result.content.get(0) {
-                    let success_msg = format!("Tool '{}' executed successfully", self.mcp_tool.name);
-                    _add_log_entry(session_logs.clone(), success_msg).await;
-                    text.clone()
-                } else {
-                    let error_msg = format!("Unexpected tool output format: {:?}", result.content);
-                    tracing::error!("{}", error_msg);
-                    _add_log_entry(session_logs.clone(), error_msg.clone()).await;
-                    return Err("Unexpected tool output format".to_string());
-                }
+            Ok(text) => {
+                let success_msg = format!("Tool '{}' executed successfully", self.mcp_tool.name);
+                _add_log_entry(session_logs.clone(), success_msg).await;
+                text
             }
             Err(e) => {
-                let error_msg = format!("Failed to call tool: {:?}", e);
+                let error_msg = format!("Failed to call tool: {}", e);
                 tracing::error!("{}", error_msg);
                 _add_log_entry(session_logs.clone(), error_msg).await;
-                    
-                let error_log = self.mcp_client.lock().await.get_stderr(None).await;
-                if let Ok(error_log) = error_log {
+
+                let error_log = self.mcp_client.lock().await.get_stderr().await;
+                if let Some(error_log) = error_log {
                     _add_log_entry(session_logs.clone(), error_log).await;
                 }
-                return Err(e.to_string());
+                return Err(e);
             }
         };
 
@@ -554,14 +879,41 @@ impl Tool for ToolMCP {
 
 pub const MCP_INTEGRATION_SCHEMA: &str = r#"
 fields:
+  transport:
+    f_type: string_short
+    f_desc: "How to talk to the server: `stdio` runs `command` as a child process, `sse` (HTTP+SSE) and `http` (streamable HTTP) connect to a server that is already running at `url`."
+    f_default: "stdio"
   command:
     f_type: string
-    f_desc: "The MCP command to execute, like `npx -y <some-mcp-server>`, `/my/path/venv/python -m <some-mcp-server>`, or `docker run -i --rm <some-mcp-image>`. On Windows, use `npx.cmd` or `npm.cmd` instead of `npx` or `npm`."
+    f_desc: "The MCP command to execute, like `npx -y <some-mcp-server>`, `/my/path/venv/python -m <some-mcp-server>`, or `docker run -i --rm <some-mcp-image>`. On Windows, use `npx.cmd` or `npm.cmd` instead of `npx` or `npm`. Only for the stdio transport."
   env:
     f_type: string_to_string_map
+    f_desc: "Environment variables for the command. Only for the stdio transport."
+  url:
+    f_type: string_long
+    f_desc: "Only for the sse and http transports. For sse it's the endpoint that opens an event stream on GET, usually ends with `/sse`. For http it's the endpoint that accepts JSON-RPC messages via POST, usually ends with `/mcp`."
+    f_placeholder: "http://127.0.0.1:8000/mcp"
+    f_extra: true
+  headers:
+    f_type: string_to_string_map
+    f_desc: "Additional HTTP headers sent with every request."
+    f_extra: true
+  auth_token:
+    f_type: string_long
+    f_desc: "Sent as `Authorization: Bearer <auth_token>`. If you don't want to send your key to the AI model that helps you to configure the agent, put it into secrets.yaml and write `$MY_SECRET_VARIABLE` in this field."
+    f_extra: true
+    smartlinks:
+      - sl_label: "Open secrets.yaml"
+        sl_goto: "EDITOR:secrets.yaml"
+  request_timeout:
+    f_type: string_short
+    f_desc: "Seconds to wait for the server to answer a single request, sse and http transports only."
+    f_default: "60"
+    f_extra: true
 description: |
-  You can add almost any MCP (Model Context Protocol) server here! This supports local MCP servers,
-  with remote servers coming up as the specificion gets updated. You can read more
+  You can add almost any MCP (Model Context Protocol) server here! By default it runs a local MCP server
+  as a child process and talks to it over stdio. Set `transport` to `sse` or `http` to connect to a shared
+  server running as an HTTP service instead. You can read more
   here https://www.anthropic.com/news/model-context-protocol
 available:
   on_your_laptop_possible: true
@@ -578,3 +930,95 @@ smartlinks:
           a tools already. Run one and express happiness. If something does wrong, or you don't see the tools, ask user if they want to fix it by rewriting the config.
     sl_enable_only_with_tool: true
 "#;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use axum::{Router, routing::post, Json};
+    use serde_json::json;
+
+    async fn stub_mcp_server(Json(msg): Json<serde_json::Value>) -> Json<serde_json::Value> {
+        let params = &msg["params"];
+        let result = match msg["method"].as_str().unwrap() {
+            // two pages, the second one has an item without uri that must be skipped
+            "resources/list" => match params["cursor"].as_str() {
+                None => json!({"resources": [{"uri": "docs://readme", "name": "README", "mimeType": "text/markdown"}], "nextCursor": "page2"}),
+                Some("page2") => json!({"resources": [{"uri": "db://schema"}, {"name": "broken"}]}),
+                Some(_) => unreachable!(),
+            },
+            "resources/read" => json!({"contents": [
+                {"uri": params["uri"], "mimeType": "text/markdown", "text": "# Title\nbody"},
+                {"uri": "docs://logo.png", "mimeType": "image/png", "blob": "iVBORw0KGgo="},
+            ]}),
+            "prompts/list" => json!({"prompts": [{"name": "review", "description": "Review code", "arguments": [
+                {"name": "language", "required": true},
+                {"name": "style"},
+            ]}]}),
+            "prompts/get" => json!({"messages": [
+                {"role": "user", "content": {"type": "text", "text": format!("Review this {} code", params["arguments"]["language"].as_str().unwrap())}},
+                {"role": "user", "content": {"type": "resource", "resource": {"uri": "docs://readme", "text": "# Title"}}},
+                {"role": "user", "content": {"type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png"}},
+                {"role": "assistant", "content": {"type": "text", "text": "Sure"}},
+            ]}),
+            _ => unreachable!(),
+        };
+        Json(json!({"jsonrpc": "2.0", "id": msg["id"], "result": result}))
+    }
+
+    #[test]
+    fn test_transport_comes_from_config() {
+        let stdio: SettingsMCP = serde_json::from_value(json!({"command": "npx -y some-server", "env": {"A": "1"}})).unwrap();
+        assert!(matches!(stdio.launch_settings(), MCPLaunchSettings::Stdio(cfg) if cfg.mcp_command == "npx -y some-server"));
+        let sse: SettingsMCP = serde_json::from_value(json!({"transport": "sse", "url": "http://127.0.0.1:8000/sse", "auth_token": "t"})).unwrap();
+        assert!(matches!(sse.launch_settings(), MCPLaunchSettings::Http(MCPHttpTransport::Sse, cfg) if cfg.mcp_url == "http://127.0.0.1:8000/sse" && cfg.mcp_auth_token == "t"));
+        let http: SettingsMCP = serde_json::from_value(json!({"transport": "http", "url": "http://127.0.0.1:8000/mcp"})).unwrap();
+        assert!(matches!(http.launch_settings(), MCPLaunchSettings::Http(MCPHttpTransport::StreamableHttp, _)));
+        let empty: SettingsMCP = serde_json::from_value(json!({"transport": "", "command": "x"})).unwrap();
+        assert_eq!(empty.transport, MCPTransport::Stdio);
+        assert!(serde_json::from_value::<SettingsMCP>(json!({"transport": "websocket"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resources_and_prompts_against_stub_server() {
+        let app = Router::new().route("/mcp", post(stub_mcp_server));
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut client = MCPTransportClient::Http(MCPHttpClient::connect(
+            reqwest::Client::new(),
+            MCPHttpTransport::StreamableHttp,
+            &format!("http://{}/mcp", addr),
+            &HashMap::new(),
+            "",
+            Duration::from_secs(10),
+        ).await.unwrap());
+
+        let resources = client.list_resources().await.unwrap();
+        assert_eq!(resources.iter().map(|r| r.uri.as_str()).collect::<Vec<_>>(), vec!["docs://readme", "db://schema"]);
+        assert_eq!(resources[0].mime_type, "text/markdown");
+        assert_eq!(resources[1].name, "");
+
+        let context_files = client.read_resource("docs://readme").await.unwrap();
+        assert_eq!(context_files.len(), 1);
+        assert_eq!(context_files[0].file_name, "docs://readme");
+        assert_eq!(context_files[0].file_content, "# Title\nbody");
+        assert_eq!((context_files[0].line1, context_files[0].line2), (1, 2));
+
+        let prompts = client.list_prompts().await.unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].missing_required_arguments(&HashMap::new()), vec!["language".to_string()]);
+        let arguments = HashMap::from([("language".to_string(), "rust".to_string())]);
+        assert!(prompts[0].missing_required_arguments(&arguments).is_empty());
+
+        let messages = client.get_prompt("review", &arguments).await.unwrap();
+        let messages = messages.iter().map(|m| (m.role.as_str(), m.content.content_text_only())).collect::<Vec<_>>();
+        assert_eq!(messages, vec![
+            ("user", "Review this rust code".to_string()),
+            ("user", "docs://readme:\n```\n# Title\n```".to_string()),
+            ("assistant", "Sure".to_string()),
+        ]);
+    }
+}
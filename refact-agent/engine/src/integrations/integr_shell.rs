@@ -16,7 +16,9 @@ use crate::tools::tools_description::{ToolParam, Tool, ToolDesc, MatchConfirmDen
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
 use crate::postprocessing::pp_command_output::CmdlineOutputFilter;
 use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationTrait};
+use crate::integrations::integr_shell_session::ToolShellSession;
 use crate::integrations::setting_up_integrations::YamlError;
+use crate::integrations::process_io_utils::output_killed_on_drop;
 use crate::tools::tools_execute::command_should_be_denied;
 
 
@@ -26,6 +28,10 @@ pub struct SettingsShell {
     pub timeout: String,
     #[serde(default)]
     pub output_filter: CmdlineOutputFilter,
+    #[serde(default)]
+    pub session_timeout: String,
+    #[serde(default)]
+    pub session_output_cap: String,
 }
 
 #[derive(Default)]
@@ -60,11 +66,18 @@ impl IntegrationTrait for ToolShell {
     }
 
     async fn integr_tools(&self, _integr_name: &str) -> Vec<Box<dyn crate::tools::tools_description::Tool + Send>> {
-        vec![Box::new(ToolShell {
-            common: self.common.clone(),
-            cfg: self.cfg.clone(),
-            config_path: self.config_path.clone(),
-        })]
+        vec![
+            Box::new(ToolShell {
+                common: self.common.clone(),
+                cfg: self.cfg.clone(),
+                config_path: self.config_path.clone(),
+            }),
+            Box::new(ToolShellSession {
+                common: self.common.clone(),
+                cfg: self.cfg.clone(),
+                config_path: self.config_path.clone(),
+            }),
+        ]
     }
 }
 
@@ -174,6 +187,10 @@ impl Tool for ToolShell {
     fn has_config_path(&self) -> Option<String> {
         Some(self.config_path.clone())
     }
+
+    fn own_timeout(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_secs(self.cfg.timeout.parse::<u64>().unwrap_or(10)))
+    }
 }
 
 pub async fn execute_shell_command(
@@ -187,6 +204,7 @@ pub async fn execute_shell_command(
     let shell = if cfg!(target_os = "windows") { "powershell.exe" } else { "sh" };
     let shell_arg = if cfg!(target_os = "windows") { "-Command" } else { "-c" };
     let mut cmd = Command::new(shell);
+    cmd.kill_on_drop(true);
 
     if let Some(workdir) = workdir_maybe {
         cmd.current_dir(workdir);
@@ -207,7 +225,7 @@ pub async fn execute_shell_command(
 
     let t0 = tokio::time::Instant::now();
     tracing::info!("SHELL: running command directory {:?}\n{:?}", workdir_maybe, command);
-    let output = tokio::time::timeout(tokio::time::Duration::from_secs(timeout), cmd.output())
+    let output = tokio::time::timeout(tokio::time::Duration::from_secs(timeout), output_killed_on_drop(&mut cmd))
         .await
         .map_err(|_| format!("Command timed out after {} seconds", timeout))?
         .map_err(|e| format!("Failed to execute command: {}", e))?;
@@ -269,8 +287,18 @@ fields:
     f_type: "output_filter"
     f_desc: "The output from the command can be long or even quasi-infinite. This section allows to set limits, prioritize top or bottom, or use regexp to show the model the relevant part."
     f_extra: true
+  session_timeout:
+    f_type: string_short
+    f_desc: "The shell_session tool keeps an interactive terminal per chat, it's closed after this many seconds without use."
+    f_default: "1800"
+    f_extra: true
+  session_output_cap:
+    f_type: string_short
+    f_desc: "How many bytes of shell_session output are kept until the model reads them, older output is dropped."
+    f_default: "1000000"
+    f_extra: true
 description: |
-  Allows to execute any command line tool with confirmation from the chat itself.
+  Allows to execute any command line tool with confirmation from the chat itself, or to work in an interactive terminal session.
 available:
   on_your_laptop_possible: true
   when_isolated_possible: true
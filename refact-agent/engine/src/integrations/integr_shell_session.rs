@@ -0,0 +1,521 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Instant, SystemTime};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use regex::Regex;
+use serde_json::Value;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tokio::time::Duration;
+use tracing::{error, info};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
+use crate::files_correction::get_active_project_path;
+use crate::global_context::GlobalContext;
+use crate::integrations::integr_abstract::{IntegrationCommon, IntegrationConfirmation};
+use crate::integrations::integr_shell::SettingsShell;
+use crate::integrations::sessions::{get_session_hashmap_key, IntegrationSession};
+use crate::integrations::setting_up_integrations::YamlError;
+use crate::postprocessing::pp_command_output::output_mini_postprocessing;
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool, ToolDesc, ToolParam};
+use crate::tools::tools_execute::command_should_be_denied;
+
+
+const DEFAULT_SESSION_TIMEOUT_SECS: u64 = 30 * 60;
+const DEFAULT_SESSION_OUTPUT_CAP: usize = 1_000_000;  // bytes kept per session until the model reads them, older output is dropped
+const OUTPUT_SETTLED_AFTER: Duration = Duration::from_millis(500);
+const MAX_WAIT_SECONDS: u64 = 120;
+
+lazy_static! {
+    static ref ANSI_ESCAPE: Regex = Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[()][0-9A-Za-z]|\x1b[=>78]").unwrap();
+}
+
+/// The `shell_session` tool, comes together with `shell` from the same integration and uses the same config
+#[derive(Default)]
+pub struct ToolShellSession {
+    pub common: IntegrationCommon,
+    pub cfg: SettingsShell,
+    pub config_path: String,
+}
+
+#[derive(Default)]
+struct PtyOutput {
+    unread: Vec<u8>,
+    dropped: usize,
+    eof: bool,
+    total: usize,
+}
+
+pub struct PtyShellSession {
+    _master: StdMutex<Box<dyn MasterPty + Send>>,  // the terminal goes away when this is dropped
+    writer: StdMutex<Box<dyn Write + Send>>,
+    child: StdMutex<Box<dyn Child + Send + Sync>>,
+    output: Arc<StdMutex<PtyOutput>>,
+    last_usage_ts: u64,
+    timeout_after_inactivity: u64,
+}
+
+impl Drop for PtyShellSession {
+    fn drop(&mut self) {
+        if let Ok(mut child) = self.child.lock() {
+            child.kill().map_err(|e| error!("Failed to kill shell session: {}", e)).ok();
+        }
+    }
+}
+
+impl IntegrationSession for PtyShellSession
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_expired(&self) -> bool {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        self.last_usage_ts + self.timeout_after_inactivity < current_time
+    }
+
+    fn try_stop(&mut self, _self_arc: Arc<AMutex<Box<dyn IntegrationSession>>>) -> Box<dyn Future<Output = String> + Send> {
+        let killed = self.child.lock().map(|mut child| child.kill().is_ok()).unwrap_or(false);
+        Box::new(async move {
+            if killed { "Shell session stopped".to_string() } else { "".to_string() }
+        })
+    }
+}
+
+impl PtyOutput {
+    fn push(&mut self, bytes: &[u8], cap: usize) {
+        self.unread.extend_from_slice(bytes);
+        self.total += bytes.len();
+        if self.unread.len() > cap {
+            let excess = self.unread.len() - cap;
+            self.unread.drain(..excess);
+            self.dropped += excess;
+        }
+    }
+}
+
+impl PtyShellSession {
+    fn start(
+        workdir: &Path,
+        env_variables: &HashMap<String, String>,
+        output_cap: usize,
+        timeout_after_inactivity: u64,
+    ) -> Result<Self, String> {
+        let pty_pair = native_pty_system()
+            .openpty(PtySize { rows: 50, cols: 200, pixel_width: 0, pixel_height: 0 })
+            .map_err(|e| format!("Failed to open a terminal: {}", e))?;
+
+        let shell = if cfg!(target_os = "windows") {
+            "powershell.exe".to_string()
+        } else {
+            std::env::var("SHELL").ok().filter(|s| !s.is_empty()).unwrap_or("sh".to_string())
+        };
+        let mut cmd = CommandBuilder::new(&shell);
+        cmd.cwd(workdir);
+        for (key, value) in env_variables {
+            cmd.env(key, value);
+        }
+        // what the model reads is plain text, ask programs not to paint or page it
+        cmd.env("TERM", "dumb");
+        cmd.env("NO_COLOR", "1");
+        cmd.env("PAGER", "cat");
+        cmd.env("GIT_PAGER", "cat");
+
+        info!("SHELL SESSION: starting {} in {:?}", shell, workdir);
+        let child = pty_pair.slave.spawn_command(cmd).map_err(|e| format!("Failed to start {}: {}", shell, e))?;
+        drop(pty_pair.slave);
+        let mut reader = pty_pair.master.try_clone_reader().map_err(|e| format!("Failed to read from the terminal: {}", e))?;
+        let writer = pty_pair.master.take_writer().map_err(|e| format!("Failed to write to the terminal: {}", e))?;
+
+        let output = Arc::new(StdMutex::new(PtyOutput::default()));
+        let output_for_reader = output.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,  // EIO on unix once the shell exits
+                    Ok(n) => output_for_reader.lock().unwrap().push(&buf[..n], output_cap),
+                }
+            }
+            output_for_reader.lock().unwrap().eof = true;
+        });
+
+        Ok(PtyShellSession {
+            _master: StdMutex::new(pty_pair.master),
+            writer: StdMutex::new(writer),
+            child: StdMutex::new(child),
+            output,
+            last_usage_ts: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(),
+            timeout_after_inactivity,
+        })
+    }
+
+    fn write(&self, bytes: &[u8]) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(bytes).map_err(|e| format!("Failed to write to the shell session: {}", e))?;
+        writer.flush().map_err(|e| format!("Failed to flush the shell session: {}", e))
+    }
+
+    fn exit_code(&self) -> Option<u32> {
+        match self.child.lock().unwrap().try_wait() {
+            Ok(Some(status)) => Some(status.exit_code()),
+            _ => None,
+        }
+    }
+
+    fn take_output(&mut self) -> (String, usize) {
+        self.last_usage_ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let mut output = self.output.lock().unwrap();
+        let text = String::from_utf8_lossy(&output.unread).to_string();
+        let dropped = output.dropped;
+        output.unread.clear();
+        output.dropped = 0;
+        (text, dropped)
+    }
+}
+
+/// Waits until the output stops changing for a moment, or `wait` runs out, or the shell exits.
+/// Takes the output only, not the session, so the session lock isn't held while waiting.
+async fn wait_for_output(output: Arc<StdMutex<PtyOutput>>, wait: Duration) {
+    let started = Instant::now();
+    let mut last_total = output.lock().unwrap().total;
+    let mut last_change = started;
+    let mut got_something = false;
+    while started.elapsed() < wait {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (total, eof) = {
+            let output = output.lock().unwrap();
+            (output.total, output.eof)
+        };
+        if eof {
+            break;
+        }
+        if total != last_total {
+            last_total = total;
+            last_change = Instant::now();
+            got_something = true;
+        } else if got_something && last_change.elapsed() >= OUTPUT_SETTLED_AFTER {
+            break;
+        }
+    }
+}
+
+pub fn strip_terminal_codes(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn signal_to_bytes(signal: &str) -> Result<Option<&'static [u8]>, String> {
+    match signal {
+        "INT" => Ok(Some(b"\x03")),
+        "QUIT" => Ok(Some(b"\x1c")),
+        "TSTP" => Ok(Some(b"\x1a")),
+        "EOF" => Ok(Some(b"\x04")),
+        "KILL" => Ok(None),
+        _ => Err(format!("Unknown signal {:?}, use one of INT, QUIT, TSTP, EOF, KILL", signal)),
+    }
+}
+
+struct SessionArgs {
+    action: String,
+    input: String,
+    press_enter: bool,
+    signal: String,
+    wait_seconds: u64,
+    workdir: Option<PathBuf>,
+}
+
+fn parse_args(args: &HashMap<String, Value>) -> Result<SessionArgs, String> {
+    fn string_arg(args: &HashMap<String, Value>, name: &str) -> Result<String, String> {
+        match args.get(name) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+            None => Ok("".to_string()),
+        }
+    }
+    let action = string_arg(args, "action")?;
+    let input = string_arg(args, "input")?;
+    let signal = string_arg(args, "signal")?.to_uppercase();
+    match action.as_str() {
+        "send_input" => {},
+        "read_output" => {},
+        "send_signal" => { signal_to_bytes(&signal)?; },
+        "" => return Err("Missing argument `action`".to_string()),
+        _ => return Err(format!("Unknown action {:?}, use one of send_input, read_output, send_signal", action)),
+    }
+    let press_enter = match args.get("press_enter") {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => s != "false",
+        Some(v) => return Err(format!("argument `press_enter` is not a boolean: {:?}", v)),
+        None => true,
+    };
+    let wait_seconds = match args.get("wait_seconds") {
+        Some(Value::Number(n)) => n.as_u64().ok_or(format!("argument `wait_seconds` is not a positive integer: {}", n))?,
+        Some(Value::String(s)) if !s.is_empty() => s.parse::<u64>().map_err(|_| format!("argument `wait_seconds` is not a number: {:?}", s))?,
+        Some(Value::String(_)) | None => if action == "read_output" { 1 } else { 3 },
+        Some(v) => return Err(format!("argument `wait_seconds` is not a number: {:?}", v)),
+    }.min(MAX_WAIT_SECONDS);
+    let workdir_str = string_arg(args, "workdir")?;
+    let workdir = if workdir_str.is_empty() {
+        None
+    } else {
+        let workdir = crate::files_correction::canonical_path(&workdir_str);
+        if !workdir.exists() {
+            return Err("Workdir doesn't exist".to_string());
+        }
+        Some(workdir)
+    };
+    Ok(SessionArgs { action, input, press_enter, signal, wait_seconds, workdir })
+}
+
+#[async_trait]
+impl Tool for ToolShellSession {
+    fn as_any(&self) -> &dyn Any { self }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let session_args = parse_args(args)?;
+        let (gcx, chat_id) = {
+            let ccx_lock = ccx.lock().await;
+            (ccx_lock.global_context.clone(), ccx_lock.chat_id.clone())
+        };
+        let session_hashmap_key = get_session_hashmap_key("shell_session", &chat_id);
+
+        let mut notes = vec![];
+        let existing_session = gcx.read().await.integration_sessions.get(&session_hashmap_key).cloned();
+        let command_session = match existing_session {
+            Some(session) => {
+                if session_args.workdir.is_some() && session_args.action == "send_input" {
+                    notes.push("The session is already running, `workdir` is ignored, use `cd` to change the directory.".to_string());
+                }
+                session
+            }
+            None if session_args.action == "send_input" => {
+                let session = start_session(gcx.clone(), &self.cfg, &session_args.workdir).await?;
+                notes.push("Started a new shell session.".to_string());
+                let session: Box<dyn IntegrationSession> = Box::new(session);
+                let session = Arc::new(AMutex::new(session));
+                gcx.write().await.integration_sessions.insert(session_hashmap_key.clone(), session.clone());
+                session
+            }
+            None => return Err("There is no shell session in this chat, start one with action `send_input`".to_string()),
+        };
+
+        let output = {
+            let mut command_session_locked = command_session.lock().await;
+            let session = command_session_locked.as_any_mut().downcast_mut::<PtyShellSession>()
+                .ok_or("Failed to downcast to PtyShellSession")?;
+            match session_args.action.as_str() {
+                "send_input" => {
+                    let mut bytes = session_args.input.into_bytes();
+                    if session_args.press_enter {
+                        bytes.push(b'\r');
+                    }
+                    session.write(&bytes)?;
+                }
+                "send_signal" => match signal_to_bytes(&session_args.signal)? {
+                    Some(bytes) => session.write(bytes)?,
+                    None => {
+                        session.child.lock().unwrap().kill().map_err(|e| format!("Failed to kill the shell session: {}", e))?;
+                        notes.push("The shell session was killed.".to_string());
+                    }
+                },
+                _ => {}
+            }
+            session.output.clone()
+        };
+        // session cleanup and other calls in this chat shouldn't wait for us
+        wait_for_output(output, Duration::from_secs(session_args.wait_seconds)).await;
+
+        let mut command_session_locked = command_session.lock().await;
+        let session = command_session_locked.as_any_mut().downcast_mut::<PtyShellSession>()
+            .ok_or("Failed to downcast to PtyShellSession")?;
+        let (raw_output, dropped) = session.take_output();
+        let exit_code = session.exit_code();
+        drop(command_session_locked);
+        if let Some(exit_code) = exit_code {
+            gcx.write().await.integration_sessions.remove(&session_hashmap_key);
+            notes.push(format!("The shell exited with code {}, the next `send_input` starts a new session.", exit_code));
+        }
+
+        let output = output_mini_postprocessing(&self.cfg.output_filter, &strip_terminal_codes(&raw_output));
+        let mut out = String::new();
+        if dropped > 0 {
+            out.push_str(&format!("...{} bytes of older output were dropped, read more often or use `wait_seconds`...\n", dropped));
+        }
+        if output.trim().is_empty() {
+            out.push_str("(no new output, the program might still be running: call again with `read_output` and a larger `wait_seconds`)\n");
+        } else {
+            out.push_str(&format!("```\n{}\n```\n", output.trim_end()));
+        }
+        for note in notes {
+            out.push_str(&format!("{}\n", note));
+        }
+
+        Ok((false, vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(out),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })]))
+    }
+
+    fn tool_name(&self) -> String {
+        "shell_session".to_string()
+    }
+
+    fn tool_description(&self) -> ToolDesc {
+        ToolDesc {
+            name: "shell_session".to_string(),
+            agentic: true,
+            experimental: false,
+            description: "A persistent interactive shell in a terminal, one per chat. Use it for programs that ask questions or keep running: REPLs, `git rebase -i`, `npm init`, dev servers. Each call returns the output that appeared since the previous call. For one-time commands use `shell` instead.".to_string(),
+            parameters: vec![
+                ToolParam {
+                    name: "action".to_string(),
+                    param_type: "string".to_string(),
+                    description: "send_input (starts the session if there is none), read_output, or send_signal".to_string(),
+                },
+                ToolParam {
+                    name: "input".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For send_input: text to type, Enter is pressed after it".to_string(),
+                },
+                ToolParam {
+                    name: "press_enter".to_string(),
+                    param_type: "boolean".to_string(),
+                    description: "For send_input: set false to type the text without pressing Enter, default true".to_string(),
+                },
+                ToolParam {
+                    name: "signal".to_string(),
+                    param_type: "string".to_string(),
+                    description: "For send_signal: INT (Ctrl-C), QUIT (Ctrl-\\), TSTP (Ctrl-Z), EOF (Ctrl-D) or KILL to end the session".to_string(),
+                },
+                ToolParam {
+                    name: "wait_seconds".to_string(),
+                    param_type: "integer".to_string(),
+                    description: "How long to wait for the output to settle, default 3 for send_input and 1 for read_output".to_string(),
+                },
+                ToolParam {
+                    name: "workdir".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Working directory for a new session".to_string(),
+                },
+            ],
+            parameters_required: vec!["action".to_string()],
+        }
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        _ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>
+    ) -> Result<MatchConfirmDeny, String> {
+        let session_args = parse_args(args)?;
+        let command_to_match = self.command_to_match_against_confirm_deny(args)?;
+        if session_args.action != "send_input" {
+            // reading output and Ctrl-C don't do anything new
+            return Ok(MatchConfirmDeny {
+                result: MatchConfirmDenyResult::PASS,
+                command: command_to_match,
+                rule: "".to_string(),
+            });
+        }
+        if let Some(rules) = &self.confirm_deny_rules() {
+            let (is_denied, deny_rule) = command_should_be_denied(&command_to_match, &rules.deny);
+            if is_denied {
+                return Ok(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::DENY,
+                    command: command_to_match.clone(),
+                    rule: deny_rule.clone(),
+                });
+            }
+        }
+        // NOTE: the same as shell, anything typed into the session waits for confirmation
+        Ok(MatchConfirmDeny {
+            result: MatchConfirmDenyResult::CONFIRMATION,
+            command: command_to_match.clone(),
+            rule: "*".to_string(),
+        })
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let session_args = parse_args(args)?;
+        Ok(match session_args.action.as_str() {
+            "send_input" => session_args.input,
+            "send_signal" => format!("send_signal {}", session_args.signal),
+            other => other.to_string(),
+        })
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(self.common.confirmation.clone())
+    }
+
+    fn has_config_path(&self) -> Option<String> {
+        Some(self.config_path.clone())
+    }
+}
+
+// 0 would drop everything the program prints, it's a typo rather than a wish
+fn session_output_cap(cfg: &SettingsShell) -> usize {
+    cfg.session_output_cap.parse::<usize>().ok().filter(|cap| *cap > 0).unwrap_or(DEFAULT_SESSION_OUTPUT_CAP)
+}
+
+async fn start_session(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cfg: &SettingsShell,
+    workdir_maybe: &Option<PathBuf>,
+) -> Result<PtyShellSession, String> {
+    let workdir = match workdir_maybe {
+        Some(workdir) => workdir.clone(),
+        None => get_active_project_path(gcx.clone()).await
+            .or_else(|| std::env::current_dir().ok())
+            .ok_or("No working directory, set `workdir`")?,
+    };
+    let mut error_log = Vec::<YamlError>::new();
+    let env_variables = crate::integrations::setting_up_integrations::get_vars_for_replacements(gcx.clone(), &mut error_log).await;
+    let output_cap = session_output_cap(cfg);
+    let timeout_after_inactivity = cfg.session_timeout.parse::<u64>().unwrap_or(DEFAULT_SESSION_TIMEOUT_SECS);
+    PtyShellSession::start(&workdir, &env_variables, output_cap, timeout_after_inactivity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_session_output_cap_and_codes() {
+        let mut output = PtyOutput::default();
+        output.push(b"0123456789", 8);
+        output.push(b"abc", 8);
+        assert_eq!(output.unread, b"56789abc");
+        assert_eq!((output.dropped, output.total), (5, 13));
+        for (cap, expected) in [("4096", 4096), ("0", DEFAULT_SESSION_OUTPUT_CAP), ("", DEFAULT_SESSION_OUTPUT_CAP)] {
+            let cfg = SettingsShell { session_output_cap: cap.to_string(), ..Default::default() };
+            assert_eq!(session_output_cap(&cfg), expected);
+        }
+
+        assert_eq!(strip_terminal_codes("\x1b[1;32mok\x1b[0m\r\n\x1b]0;title\x07$ "), "ok\n$ ");
+
+        let args: HashMap<String, Value> = serde_json::from_value(serde_json::json!({"action": "send_signal", "signal": "int"})).unwrap();
+        assert_eq!(parse_args(&args).unwrap().signal, "INT");
+        let args: HashMap<String, Value> = serde_json::from_value(serde_json::json!({"action": "send_signal", "signal": "HUP"})).unwrap();
+        assert!(parse_args(&args).is_err());
+        let args: HashMap<String, Value> = serde_json::from_value(serde_json::json!({"action": "read_output", "wait_seconds": 1000})).unwrap();
+        assert_eq!(parse_args(&args).unwrap().wait_seconds, MAX_WAIT_SECONDS);
+    }
+}
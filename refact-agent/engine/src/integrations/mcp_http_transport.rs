@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest_eventsource::{Event, EventSource};
+use serde_json::json;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+
+const MCP_PROTOCOL_VERSION: &str = "2025-03-26";
+const MCP_SESSION_ID_HEADER: &str = "mcp-session-id";
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum MCPHttpTransport {
+    // Legacy HTTP+SSE: GET opens an event stream, the server announces a POST endpoint via the `endpoint` event,
+    // responses to our POSTs come back over the event stream
+    #[default]
+    Sse,
+    // Streamable HTTP: every JSON-RPC message is a POST, the response is either JSON or a short-lived event stream
+    StreamableHttp,
+}
+
+type PendingRequests = Arc<AMutex<HashMap<u64, oneshot::Sender<serde_json::Value>>>>;
+
+pub struct MCPHttpClient {
+    http_client: reqwest::Client,
+    transport: MCPHttpTransport,
+    url: String,
+    headers: HeaderMap,
+    request_timeout: Duration,
+    next_id: u64,
+    session_id: Option<String>,
+    sse_post_url: Option<String>,
+    sse_pending: PendingRequests,
+    sse_reader: Option<JoinHandle<()>>,
+}
+
+impl MCPHttpClient {
+    pub async fn connect(
+        http_client: reqwest::Client,
+        transport: MCPHttpTransport,
+        url: &str,
+        headers: &HashMap<String, String>,
+        auth_token: &str,
+        request_timeout: Duration,
+    ) -> Result<Self, String> {
+        let mut header_map = HeaderMap::new();
+        for (key, value) in headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| format!("invalid header name {:?}: {}", key, e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| format!("invalid value for header {:?}: {}", key, e))?;
+            header_map.insert(name, value);
+        }
+        if !auth_token.is_empty() {
+            header_map.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", auth_token))
+                .map_err(|e| format!("invalid auth_token: {}", e))?);
+        }
+
+        let mut client = MCPHttpClient {
+            http_client,
+            transport,
+            url: url.to_string(),
+            headers: header_map,
+            request_timeout,
+            next_id: 0,
+            session_id: None,
+            sse_post_url: None,
+            sse_pending: Arc::new(AMutex::new(HashMap::new())),
+            sse_reader: None,
+        };
+        if transport == MCPHttpTransport::Sse {
+            client.sse_open_stream().await?;
+        }
+        Ok(client)
+    }
+
+    pub async fn initialize(&mut self) -> Result<serde_json::Value, String> {
+        let result = self.request("initialize", json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {
+                "name": "refact-lsp",
+                "version": crate::version::build_info::PKG_VERSION,
+            },
+        })).await?;
+        self.notify("notifications/initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    pub async fn list_tools(&mut self) -> Result<Vec<mcp_client_rs::Tool>, String> {
+        let mut tools = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({"cursor": c}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page: Vec<mcp_client_rs::Tool> = serde_json::from_value(result.get("tools").cloned().unwrap_or(json!([])))
+                .map_err(|e| format!("cannot parse tools/list result: {}", e))?;
+            tools.extend(page);
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(|c| c.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(tools)
+    }
+
+    pub async fn call_tool(&mut self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, String> {
+        self.request("tools/call", json!({"name": name, "arguments": args})).await
+    }
+
+    pub async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let response = match self.transport {
+            MCPHttpTransport::Sse => self.sse_request(id, &message).await?,
+            MCPHttpTransport::StreamableHttp => self.streamable_request(id, &message).await?,
+        };
+        jsonrpc_response_to_result(response)
+    }
+
+    pub async fn notify(&mut self, method: &str, params: serde_json::Value) -> Result<(), String> {
+        let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        let url = self.post_url()?;
+        let resp = self.post(&url, &message).await?;
+        if !resp.status().is_success() {
+            return Err(format!("notification {} failed: HTTP {}", method, resp.status()));
+        }
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), String> {
+        if let Some(reader) = self.sse_reader.take() {
+            reader.abort();
+        }
+        self.sse_pending.lock().await.clear();
+        if let Some(session_id) = self.session_id.take() {
+            // The spec allows servers to answer 405 here, so the result is informational only
+            let resp = self.http_client.delete(&self.url)
+                .headers(self.headers.clone())
+                .header(MCP_SESSION_ID_HEADER, session_id)
+                .timeout(self.request_timeout)
+                .send().await
+                .map_err(|e| format!("failed to close MCP session: {}", e))?;
+            tracing::info!("MCP HTTP session closed with status {}", resp.status());
+        }
+        Ok(())
+    }
+
+    fn post_url(&self) -> Result<String, String> {
+        match self.transport {
+            MCPHttpTransport::Sse => self.sse_post_url.clone().ok_or("SSE endpoint is not known yet".to_string()),
+            MCPHttpTransport::StreamableHttp => Ok(self.url.clone()),
+        }
+    }
+
+    async fn post(&self, url: &str, message: &serde_json::Value) -> Result<reqwest::Response, String> {
+        let mut builder = self.http_client.post(url)
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .timeout(self.request_timeout)
+            .body(message.to_string());
+        if let Some(session_id) = &self.session_id {
+            builder = builder.header(MCP_SESSION_ID_HEADER, session_id.as_str());
+        }
+        builder.send().await.map_err(|e| format!("POST {} failed: {}", url, e))
+    }
+
+    async fn streamable_request(&mut self, id: u64, message: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let resp = self.post(&self.url.clone(), message).await?;
+        let status = resp.status();
+        if let Some(session_id) = resp.headers().get(MCP_SESSION_ID_HEADER).and_then(|v| v.to_str().ok()) {
+            self.session_id = Some(session_id.to_string());
+        }
+        let is_event_stream = resp.headers().get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.starts_with("text/event-stream"))
+            .unwrap_or(false);
+        let body = resp.text().await.map_err(|e| format!("failed to read response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("HTTP {}: {}", status, body));
+        }
+        let candidates: Vec<serde_json::Value> = if is_event_stream {
+            parse_sse_events(&body).into_iter()
+                .filter_map(|(_, data)| serde_json::from_str(&data).ok())
+                .collect()
+        } else {
+            match serde_json::from_str(&body).map_err(|e| format!("cannot parse response: {}", e))? {
+                serde_json::Value::Array(batch) => batch,
+                single => vec![single],
+            }
+        };
+        candidates.into_iter()
+            .find(|msg| msg.get("id").and_then(|x| x.as_u64()) == Some(id))
+            .ok_or(format!("no response for request id {}", id))
+    }
+
+    async fn sse_request(&mut self, id: u64, message: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let (tx, rx) = oneshot::channel();
+        self.sse_pending.lock().await.insert(id, tx);
+        let url = self.post_url()?;
+        let post_result = self.post(&url, message).await;
+        let resp = match post_result {
+            Ok(resp) => resp,
+            Err(e) => {
+                self.sse_pending.lock().await.remove(&id);
+                return Err(e);
+            }
+        };
+        if !resp.status().is_success() {
+            self.sse_pending.lock().await.remove(&id);
+            return Err(format!("HTTP {}: {}", resp.status(), resp.text().await.unwrap_or_default()));
+        }
+        match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err("SSE stream closed before the response arrived".to_string()),
+            Err(_) => {
+                self.sse_pending.lock().await.remove(&id);
+                Err(format!("timeout waiting for response to request id {}", id))
+            }
+        }
+    }
+
+    async fn sse_open_stream(&mut self) -> Result<(), String> {
+        let builder = self.http_client.get(&self.url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "text/event-stream");
+        let mut event_source = EventSource::new(builder).map_err(|e| format!("can't stream from {}: {}", self.url, e))?;
+        let (endpoint_tx, endpoint_rx) = oneshot::channel::<String>();
+        let pending = self.sse_pending.clone();
+        let url = self.url.clone();
+        self.sse_reader = Some(tokio::spawn(async move {
+            let mut endpoint_tx = Some(endpoint_tx);
+            while let Some(event) = event_source.next().await {
+                match event {
+                    Ok(Event::Open) => {}
+                    Ok(Event::Message(message)) if message.event == "endpoint" => {
+                        if let Some(tx) = endpoint_tx.take() {
+                            let _ = tx.send(message.data);
+                        }
+                    }
+                    Ok(Event::Message(message)) => {
+                        let Ok(value) = serde_json::from_str::<serde_json::Value>(&message.data) else {
+                            tracing::warn!("MCP SSE {} sent non-JSON message: {}", url, message.data);
+                            continue;
+                        };
+                        if let Some(id) = value.get("id").and_then(|x| x.as_u64()) {
+                            if let Some(tx) = pending.lock().await.remove(&id) {
+                                let _ = tx.send(value);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("MCP SSE stream {} closed: {}", url, e);
+                        event_source.close();
+                        break;
+                    }
+                }
+            }
+            pending.lock().await.clear();
+        }));
+
+        let endpoint = match tokio::time::timeout(self.request_timeout, endpoint_rx).await {
+            Ok(Ok(endpoint)) => endpoint,
+            Ok(Err(_)) => return Err(format!("SSE stream {} closed before announcing an endpoint", self.url)),
+            Err(_) => return Err(format!("timeout waiting for the endpoint event from {}", self.url)),
+        };
+        let base = url::Url::parse(&self.url).map_err(|e| format!("invalid url {}: {}", self.url, e))?;
+        let post_url = base.join(endpoint.trim()).map_err(|e| format!("invalid endpoint {:?}: {}", endpoint, e))?;
+        self.sse_post_url = Some(post_url.to_string());
+        Ok(())
+    }
+}
+
+impl Drop for MCPHttpClient {
+    fn drop(&mut self) {
+        if let Some(reader) = self.sse_reader.take() {
+            reader.abort();
+        }
+    }
+}
+
+fn jsonrpc_response_to_result(response: serde_json::Value) -> Result<serde_json::Value, String> {
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+        let code = error.get("code").and_then(|c| c.as_i64()).unwrap_or(0);
+        return Err(format!("{} (code {})", message, code));
+    }
+    Ok(response.get("result").cloned().unwrap_or(serde_json::Value::Null))
+}
+
+// Returns (event name, data) pairs, event name defaults to "message" as the SSE spec says
+fn parse_sse_events(body: &str) -> Vec<(String, String)> {
+    let mut events = vec![];
+    let mut event_name = String::new();
+    let mut data_lines: Vec<&str> = vec![];
+    for line in body.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if !data_lines.is_empty() {
+                let name = if event_name.is_empty() { "message".to_string() } else { event_name.clone() };
+                events.push((name, data_lines.join("\n")));
+            }
+            event_name.clear();
+            data_lines.clear();
+        } else if let Some(value) = line.strip_prefix("event:") {
+            event_name = value.trim_start().to_string();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data_lines.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    events
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use axum::{Router, routing::{get, post}, Extension, Json};
+    use axum::http::HeaderMap as AxumHeaderMap;
+    use axum::response::sse::{Event as SseEvent, Sse};
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_parse_sse_events() {
+        let body = "event: message\ndata: {\"id\":1}\n\n: comment\ndata: line1\ndata: line2\n\nevent: endpoint\ndata: /messages?session=42";
+        let events = parse_sse_events(body);
+        assert_eq!(events, vec![
+            ("message".to_string(), "{\"id\":1}".to_string()),
+            ("message".to_string(), "line1\nline2".to_string()),
+            ("endpoint".to_string(), "/messages?session=42".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_jsonrpc_error_is_reported() {
+        let err = jsonrpc_response_to_result(json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "Method not found"}}));
+        assert_eq!(err, Err("Method not found (code -32601)".to_string()));
+        let ok = jsonrpc_response_to_result(json!({"jsonrpc": "2.0", "id": 1, "result": {"tools": []}}));
+        assert_eq!(ok, Ok(json!({"tools": []})));
+    }
+
+    async fn stub_mcp_server(headers: AxumHeaderMap, Json(msg): Json<serde_json::Value>) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        assert_eq!(headers.get("authorization").unwrap(), "Bearer secret");
+        let id = msg.get("id").cloned();
+        let result = match msg["method"].as_str().unwrap() {
+            "initialize" => json!({"protocolVersion": MCP_PROTOCOL_VERSION, "capabilities": {"tools": {}}, "serverInfo": {"name": "stub", "version": "0"}}),
+            "notifications/initialized" => return axum::http::StatusCode::ACCEPTED.into_response(),
+            "tools/list" => json!({"tools": [{"name": "add", "description": "Adds two numbers", "inputSchema": {"type": "object", "properties": {"a": {"type": "integer"}, "b": {"type": "integer"}}}}]}),
+            "tools/call" => {
+                assert_eq!(headers.get(MCP_SESSION_ID_HEADER).unwrap(), "stub-session");
+                let sum = msg["params"]["arguments"]["a"].as_i64().unwrap() + msg["params"]["arguments"]["b"].as_i64().unwrap();
+                let payload = json!({"jsonrpc": "2.0", "id": id, "result": {"content": [{"type": "text", "text": sum.to_string()}], "isError": false}});
+                return ([("content-type", "text/event-stream")], format!("event: message\ndata: {}\n\n", payload)).into_response();
+            }
+            _ => unreachable!(),
+        };
+        ([(MCP_SESSION_ID_HEADER, "stub-session")], Json(json!({"jsonrpc": "2.0", "id": id, "result": result}))).into_response()
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_against_stub_server() {
+        let app = Router::new().route("/mcp", post(stub_mcp_server));
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut client = MCPHttpClient::connect(
+            reqwest::Client::new(),
+            MCPHttpTransport::StreamableHttp,
+            &format!("http://{}/mcp", addr),
+            &HashMap::new(),
+            "secret",
+            Duration::from_secs(10),
+        ).await.unwrap();
+        let init = client.initialize().await.unwrap();
+        assert_eq!(init["serverInfo"]["name"], "stub");
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "add");
+        let result = client.call_tool("add", json!({"a": 2, "b": 3})).await.unwrap();
+        assert_eq!(result["content"][0]["text"], "5");
+    }
+
+    type StubSseSender = Arc<AMutex<Option<mpsc::UnboundedSender<serde_json::Value>>>>;
+
+    async fn stub_sse_open(Extension(sender): Extension<StubSseSender>) -> Sse<impl futures::Stream<Item = Result<SseEvent, Infallible>>> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<serde_json::Value>();
+        *sender.lock().await = Some(tx);
+        Sse::new(async_stream::stream! {
+            yield Ok(SseEvent::default().event("endpoint").data("/messages?session_id=42"));
+            while let Some(msg) = rx.recv().await {
+                yield Ok(SseEvent::default().event("message").data(msg.to_string()));
+            }
+        })
+    }
+
+    async fn stub_sse_message(Extension(sender): Extension<StubSseSender>, headers: AxumHeaderMap, Json(msg): Json<serde_json::Value>) -> axum::http::StatusCode {
+        assert_eq!(headers.get("authorization").unwrap(), "Bearer secret");
+        let id = msg.get("id").cloned();
+        let result = match msg["method"].as_str().unwrap() {
+            "initialize" => json!({"protocolVersion": "2024-11-05", "capabilities": {"tools": {}}, "serverInfo": {"name": "stub-sse", "version": "0"}}),
+            "notifications/initialized" => return axum::http::StatusCode::ACCEPTED,
+            "tools/list" => json!({"tools": [{"name": "echo", "description": "Echoes the text back", "inputSchema": {"type": "object", "properties": {"text": {"type": "string"}}}}]}),
+            "tools/call" => json!({"content": [{"type": "text", "text": msg["params"]["arguments"]["text"]}], "isError": false}),
+            _ => unreachable!(),
+        };
+        // Responses go over the event stream, the POST itself only gets 202
+        sender.lock().await.as_ref().unwrap().send(json!({"jsonrpc": "2.0", "id": id, "result": result})).unwrap();
+        axum::http::StatusCode::ACCEPTED
+    }
+
+    #[tokio::test]
+    async fn test_sse_against_stub_server() {
+        let sender: StubSseSender = Arc::new(AMutex::new(None));
+        let app = Router::new()
+            .route("/sse", get(stub_sse_open))
+            .route("/messages", post(stub_sse_message))
+            .layer(Extension(sender));
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut client = MCPHttpClient::connect(
+            reqwest::Client::new(),
+            MCPHttpTransport::Sse,
+            &format!("http://{}/sse", addr),
+            &HashMap::from([("authorization".to_string(), "Bearer secret".to_string())]),
+            "",
+            Duration::from_secs(10),
+        ).await.unwrap();
+        assert_eq!(client.sse_post_url, Some(format!("http://{}/messages?session_id=42", addr)));
+        let init = client.initialize().await.unwrap();
+        assert_eq!(init["serverInfo"]["name"], "stub-sse");
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "echo");
+        let result = client.call_tool("echo", json!({"text": "hello"})).await.unwrap();
+        assert_eq!(result["content"][0]["text"], "hello");
+        client.shutdown().await.unwrap();
+    }
+}
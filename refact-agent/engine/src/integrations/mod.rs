@@ -12,15 +12,18 @@ pub mod integr_abstract;
 pub mod integr_github;
 pub mod integr_gitlab;
 pub mod integr_pdb;
+pub mod integr_dap;
 pub mod integr_chrome;
 pub mod integr_postgres;
 pub mod integr_mysql;
 pub mod integr_cmdline;
 pub mod integr_cmdline_service;
 pub mod integr_shell;
+pub mod integr_shell_session;
 pub mod integr_mcp;
 
 pub mod process_io_utils;
+pub mod mcp_http_transport;
 pub mod docker;
 pub mod sessions;
 pub mod config_chat;
@@ -48,6 +51,9 @@ pub fn integration_from_name(n: &str) -> Result<Box<dyn IntegrationTrait + Send
             // let tool_name = cmdline.strip_prefix("cmdline_").unwrap();
             Ok(Box::new(integr_cmdline::ToolCmdline {..Default::default()}) as Box<dyn IntegrationTrait + Send + Sync>)
         },
+        dap if dap.starts_with("dap_") => {
+            Ok(Box::new(integr_dap::ToolDap {..Default::default()}) as Box<dyn IntegrationTrait + Send + Sync>)
+        },
         service if service.starts_with("service_") => {
             Ok(Box::new(integr_cmdline_service::ToolService {..Default::default()}) as Box<dyn IntegrationTrait + Send + Sync>)
         },
@@ -69,6 +75,7 @@ pub fn integrations_list(allow_experimental: bool) -> Vec<&'static str> {
         "mysql",
         "cmdline_TEMPLATE",
         "service_TEMPLATE",
+        "dap_TEMPLATE",
         "mcp_TEMPLATE",
         "docker",
         "shell",
@@ -1,8 +1,9 @@
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::process::ChildStdin;
+use tokio::process::{ChildStdin, Command};
 use tokio::time::Duration;
 use std::time::Instant;
+use std::process::{Output, Stdio};
 use tracing::error;
 
 
@@ -84,6 +85,37 @@ pub async fn is_someone_listening_on_that_tcp_port(port: u16, timeout: tokio::ti
     }
 }
 
+struct KillProcessGroupOnDrop {
+    pgid: Option<u32>,
+}
+
+impl Drop for KillProcessGroupOnDrop {
+    fn drop(&mut self) {
+        // kill_on_drop only gets the shell itself, this gets whatever the shell has started
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            let _ = std::process::Command::new("kill")
+                .arg("-KILL").arg("--").arg(format!("-{}", pgid))
+                .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+pub async fn output_killed_on_drop(cmd: &mut Command) -> std::io::Result<Output>
+{
+    // Like cmd.output(), but if the future is dropped (timeout, cancelled tool call) the process
+    // and its children are killed, instead of running on in the background
+    cmd.kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+    let child = cmd.spawn()?;
+    let mut guard = KillProcessGroupOnDrop { pgid: child.id() };
+    let output = child.wait_with_output().await;
+    guard.pgid = None;
+    output
+}
+
 pub fn first_n_chars(msg: &str, n: usize) -> String {
     let mut last_n_chars: String = msg.chars().take(n).collect();
     if last_n_chars.len() == n {
@@ -162,7 +162,7 @@ pub fn read_integrations_d(
                         continue;
                     }
                 };
-                if file_name_str.starts_with("cmdline_") || file_name_str.starts_with("service_") || file_name_str.starts_with("mcp_") {
+                if file_name_str.starts_with("cmdline_") || file_name_str.starts_with("service_") || file_name_str.starts_with("mcp_") || file_name_str.starts_with("dap_") {
                     files_to_read.push((entry.path().to_string_lossy().to_string(), file_name_str_no_yaml, project_path));
                 }
             }
@@ -229,7 +229,7 @@ pub fn read_integrations_d(
             Some(mapping) => {
                 for (key, value) in mapping {
                     if let Some(key_str) = key.as_str() {
-                        if key_str.starts_with("cmdline_") || key_str.starts_with("service_") {
+                        if key_str.starts_with("cmdline_") || key_str.starts_with("service_") || key_str.starts_with("dap_") {
                             let mut rec: IntegrationRecord = Default::default();
                             rec.integr_config_path = integrations_yaml_path.clone();
                             rec.integr_name = key_str.to_string();
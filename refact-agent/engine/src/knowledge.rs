@@ -14,8 +14,7 @@ use tokio_rusqlite::Connection;
 
 use zerocopy::IntoBytes;
 use crate::ast::chunk_utils::official_text_hashing_function;
-use crate::vecdb::vdb_sqlite::VecDBSqlite;
-use crate::vecdb::vdb_structs::{MemoRecord, SimpleTextHashVector, VecDbStatus, VecdbConstants};
+use crate::vecdb::vdb_structs::{MemoRecord, SimpleTextHashVector, VecDbStatus, VecdbBackend, VecdbConstants};
 
 
 pub struct MemoriesDatabase {
@@ -501,7 +500,7 @@ async fn recall_dirty_memories_and_mark_them_not_dirty(
 
 pub async fn vectorize_dirty_memories(
     memdb: Arc<AMutex<MemoriesDatabase>>,
-    vecdb_handler: Arc<AMutex<VecDBSqlite>>,
+    vecdb_handler: Arc<AMutex<Box<dyn VecdbBackend>>>,
     _status: Arc<AMutex<VecDbStatus>>,
     client: Arc<AMutex<Client>>,
     api_key: &String,
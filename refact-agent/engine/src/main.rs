@@ -54,6 +54,7 @@ mod scratchpads;
 
 #[cfg(feature="vecdb")]
 mod fetch_embedding;
+mod forward_to_anthropic_endpoint;
 mod forward_to_hf_endpoint;
 mod forward_to_openai_endpoint;
 mod restream;
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, RwLock as StdRwLock};
 use tokio::sync::Mutex as AMutex;
 use tokio::sync::RwLock as ARwLock;
@@ -16,24 +17,26 @@ use crate::nicer_logs;
 use crate::scratchpad_abstract::{FinishReason, ScratchpadAbstract};
 use crate::telemetry::telemetry_structs;
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::caps::get_api_key;
+use crate::caps::{get_api_key, model_record_any_kind};
 
 
 async fn _get_endpoint_and_stuff_from_model_name(
     gcx: Arc<ARwLock<crate::global_context::GlobalContext>>,
     caps: Arc<StdRwLock<crate::caps::CodeAssistantCaps>>,
     model_name: String,
-) -> (String, String, String, String)
+) -> (String, String, String, String, HashMap<String, String>)
 {
     let (
-        custom_apikey,
+        mut custom_apikey,
         mut endpoint_style,
-        custom_endpoint_style,
+        mut custom_endpoint_style,
         mut endpoint_template,
-        custom_endpoint_template,
-        endpoint_chat_passthrough,
+        mut custom_endpoint_template,
+        mut endpoint_chat_passthrough,
+        provider_mb,
     ) = {
         let caps_locked = caps.read().unwrap();
+        let provider_mb = model_record_any_kind(&caps_locked, &model_name).and_then(|rec| rec.provider);
         if caps_locked.code_chat_models.contains_key(&model_name) {
             (
                 caps_locked.chat_apikey.clone(),
@@ -42,6 +45,7 @@ async fn _get_endpoint_and_stuff_from_model_name(
                 caps_locked.endpoint_template.clone(),   // abstract
                 caps_locked.chat_endpoint.clone(),       // chat-specific
                 caps_locked.endpoint_chat_passthrough.clone(),
+                provider_mb,
             )
         } else {
             (
@@ -51,9 +55,24 @@ async fn _get_endpoint_and_stuff_from_model_name(
                 caps_locked.endpoint_template.clone(),          // abstract
                 caps_locked.completion_endpoint.clone(),        // completion-specific
                 "".to_string(),
+                provider_mb,
             )
         }
     };
+    let mut extra_headers = HashMap::new();
+    if let Some(provider) = provider_mb {  // model-specific
+        if !provider.apikey.is_empty() {
+            custom_apikey = provider.apikey;
+        }
+        if !provider.endpoint_style.is_empty() {
+            custom_endpoint_style = provider.endpoint_style;
+        }
+        if !provider.endpoint.is_empty() {
+            custom_endpoint_template = provider.endpoint.clone();
+            endpoint_chat_passthrough = provider.endpoint;
+        }
+        extra_headers = provider.headers;
+    }
     let api_key = get_api_key(gcx, custom_apikey).await;
     if !custom_endpoint_style.is_empty() {
         endpoint_style = custom_endpoint_style;
@@ -66,9 +85,54 @@ async fn _get_endpoint_and_stuff_from_model_name(
         endpoint_template,
         endpoint_style,
         endpoint_chat_passthrough,
+        extra_headers,
     )
 }
 
+fn _fallback_models(caps: Arc<StdRwLock<crate::caps::CodeAssistantCaps>>, model_name: &str) -> VecDeque<String> {
+    let caps_locked = caps.read().unwrap();
+    model_record_any_kind(&caps_locked, model_name).map(|rec| rec.fallbacks).unwrap_or_default().into_iter()
+        .filter(|m| {
+            let known = model_record_any_kind(&caps_locked, m).is_some();
+            if !known {
+                tracing::warn!("fallback model {:?} for {:?} is not in caps, skipped", m, model_name);
+            }
+            known
+        })
+        .collect()
+}
+
+// "http://... status=503 text ..." from forward_to_*_endpoint()
+fn _status_from_forward_error(e: &str) -> Option<u16> {
+    let after = e.split_once(" status=")?.1;
+    after.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+fn _next_fallback_model(status: u16, fallback_models: &mut VecDeque<String>) -> Option<String> {
+    if status == 429 || (500..600).contains(&status) {
+        fallback_models.pop_front()
+    } else {
+        None
+    }
+}
+
+// Nothing is answered yet at this point, the scratchpad builds the same conversation for the next model that can take it
+async fn _prompt_for_next_fallback(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    scratchpad: &mut Box<dyn ScratchpadAbstract>,
+    status: u16,
+    fallback_models: &mut VecDeque<String>,
+    parameters: &mut SamplingParameters,
+) -> Option<(String, String)> {
+    while let Some(next_model) = _next_fallback_model(status, fallback_models) {
+        match scratchpad.prompt_for_fallback_model(ccx.clone(), &next_model, parameters).await {
+            Ok(prompt) => return Some((next_model, prompt)),
+            Err(e) => tracing::warn!("cannot fall back to {:?}: {}", next_model, e),
+        }
+    }
+    None
+}
+
 pub async fn scratchpad_interaction_not_stream_json(
     ccx: Arc<AMutex<AtCommandsContext>>,
     scratchpad: &mut Box<dyn ScratchpadAbstract>,
@@ -92,52 +156,87 @@ pub async fn scratchpad_interaction_not_stream_json(
             gcx_locked.http_client_slowdown.clone()
         )
     };
+    let mut model_name = model_name;
+    let mut prompt = prompt.to_string();
+    let mut parameters = parameters.clone();
+    let mut fallback_models = _fallback_models(caps.clone(), &model_name);
     let (
-        bearer,
-        endpoint_template,
-        endpoint_style,
-        endpoint_chat_passthrough,
+        mut bearer,
+        mut endpoint_template,
+        mut endpoint_style,
+        mut endpoint_chat_passthrough,
+        mut extra_headers,
     ) = _get_endpoint_and_stuff_from_model_name(gcx.clone(), caps.clone(), model_name.clone()).await;
 
     let mut save_url: String = String::new();
     let _ = slowdown_arc.acquire().await;
     let metadata_supported = crate::global_context::is_metadata_supported(gcx.clone()).await;
-    let mut model_says = if only_deterministic_messages {
-        save_url = "only-det-messages".to_string();
-        Ok(Value::Object(serde_json::Map::new()))
-    } else if endpoint_style == "hf" {
-        crate::forward_to_hf_endpoint::forward_to_hf_style_endpoint(
-            &mut save_url,
-            bearer.clone(),
-            &model_name,
-            &prompt,
-            &client,
-            &endpoint_template,
-            &parameters,
-            meta
-        ).await
-    } else {
-        crate::forward_to_openai_endpoint::forward_to_openai_style_endpoint(
-            &mut save_url,
-            bearer.clone(),
-            &model_name,
-            &prompt,
-            &client,
-            &endpoint_template,
-            &endpoint_chat_passthrough,
-            &parameters,  // includes n
-            metadata_supported,
-            meta
-        ).await
-    }.map_err(|e| {
-        tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
-                save_url.clone(),
-                scope.clone(),
-                false,
-                e.to_string(),
-            ));
-        ScratchError::new_but_skip_telemetry(StatusCode::INTERNAL_SERVER_ERROR, format!("forward_to_endpoint: {}", e))
-    })?;
+    let mut model_says = loop {
+        let model_says_maybe = if only_deterministic_messages {
+            save_url = "only-det-messages".to_string();
+            Ok(Value::Object(serde_json::Map::new()))
+        } else if endpoint_style == "hf" {
+            crate::forward_to_hf_endpoint::forward_to_hf_style_endpoint(
+                &mut save_url,
+                bearer.clone(),
+                &extra_headers,
+                &model_name,
+                &prompt,
+                &client,
+                &endpoint_template,
+                &parameters,
+                meta.clone()
+            ).await
+        } else if endpoint_style == "anthropic" {
+            crate::forward_to_anthropic_endpoint::forward_to_anthropic_endpoint(
+                &mut save_url,
+                bearer.clone(),
+                &extra_headers,
+                &model_name,
+                &prompt,
+                &client,
+                &endpoint_chat_passthrough,
+                &parameters,
+                metadata_supported,
+                meta.clone()
+            ).await
+        } else {
+            crate::forward_to_openai_endpoint::forward_to_openai_style_endpoint(
+                &mut save_url,
+                bearer.clone(),
+                &extra_headers,
+                &model_name,
+                &prompt,
+                &client,
+                &endpoint_template,
+                &endpoint_chat_passthrough,
+                &parameters,  // includes n
+                metadata_supported,
+                meta.clone()
+            ).await
+        };
+        match model_says_maybe {
+            Ok(model_says) => break model_says,
+            Err(e) => {
+                tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
+                    save_url.clone(),
+                    scope.clone(),
+                    false,
+                    e.to_string(),
+                ));
+                let status = _status_from_forward_error(&e).unwrap_or_default();
+                if let Some((next_model, next_prompt)) = _prompt_for_next_fallback(ccx.clone(), scratchpad, status, &mut fallback_models, &mut parameters).await {
+                    tracing::warn!("{} answered {}, falling back from {:?} to {:?}", save_url, status, model_name, next_model);
+                    model_name = next_model;
+                    prompt = next_prompt;
+                    (bearer, endpoint_template, endpoint_style, endpoint_chat_passthrough, extra_headers) =
+                        _get_endpoint_and_stuff_from_model_name(gcx.clone(), caps.clone(), model_name.clone()).await;
+                    continue;
+                }
+                return Err(ScratchError::new_but_skip_telemetry(StatusCode::INTERNAL_SERVER_ERROR, format!("forward_to_endpoint: {}", e)));
+            }
+        }
+    };
     tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
         save_url.clone(),
         scope.clone(),
@@ -319,11 +418,13 @@ pub async fn scratchpad_interaction_stream(
             )
         };
         let (
-            bearer,
-            endpoint_template,
-            endpoint_style,
-            endpoint_chat_passthrough,
+            mut bearer,
+            mut endpoint_template,
+            mut endpoint_style,
+            mut endpoint_chat_passthrough,
+            mut extra_headers,
         ) = _get_endpoint_and_stuff_from_model_name(gcx.clone(), caps.clone(), model_name.clone()).await;
+        let mut fallback_models = _fallback_models(caps.clone(), &model_name);
 
         let t0 = std::time::Instant::now();
         let mut prompt = String::new();
@@ -378,21 +479,24 @@ pub async fn scratchpad_interaction_stream(
 
         let mut save_url: String = String::new();
         let _ = slowdown_arc.acquire().await;
-        loop {
-            let value_maybe = my_scratchpad.response_spontaneous();
-            if let Ok(value) = value_maybe {
-                for el in value {
-                    let mut el_with_compression = el.clone();
-                    el_with_compression["compression_strength"] = crate::forward_to_openai_endpoint::try_get_compression_from_prompt(&prompt);
-                    let value_str = format!("data: {}\n\n", serde_json::to_string(&el_with_compression).unwrap());
-                    info!("yield: {:?}", nicer_logs::first_n_chars(&value_str, 40));
+        let mut is_fallback_attempt = false;
+        'attempts: loop {
+            if !is_fallback_attempt {  // already sent before the first attempt
+                let value_maybe = my_scratchpad.response_spontaneous();
+                if let Ok(value) = value_maybe {
+                    for el in value {
+                        let mut el_with_compression = el.clone();
+                        el_with_compression["compression_strength"] = crate::forward_to_openai_endpoint::try_get_compression_from_prompt(&prompt);
+                        let value_str = format!("data: {}\n\n", serde_json::to_string(&el_with_compression).unwrap());
+                        info!("yield: {:?}", nicer_logs::first_n_chars(&value_str, 40));
+                        yield Result::<_, String>::Ok(value_str);
+                    }
+                } else {
+                    let err_str = value_maybe.unwrap_err();
+                    tracing::error!("response_spontaneous error: {}", err_str);
+                    let value_str = format!("data: {}\n\n", serde_json::to_string(&json!({"detail": err_str})).unwrap());
                     yield Result::<_, String>::Ok(value_str);
                 }
-            } else {
-                let err_str = value_maybe.unwrap_err();
-                tracing::error!("response_spontaneous error: {}", err_str);
-                let value_str = format!("data: {}\n\n", serde_json::to_string(&json!({"detail": err_str})).unwrap());
-                yield Result::<_, String>::Ok(value_str);
             }
             if only_deterministic_messages {
                 break;
@@ -403,6 +507,7 @@ pub async fn scratchpad_interaction_stream(
                 crate::forward_to_hf_endpoint::forward_to_hf_style_endpoint_streaming(
                     &mut save_url,
                     bearer.clone(),
+                    &extra_headers,
                     &model_name,
                     prompt.as_str(),
                     &client,
@@ -410,10 +515,24 @@ pub async fn scratchpad_interaction_stream(
                     &my_parameters,
                     meta
                 ).await
+            } else if endpoint_style == "anthropic" {
+                crate::forward_to_anthropic_endpoint::forward_to_anthropic_endpoint_streaming(
+                    &mut save_url,
+                    bearer.clone(),
+                    &extra_headers,
+                    &model_name,
+                    prompt.as_str(),
+                    &client,
+                    &endpoint_chat_passthrough,
+                    &my_parameters,
+                    metadata_supported,
+                    meta
+                ).await
             } else {
                 crate::forward_to_openai_endpoint::forward_to_openai_style_endpoint_streaming(
                     &mut save_url,
                     bearer.clone(),
+                    &extra_headers,
                     &model_name,
                     prompt.as_str(),
                     &client,
@@ -442,6 +561,7 @@ pub async fn scratchpad_interaction_stream(
             };
             let mut was_correct_output_even_if_error = false;
             let mut last_finish_reason = FinishReason::None;
+            let mut anthropic_converter = crate::forward_to_anthropic_endpoint::AnthropicStreamConverter::new();
             // let mut test_countdown = 250;
             while let Some(event) = event_source.next().await {
                 match event {
@@ -451,7 +571,17 @@ pub async fn scratchpad_interaction_stream(
                         if message.data.starts_with("[DONE]") {
                             break;
                         }
-                        let json = serde_json::from_str::<serde_json::Value>(&message.data).unwrap();
+                        let mut json = serde_json::from_str::<serde_json::Value>(&message.data).unwrap();
+                        if endpoint_style == "anthropic" {
+                            let chunk_mb = anthropic_converter.event_to_openai_chunk(&json);
+                            if anthropic_converter.finished {
+                                break;
+                            }
+                            match chunk_mb {
+                                Some(chunk) => json = chunk,
+                                None => continue,
+                            }
+                        }
                         crate::global_context::look_for_piggyback_fields(gcx.clone(), &json).await;
                         match _push_streaming_json_into_scratchpad(
                             my_scratchpad,
@@ -485,6 +615,25 @@ pub async fn scratchpad_interaction_stream(
                             // "restream error: Stream ended"
                             break;
                         }
+                        if let REError::InvalidStatusCode(status, _) = &err {
+                            let status = status.as_u16();
+                            if let Some((next_model, next_prompt)) = _prompt_for_next_fallback(my_ccx.clone(), my_scratchpad, status, &mut fallback_models, &mut my_parameters).await {
+                                tracing::warn!("{} answered {}, falling back from {:?} to {:?}", save_url, status, model_name, next_model);
+                                tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
+                                    save_url.clone(),
+                                    scope.clone(),
+                                    false,
+                                    format!("{}, falling back to {}", status, next_model),
+                                ));
+                                event_source.close();
+                                model_name = next_model;
+                                prompt = next_prompt;
+                                (bearer, endpoint_template, endpoint_style, endpoint_chat_passthrough, extra_headers) =
+                                    _get_endpoint_and_stuff_from_model_name(gcx.clone(), caps.clone(), model_name.clone()).await;
+                                is_fallback_attempt = true;
+                                continue 'attempts;
+                            }
+                        }
                         let problem_str = match err {
                             REError::InvalidStatusCode(err, resp) => {
                                 let text = resp.text().await.unwrap();
@@ -492,6 +641,8 @@ pub async fn scratchpad_interaction_stream(
                                 if let Ok(value) = serde_json::from_str::<Value>(&text) {
                                     if let Some(detail) = value.get("detail") {
                                         res = format!("{}: {}", err, detail);
+                                    } else if let Some(error) = value.get("error") {  // anthropic style
+                                        res = format!("{}: {}", err, error);
                                     }
                                 }
                                 res
@@ -661,3 +812,32 @@ pub async fn cached_stream(
        .unwrap();
     return Ok(response);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caps::{CodeAssistantCaps, ModelRecord};
+
+    #[test]
+    fn test_error_switches_to_fallback_model() {
+        let mut caps = CodeAssistantCaps::default();
+        caps.code_chat_models.insert("claude".to_string(), ModelRecord {
+            fallbacks: vec!["gpt-4o".to_string(), "no-such-model".to_string(), "gpt-4o-mini".to_string()],
+            ..Default::default()
+        });
+        caps.code_chat_models.insert("gpt-4o".to_string(), ModelRecord::default());
+        caps.code_chat_models.insert("gpt-4o-mini".to_string(), ModelRecord::default());
+        let mut fallback_models = _fallback_models(Arc::new(StdRwLock::new(caps)), "claude");
+        assert_eq!(fallback_models, VecDeque::from(vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()]));
+
+        let overloaded = "https://api.anthropic.com/v1/messages status=529 text {\"type\":\"error\"}";
+        assert_eq!(_status_from_forward_error(overloaded), Some(529));
+        assert_eq!(_status_from_forward_error("error sending request for url"), None);
+
+        assert_eq!(_next_fallback_model(400, &mut fallback_models), None);
+        assert_eq!(_next_fallback_model(0, &mut fallback_models), None);
+        assert_eq!(_next_fallback_model(529, &mut fallback_models), Some("gpt-4o".to_string()));
+        assert_eq!(_next_fallback_model(429, &mut fallback_models), Some("gpt-4o-mini".to_string()));
+        assert_eq!(_next_fallback_model(503, &mut fallback_models), None);
+    }
+}
@@ -84,6 +84,18 @@ pub trait ScratchpadAbstract: Send {
         sampling_parameters_to_patch: &mut SamplingParameters,
     ) -> Result<String, String>;
 
+    // The provider of the model answered 429 or 5xx before producing anything. Build the prompt from the same
+    // conversation again for `fallback_model` (its tokenizer, n_ctx, reasoning style), at-commands and tools don't run twice.
+    // Scratchpads that can't do that don't fall back.
+    async fn prompt_for_fallback_model(
+        &mut self,
+        _ccx: Arc<AMutex<AtCommandsContext>>,
+        _fallback_model: &str,
+        _sampling_parameters_to_patch: &mut SamplingParameters,
+    ) -> Result<String, String> {
+        Err("not implemented".to_string())
+    }
+
     // Not streaming, convert what model says (choices) to final result
     fn response_n_choices(
         &mut self,
@@ -10,6 +10,7 @@ use tracing::{info, error};
 use crate::at_commands::execute_at::run_at_commands_locally;
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::call_validation::{ChatMessage, ChatPost, ContextFile, SamplingParameters};
+use crate::privacy::redact_secrets_in_messages;
 use crate::scratchpad_abstract::{FinishReason, HasTokenizerAndEot, ScratchpadAbstract};
 use crate::scratchpads::chat_utils_deltadelta::DeltaDeltaChatStreamer;
 use crate::scratchpads::chat_utils_limit_history::fix_and_limit_messages_history;
@@ -116,6 +117,7 @@ impl ScratchpadAbstract for GenericChatScratchpad {
         } else {
             (self.messages.clone(), false)
         };
+        let messages = redact_secrets_in_messages(gcx.clone(), messages).await;
         let (limited_msgs, _compression_strength) = fix_and_limit_messages_history(&self.t, &messages, sampling_parameters_to_patch, n_ctx, None, self.post.model.as_str())?;
         // if self.supports_tools {
         // };
@@ -4,20 +4,24 @@ use indexmap::IndexMap;
 use serde_json::{json, Value};
 use tokenizers::Tokenizer;
 use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
 use async_trait::async_trait;
 use tracing::info;
 
 use crate::at_commands::execute_at::{run_at_commands_locally, run_at_commands_remotely};
 use crate::at_commands::at_commands::AtCommandsContext;
+use crate::cached_tokenizers::cached_tokenizer;
 use crate::call_validation::{ChatMessage, ChatPost, ReasoningEffort, SamplingParameters};
+use crate::global_context::GlobalContext;
 use crate::http::http_get_json;
 use crate::integrations::docker::docker_container_manager::docker_container_get_host_lsp_port_to_connect;
+use crate::privacy::redact_secrets_in_messages;
 use crate::scratchpad_abstract::{FinishReason, HasTokenizerAndEot, ScratchpadAbstract};
 use crate::scratchpads::chat_utils_limit_history::fix_and_limit_messages_history;
 use crate::scratchpads::scratchpad_utils::HasRagResults;
 use crate::scratchpads::chat_utils_prompts::prepend_the_right_system_prompt_and_maybe_more_initial_messages;
 use crate::scratchpads::passthrough_convert_messages::convert_messages_to_openai_format;
-use crate::tools::tools_description::{tool_description_list_from_yaml, tools_merged_and_filtered};
+use crate::tools::tools_description::{model_supports_array_param_type, tool_description_list_from_yaml, tools_merged_and_filtered};
 use crate::tools::tools_execute::{run_tools_locally, run_tools_remotely};
 
 
@@ -53,6 +57,13 @@ impl DeltaSender {
 }
 
 
+// Conversation after at-commands and tools, kept to build the prompt again for a fallback model
+struct PreparedConversation {
+    messages: Vec<ChatMessage>,
+    big_json: Value,
+    sampling_parameters: SamplingParameters,  // before the model-specific patches
+}
+
 // #[derive(Debug)]
 pub struct ChatPassthrough {
     pub t: HasTokenizerAndEot,
@@ -64,6 +75,7 @@ pub struct ChatPassthrough {
     pub allow_at: bool,
     pub supports_tools: bool,
     pub supports_clicks: bool,
+    prepared: Option<PreparedConversation>,
 }
 
 impl ChatPassthrough {
@@ -86,8 +98,76 @@ impl ChatPassthrough {
             allow_at,
             supports_tools,
             supports_clicks,
+            prepared: None,
         }
     }
+
+    // Limits the prepared conversation to n_ctx of self.post.model and formats it, the model can change between calls
+    async fn prompt_from_prepared(
+        &mut self,
+        gcx: Arc<ARwLock<GlobalContext>>,
+        n_ctx: usize,
+        sampling_parameters_to_patch: &mut SamplingParameters,
+    ) -> Result<String, String> {
+        let prepared = self.prepared.as_ref().ok_or("prompt() was not called yet")?;
+        let style = self.post.style.clone();
+        let messages = &prepared.messages;
+        let mut big_json = prepared.big_json.clone();
+
+        let (limited_msgs, compression_strength) = match fix_and_limit_messages_history(
+            &self.t,
+            messages,
+            sampling_parameters_to_patch,
+            n_ctx,
+            big_json.get("tools").map(|x| x.to_string()),
+            self.post.model.as_str()
+        ) {
+            Ok((limited_msgs, compression_strength)) => (limited_msgs, compression_strength),
+            Err(e) => {
+                tracing::error!("error limiting messages: {}", e);
+                return Err(format!("error limiting messages: {}", e));
+            }
+        };
+        if self.prepend_system_prompt {
+            assert_eq!(limited_msgs.first().unwrap().role, "system");
+        }
+
+        // Handle models that support reasoning
+        let caps = {
+            let gcx_locked = gcx.write().await;
+            gcx_locked.caps.clone().unwrap()
+        };
+        let model_record_mb = {
+            let caps_locked = caps.read().unwrap();
+            caps_locked.code_chat_models.get(&self.post.model).cloned()
+        };
+
+        let supports_reasoning = if let Some(model_record) = model_record_mb.clone() {
+            !model_record.supports_reasoning.is_none()
+        } else {
+            false
+        };
+
+        let limited_adapted_msgs = if supports_reasoning {
+            let model_record = model_record_mb.unwrap();
+            _adapt_for_reasoning_models(
+                &limited_msgs,
+                sampling_parameters_to_patch,
+                model_record.supports_reasoning.unwrap(),
+                model_record.default_temperature.clone(),
+                model_record.supports_boost_reasoning.clone(),
+            )
+        } else {
+            limited_msgs
+        };
+
+        let converted_messages = convert_messages_to_openai_format(limited_adapted_msgs, &style);
+        big_json["messages"] = json!(converted_messages);
+        big_json["compression_strength"] = json!(compression_strength);
+
+        let prompt = "PASSTHROUGH ".to_string() + &serde_json::to_string(&big_json).unwrap();
+        Ok(prompt.to_string())
+    }
 }
 
 #[async_trait]
@@ -110,6 +190,7 @@ impl ScratchpadAbstract for ChatPassthrough {
             let ccx_locked = ccx.lock().await;
             (ccx_locked.global_context.clone(), ccx_locked.n_ctx, ccx_locked.should_execute_remotely)
         };
+        let sampling_parameters_before_patch = sampling_parameters_to_patch.clone();
         let style = self.post.style.clone();
         let mut at_tools = if !should_execute_remotely {
             tools_merged_and_filtered(gcx.clone(), self.supports_clicks).await?
@@ -136,6 +217,7 @@ impl ScratchpadAbstract for ChatPassthrough {
                 run_tools_locally(ccx.clone(), &mut at_tools, self.t.tokenizer.clone(), sampling_parameters_to_patch.max_new_tokens, &messages, &mut self.has_rag_results, &style).await?
             }
         };
+        let messages = redact_secrets_in_messages(gcx.clone(), messages).await;
 
         let mut big_json = serde_json::json!({});
 
@@ -192,59 +274,42 @@ impl ScratchpadAbstract for ChatPassthrough {
             info!("PASSTHROUGH TOOLS NOT SUPPORTED");
         }
 
-        let (limited_msgs, compression_strength) = match fix_and_limit_messages_history(
-            &self.t,
-            &messages,
-            sampling_parameters_to_patch,
-            n_ctx,
-            big_json.get("tools").map(|x| x.to_string()),
-            self.post.model.as_str()
-        ) {
-            Ok((limited_msgs, compression_strength)) => (limited_msgs, compression_strength),
-            Err(e) => {
-                tracing::error!("error limiting messages: {}", e);
-                return Err(format!("error limiting messages: {}", e));
+        self.prepared = Some(PreparedConversation {
+            messages,
+            big_json,
+            sampling_parameters: sampling_parameters_before_patch,
+        });
+        self.prompt_from_prepared(gcx, n_ctx, sampling_parameters_to_patch).await
+    }
+
+    async fn prompt_for_fallback_model(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        fallback_model: &str,
+        sampling_parameters_to_patch: &mut SamplingParameters,
+    ) -> Result<String, String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let caps = gcx.read().await.caps.clone().ok_or("No caps available")?;
+        let model_record = caps.read().unwrap().code_chat_models.get(fallback_model).cloned()
+            .ok_or(format!("{} is not a chat model", fallback_model))?;
+        if !model_record.supports_scratchpads.contains_key("PASSTHROUGH") {
+            return Err(format!("{} doesn't support the passthrough scratchpad", fallback_model));
+        }
+        let prepared = self.prepared.as_mut().ok_or("prompt() was not called yet")?;
+        // tools were picked for the original model, the fallback might not take all of them or any at all
+        if let Some(big_json) = prepared.big_json.as_object_mut() {
+            if !model_record.supports_tools {
+                big_json.remove("tools");
+                big_json.remove("tool_choice");
+            } else if let Some(Value::Array(tools)) = big_json.get_mut("tools") {
+                tools.retain(|tool| openai_tool_is_supported_by(tool, fallback_model));
             }
-        };
-        if self.prepend_system_prompt {
-            assert_eq!(limited_msgs.first().unwrap().role, "system");
         }
-
-        // Handle models that support reasoning
-        let caps = {
-            let gcx_locked = gcx.write().await;
-            gcx_locked.caps.clone().unwrap()
-        };
-        let model_record_mb = {
-            let caps_locked = caps.read().unwrap();
-            caps_locked.code_chat_models.get(&self.post.model).cloned()
-        };
-
-        let supports_reasoning = if let Some(model_record) = model_record_mb.clone() {
-            !model_record.supports_reasoning.is_none()
-        } else {
-            false
-        };
-
-        let limited_adapted_msgs = if supports_reasoning {
-            let model_record = model_record_mb.unwrap();
-            _adapt_for_reasoning_models(
-                &limited_msgs,
-                sampling_parameters_to_patch,
-                model_record.supports_reasoning.unwrap(),
-                model_record.default_temperature.clone(),
-                model_record.supports_boost_reasoning.clone(),
-            )
-        } else {
-            limited_msgs
-        };
-
-        let converted_messages = convert_messages_to_openai_format(limited_adapted_msgs, &style);
-        big_json["messages"] = json!(converted_messages);
-        big_json["compression_strength"] = json!(compression_strength);
-
-        let prompt = "PASSTHROUGH ".to_string() + &serde_json::to_string(&big_json).unwrap();
-        Ok(prompt.to_string())
+        *sampling_parameters_to_patch = prepared.sampling_parameters.clone();
+        sampling_parameters_to_patch.max_new_tokens = sampling_parameters_to_patch.max_new_tokens.min(model_record.n_ctx / 4);
+        self.t = HasTokenizerAndEot::new(cached_tokenizer(caps.clone(), gcx.clone(), fallback_model.to_string()).await?);
+        self.post.model = fallback_model.to_string();
+        self.prompt_from_prepared(gcx, model_record.n_ctx, sampling_parameters_to_patch).await
     }
 
     fn response_n_choices(
@@ -284,6 +349,14 @@ impl ScratchpadAbstract for ChatPassthrough {
     }
 }
 
+fn openai_tool_is_supported_by(tool: &Value, model: &str) -> bool {
+    if model_supports_array_param_type(model) {
+        return true;
+    }
+    let properties = tool.pointer("/function/parameters/properties").and_then(|p| p.as_object());
+    !properties.map_or(false, |props| props.values().any(|p| p.get("type").and_then(|t| t.as_str()) == Some("array")))
+}
+
 fn _adapt_for_reasoning_models(
     messages: &Vec<ChatMessage>,
     sampling_parameters: &mut SamplingParameters,
@@ -374,7 +374,7 @@ fn replace_broken_tool_call_messages(
     sampling_parameters: &mut SamplingParameters,
     new_max_new_tokens: usize
 ) {
-    let high_budget_tools = vec!["create_textdoc"];
+    let high_budget_tools = vec!["create_textdoc", "apply_patch", "replace_symbol"];
     let last_index_assistant = messages.iter()
         .rposition(|msg| msg.role == "assistant")
         .unwrap_or(0);
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use tracing::debug;
+use std::path::PathBuf;
+use tracing::{debug, error};
 use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -7,13 +8,16 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock as ARwLock;
 
 use crate::global_context;
+use crate::files_correction::{canonical_path, get_project_dirs};
 use crate::telemetry::utils;
 use crate::telemetry::telemetry_structs::{SnippetTracker, Storage, TeleRobotHumanAccum};
-use crate::telemetry::utils::compress_tele_records_to_file;
+use crate::telemetry::utils::{append_to_local_history, cleanup_local_history, compress_tele_records_to_file};
 
 
 // if human characters / diff_time > 20 => ignore (don't count copy-paste and branch changes)
 const MAX_CHARS_PER_SECOND: i64 = 20;
+// local history is what the dashboard shows without a server, a year is enough for weekly plots
+const TELEMETRY_LOCAL_HISTORY_DAYS: u64 = 365;
 
 
 pub fn create_robot_human_record_if_not_exists(
@@ -147,9 +151,49 @@ fn compress_robot_human(
     compressed_vec
 }
 
+fn project_name_for_uri(uri: &String, project_dirs: &Vec<PathBuf>) -> String {
+    let path = canonical_path(uri);
+    project_dirs.iter()
+        .filter(|dir| path.starts_with(dir))
+        .max_by_key(|dir| dir.components().count())
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn compress_robot_human_by_project(
+    storage_locked: &RwLockReadGuard<Storage>,
+    project_dirs: &Vec<PathBuf>,
+) -> Vec<TeleRobotHumanLocal> {
+    // Same as compress_robot_human() but keeps the project, stays on this computer
+    let mut unique_combinations: HashMap<(String, String, String), TeleRobotHumanLocal> = HashMap::new();
+    for accum in storage_locked.tele_robot_human.iter() {
+        let project = project_name_for_uri(&accum.uri, project_dirs);
+        let key = (accum.file_extension.clone(), accum.model.clone(), project.clone());
+        let record = unique_combinations.entry(key).or_insert_with(|| TeleRobotHumanLocal {
+            file_extension: accum.file_extension.clone(),
+            model: accum.model.clone(),
+            project,
+            ..Default::default()
+        });
+        record.human_characters += accum.human_characters;
+        record.robot_characters += accum.robot_characters + accum.robot_characters_acc_baseline;
+        record.completions_cnt += accum.used_snip_ids.len() as i64;
+    }
+    unique_combinations.into_values().collect()
+}
+
 pub async fn tele_robot_human_compress_to_file(
     cx: Arc<ARwLock<global_context::GlobalContext>>,
 ) {
+    let project_dirs = get_project_dirs(cx.clone()).await;
+    let cache_dir = cx.read().await.cache_dir.clone();
+    let local_records = compress_robot_human_by_project(&cx.read().await.telemetry.read().unwrap(), &project_dirs)
+        .into_iter()
+        .filter(|rec| !(rec.model.is_empty() && rec.robot_characters == 0 && rec.human_characters == 0))
+        .map(|rec| serde_json::to_value(rec).unwrap())
+        .collect::<Vec<_>>();
+
     let mut records = vec![];
     for rec in compress_robot_human(&cx.read().await.telemetry.read().unwrap()) {
         if rec.model.is_empty() && rec.robot_characters == 0 && rec.human_characters == 0 {
@@ -161,9 +205,25 @@ pub async fn tele_robot_human_compress_to_file(
     match compress_tele_records_to_file(cx.clone(), records, "robot_human".to_string(), "rh".to_string()).await {
         Ok(_) => {
             cx.write().await.telemetry.write().unwrap().tele_robot_human.clear();
+            // only now, otherwise the same characters get into the history again on the next try
+            if let Err(e) = append_to_local_history(&cache_dir, local_records, "rh").await {
+                error!("error saving local robot_human history: {}", e);
+            }
         },
         Err(_) => {}
     };
+    cleanup_local_history(&cache_dir, TELEMETRY_LOCAL_HISTORY_DAYS).await;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct TeleRobotHumanLocal {
+    file_extension: String,
+    model: String,
+    project: String,
+
+    human_characters: i64,
+    robot_characters: i64,
+    completions_cnt: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -20,6 +20,61 @@ pub async fn telemetry_storage_dirs(cache_dir: &PathBuf) -> (PathBuf, PathBuf) {
     (dir, dir2)
 }
 
+pub async fn telemetry_local_history_dir(cache_dir: &PathBuf) -> PathBuf {
+    // Never sent anywhere, feeds the dashboard when there's no server to ask
+    let dir = cache_dir.join("telemetry").join("local_history");
+    tokio::fs::create_dir_all(dir.clone()).await.unwrap_or_else(|_| {});
+    dir
+}
+
+pub async fn append_to_local_history(
+    cache_dir: &PathBuf,
+    records: Vec<Value>,
+    teletype_short: &str,
+) -> Result<(), String> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let now = chrono::Local::now();
+    let dir = telemetry_local_history_dir(cache_dir).await;
+    // One file per day, one line per flush
+    let file_name = dir.join(format!("{}-{}.jsonl", now.format("%Y%m%d"), teletype_short));
+    let line = json!({
+        "records": records,
+        "ts_start": now.timestamp(),
+        "ts_end": now.timestamp(),
+    });
+    let mut f = tokio::fs::OpenOptions::new().create(true).append(true).open(&file_name).await
+        .map_err(|e| format!("cannot open {}: {}", file_name.display(), e))?;
+    f.write_all(format!("{}\n", line).as_bytes()).await
+        .map_err(|e| format!("cannot write {}: {}", file_name.display(), e))?;
+    Ok(())
+}
+
+pub async fn cleanup_local_history(
+    cache_dir: &PathBuf,
+    keep_days: u64,
+) {
+    let dir = telemetry_local_history_dir(cache_dir).await;
+    let max_age = std::time::Duration::from_secs(keep_days * 24 * 60 * 60);
+    let now = std::time::SystemTime::now();
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let modified = match entry.metadata().await.and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        if now.duration_since(modified).unwrap_or_default() > max_age {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                error!("failed to delete old local history file {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
 pub async fn compress_tele_records_to_file(
     cx: Arc<ARwLock<global_context::GlobalContext>>,
     records: Vec<Value>,
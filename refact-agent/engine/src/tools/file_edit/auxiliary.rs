@@ -2,6 +2,7 @@ use crate::ast::ast_indexer_thread::{ast_indexer_block_until_finished, ast_index
 use crate::call_validation::DiffChunk;
 use crate::files_in_workspace::get_file_text_from_memory_or_disk;
 use crate::global_context::GlobalContext;
+use crate::tools::file_edit::syntax_check::check_edit_keeps_syntax;
 use regex::{Match, Regex};
 use std::fs;
 use std::path::PathBuf;
@@ -141,7 +142,7 @@ pub async fn sync_documents_ast(
     Ok(())
 }
 
-pub async fn write_file(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, file_text: &String, dry: bool) -> Result<(String, String), String> {
+pub async fn write_file(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, file_text: &String, check_syntax: bool, dry: bool) -> Result<(String, String), String> {
     let parent = path.parent().ok_or(format!(
         "Failed to Add: {:?}. Path is invalid.\nReason: path must have had a parent directory",
         path
@@ -162,6 +163,10 @@ pub async fn write_file(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, file_t
     } else {
         "".to_string()
     };
+
+    if check_syntax {
+        check_edit_keeps_syntax(path, &before_text, file_text)?;
+    }
     
     if !dry {
         fs::write(&path, file_text).map_err(|e| {
@@ -180,6 +185,7 @@ pub async fn str_replace(
     old_str: &String,
     new_str: &String,
     replace_multiple: bool,
+    check_syntax: bool,
     dry: bool,
 ) -> Result<(String, String), String> {
     let file_content = get_file_text_from_memory_or_disk(gcx.clone(), path).await?;
@@ -212,7 +218,7 @@ pub async fn str_replace(
     let normalized_new_str = normalize_line_endings(new_str);
     let new_content = normalized_content.replace(&normalized_old_str, &normalized_new_str);
     let new_file_content = restore_line_endings(&new_content, has_crlf);
-    write_file(gcx.clone(), path, &new_file_content, dry).await?;
+    write_file(gcx.clone(), path, &new_file_content, check_syntax, dry).await?;
     Ok((file_content, new_file_content))
 }
 
@@ -222,6 +228,7 @@ pub async fn str_replace_regex(
     pattern: &Regex,
     replacement: &String,
     multiple: bool,
+    check_syntax: bool,
     dry: bool
 ) -> Result<(String, String), String> {
     let file_content = get_file_text_from_memory_or_disk(gcx.clone(), path).await?;
@@ -251,6 +258,6 @@ pub async fn str_replace_regex(
             .to_string()
     };
     let new_file_content = restore_line_endings(&new_content, has_crlf);
-    write_file(gcx.clone(), path, &new_file_content, dry).await?;
+    write_file(gcx.clone(), path, &new_file_content, check_syntax, dry).await?;
     Ok((file_content, new_file_content))
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
 pub mod auxiliary;
+pub mod syntax_check;
+pub mod tool_apply_patch;
 pub mod tool_create_textdoc;
+pub mod tool_rename_symbol;
+pub mod tool_symbol_edit;
 pub mod tool_update_textdoc;
 pub mod tool_update_textdoc_regex;
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock as ARwLock;
+use tree_sitter::{Node, Parser};
+
+use crate::ast::treesitter::parsers::{get_language_id_by_filename, get_tree_sitter_language};
+use crate::global_context::GlobalContext;
+use crate::yaml_configs::customization_loader::load_customization;
+
+const MAX_REPORTED_ERRORS: usize = 5;
+const MAX_LINES_PER_ERROR: usize = 3;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub line1: usize,  // 1-based, inclusive
+    pub line2: usize,
+    /// Kind of the node the parser expected but didn't find, None for unexpected code
+    pub missing: Option<String>,
+    text: String,
+}
+
+impl SyntaxError {
+    fn key(&self) -> (Option<&str>, &str) {
+        (self.missing.as_deref(), self.text.trim())
+    }
+}
+
+fn collect_errors(node: Node, code: &str, errors: &mut Vec<SyntaxError>) {
+    if node.is_error() || node.is_missing() {
+        errors.push(SyntaxError {
+            line1: node.start_position().row + 1,
+            line2: node.end_position().row + 1,
+            missing: node.is_missing().then(|| node.kind().to_string()),
+            text: code.get(node.byte_range()).unwrap_or_default().to_string(),
+        });
+        return;
+    }
+    if !node.has_error() {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_errors(child, code, errors);
+    }
+}
+
+/// None if there's no parser for the file type
+pub fn syntax_errors(path: &PathBuf, code: &str) -> Option<Vec<SyntaxError>> {
+    let language = get_language_id_by_filename(path).and_then(get_tree_sitter_language)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(code, None)?;
+    let mut errors = Vec::new();
+    collect_errors(tree.root_node(), code, &mut errors);
+    Some(errors)
+}
+
+fn format_syntax_errors(path: &PathBuf, code: &str, errors: &[&SyntaxError]) -> String {
+    let lines: Vec<&str> = code.lines().collect();
+    let mut result = format!(
+        "Error: the edit breaks the syntax of {:?}, the file was not changed. Problems the edit introduces:\n",
+        path
+    );
+    for e in errors.iter().take(MAX_REPORTED_ERRORS) {
+        let what = match &e.missing {
+            Some(kind) => format!("missing `{}`", kind),
+            None => "unexpected code".to_string(),
+        };
+        if e.line1 == e.line2 {
+            result.push_str(&format!("line {}: {}\n", e.line1, what));
+        } else {
+            result.push_str(&format!("lines {}-{}: {}\n", e.line1, e.line2, what));
+        }
+        let last_line = e.line2.min(e.line1 + MAX_LINES_PER_ERROR - 1);
+        for line_n in e.line1..=last_line {
+            if let Some(line) = lines.get(line_n - 1) {
+                result.push_str(&format!("{:>6} | {}\n", line_n, line));
+            }
+        }
+    }
+    if errors.len() > MAX_REPORTED_ERRORS {
+        result.push_str(&format!("...and {} more\n", errors.len() - MAX_REPORTED_ERRORS));
+    }
+    result.push_str("Fix the edit so the code stays valid and try again.");
+    result
+}
+
+/// Errors that were already in the file before the edit don't count, only the new ones are reported
+pub fn check_edit_keeps_syntax(path: &PathBuf, before: &str, after: &str) -> Result<(), String> {
+    let errors_after = match syntax_errors(path, after) {
+        Some(errors) if !errors.is_empty() => errors,
+        _ => return Ok(()),
+    };
+    let errors_before = syntax_errors(path, before).unwrap_or_default();
+    // Line numbers shift after an edit, so old errors are matched by their text
+    let mut old_errors: HashMap<(Option<&str>, &str), usize> = HashMap::new();
+    for e in errors_before.iter() {
+        *old_errors.entry(e.key()).or_insert(0) += 1;
+    }
+    let new_errors: Vec<&SyntaxError> = errors_after.iter()
+        .filter(|e| match old_errors.get_mut(&e.key()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    if new_errors.is_empty() {
+        return Ok(());
+    }
+    Err(format_syntax_errors(path, after, &new_errors))
+}
+
+pub async fn syntax_check_enabled(gcx: Arc<ARwLock<GlobalContext>>, tool_name: &str) -> bool {
+    let mut error_log = Vec::new();
+    let customization = load_customization(gcx, true, &mut error_log).await;
+    customization.edit_syntax_check.get(tool_name).cloned().unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_CODE: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+
+    #[test]
+    fn test_broken_edit_is_rejected() {
+        let path = PathBuf::from("main.rs");
+        let broken = RUST_CODE.replace("let x = 1;", "let x = (1;");
+        let err = check_edit_keeps_syntax(&path, RUST_CODE, &broken).unwrap_err();
+        assert!(err.contains("let x = (1;"), "{}", err);
+
+        let fine = RUST_CODE.replace("let x = 1;", "let x = (1 + 2);");
+        assert!(check_edit_keeps_syntax(&path, RUST_CODE, &fine).is_ok());
+    }
+
+    #[test]
+    fn test_old_errors_and_unknown_languages_are_ignored() {
+        let path = PathBuf::from("main.rs");
+        let already_broken = format!("{}fn broken( {{\n", RUST_CODE);
+        let edited = format!("// comment\n{}", already_broken);
+        assert!(check_edit_keeps_syntax(&path, &already_broken, &edited).is_ok());
+
+        let path = PathBuf::from("notes.txt");
+        assert!(check_edit_keeps_syntax(&path, "", "fn main( {").is_ok());
+    }
+}
@@ -0,0 +1,460 @@
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, DiffChunk};
+use crate::integrations::integr_abstract::IntegrationConfirmation;
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel, PrivacySettings};
+use crate::tools::file_edit::auxiliary::{
+    await_ast_indexing, convert_edit_to_diffchunks, normalize_line_endings, restore_line_endings, sync_documents_ast, write_file,
+};
+use crate::tools::file_edit::syntax_check::{check_edit_keeps_syntax, syntax_check_enabled};
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex as AMutex;
+use crate::files_correction::{canonicalize_normalized_path, get_active_project_path, get_project_dirs, preprocess_path_for_normalization};
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use tokio::sync::RwLock as ARwLock;
+use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_or_a_good_error};
+use crate::global_context::GlobalContext;
+
+#[derive(Debug, Clone, PartialEq)]
+struct PatchHunk {
+    old_start: usize,
+    lines: Vec<(char, String)>,  // ' ' context, '-' removed, '+' added
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FilePatch {
+    old_path: Option<String>,  // None for /dev/null
+    new_path: Option<String>,
+    hunks: Vec<PatchHunk>,
+}
+
+#[derive(Debug)]
+enum PlannedChange {
+    Write { path: PathBuf, before: String, after: String, existed: bool },
+    Remove { path: PathBuf, before: String },
+}
+
+pub struct ToolApplyPatch;
+
+fn parse_patch_path(header: &str) -> Option<String> {
+    // "a/src/main.rs\t2024-01-01 00:00:00" -> "src/main.rs"
+    let path = header.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+    Some(path.to_string())
+}
+
+fn parse_hunk_header(line: &str) -> Result<usize, String> {
+    // "@@ -12,7 +12,8 @@ fn main() {", line counts are ignored, models rarely get them right
+    let old_range = line.trim_start_matches('@').trim()
+        .split_whitespace().next()
+        .and_then(|r| r.strip_prefix('-'))
+        .ok_or(format!("bad hunk header {:?}", line))?;
+    old_range.split(',').next().unwrap_or("")
+        .parse::<usize>()
+        .map_err(|_| format!("bad hunk header {:?}", line))
+}
+
+fn parse_unified_diff(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let patch = normalize_line_endings(patch);
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ") {
+            files.push(FilePatch {
+                old_path: parse_patch_path(&line[4..]),
+                new_path: parse_patch_path(&lines[i + 1][4..]),
+                hunks: vec![],
+            });
+            i += 2;
+            continue;
+        }
+        if line.starts_with("@@") {
+            let file = files.last_mut().ok_or(format!("hunk {:?} goes before any --- +++ file header", line))?;
+            let mut hunk = PatchHunk { old_start: parse_hunk_header(line)?, lines: vec![] };
+            i += 1;
+            while i < lines.len() {
+                let body_line = lines[i];
+                if body_line.starts_with("@@") || body_line.starts_with("diff --git")
+                    || (body_line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ")) {
+                    break;
+                }
+                match body_line.chars().next() {
+                    Some(c @ (' ' | '-' | '+')) => hunk.lines.push((c, body_line[1..].to_string())),
+                    Some('\\') => {},  // "\ No newline at end of file"
+                    None => hunk.lines.push((' ', String::new())),  // editors and models strip the space of empty context lines
+                    Some(_) => break,
+                }
+                i += 1;
+            }
+            while hunk.lines.last().is_some_and(|(c, l)| *c == ' ' && l.is_empty()) {
+                hunk.lines.pop();
+            }
+            file.hunks.push(hunk);
+            continue;
+        }
+        i += 1;  // "diff --git", "index ...", "new file mode ..." and other noise
+    }
+    if files.is_empty() {
+        return Err("no files found in the patch, expected unified diff format with --- and +++ headers".to_string());
+    }
+    Ok(files)
+}
+
+fn lines_match(file_line: &str, patch_line: &str, fuzz: usize) -> bool {
+    match fuzz {
+        0 => file_line == patch_line,
+        1 => file_line.trim_end() == patch_line.trim_end(),
+        _ => file_line.split_whitespace().eq(patch_line.split_whitespace()),
+    }
+}
+
+fn find_hunk_position(file_lines: &[&str], old_lines: &[&str], expected: usize, min_pos: usize) -> Option<usize> {
+    if file_lines.len() < old_lines.len() {
+        return None;
+    }
+    let last_pos = file_lines.len() - old_lines.len();
+    if min_pos > last_pos {
+        return None;
+    }
+    let expected = expected.clamp(min_pos, last_pos);
+    // Exact match anywhere wins over a fuzzy one, nearest to the line number from the header wins among equals
+    for fuzz in 0..3 {
+        let matches_at = |pos: usize| old_lines.iter().enumerate().all(|(k, l)| lines_match(file_lines[pos + k], l, fuzz));
+        for distance in 0..=(last_pos - min_pos) {
+            if expected >= min_pos + distance && matches_at(expected - distance) {
+                return Some(expected - distance);
+            }
+            if distance > 0 && expected + distance <= last_pos && matches_at(expected + distance) {
+                return Some(expected + distance);
+            }
+        }
+    }
+    None
+}
+
+fn apply_hunks(text: &str, hunks: &[PatchHunk]) -> Result<String, String> {
+    let has_crlf = text.contains("\r\n");
+    let text = normalize_line_endings(text);
+    let file_lines: Vec<&str> = text.lines().collect();
+
+    let mut result: Vec<String> = Vec::new();
+    let mut copied_up_to = 0;
+    let mut offset: i64 = 0;
+    for (hunk_n, hunk) in hunks.iter().enumerate() {
+        let old_lines: Vec<&str> = hunk.lines.iter().filter(|(c, _)| *c != '+').map(|(_, l)| l.as_str()).collect();
+        let expected = (hunk.old_start.max(1) as i64 - 1 + offset).max(0) as usize;
+        let pos = if old_lines.is_empty() {
+            expected.clamp(copied_up_to, file_lines.len())
+        } else {
+            find_hunk_position(&file_lines, &old_lines, expected, copied_up_to).ok_or_else(|| {
+                let preview = old_lines.iter().take(3).map(|l| format!("    {}", l)).collect::<Vec<_>>().join("\n");
+                format!("hunk #{} (line {}) does not match the file, these lines were not found:\n{}", hunk_n + 1, hunk.old_start, preview)
+            })?
+        };
+        offset = pos as i64 - (hunk.old_start.max(1) as i64 - 1);
+
+        result.extend(file_lines[copied_up_to..pos].iter().map(|l| l.to_string()));
+        let mut k = pos;
+        for (c, line) in hunk.lines.iter() {
+            match c {
+                ' ' => { result.push(file_lines[k].to_string()); k += 1; },  // keep the file's own whitespace
+                '-' => k += 1,
+                _ => result.push(line.clone()),
+            }
+        }
+        copied_up_to = k;
+    }
+    result.extend(file_lines[copied_up_to..].iter().map(|l| l.to_string()));
+
+    let mut new_text = result.join("\n");
+    if !new_text.is_empty() && (text.ends_with('\n') || text.is_empty()) {
+        new_text.push('\n');
+    }
+    Ok(restore_line_endings(&new_text, has_crlf))
+}
+
+async fn resolve_existing_path(gcx: Arc<ARwLock<GlobalContext>>, path_str: &String) -> Result<PathBuf, String> {
+    let candidates_file = file_repair_candidates(gcx.clone(), path_str, 3, false).await;
+    let path = return_one_candidate_or_a_good_error(gcx.clone(), path_str, &candidates_file, &get_project_dirs(gcx.clone()).await, false).await?;
+    Ok(canonicalize_normalized_path(PathBuf::from(preprocess_path_for_normalization(path.trim().to_string()))))
+}
+
+async fn resolve_new_path(gcx: Arc<ARwLock<GlobalContext>>, path_str: &String) -> Result<PathBuf, String> {
+    let raw_path = PathBuf::from(preprocess_path_for_normalization(path_str.trim().to_string()));
+    if raw_path.is_absolute() {
+        return Ok(canonicalize_normalized_path(raw_path));
+    }
+    let project_dir = get_active_project_path(gcx.clone()).await
+        .ok_or(format!("Error: cannot create '{}', the path is relative and there is no project open", path_str))?;
+    Ok(canonicalize_normalized_path(project_dir.join(raw_path)))
+}
+
+async fn plan_changes(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    privacy_settings: Arc<PrivacySettings>,
+    check_syntax: bool,
+) -> Result<Vec<PlannedChange>, String> {
+    let patch = match args.get("patch") {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => return Err(format!("Error: The 'patch' argument must be a string with a unified diff, but received: {:?}", v)),
+        None => return Err("Error: The 'patch' argument is required but was not provided.".to_string()),
+    };
+    let file_patches = parse_unified_diff(&patch)?;
+
+    // Everything is checked before anything is written, all problems are reported at once
+    let mut planned = Vec::new();
+    let mut errors = Vec::new();
+    for file_patch in file_patches {
+        let change: Result<PlannedChange, String> = async {
+            match (&file_patch.old_path, &file_patch.new_path) {
+                (None, None) => Err("both paths are /dev/null".to_string()),
+                (Some(old), Some(new)) if old != new => Err(format!("renaming {} to {} is not supported, use mv() first", old, new)),
+                (Some(old), new) => {
+                    let path = resolve_existing_path(gcx.clone(), old).await?;
+                    if check_file_privacy(privacy_settings.clone(), &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+                        return Err(format!("Cannot update the file '{}' due to privacy settings", old));
+                    }
+                    let before = get_file_text_from_memory_or_disk(gcx.clone(), &path).await?;
+                    let after = apply_hunks(&before, &file_patch.hunks).map_err(|e| format!("{}: {}", old, e))?;
+                    if new.is_none() {
+                        if !after.trim().is_empty() {
+                            return Err(format!("{}: the file is deleted by the patch, but the hunks don't remove all of its lines", old));
+                        }
+                        return Ok(PlannedChange::Remove { path, before });
+                    }
+                    if check_syntax {
+                        check_edit_keeps_syntax(&path, &before, &after)?;
+                    }
+                    Ok(PlannedChange::Write { path, before, after, existed: true })
+                },
+                (None, Some(new)) => {
+                    let path = resolve_new_path(gcx.clone(), new).await?;
+                    if check_file_privacy(privacy_settings.clone(), &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+                        return Err(format!("Cannot create the file '{}' due to privacy settings", new));
+                    }
+                    if path.exists() {
+                        return Err(format!("{} already exists, the patch should modify it instead of creating", new));
+                    }
+                    let after = apply_hunks("", &file_patch.hunks).map_err(|e| format!("{}: {}", new, e))?;
+                    if check_syntax {
+                        check_edit_keeps_syntax(&path, "", &after)?;
+                    }
+                    Ok(PlannedChange::Write { path, before: String::new(), after, existed: false })
+                },
+            }
+        }.await;
+        match change {
+            Ok(change) => planned.push(change),
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("Error: the patch was not applied, no files were changed.\n{}", errors.join("\n")));
+    }
+    Ok(planned)
+}
+
+async fn rollback(gcx: Arc<ARwLock<GlobalContext>>, applied: &[&PlannedChange]) {
+    for change in applied.iter().rev() {
+        let result = match change {
+            PlannedChange::Write { path, existed: false, .. } => std::fs::remove_file(path).map_err(|e| e.to_string()),
+            PlannedChange::Write { path, before, .. } | PlannedChange::Remove { path, before } => {
+                write_file(gcx.clone(), path, before, false, false).await.map(|_| ())
+            },
+        };
+        if let Err(e) = result {
+            tracing::error!("apply_patch rollback failed: {}", e);
+        }
+    }
+}
+
+pub async fn tool_apply_patch_exec(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    dry: bool
+) -> Result<Vec<DiffChunk>, String> {
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+    let check_syntax = syntax_check_enabled(gcx.clone(), "apply_patch").await;
+    let planned = plan_changes(gcx.clone(), args, privacy_settings, check_syntax).await?;
+    await_ast_indexing(gcx.clone()).await?;
+
+    let mut applied: Vec<&PlannedChange> = Vec::new();
+    if !dry {
+        for change in planned.iter() {
+            let result = match change {
+                PlannedChange::Write { path, after, .. } => write_file(gcx.clone(), path, after, false, false).await.map(|_| ()),
+                PlannedChange::Remove { path, .. } => std::fs::remove_file(path)
+                    .map_err(|e| format!("Failed to remove file: {:?}\nERROR: {}", path, e)),
+            };
+            if let Err(e) = result {
+                rollback(gcx.clone(), &applied).await;
+                return Err(format!("Error: {}\nThe files changed before the error were restored.", e));
+            }
+            applied.push(change);
+        }
+    }
+
+    let mut diff_chunks = Vec::new();
+    for change in planned.iter() {
+        match change {
+            PlannedChange::Write { path, before, after, .. } => {
+                if !dry {
+                    sync_documents_ast(gcx.clone(), path).await?;
+                }
+                diff_chunks.extend(convert_edit_to_diffchunks(path.clone(), before, after)?);
+            },
+            PlannedChange::Remove { path, before } => {
+                if !dry {
+                    sync_documents_ast(gcx.clone(), path).await?;
+                }
+                let file_name = path.to_string_lossy().to_string();
+                diff_chunks.push(DiffChunk {
+                    file_name: file_name.clone(),
+                    file_action: "remove".to_string(),
+                    line1: 1,
+                    line2: before.lines().count(),
+                    lines_remove: before.clone(),
+                    lines_add: "".to_string(),
+                    file_name_rename: None,
+                    is_file: true,
+                    application_details: format!("File `{}` removed", file_name),
+                });
+            },
+        }
+    }
+    Ok(diff_chunks)
+}
+
+#[async_trait]
+impl Tool for ToolApplyPatch {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let diff_chunks = tool_apply_patch_exec(gcx.clone(), args, false).await?;
+        let results = vec![ChatMessage {
+            role: "diff".to_string(),
+            content: ChatContent::SimpleText(json!(diff_chunks).to_string()),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            usage: None,
+            ..Default::default()
+        }]
+        .into_iter()
+        .map(|x| ContextEnum::ChatMessage(x))
+        .collect::<Vec<_>>();
+        Ok((false, results))
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<MatchConfirmDeny, String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+
+        let msgs_len = ccx.lock().await.messages.len();
+
+        // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
+        if msgs_len != 0 {
+            // if the patch doesn't apply, there's no need for confirmation
+            let check_syntax = syntax_check_enabled(gcx.clone(), "apply_patch").await;
+            if plan_changes(gcx.clone(), args, privacy_settings, check_syntax).await.is_err() {
+                return Ok(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::PASS,
+                    command: "apply_patch".to_string(),
+                    rule: "".to_string(),
+                });
+            }
+        }
+        Ok(MatchConfirmDeny {
+            result: MatchConfirmDenyResult::CONFIRMATION,
+            command: "apply_patch".to_string(),
+            rule: "default".to_string(),
+        })
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        _args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        Ok("apply_patch".to_string())
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(IntegrationConfirmation {
+            ask_user: vec!["apply_patch*".to_string()],
+            deny: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,3 @@
+ fn a() {
+-    1
++    2
+ }
+@@ -9,2 +9,3 @@ fn c() {
+ fn d() {
++    // new
+ }
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++fn new() {
++}
+";
+
+    #[test]
+    fn test_parse_unified_diff() {
+        let files = parse_unified_diff(PATCH).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(files[0].hunks[1].old_start, 9);
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].new_path.as_deref(), Some("src/new.rs"));
+        assert!(parse_unified_diff("just some text").is_err());
+    }
+
+    #[test]
+    fn test_apply_hunks_with_offset_and_fuzz() {
+        let files = parse_unified_diff(PATCH).unwrap();
+        // two extra lines on top shift everything, and trailing spaces differ
+        let text = "// header\n// header\n\nfn a() {   \n    1\n}\n\nfn b() {}\n\nfn d() {\n}\n";
+        let result = apply_hunks(text, &files[0].hunks).unwrap();
+        assert_eq!(result, "// header\n// header\n\nfn a() {   \n    2\n}\n\nfn b() {}\n\nfn d() {\n    // new\n}\n");
+
+        assert_eq!(apply_hunks("", &files[1].hunks).unwrap(), "fn new() {\n}\n");
+        assert!(apply_hunks("fn x() {}\n", &files[0].hunks).is_err());
+
+        let crlf = text.replace("\n", "\r\n");
+        assert!(apply_hunks(&crlf, &files[0].hunks).unwrap().contains("    2\r\n"));
+    }
+}
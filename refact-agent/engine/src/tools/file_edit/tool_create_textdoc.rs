@@ -5,6 +5,7 @@ use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLeve
 use crate::tools::file_edit::auxiliary::{
     await_ast_indexing, convert_edit_to_diffchunks, sync_documents_ast, write_file,
 };
+use crate::tools::file_edit::syntax_check::syntax_check_enabled;
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool};
 use async_trait::async_trait;
 use serde_json::{json, Value};
@@ -94,7 +95,8 @@ pub async fn tool_create_text_doc_exec(
     let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
     let args = parse_args(gcx.clone(), args, privacy_settings).await?;
     await_ast_indexing(gcx.clone()).await?;
-    let (before_text, after_text) = write_file(gcx.clone(), &args.path, &args.content, dry).await?;
+    let check_syntax = syntax_check_enabled(gcx.clone(), "create_textdoc").await;
+    let (before_text, after_text) = write_file(gcx.clone(), &args.path, &args.content, check_syntax, dry).await?;
     sync_documents_ast(gcx.clone(), &args.path).await?;
     let diff_chunks = convert_edit_to_diffchunks(args.path.clone(), &before_text, &after_text)?;
     Ok((before_text, after_text, diff_chunks))
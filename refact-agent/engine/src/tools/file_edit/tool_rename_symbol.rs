@@ -0,0 +1,411 @@
+use crate::ast::ast_structs::{AstDB, AstDefinition, AstUsage};
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, DiffChunk};
+use crate::git::checkpoints::{create_workspace_checkpoint, Checkpoint};
+use crate::integrations::integr_abstract::IntegrationConfirmation;
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel};
+use crate::tools::file_edit::auxiliary::{
+    await_ast_indexing, convert_edit_to_diffchunks, normalize_line_endings, restore_line_endings, sync_documents_ast, write_file,
+};
+use crate::tools::file_edit::tool_symbol_edit::resolve_symbol;
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex as AMutex;
+use crate::files_correction::{canonicalize_normalized_path, get_project_dirs, preprocess_path_for_normalization};
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use tokio::sync::RwLock as ARwLock;
+use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_or_a_good_error};
+use crate::global_context::GlobalContext;
+
+const USAGES_LIMIT: usize = 1000;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedUsage {
+    pub file_name: String,
+    pub line: usize,  // 1-based
+    pub used_in: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RenamePlan {
+    pub symbol: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub chunks: Vec<DiffChunk>,
+    pub skipped_usages: Vec<SkippedUsage>,
+    #[serde(skip)]
+    files: Vec<(PathBuf, String, String)>,  // path, before, after
+}
+
+impl RenamePlan {
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "Renamed `{}` to `{}`, {} files changed.",
+            self.symbol, self.new_name, self.files.len()
+        );
+        if !self.skipped_usages.is_empty() {
+            summary.push_str("\nThese usages were NOT renamed, check them and fix by hand if needed:");
+            for s in self.skipped_usages.iter() {
+                summary.push_str(&format!("\n{}:{} in {}: {}", s.file_name, s.line, s.used_in, s.reason));
+            }
+        }
+        summary
+    }
+}
+
+pub struct ToolRenameSymbol;
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Err contains the reason why the usage might not point to the target
+async fn usage_points_to(
+    ast_index: Arc<AMutex<AstDB>>,
+    usage: &AstUsage,
+    target: &str,
+    guess_cache: &mut HashMap<String, Vec<String>>,
+) -> Result<(), String> {
+    if usage.resolved_as == target {
+        return Ok(());
+    }
+    for guess in usage.targets_for_guesswork.iter() {
+        let Some(to_resolve) = guess.strip_prefix("?::") else { continue };
+        if to_resolve.contains('🔎') {
+            return Err(format!("`{}` depends on the type of an expression, the AST can only guess it", to_resolve));
+        }
+        if !guess_cache.contains_key(to_resolve) {
+            let found = crate::ast::ast_db::definitions(ast_index.clone(), to_resolve).await
+                .iter().map(|d| d.path()).collect();
+            guess_cache.insert(to_resolve.to_string(), found);
+        }
+        let found = &guess_cache[to_resolve];
+        match found.len() {
+            0 => continue,
+            1 if found[0] == target => return Ok(()),
+            1 => return Err(format!("`{}` resolves to {}", to_resolve, found[0])),
+            n => return Err(format!("`{}` can mean any of {} definitions", to_resolve, n)),
+        }
+    }
+    Err("the usage was linked by guesswork".to_string())
+}
+
+// A place to rename: row from 0 and byte columns col1..col2 of the line, the same as AstUsage::name_range
+type NameSpot = (usize, usize, usize);
+
+/// Replaces the first whole-word match inside each spot and nothing else on the line, returns spots without a match
+fn rename_at_spots(text: &str, spots: &BTreeSet<NameSpot>, re: &Regex, new_name: &str) -> (String, Vec<NameSpot>) {
+    let mut not_found = Vec::new();
+    let mut result = String::with_capacity(text.len());
+    for (row, line) in text.split_inclusive('\n').enumerate() {
+        let mut matches = Vec::new();
+        for &(spot_row, col1, col2) in spots.range((row, 0, 0)..(row + 1, 0, 0)) {
+            match re.find_iter(line).find(|m| m.start() >= col1 && m.end() <= col2) {
+                Some(m) => matches.push((m.start(), m.end())),
+                None => not_found.push((spot_row, col1, col2)),
+            }
+        }
+        matches.sort();
+        matches.dedup();
+        let mut copied_up_to = 0;
+        for (start, end) in matches {
+            result.push_str(&line[copied_up_to..start]);
+            result.push_str(new_name);
+            copied_up_to = end;
+        }
+        result.push_str(&line[copied_up_to..]);
+    }
+    (result, not_found)
+}
+
+pub async fn plan_rename(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    symbol: &str,
+    path: &Option<PathBuf>,
+    new_name: &str,
+) -> Result<RenamePlan, String> {
+    if !is_identifier(new_name) {
+        return Err(format!("Error: `{}` is not a valid identifier", new_name));
+    }
+    await_ast_indexing(gcx.clone()).await?;
+    let def: Arc<AstDefinition> = resolve_symbol(gcx.clone(), symbol, path).await?;
+    let old_name = def.name();
+    if old_name == new_name {
+        return Err(format!("Error: `{}` already has this name", def.path_drop0()));
+    }
+    let re = Regex::new(&format!(r"\b{}\b", regex::escape(&old_name))).map_err(|e| e.to_string())?;
+    let ast_service = gcx.read().await.ast_service.clone().ok_or("Error: the AST index is turned off".to_string())?;
+    let ast_index = ast_service.lock().await.ast_index.clone();
+
+    let mut plan = RenamePlan {
+        symbol: def.path_drop0(),
+        old_name: old_name.clone(),
+        new_name: new_name.to_string(),
+        ..Default::default()
+    };
+    let mut spots_per_file: HashMap<String, BTreeSet<NameSpot>> = HashMap::new();
+    let mut usage_sources: HashMap<(String, usize), String> = HashMap::new();
+
+    let mut guess_cache = HashMap::new();
+    let target = def.path();
+    for (used_in, uline) in crate::ast::ast_db::usages(ast_index.clone(), target.clone(), USAGES_LIMIT).await {
+        let mut spots = Vec::new();
+        let mut skip = (uline, "the usage is not found in the AST index".to_string());
+        for usage in used_in.usages.iter().filter(|u| u.uline == uline) {
+            match (usage_points_to(ast_index.clone(), usage, &target, &mut guess_cache).await, usage.name_range) {
+                (Ok(()), Some(spot)) => spots.push(spot),
+                (Ok(()), None) => skip.1 = "the AST index doesn't know where exactly the usage is".to_string(),
+                (Err(reason), spot) => skip = (spot.map_or(uline, |s| s.0), reason),
+            }
+        }
+        if spots.is_empty() {
+            plan.skipped_usages.push(SkippedUsage {
+                file_name: used_in.cpath.clone(),
+                line: skip.0 + 1,
+                used_in: used_in.path_drop0(),
+                reason: skip.1,
+            });
+            continue;
+        }
+        for spot in spots {
+            spots_per_file.entry(used_in.cpath.clone()).or_default().insert(spot);
+            usage_sources.insert((used_in.cpath.clone(), spot.0), used_in.path_drop0());
+        }
+    }
+    spots_per_file.entry(def.cpath.clone()).or_default();
+
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+    let mut file_names: Vec<String> = spots_per_file.keys().cloned().collect();
+    file_names.sort();
+    for file_name in file_names {
+        let path = PathBuf::from(&file_name);
+        let mut spots = spots_per_file[&file_name].clone();
+        if check_file_privacy(privacy_settings.clone(), &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+            if file_name == def.cpath {
+                return Err(format!("Error: Cannot update the file '{:?}' due to privacy settings.", path));
+            }
+            for (row, _, _) in spots.iter() {
+                plan.skipped_usages.push(SkippedUsage {
+                    file_name: file_name.clone(),
+                    line: row + 1,
+                    used_in: usage_sources.get(&(file_name.clone(), *row)).cloned().unwrap_or_default(),
+                    reason: "the file is private according to privacy settings".to_string(),
+                });
+            }
+            continue;
+        }
+        let before = get_file_text_from_memory_or_disk(gcx.clone(), &path).await?;
+        let has_crlf = before.contains("\r\n");
+        let normalized = normalize_line_endings(&before);
+        if file_name == def.cpath {
+            // the name of the definition is the first match within its declaration
+            let decl_row = normalized.split_inclusive('\n').enumerate()
+                .skip(def.decl_line1 - 1)
+                .take(def.decl_line2 + 1 - def.decl_line1)
+                .find(|(_, line)| re.is_match(line))
+                .map(|(row, _)| row);
+            match decl_row {
+                Some(row) => { spots.insert((row, 0, usize::MAX)); }
+                None => return Err(format!(
+                    "Error: `{}` is not found at lines {}-{} of {:?}, the AST index is out of date, try again in a few seconds",
+                    old_name, def.decl_line1, def.decl_line2, path
+                )),
+            }
+        }
+        let (after, not_found) = rename_at_spots(&normalized, &spots, &re, new_name);
+        for (row, _, _) in not_found.iter() {
+            plan.skipped_usages.push(SkippedUsage {
+                file_name: file_name.clone(),
+                line: row + 1,
+                used_in: usage_sources.get(&(file_name.clone(), *row)).cloned().unwrap_or_default(),
+                reason: format!("`{}` is not where the AST index says it is, the index might be out of date", old_name),
+            });
+        }
+        let after = restore_line_endings(&after, has_crlf);
+        if after != before {
+            plan.chunks.extend(convert_edit_to_diffchunks(path.clone(), &before, &after)?);
+            plan.files.push((path, before, after));
+        }
+    }
+    plan.skipped_usages.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+    if let Some(first_chunk) = plan.chunks.first_mut() {
+        first_chunk.application_details = plan.summary();
+    }
+    Ok(plan)
+}
+
+/// Saves a checkpoint first, so the whole rename can be undone, then writes all files or none of them
+pub async fn apply_rename(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    plan: &RenamePlan,
+    chat_id: &str,
+) -> Result<Checkpoint, String> {
+    let (checkpoint, _) = create_workspace_checkpoint(gcx.clone(), None, chat_id).await
+        .map_err(|e| format!("Error: failed to create a checkpoint, nothing was renamed: {}", e))?;
+    let mut written: Vec<&(PathBuf, String, String)> = Vec::new();
+    for file in plan.files.iter() {
+        let (path, _, after) = file;
+        if let Err(e) = write_file(gcx.clone(), path, after, false, false).await {
+            for (path, before, _) in written.iter().rev() {
+                if let Err(e) = write_file(gcx.clone(), path, before, false, false).await {
+                    tracing::error!("rename_symbol rollback failed: {}", e);
+                }
+            }
+            return Err(format!("Error: {}\nThe files changed before the error were restored.", e));
+        }
+        written.push(file);
+    }
+    for (path, _, _) in plan.files.iter() {
+        sync_documents_ast(gcx.clone(), path).await?;
+    }
+    Ok(checkpoint)
+}
+
+pub async fn parse_args(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+) -> Result<(String, Option<PathBuf>, String), String> {
+    let symbol = match args.get("symbol") {
+        Some(Value::String(s)) if !s.trim().is_empty() => s.trim().replace('.', "::"),
+        Some(v) => return Err(format!("Error: The 'symbol' argument must be a non-empty string like 'MyClass::method', but received: {:?}", v)),
+        None => return Err("Error: The 'symbol' argument is required but was not provided.".to_string()),
+    };
+    let new_name = match args.get("new_name") {
+        Some(Value::String(s)) => s.trim().to_string(),
+        Some(v) => return Err(format!("Error: The 'new_name' argument must be a string, but received: {:?}", v)),
+        None => return Err("Error: The 'new_name' argument is required but was not provided.".to_string()),
+    };
+    let path = match args.get("path") {
+        Some(Value::String(s)) if !s.trim().is_empty() => {
+            let candidates_file = file_repair_candidates(gcx.clone(), &s, 3, false).await;
+            let path = return_one_candidate_or_a_good_error(gcx.clone(), &s, &candidates_file, &get_project_dirs(gcx.clone()).await, false).await?;
+            Some(canonicalize_normalized_path(PathBuf::from(preprocess_path_for_normalization(path.trim().to_string()))))
+        }
+        Some(Value::String(_)) | None => None,
+        Some(v) => return Err(format!("Error: The 'path' argument must be a string, but received: {:?}", v)),
+    };
+    Ok((symbol, path, new_name))
+}
+
+#[async_trait]
+impl Tool for ToolRenameSymbol {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let (gcx, chat_id) = {
+            let ccx_locked = ccx.lock().await;
+            (ccx_locked.global_context.clone(), ccx_locked.chat_id.clone())
+        };
+        let (symbol, path, new_name) = parse_args(gcx.clone(), args).await?;
+        let plan = plan_rename(gcx.clone(), &symbol, &path, &new_name).await?;
+        if plan.chunks.is_empty() {
+            return Err(format!("Nothing was renamed.\n{}", plan.summary()));
+        }
+        apply_rename(gcx.clone(), &plan, &chat_id).await?;
+        let results = vec![ChatMessage {
+            role: "diff".to_string(),
+            content: ChatContent::SimpleText(json!(plan.chunks).to_string()),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            usage: None,
+            ..Default::default()
+        }]
+        .into_iter()
+        .map(|x| ContextEnum::ChatMessage(x))
+        .collect::<Vec<_>>();
+        Ok((false, results))
+    }
+
+    fn tool_depends_on(&self) -> Vec<String> {
+        vec!["ast".to_string()]
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<MatchConfirmDeny, String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let msgs_len = ccx.lock().await.messages.len();
+
+        // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
+        if msgs_len != 0 {
+            // if the symbol can't be found, there's no need for confirmation
+            let can_execute = match parse_args(gcx.clone(), args).await {
+                Ok((symbol, path, _)) => resolve_symbol(gcx.clone(), &symbol, &path).await.is_ok(),
+                Err(_) => false,
+            };
+            if !can_execute {
+                return Ok(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::PASS,
+                    command: "rename_symbol".to_string(),
+                    rule: "".to_string(),
+                });
+            }
+        }
+        Ok(MatchConfirmDeny {
+            result: MatchConfirmDenyResult::CONFIRMATION,
+            command: "rename_symbol".to_string(),
+            rule: "default".to_string(),
+        })
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        _args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        Ok("rename_symbol".to_string())
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(IntegrationConfirmation {
+            ask_user: vec!["rename_symbol*".to_string()],
+            deny: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_at_spots_changes_only_the_usage() {
+        let text = "fn age() {}\nlet x = age() + page + age_days + other.age();\nage(); age();\n";
+        let re = Regex::new(r"\bage\b").unwrap();
+        // the definition, the first call on line 1 and the second call on line 2, `other.age()` is a different method
+        let spots: BTreeSet<NameSpot> = [(0, 0, usize::MAX), (1, 8, 11), (2, 7, 10)].into_iter().collect();
+        let (result, not_found) = rename_at_spots(text, &spots, &re, "years");
+        assert_eq!(result, "fn years() {}\nlet x = years() + page + age_days + other.age();\nage(); years();\n");
+        assert!(not_found.is_empty());
+
+        let spots: BTreeSet<NameSpot> = [(1, 0, 4)].into_iter().collect();
+        let (result, not_found) = rename_at_spots("age\npage\n", &spots, &re, "years");
+        assert_eq!(result, "age\npage\n");
+        assert_eq!(not_found, vec![(1, 0, 4)]);
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("new_name2"));
+        assert!(is_identifier("_x"));
+        assert!(!is_identifier("2x"));
+        assert!(!is_identifier("a-b"));
+        assert!(!is_identifier(""));
+    }
+}
@@ -0,0 +1,290 @@
+use crate::ast::ast_structs::AstDefinition;
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, DiffChunk};
+use crate::integrations::integr_abstract::IntegrationConfirmation;
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel, PrivacySettings};
+use crate::tools::file_edit::auxiliary::{
+    await_ast_indexing, convert_edit_to_diffchunks, normalize_line_endings, restore_line_endings, sync_documents_ast, write_file,
+};
+use crate::tools::file_edit::syntax_check::syntax_check_enabled;
+use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex as AMutex;
+use crate::files_correction::{canonicalize_normalized_path, get_project_dirs, preprocess_path_for_normalization, shortify_paths};
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use tokio::sync::RwLock as ARwLock;
+use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_or_a_good_error};
+use crate::global_context::GlobalContext;
+
+struct ToolSymbolEditArgs {
+    symbol: String,
+    path: Option<PathBuf>,
+    content: String,
+}
+
+/// replace_symbol and insert_after_symbol, the only difference is where the content goes
+pub struct ToolSymbolEdit {
+    pub insert_after: bool,
+}
+
+impl ToolSymbolEdit {
+    fn tool_name(&self) -> &'static str {
+        if self.insert_after { "insert_after_symbol" } else { "replace_symbol" }
+    }
+}
+
+async fn parse_args(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    privacy_settings: Arc<PrivacySettings>
+) -> Result<ToolSymbolEditArgs, String> {
+    let symbol = match args.get("symbol") {
+        Some(Value::String(s)) if !s.trim().is_empty() => s.trim().replace('.', "::"),
+        Some(v) => return Err(format!("Error: The 'symbol' argument must be a non-empty string like 'MyClass::method', but received: {:?}", v)),
+        None => return Err("Error: The 'symbol' argument is required but was not provided.".to_string()),
+    };
+    let path = match args.get("path") {
+        Some(Value::String(s)) if !s.trim().is_empty() => {
+            let candidates_file = file_repair_candidates(gcx.clone(), &s, 3, false).await;
+            let path = return_one_candidate_or_a_good_error(gcx.clone(), &s, &candidates_file, &get_project_dirs(gcx.clone()).await, false).await?;
+            let path = canonicalize_normalized_path(PathBuf::from(preprocess_path_for_normalization(path.trim().to_string())));
+            if check_file_privacy(privacy_settings, &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+                return Err(format!("Error: Cannot update the file '{:?}' due to privacy settings.", s.trim()));
+            }
+            Some(path)
+        }
+        Some(Value::String(_)) | None => None,
+        Some(v) => return Err(format!("Error: The 'path' argument must be a string, but received: {:?}", v)),
+    };
+    let content = match args.get("content") {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => return Err(format!("Error: The 'content' argument must be a string with the new code, but received: {:?}", v)),
+        None => return Err("Error: The 'content' argument is required. Please provide the new code of the symbol.".to_string()),
+    };
+    Ok(ToolSymbolEditArgs { symbol, path, content })
+}
+
+pub(crate) async fn resolve_symbol(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    symbol: &str,
+    path: &Option<PathBuf>,
+) -> Result<Arc<AstDefinition>, String> {
+    let ast_service = gcx.read().await.ast_service.clone()
+        .ok_or("Error: the AST index is turned off, use update_textdoc() instead".to_string())?;
+    let ast_index = ast_service.lock().await.ast_index.clone();
+    let mut defs = crate::ast::ast_db::definitions(ast_index.clone(), symbol).await;
+    if let Some(path) = path {
+        let path_str = path.to_string_lossy().to_string();
+        defs.retain(|d| d.cpath == path_str);
+    }
+    match defs.len() {
+        0 => {
+            let mut msg = crate::tools::tool_ast_definition::there_are_definitions_with_similar_names_though(ast_index, symbol).await;
+            if let Some(path) = path {
+                msg = format!("Looked only in {:?}. {}", path, msg);
+            }
+            Err(format!("Error: {}", msg.trim_end()))
+        }
+        1 => Ok(defs.remove(0)),
+        _ => {
+            let short_paths = shortify_paths(gcx.clone(), &defs.iter().map(|d| d.cpath.clone()).collect()).await;
+            let mut msg = format!("Error: `{}` is ambiguous, pass a more specific symbol or the 'path' argument:\n", symbol);
+            for (d, short_path) in defs.iter().zip(short_paths.iter()) {
+                msg.push_str(&format!("{} defined at {}:{}-{}\n", d.path_drop0(), short_path, d.full_line1(), d.full_line2()));
+            }
+            Err(msg)
+        }
+    }
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// The model often writes a method without its indentation, shift it to where the symbol is
+fn reindent(content: &str, indent: &str) -> String {
+    let already_indented = content.lines()
+        .filter(|l| !l.trim().is_empty())
+        .all(|l| !indentation(l).is_empty());
+    let content_starts_at_zero = content.lines()
+        .find(|l| !l.trim().is_empty())
+        .is_some_and(|l| indentation(l).is_empty());
+    if indent.is_empty() || already_indented || !content_starts_at_zero {
+        return content.to_string();
+    }
+    content.lines()
+        .map(|l| if l.trim().is_empty() { "".to_string() } else { format!("{}{}", indent, l) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lines are 1-based and inclusive, the text must have \n line endings
+fn splice_symbol(text: &str, line1: usize, line2: usize, content: &str, insert_after: bool) -> Result<String, String> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    if line1 == 0 || line1 > line2 || line2 > lines.len() {
+        return Err(format!("Error: the symbol is at lines {}-{}, but the file has {} lines, the AST index is out of date", line1, line2, lines.len()));
+    }
+    let new_code = reindent(content.trim_end_matches('\n'), indentation(lines[line1 - 1]));
+    let mut result = String::new();
+    for line in lines[..line1 - 1].iter() {
+        result.push_str(line);
+    }
+    if insert_after {
+        for line in lines[line1 - 1..line2].iter() {
+            result.push_str(line);
+        }
+        if !result.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push('\n');
+    }
+    result.push_str(&new_code);
+    if line2 < lines.len() || lines[line2 - 1].ends_with('\n') {
+        result.push('\n');
+    }
+    for line in lines[line2..].iter() {
+        result.push_str(line);
+    }
+    Ok(result)
+}
+
+pub async fn tool_symbol_edit_exec(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    args: &HashMap<String, Value>,
+    insert_after: bool,
+    dry: bool
+) -> Result<(String, String, Vec<DiffChunk>), String> {
+    let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+    let args = parse_args(gcx.clone(), args, privacy_settings.clone()).await?;
+    await_ast_indexing(gcx.clone()).await?;
+    let def = resolve_symbol(gcx.clone(), &args.symbol, &args.path).await?;
+    let path = PathBuf::from(&def.cpath);
+    if check_file_privacy(privacy_settings, &path, &FilePrivacyLevel::AllowToSendAnywhere).is_err() {
+        return Err(format!("Error: Cannot update the file '{:?}' due to privacy settings.", path));
+    }
+
+    let file_text = get_file_text_from_memory_or_disk(gcx.clone(), &path).await?;
+    let has_crlf = file_text.contains("\r\n");
+    let normalized_text = normalize_line_endings(&file_text);
+    let (line1, line2) = (def.full_line1(), def.full_line2());
+    if !normalized_text.lines().skip(line1 - 1).take(line2 - line1 + 1).any(|l| l.contains(&def.name())) {
+        return Err(format!(
+            "Error: `{}` is not found at lines {}-{} of {:?}, the AST index is out of date, try again in a few seconds",
+            def.path_drop0(), line1, line2, path
+        ));
+    }
+    let new_text = restore_line_endings(&splice_symbol(&normalized_text, line1, line2, &normalize_line_endings(&args.content), insert_after)?, has_crlf);
+
+    let check_syntax = syntax_check_enabled(gcx.clone(), ToolSymbolEdit { insert_after }.tool_name()).await;
+    let (before_text, after_text) = write_file(gcx.clone(), &path, &new_text, check_syntax, dry).await?;
+    if !dry {
+        sync_documents_ast(gcx.clone(), &path).await?;
+    }
+    let diff_chunks = convert_edit_to_diffchunks(path.clone(), &before_text, &after_text)?;
+    Ok((before_text, after_text, diff_chunks))
+}
+
+#[async_trait]
+impl Tool for ToolSymbolEdit {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let (_, _, diff_chunks) = tool_symbol_edit_exec(gcx.clone(), args, self.insert_after, false).await?;
+        let results = vec![ChatMessage {
+            role: "diff".to_string(),
+            content: ChatContent::SimpleText(json!(diff_chunks).to_string()),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            usage: None,
+            ..Default::default()
+        }]
+        .into_iter()
+        .map(|x| ContextEnum::ChatMessage(x))
+        .collect::<Vec<_>>();
+        Ok((false, results))
+    }
+
+    fn tool_depends_on(&self) -> Vec<String> {
+        vec!["ast".to_string()]
+    }
+
+    async fn match_against_confirm_deny(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        args: &HashMap<String, Value>,
+    ) -> Result<MatchConfirmDeny, String> {
+        let gcx = ccx.lock().await.global_context.clone();
+        let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
+
+        let msgs_len = ccx.lock().await.messages.len();
+
+        // workaround: if messages weren't passed by ToolsPermissionCheckPost, legacy
+        if msgs_len != 0 {
+            // if the symbol can't be found, there's no need for confirmation
+            let can_execute = match parse_args(gcx.clone(), args, privacy_settings).await {
+                Ok(args) => resolve_symbol(gcx.clone(), &args.symbol, &args.path).await.is_ok(),
+                Err(_) => false,
+            };
+            if !can_execute {
+                return Ok(MatchConfirmDeny {
+                    result: MatchConfirmDenyResult::PASS,
+                    command: self.tool_name().to_string(),
+                    rule: "".to_string(),
+                });
+            }
+        }
+        Ok(MatchConfirmDeny {
+            result: MatchConfirmDenyResult::CONFIRMATION,
+            command: self.tool_name().to_string(),
+            rule: "default".to_string(),
+        })
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        _args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        Ok(self.tool_name().to_string())
+    }
+
+    fn confirm_deny_rules(&self) -> Option<IntegrationConfirmation> {
+        Some(IntegrationConfirmation {
+            ask_user: vec![format!("{}*", self.tool_name())],
+            deny: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "class A:\n    def f(self):\n        return 1\n\n    def g(self):\n        return 2\n";
+
+    #[test]
+    fn test_replace_symbol_reindents_content() {
+        let result = splice_symbol(CODE, 2, 3, "def f(self):\n    return 42\n", false).unwrap();
+        assert_eq!(result, "class A:\n    def f(self):\n        return 42\n\n    def g(self):\n        return 2\n");
+        let result = splice_symbol(CODE, 5, 6, "    def g(self):\n        return 3", false).unwrap();
+        assert_eq!(result, "class A:\n    def f(self):\n        return 1\n\n    def g(self):\n        return 3\n");
+    }
+
+    #[test]
+    fn test_insert_after_symbol() {
+        let result = splice_symbol(CODE, 2, 3, "def h(self):\n    return 0", true).unwrap();
+        assert_eq!(result, "class A:\n    def f(self):\n        return 1\n\n    def h(self):\n        return 0\n\n    def g(self):\n        return 2\n");
+        assert!(splice_symbol(CODE, 5, 9, "x = 1", true).is_err());
+    }
+}
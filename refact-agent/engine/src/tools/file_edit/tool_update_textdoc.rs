@@ -3,6 +3,7 @@ use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, DiffChunk};
 use crate::integrations::integr_abstract::IntegrationConfirmation;
 use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel, PrivacySettings};
 use crate::tools::file_edit::auxiliary::{await_ast_indexing, convert_edit_to_diffchunks, str_replace, sync_documents_ast};
+use crate::tools::file_edit::syntax_check::syntax_check_enabled;
 use crate::tools::tools_description::{MatchConfirmDeny, MatchConfirmDenyResult, Tool};
 use async_trait::async_trait;
 use serde_json::{json, Value};
@@ -85,7 +86,8 @@ pub async fn tool_update_text_doc_exec(
     let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
     let args = parse_args(gcx.clone(), args, privacy_settings).await?;
     await_ast_indexing(gcx.clone()).await?;
-    let (before_text, after_text) = str_replace(gcx.clone(), &args.path, &args.old_str, &args.replacement, args.multiple, dry).await?;
+    let check_syntax = syntax_check_enabled(gcx.clone(), "update_textdoc").await;
+    let (before_text, after_text) = str_replace(gcx.clone(), &args.path, &args.old_str, &args.replacement, args.multiple, check_syntax, dry).await?;
     sync_documents_ast(gcx.clone(), &args.path).await?;
     let diff_chunks = convert_edit_to_diffchunks(args.path.clone(), &before_text, &after_text)?;
     Ok((before_text, after_text, diff_chunks))
@@ -1,9 +1,13 @@
 pub mod tools_description;
 pub mod tools_execute;
+pub mod tools_audit;
+pub mod tools_limits;
+pub mod tools_policy;
 pub mod scope_utils;
 
 mod tool_ast_definition;
 mod tool_ast_reference;
+mod tool_ast_call_graph;
 mod tool_web;
 mod tool_tree;
 mod tool_relevant_files;
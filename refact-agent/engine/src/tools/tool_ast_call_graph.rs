@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::Mutex as AMutex;
+
+use crate::ast::ast_call_graph::{call_graph_for_query, CallGraphDirection};
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::tools::tools_description::Tool;
+use crate::call_validation::{ChatMessage, ChatContent, ContextEnum};
+
+pub struct ToolAstCallGraph;
+
+const DEFAULT_DEPTH: usize = 2;
+const MAX_DEPTH: usize = 5;
+
+#[async_trait]
+impl Tool for ToolAstCallGraph {
+    fn as_any(&self) -> &dyn std::any::Any { self }
+
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let symbol = match args.get("symbol") {
+            Some(Value::String(s)) => s.trim().to_string(),
+            Some(v) => return Err(format!("argument `symbol` is not a string: {:?}", v)),
+            None => "".to_string(),
+        };
+        let file_name = match args.get("file") {
+            Some(Value::String(s)) => s.trim().to_string(),
+            Some(v) => return Err(format!("argument `file` is not a string: {:?}", v)),
+            None => "".to_string(),
+        };
+        if symbol.is_empty() == file_name.is_empty() {
+            return Err("set either `symbol` or `file`, but not both".to_string());
+        }
+        let depth = match args.get("depth") {
+            Some(Value::Number(n)) => n.as_u64().map(|v| v as usize).unwrap_or(DEFAULT_DEPTH),
+            Some(Value::String(s)) => s.trim().parse::<usize>().map_err(|_| format!("argument `depth` is not a number: {:?}", s))?,
+            _ => DEFAULT_DEPTH,
+        }.clamp(1, MAX_DEPTH);
+        let direction = match args.get("direction") {
+            Some(Value::String(s)) if !s.trim().is_empty() => serde_json::from_value::<CallGraphDirection>(Value::String(s.trim().to_lowercase()))
+                .map_err(|_| format!("argument `direction` should be 'callers', 'callees' or 'both', got {:?}", s))?,
+            _ => CallGraphDirection::Both,
+        };
+
+        let gcx = ccx.lock().await.global_context.clone();
+        let graph = call_graph_for_query(gcx.clone(), &symbol, &file_name, depth, direction).await?;
+        let title = if file_name.is_empty() {
+            format!("Call graph for `{}`, depth {}: ", symbol, depth)
+        } else {
+            format!("Changing {} affects, depth {}: ", file_name, depth)
+        };
+
+        Ok((false, vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(format!("{}{}", title, graph.to_text())),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })]))
+    }
+
+    fn tool_depends_on(&self) -> Vec<String> {
+        vec!["ast".to_string()]
+    }
+}
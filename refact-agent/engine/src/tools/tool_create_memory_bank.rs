@@ -381,6 +381,8 @@ impl Tool for ToolCreateMemoryBank {
                 ccx_lock.should_execute_remotely,
             ).await;
             ctx.subchat_tx = ccx_lock.subchat_tx.clone();
+            ctx.chat_mode = ccx_lock.chat_mode;
+            ctx.autonomous = ccx_lock.autonomous;
             ctx.subchat_rx = ccx_lock.subchat_rx.clone();
             Arc::new(AMutex::new(ctx))
         };
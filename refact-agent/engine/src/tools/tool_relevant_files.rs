@@ -94,6 +94,8 @@ impl Tool for ToolRelevantFiles {
                 ccx_lock.should_execute_remotely,
             ).await;
             t.subchat_tx = ccx_lock.subchat_tx.clone();
+            t.chat_mode = ccx_lock.chat_mode;
+            t.autonomous = ccx_lock.autonomous;
             t.subchat_rx = ccx_lock.subchat_rx.clone();
             Arc::new(AMutex::new(t))
         };
@@ -12,6 +12,7 @@ use crate::at_commands::at_search::execute_at_search;
 use crate::tools::scope_utils::create_scope_filter;
 use crate::tools::tools_description::Tool;
 use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile};
+use crate::vecdb::vdb_structs::SearchMode;
 
 
 pub struct ToolSearch;
@@ -20,6 +21,7 @@ async fn execute_att_search(
     ccx: Arc<AMutex<AtCommandsContext>>,
     query: &String,
     scope: &String,
+    mode: SearchMode,
 ) -> Result<Vec<ContextFile>, String> {
     let gcx = ccx.lock().await.global_context.clone();
     
@@ -27,7 +29,7 @@ async fn execute_att_search(
     let filter = create_scope_filter(gcx.clone(), scope).await?;
 
     info!("att-search: filter: {:?}", filter);
-    execute_at_search(ccx.clone(), &query, filter).await
+    execute_at_search(ccx.clone(), &query, filter, mode).await
 }
 
 #[async_trait]
@@ -50,8 +52,13 @@ impl Tool for ToolSearch {
             Some(v) => return Err(format!("argument `scope` is not a string: {:?}", v)),
             None => return Err("Missing argument `scope` in the search() call.".to_string())
         };
+        let mode = match args.get("mode") {
+            Some(Value::String(s)) => s.parse::<SearchMode>()?,
+            Some(v) => return Err(format!("argument `mode` is not a string: {:?}", v)),
+            None => SearchMode::default(),
+        };
 
-        let vector_of_context_file = execute_att_search(ccx.clone(), &query, &scope).await?;
+        let vector_of_context_file = execute_att_search(ccx.clone(), &query, &scope, mode).await?;
         info!("att-search: vector_of_context_file={:?}", vector_of_context_file);
 
         if vector_of_context_file.is_empty() {
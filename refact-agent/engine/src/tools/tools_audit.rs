@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use chrono::{Local, NaiveDate, TimeZone};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+use tracing::{error, warn};
+
+use crate::call_validation::{ChatMessage, ContextEnum, DiffChunk};
+use crate::global_context::GlobalContext;
+use crate::tools::tools_description::MatchConfirmDenyResult;
+use crate::tools::tools_policy::PolicyDecision;
+
+// Append-only record of every tool call run_tools has seen, one file per day:
+//   cache_dir/audit/20250131.jsonl
+// Nothing in the engine rewrites or deletes these files, rotation is up to whoever collects them.
+
+pub const AUDIT_QUERY_DEFAULT_LIMIT: usize = 1000;
+
+lazy_static! {
+    static ref AUDIT_WRITE_LOCK: AMutex<()> = AMutex::new(());  // lines from concurrent chats don't interleave
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AuditFileChange {
+    pub file_name: String,
+    pub file_action: String,  // edit, rename, add, remove
+    pub lines_added: usize,
+    pub lines_removed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AuditEntry {
+    pub ts: f64,
+    pub chat_id: String,
+    pub tool_call_id: String,
+    pub tool_name: String,
+    pub args: Value,  // a string if the model sent something that isn't json
+    pub decision: String,  // "pass", "confirmation" (the user confirmed before the call got here), "deny", or "" if the call was never checked
+    pub rule: String,
+    pub explanation: String,
+    pub outcome: String,  // "ok", "error", "denied", "skipped"
+    pub duration_seconds: f64,
+    pub result_chars: usize,
+    #[serde(default)]
+    pub files_changed: Vec<AuditFileChange>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub from_ts: Option<f64>,
+    pub to_ts: Option<f64>,
+    pub tool: Option<String>,
+    pub chat_id: Option<String>,
+    pub limit: Option<usize>,  // the most recent entries are kept, AUDIT_QUERY_DEFAULT_LIMIT if not set
+}
+
+impl AuditEntry {
+    pub fn new(chat_id: &str, tool_call_id: &str, tool_name: &str, arguments: &str) -> Self {
+        AuditEntry {
+            ts: chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
+            chat_id: chat_id.to_string(),
+            tool_call_id: tool_call_id.to_string(),
+            tool_name: tool_name.to_string(),
+            args: serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string())),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_decision(&mut self, decision: &PolicyDecision) {
+        self.decision = match decision.result {
+            MatchConfirmDenyResult::PASS => "pass",
+            MatchConfirmDenyResult::CONFIRMATION => "confirmation",
+            MatchConfirmDenyResult::DENY => "deny",
+        }.to_string();
+        self.rule = decision.rule.clone();
+        self.explanation = decision.explanation.clone();
+    }
+
+    /// Sizes and diff summaries from whatever the tool returned, call before postprocessing
+    pub fn add_results(&mut self, results: &[ContextEnum]) {
+        for result in results {
+            match result {
+                ContextEnum::ChatMessage(m) => self.add_message(m),
+                ContextEnum::ContextFile(f) => self.result_chars += f.file_content.len(),
+            }
+        }
+    }
+
+    pub fn add_message(&mut self, m: &ChatMessage) {
+        let text = m.content.content_text_only();
+        self.result_chars += text.len();
+        if m.role == "diff" {
+            match serde_json::from_str::<Vec<DiffChunk>>(&text) {
+                Ok(chunks) => self.files_changed.extend(diff_summary(&chunks)),
+                Err(e) => warn!("audit: cannot parse diff from {}: {}", self.tool_name, e),
+            }
+        }
+    }
+}
+
+pub fn diff_summary(chunks: &[DiffChunk]) -> Vec<AuditFileChange> {
+    let mut changes: Vec<AuditFileChange> = vec![];
+    let mut index_by_file: HashMap<(String, String), usize> = HashMap::new();
+    for chunk in chunks {
+        let file_name = match &chunk.file_name_rename {
+            Some(new_name) if chunk.file_action == "rename" => format!("{} -> {}", chunk.file_name, new_name),
+            _ => chunk.file_name.clone(),
+        };
+        let idx = *index_by_file.entry((file_name.clone(), chunk.file_action.clone())).or_insert_with(|| {
+            changes.push(AuditFileChange { file_name, file_action: chunk.file_action.clone(), ..Default::default() });
+            changes.len() - 1
+        });
+        changes[idx].lines_added += chunk.lines_add.lines().count();
+        changes[idx].lines_removed += chunk.lines_remove.lines().count();
+    }
+    changes
+}
+
+fn audit_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("audit")
+}
+
+pub async fn audit_log_append(gcx: Arc<ARwLock<GlobalContext>>, entries: &[AuditEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+    let cache_dir = gcx.read().await.cache_dir.clone();
+    let dir = audit_dir(&cache_dir);
+    let file_name = dir.join(format!("{}.jsonl", Local::now().format("%Y%m%d")));
+    let mut lines = String::new();
+    for entry in entries {
+        match serde_json::to_string(entry) {
+            Ok(line) => { lines.push_str(&line); lines.push('\n'); }
+            Err(e) => error!("audit: cannot serialize entry for {}: {}", entry.tool_name, e),
+        }
+    }
+    let _write_locked = AUDIT_WRITE_LOCK.lock().await;
+    let result = async {
+        tokio::fs::create_dir_all(&dir).await.map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        let mut f = tokio::fs::OpenOptions::new().create(true).append(true).open(&file_name).await
+            .map_err(|e| format!("cannot open {}: {}", file_name.display(), e))?;
+        f.write_all(lines.as_bytes()).await
+            .map_err(|e| format!("cannot write {}: {}", file_name.display(), e))?;
+        f.flush().await.map_err(|e| format!("cannot flush {}: {}", file_name.display(), e))
+    }.await;
+    if let Err(e) = result {
+        error!("audit: {}", e);
+    }
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.from_ts.map_or(true, |from_ts| entry.ts >= from_ts)
+            && self.to_ts.map_or(true, |to_ts| entry.ts <= to_ts)
+            && self.tool.as_ref().map_or(true, |tool| &entry.tool_name == tool)
+            && self.chat_id.as_ref().map_or(true, |chat_id| &entry.chat_id == chat_id)
+    }
+
+    /// Day files can't contain entries outside of the range, give or take a day for timezone changes
+    fn may_contain(&self, day: NaiveDate) -> bool {
+        let Some(day_start) = day.and_hms_opt(0, 0, 0).and_then(|t| Local.from_local_datetime(&t).earliest()) else {
+            return true;
+        };
+        let day_start = day_start.timestamp() as f64;
+        let slack = 2.0 * 24.0 * 3600.0;
+        self.from_ts.map_or(true, |from_ts| day_start + slack >= from_ts)
+            && self.to_ts.map_or(true, |to_ts| day_start - slack <= to_ts)
+    }
+}
+
+/// Oldest first, at most `limit` most recent entries that match
+pub async fn audit_log_query(gcx: Arc<ARwLock<GlobalContext>>, query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+    let cache_dir = gcx.read().await.cache_dir.clone();
+    let dir = audit_dir(&cache_dir);
+    let mut day_files = vec![];
+    let mut dir_entries = match tokio::fs::read_dir(&dir).await {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),  // nothing recorded yet
+    };
+    while let Some(dir_entry) = dir_entries.next_entry().await.map_err(|e| format!("cannot list {}: {}", dir.display(), e))? {
+        let path = dir_entry.path();
+        let Some(day) = path.file_stem()
+            .and_then(|s| NaiveDate::parse_from_str(&s.to_string_lossy(), "%Y%m%d").ok()) else { continue };
+        if query.may_contain(day) {
+            day_files.push((day, path));
+        }
+    }
+    day_files.sort();
+
+    let limit = query.limit.unwrap_or(AUDIT_QUERY_DEFAULT_LIMIT);
+    let mut result: Vec<AuditEntry> = vec![];
+    for (_, path) in day_files {
+        let content = tokio::fs::read_to_string(&path).await
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<AuditEntry>(line) {
+                Ok(entry) if query.matches(&entry) => result.push(entry),
+                Ok(_) => {}
+                Err(e) => warn!("audit: skipping a bad line in {}: {}", path.display(), e),
+            }
+        }
+    }
+    result.sort_by(|a, b| a.ts.total_cmp(&b.ts));
+    if result.len() > limit {
+        result.drain(..result.len() - limit);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(file_name: &str, file_action: &str, lines_remove: &str, lines_add: &str) -> DiffChunk {
+        DiffChunk {
+            file_name: file_name.to_string(),
+            file_action: file_action.to_string(),
+            line1: 1,
+            line2: 1,
+            lines_remove: lines_remove.to_string(),
+            lines_add: lines_add.to_string(),
+            file_name_rename: None,
+            is_file: true,
+            application_details: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_audit_diff_summary_and_query() {
+        let chunks = vec![
+            chunk("/p/a.rs", "edit", "old1\nold2\n", "new1\n"),
+            chunk("/p/a.rs", "edit", "", "new2\nnew3\n"),
+            chunk("/p/b.rs", "remove", "x\ny\nz\n", ""),
+        ];
+        let summary = diff_summary(&chunks);
+        assert_eq!(summary.len(), 2);
+        assert_eq!((summary[0].lines_added, summary[0].lines_removed), (3, 2));
+        assert_eq!((summary[1].file_action.as_str(), summary[1].lines_removed), ("remove", 3));
+
+        let mut entry = AuditEntry::new("chat1", "call1", "update_textdoc", "{\"path\": \"/p/a.rs\"}");
+        entry.ts = 1000.0;
+        assert_eq!(entry.args["path"], "/p/a.rs");
+        assert_eq!(AuditEntry::new("chat1", "call1", "cat", "not json").args, Value::String("not json".to_string()));
+
+        let query = AuditQuery { from_ts: Some(500.0), to_ts: Some(1500.0), tool: Some("update_textdoc".to_string()), ..Default::default() };
+        assert!(query.matches(&entry));
+        assert!(!AuditQuery { chat_id: Some("chat2".to_string()), ..Default::default() }.matches(&entry));
+        assert!(!AuditQuery { from_ts: Some(1001.0), ..Default::default() }.matches(&entry));
+        assert!(!AuditQuery { tool: Some("cat".to_string()), ..Default::default() }.matches(&entry));
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use indexmap::IndexMap;
 use serde_json::{Value, json};
 use serde::{Deserialize, Serialize};
@@ -98,6 +99,9 @@ pub trait Tool: Send + Sync {
 
     fn tool_depends_on(&self) -> Vec<String> { vec![] }   // "ast", "vecdb"
 
+    /// Integrations that enforce a configured timeout themselves, run_tools never stops them sooner than that
+    fn own_timeout(&self) -> Option<Duration> { None }
+
     fn usage(&mut self) -> &mut Option<ChatUsage> {
         static mut DEFAULT_USAGE: Option<ChatUsage> = None;
         #[allow(static_mut_refs)]
@@ -129,10 +133,15 @@ pub async fn tools_merged_and_filtered(
     let mut tools_all = IndexMap::from([
         ("definition".to_string(), Box::new(crate::tools::tool_ast_definition::ToolAstDefinition{}) as Box<dyn Tool + Send>),
         ("references".to_string(), Box::new(crate::tools::tool_ast_reference::ToolAstReference{}) as Box<dyn Tool + Send>),
+        ("call_graph".to_string(), Box::new(crate::tools::tool_ast_call_graph::ToolAstCallGraph{}) as Box<dyn Tool + Send>),
         ("tree".to_string(), Box::new(crate::tools::tool_tree::ToolTree{}) as Box<dyn Tool + Send>),
         ("create_textdoc".to_string(), Box::new(crate::tools::file_edit::tool_create_textdoc::ToolCreateTextDoc{}) as Box<dyn Tool + Send>),
         ("update_textdoc".to_string(), Box::new(crate::tools::file_edit::tool_update_textdoc::ToolUpdateTextDoc {}) as Box<dyn Tool + Send>),
         ("update_textdoc_regex".to_string(), Box::new(crate::tools::file_edit::tool_update_textdoc_regex::ToolUpdateTextDocRegex {}) as Box<dyn Tool + Send>),
+        ("apply_patch".to_string(), Box::new(crate::tools::file_edit::tool_apply_patch::ToolApplyPatch {}) as Box<dyn Tool + Send>),
+        ("replace_symbol".to_string(), Box::new(crate::tools::file_edit::tool_symbol_edit::ToolSymbolEdit { insert_after: false }) as Box<dyn Tool + Send>),
+        ("insert_after_symbol".to_string(), Box::new(crate::tools::file_edit::tool_symbol_edit::ToolSymbolEdit { insert_after: true }) as Box<dyn Tool + Send>),
+        ("rename_symbol".to_string(), Box::new(crate::tools::file_edit::tool_rename_symbol::ToolRenameSymbol {}) as Box<dyn Tool + Send>),
         ("web".to_string(), Box::new(crate::tools::tool_web::ToolWeb{}) as Box<dyn Tool + Send>),
         ("cat".to_string(), Box::new(crate::tools::tool_cat::ToolCat{}) as Box<dyn Tool + Send>),
         ("rm".to_string(), Box::new(crate::tools::tool_rm::ToolRm{}) as Box<dyn Tool + Send>),
@@ -177,7 +186,7 @@ pub async fn tools_merged_and_filtered(
 const BUILT_IN_TOOLS: &str = r####"
 tools:
   - name: "search"
-    description: "Find semantically similar pieces of code or text using vector database (semantic search)"
+    description: "Find semantically similar pieces of code or text using vector database (semantic search), combined with keyword search for exact identifiers, error codes and config keys"
     parameters:
       - name: "query"
         type: "string"
@@ -185,6 +194,9 @@ tools:
       - name: "scope"
         type: "string"
         description: "'workspace' to search all files in workspace, 'dir/subdir/' to search in files within a directory, 'dir/file.ext' to search in a single file."
+      - name: "mode"
+        type: "string"
+        description: "Optional. 'hybrid' (default) combines both, 'semantic' for meaning only, 'lexical' for exact words like identifiers, error codes, config keys."
     parameters_required:
       - "query"
       - "scope"
@@ -207,6 +219,23 @@ tools:
     parameters_required:
       - "symbol"
 
+  - name: "call_graph"
+    description: "Find callers and callees of a symbol using AST, or what is affected if a file changes: symbols that use it directly or indirectly and derived classes. Useful before refactoring and to pick tests to run."
+    parameters:
+      - name: "symbol"
+        type: "string"
+        description: "The exact name of a function, method or class, leave empty if `file` is set."
+      - name: "file"
+        type: "string"
+        description: "Path to a file to find everything affected by changing it, leave empty if `symbol` is set."
+      - name: "depth"
+        type: "number"
+        description: "How many levels of calls to follow, 2 by default, 5 at most."
+      - name: "direction"
+        type: "string"
+        description: "For a symbol: 'callers', 'callees' or 'both' (default)."
+    parameters_required: []
+
   - name: "tree"
     description: "Get a files tree with symbols for the project. Use it to get familiar with the project, file names and symbols"
     parameters:
@@ -331,6 +360,67 @@ tools:
       - "replacement"
       - "multiple"
 
+  - name: "apply_patch"
+    agentic: false
+    description: "Applies a unified diff that can change several files at once, use it for edits spread over many places or files. Line numbers in @@ headers may be approximate, but the context lines must match the files. Nothing is changed if any hunk doesn't apply."
+    parameters:
+      - name: "patch"
+        type: "string"
+        description: "Unified diff with '--- a/path' and '+++ b/path' headers for every file, '--- /dev/null' creates a file, '+++ /dev/null' deletes it. Paths are absolute or relative to the project."
+    parameters_required:
+      - "patch"
+
+  - name: "replace_symbol"
+    agentic: false
+    description: "Replaces the whole definition of a function, method, class or other symbol found using AST, no need to reproduce the old code. Avoid trailing spaces and tabs."
+    parameters:
+      - name: "symbol"
+        type: "string"
+        description: "Symbol path like 'MyClass::method' or 'my_function', the same as for definition()."
+      - name: "content"
+        type: "string"
+        description: "The new code of the whole definition, including its signature, decorators and comments that should stay."
+      - name: "path"
+        type: "string"
+        description: "Optional. The file where the symbol is defined, only needed if the symbol is ambiguous."
+    parameters_required:
+      - "symbol"
+      - "content"
+
+  - name: "insert_after_symbol"
+    agentic: false
+    description: "Inserts new code right after the definition of a function, method, class or other symbol found using AST, at the same indentation. Avoid trailing spaces and tabs."
+    parameters:
+      - name: "symbol"
+        type: "string"
+        description: "Symbol path like 'MyClass::method' or 'my_function', the same as for definition()."
+      - name: "content"
+        type: "string"
+        description: "The code to insert, for example a new method."
+      - name: "path"
+        type: "string"
+        description: "Optional. The file where the symbol is defined, only needed if the symbol is ambiguous."
+    parameters_required:
+      - "symbol"
+      - "content"
+
+  - name: "rename_symbol"
+    agentic: false
+    description: "Renames a function, method, class or other symbol at its definition and at all its usages found using AST, in all files at once. Usages the AST is not sure about are listed in the result instead of being renamed."
+    parameters:
+      - name: "symbol"
+        type: "string"
+        description: "Symbol path like 'MyClass::method' or 'my_function', the same as for definition()."
+      - name: "new_name"
+        type: "string"
+        description: "The new name, just the identifier without the class or namespace."
+      - name: "path"
+        type: "string"
+        description: "Optional. The file where the symbol is defined, only needed if the symbol is ambiguous."
+    parameters_required:
+      - "symbol"
+      - "new_name"
+
   # -- agentic tools below --
   - name: "locate"
     agentic: true
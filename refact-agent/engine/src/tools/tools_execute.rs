@@ -1,21 +1,26 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use glob::Pattern;
 use indexmap::IndexMap;
 use tokio::sync::Mutex as AMutex;
 use serde_json::{json, Value};
 use tokenizers::Tokenizer;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::at_commands::execute_at::MIN_RAG_CONTEXT_LIMIT;
-use crate::call_validation::{ChatMessage, ChatContent, ContextEnum, ContextFile, SubchatParameters};
+use crate::call_validation::{ChatMessage, ChatContent, ChatMode, ChatToolCall, ContextEnum, ContextFile, SubchatParameters};
 use crate::http::http_post_json;
 use crate::integrations::docker::docker_container_manager::docker_container_get_host_lsp_port_to_connect;
 use crate::postprocessing::pp_context_files::postprocess_context_files;
 use crate::postprocessing::pp_plain_text::postprocess_plain_text;
 use crate::scratchpads::scratchpad_utils::{HasRagResults, max_tokens_for_rag_chat_by_tools};
 use crate::tools::tools_description::{MatchConfirmDenyResult, Tool};
+use crate::tools::tools_audit::{audit_log_append, AuditEntry};
+use crate::tools::tools_policy::check_tool_call;
+use crate::tools::tools_limits::{chat_tool_spend, chat_tool_spent, tool_timeout, ToolBudget};
 use crate::yaml_configs::customization_loader::load_customization;
 use crate::caps::get_model_record;
 use crate::http::routers::v1::at_tools::{ToolExecuteResponse, ToolsExecutePost};
@@ -67,7 +72,7 @@ pub async fn run_tools_remotely(
     stream_back_to_user: &mut HasRagResults,
     style: &Option<String>,
 ) -> Result<(Vec<ChatMessage>, bool), String> {
-    let (n_ctx, subchat_tool_parameters, postprocess_parameters, gcx, chat_id) = {
+    let (n_ctx, subchat_tool_parameters, postprocess_parameters, gcx, chat_id, cancel_token, chat_mode) = {
         let ccx_locked = ccx.lock().await;
         (
             ccx_locked.n_ctx,
@@ -75,6 +80,8 @@ pub async fn run_tools_remotely(
             ccx_locked.postprocess_parameters.clone(),
             ccx_locked.global_context.clone(),
             ccx_locked.chat_id.clone(),
+            ccx_locked.cancel_token.clone(),
+            ccx_locked.chat_mode,
         )
     };
 
@@ -88,12 +95,25 @@ pub async fn run_tools_remotely(
         subchat_tool_parameters,
         postprocess_parameters,
         model_name: model_name.to_string(),
-        chat_id,
+        chat_id: chat_id.clone(),
         style: style.clone(),
+        chat_mode,
     };
 
     let url = format!("http://localhost:{port}/v1/tools-execute");
-    let response: ToolExecuteResponse = http_post_json(&url, &tools_execute_post).await?;
+    let response_future = http_post_json::<_, ToolExecuteResponse>(&url, &tools_execute_post);
+    tokio::pin!(response_future);
+    let response: ToolExecuteResponse = tokio::select! {
+        response = &mut response_future => response?,
+        _ = cancel_token.cancelled() => {
+            // tools inside the container answer with "cancelled" messages, the response still arrives
+            let cancel_url = format!("http://localhost:{port}/v1/chat-cancel");
+            if let Err(e) = http_post_json::<_, Value>(&cancel_url, &json!({"chat_id": chat_id})).await {
+                warn!("run_tools_remotely: failed to cancel tools in the container: {}", e);
+            }
+            response_future.await?
+        }
+    };
     info!("run_tools_remotely: got response: {:?}", response);
 
     let mut all_messages = tools_execute_post.messages;
@@ -147,71 +167,61 @@ pub async fn run_tools(
     if last_msg_tool_calls.is_empty() {
         return Ok((vec![], false));
     }
-    
+
+    let (gcx, chat_id, cancel_token, chat_mode) = {
+        let ccx_locked = ccx.lock().await;
+        (ccx_locked.global_context.clone(), ccx_locked.chat_id.clone(), ccx_locked.cancel_token.clone(), ccx_locked.chat_mode)
+    };
+    let customization = load_customization(gcx.clone(), true, &mut vec![]).await;
+    let (spent_seconds, spent_tokens) = if chat_id.is_empty() {
+        (0.0, 0)
+    } else {
+        chat_tool_spent(gcx.clone(), &chat_id).await
+    };
+    let limits = ToolCallLimits {
+        tool_timeouts: customization.tool_timeouts,
+        budget: customization.tool_budget.unwrap_or_default(),
+        spent_seconds_before: spent_seconds,
+        started: Instant::now(),
+        cancel_token,
+    };
+    if let Some(reason) = limits.budget.exhausted(spent_seconds, spent_tokens) {
+        warn!("run_tools: {}, no tools will run", reason);
+        let budget_messages = last_msg_tool_calls.iter().map(|t_call| tool_answer(
+            format!("tool use: not executed, {}. Finish without tools or ask the user to raise `tool_budget` in the customization.", reason),
+            t_call.id.to_string(),
+        )).collect();
+        let skipped = last_msg_tool_calls.iter().map(|t_call| AuditEntry {
+            outcome: "skipped".to_string(),
+            explanation: reason.clone(),
+            ..AuditEntry::new(&chat_id, &t_call.id, &t_call.function.name, &t_call.function.arguments)
+        }).collect::<Vec<_>>();
+        audit_log_append(gcx.clone(), &skipped).await;
+        return Ok((budget_messages, true));
+    }
+
     let mut context_files_for_pp = vec![];
     let mut generated_tool = vec![];  // tool results must go first
     let mut generated_other = vec![];
     let mut any_corrections = false;
 
+    // Each entry is written as soon as its call is decided or finished, so a crash or a cancelled
+    // chat in the middle of the batch still leaves a record of what already ran
     for t_call in last_msg_tool_calls.iter() {
-        let cmd = match tools.get_mut(&t_call.function.name) {
-            Some(cmd) => cmd,
-            None => {
-                let tool_failed_message = tool_answer(
-                    format!("tool use: function {:?} not found", &t_call.function.name), t_call.id.to_string()
-                );
-                warn!("{}", tool_failed_message.content.content_text_only());
-                generated_tool.push(tool_failed_message.clone());
-                continue;
-            }
-        };
-
-        let args = match serde_json::from_str::<HashMap<String, Value>>(&t_call.function.arguments) {
-            Ok(args) => args,
-            Err(e) => {
-                let tool_failed_message = tool_answer(
-                    format!("Tool use: couldn't parse arguments: {}. Error:\n{}", t_call.function.arguments, e), t_call.id.to_string()
-                );
-                generated_tool.push(tool_failed_message);
-                continue;
-            }
-        };
-        info!("tool use {}({:?})", &t_call.function.name, args);
-
-        match cmd.match_against_confirm_deny(ccx.clone(), &args).await {
-            Ok(res) => {
-                match res.result {
-                    MatchConfirmDenyResult::DENY => {
-                        let command_to_match = cmd
-                            .command_to_match_against_confirm_deny(&args)
-                            .unwrap_or("<error_command>".to_string());
-                        generated_tool.push(tool_answer(format!("tool use: command '{command_to_match}' is denied"), t_call.id.to_string()));
-                        continue;
-                    }
-                    _ => {}
-                }
-            }
-            Err(err) => {
-                generated_tool.push(tool_answer(format!("tool use: {}", err), t_call.id.to_string()));
+        let mut audit_entry = AuditEntry::new(&chat_id, &t_call.id, &t_call.function.name, &t_call.function.arguments);
+        let (corrections, tool_execute_results) = match run_tool_call(ccx.clone(), tools, t_call, chat_mode, &limits, &mut audit_entry).await {
+            Ok(corrections_and_results) => corrections_and_results,
+            Err(msg) => {
+                audit_entry.outcome = if audit_entry.decision == "deny" { "denied" } else { "error" }.to_string();
+                audit_entry.add_message(&msg);
+                audit_log_append(gcx.clone(), &[audit_entry]).await;
+                generated_tool.push(msg);
                 continue;
             }
         };
-
-        let (corrections, tool_execute_results) = {
-            match cmd.tool_execute(ccx.clone(), &t_call.id.to_string(), &args).await {
-                Ok(msg_and_maybe_more) => msg_and_maybe_more,
-                Err(e) => {
-                    warn!("tool use {}({:?}) FAILED: {}", &t_call.function.name, &args, e);
-                    let mut tool_failed_message = tool_answer(e, t_call.id.to_string());
-
-                    tool_failed_message.usage = cmd.usage().clone();
-                    *cmd.usage() = None;
-
-                    generated_tool.push(tool_failed_message.clone());
-                    continue;
-                }
-            }
-        };
+        audit_entry.outcome = "ok".to_string();
+        audit_entry.add_results(&tool_execute_results);
+        audit_log_append(gcx.clone(), &[audit_entry]).await;
 
         any_corrections |= corrections;
 
@@ -234,6 +244,9 @@ pub async fn run_tools(
         }
         assert!(have_answer);
     }
+    if !chat_id.is_empty() {
+        chat_tool_spend(gcx.clone(), &chat_id, limits.started.elapsed().as_secs_f64(), 0).await;
+    }
 
     let reserve_for_context = max_tokens_for_rag_chat_by_tools(
         &last_msg_tool_calls,
@@ -263,11 +276,133 @@ pub async fn run_tools(
     let new_messages = generated_tool.into_iter().chain(generated_other.into_iter())
         .collect::<Vec<_>>();
 
+    if !chat_id.is_empty() {
+        let tokens_produced: usize = new_messages.iter().map(|m| {
+            let subchat_tokens = m.usage.as_ref().map(|u| u.prompt_tokens + u.completion_tokens).unwrap_or(0);
+            m.content.count_tokens(tokenizer.clone(), style).unwrap_or(0).max(0) as usize + subchat_tokens
+        }).sum();
+        chat_tool_spend(gcx.clone(), &chat_id, 0.0, tokens_produced).await;
+    }
+
     ccx.lock().await.pp_skeleton = false;
 
     Ok((new_messages, true))
 }
 
+struct ToolCallLimits {
+    tool_timeouts: IndexMap<String, u64>,
+    budget: ToolBudget,
+    spent_seconds_before: f64,
+    started: Instant,
+    cancel_token: CancellationToken,
+}
+
+impl ToolCallLimits {
+    /// The tool's own timeout, or less if the wall-clock budget of the chat runs out sooner
+    fn deadline(&self, tool_name: &str, own_timeout: Option<Duration>) -> Option<(Duration, String)> {
+        let timeout = tool_timeout(&self.tool_timeouts, tool_name, own_timeout)
+            .map(|d| (d, format!("it didn't finish in {}s", d.as_secs())));
+        let budget_left = self.budget.remaining_seconds(self.spent_seconds_before + self.started.elapsed().as_secs_f64())
+            .map(|secs| (Duration::from_secs_f64(secs), format!("the tool time budget of this chat ({}s) ran out", self.budget.wall_clock_seconds)));
+        match (timeout, budget_left) {
+            (Some(t), Some(b)) => Some(if b.0 < t.0 { b } else { t }),
+            (t, b) => t.or(b),
+        }
+    }
+}
+
+/// Err is the whole answer to the call: the tool is unknown, denied, failed or stopped
+async fn run_tool_call(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    tools: &mut IndexMap<String, Box<dyn Tool+Send>>,
+    t_call: &ChatToolCall,
+    chat_mode: ChatMode,
+    limits: &ToolCallLimits,
+    audit_entry: &mut AuditEntry,
+) -> Result<(bool, Vec<ContextEnum>), ChatMessage> {
+    let cmd = match tools.get_mut(&t_call.function.name) {
+        Some(cmd) => cmd,
+        None => {
+            let tool_failed_message = tool_answer(
+                format!("tool use: function {:?} not found", &t_call.function.name), t_call.id.to_string()
+            );
+            warn!("{}", tool_failed_message.content.content_text_only());
+            return Err(tool_failed_message);
+        }
+    };
+
+    let args = match serde_json::from_str::<HashMap<String, Value>>(&t_call.function.arguments) {
+        Ok(args) => args,
+        Err(e) => {
+            return Err(tool_answer(
+                format!("Tool use: couldn't parse arguments: {}. Error:\n{}", t_call.function.arguments, e), t_call.id.to_string()
+            ));
+        }
+    };
+    info!("tool use {}({:?})", &t_call.function.name, args);
+
+    match check_tool_call(ccx.clone(), &**cmd, &t_call.function.name, &args, chat_mode).await {
+        Ok(decision) => {
+            audit_entry.set_decision(&decision);
+            match decision.result {
+                MatchConfirmDenyResult::DENY => {
+                    info!("tool use {} denied: {}", &t_call.function.name, decision.explanation);
+                    return Err(tool_answer(format!("tool use: command '{}' is denied, {}", decision.command, decision.explanation), t_call.id.to_string()));
+                }
+                MatchConfirmDenyResult::CONFIRMATION if ccx.lock().await.autonomous => {
+                    info!("tool use {} needs confirmation, nobody to ask in an autonomous run: {}", &t_call.function.name, decision.explanation);
+                    audit_entry.decision = "deny".to_string();
+                    return Err(tool_answer(format!("tool use: command '{}' needs a confirmation from the user, and this chat runs autonomously, {}", decision.command, decision.explanation), t_call.id.to_string()));
+                }
+                _ => {}
+            }
+        }
+        Err(err) => {
+            return Err(tool_answer(format!("tool use: {}", err), t_call.id.to_string()));
+        }
+    };
+
+    let started = Instant::now();
+    let result = execute_tool_call(ccx.clone(), cmd, t_call, &args, limits).await;
+    audit_entry.duration_seconds = started.elapsed().as_secs_f64();
+    result
+}
+
+async fn execute_tool_call(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    cmd: &mut Box<dyn Tool + Send>,
+    t_call: &ChatToolCall,
+    args: &HashMap<String, Value>,
+    limits: &ToolCallLimits,
+) -> Result<(bool, Vec<ContextEnum>), ChatMessage> {
+    let deadline = limits.deadline(&t_call.function.name, cmd.own_timeout());
+    let result = {
+        let execution = cmd.tool_execute(ccx, &t_call.id.to_string(), args);
+        let stop_at_deadline = async {
+            match &deadline {
+                Some((duration, _)) => tokio::time::sleep(*duration).await,
+                None => std::future::pending::<()>().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = limits.cancel_token.cancelled() => Err("The tool was cancelled by the user.".to_string()),
+            _ = stop_at_deadline => Err(format!(
+                "The tool was stopped because {}.",
+                deadline.as_ref().map(|(_, reason)| reason.as_str()).unwrap_or_default(),
+            )),
+            result = execution => result,
+        }
+    };
+    result.map_err(|e| {
+        warn!("tool use {}({:?}) FAILED: {}", &t_call.function.name, args, e);
+        let mut tool_failed_message = tool_answer(e, t_call.id.to_string());
+        tool_failed_message.usage = cmd.usage().clone();
+        *cmd.usage() = None;
+        tool_failed_message
+    })
+}
+
 async fn pp_run_tools(
     ccx: Arc<AMutex<AtCommandsContext>>,
     original_messages: &Vec<ChatMessage>,
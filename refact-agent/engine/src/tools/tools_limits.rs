@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock as ARwLock;
+use tokio_util::sync::CancellationToken;
+
+use crate::global_context::GlobalContext;
+
+pub const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 300;
+const FORGET_IDLE_CHATS_AFTER: Duration = Duration::from_secs(24 * 3600);
+const OWN_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+
+/// How much tools are allowed to spend within one chat, zero means no limit
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ToolBudget {
+    pub wall_clock_seconds: u64,
+    pub tokens: usize,  // tool results going back to the model, plus whatever subchats inside tools have used
+}
+
+pub type ChatToolRuns = HashMap<String, ChatToolRun>;
+
+pub struct ChatToolRun {
+    pub cancel_token: CancellationToken,
+    pub spent_seconds: f64,
+    pub spent_tokens: usize,
+    touched: Instant,
+}
+
+impl ChatToolRun {
+    fn new() -> Self {
+        ChatToolRun {
+            cancel_token: CancellationToken::new(),
+            spent_seconds: 0.0,
+            spent_tokens: 0,
+            touched: Instant::now(),
+        }
+    }
+}
+
+/// Timeout from `tool_timeouts` in customization, the "default" key applies to tools that are not listed, 0 turns it off.
+/// A tool with its own configured timeout (cmdline_*, shell) gets at least that plus a little grace, so its own error wins.
+pub fn tool_timeout(tool_timeouts: &IndexMap<String, u64>, tool_name: &str, own_timeout: Option<Duration>) -> Option<Duration> {
+    let secs = tool_timeouts.get(tool_name)
+        .or_else(|| tool_timeouts.get("default"))
+        .cloned()
+        .unwrap_or(DEFAULT_TOOL_TIMEOUT_SECS);
+    (secs > 0).then(|| {
+        let central = Duration::from_secs(secs);
+        own_timeout.map_or(central, |own| central.max(own + OWN_TIMEOUT_GRACE))
+    })
+}
+
+impl ToolBudget {
+    pub fn remaining_seconds(&self, spent_seconds: f64) -> Option<f64> {
+        (self.wall_clock_seconds > 0).then(|| (self.wall_clock_seconds as f64 - spent_seconds).max(0.0))
+    }
+
+    pub fn exhausted(&self, spent_seconds: f64, spent_tokens: usize) -> Option<String> {
+        if self.wall_clock_seconds > 0 && spent_seconds >= self.wall_clock_seconds as f64 {
+            return Some(format!("tools in this chat have already run for {:.0}s, the budget is {}s", spent_seconds, self.wall_clock_seconds));
+        }
+        if self.tokens > 0 && spent_tokens >= self.tokens {
+            return Some(format!("tools in this chat have already produced {} tokens, the budget is {} tokens", spent_tokens, self.tokens));
+        }
+        None
+    }
+}
+
+/// Called when a chat request starts, gives a token that the next `chat_cancel()` for the same chat will trigger
+pub async fn chat_cancel_token(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str) -> CancellationToken {
+    let chat_tool_runs = gcx.read().await.chat_tool_runs.clone();
+    let mut runs_locked = chat_tool_runs.lock().await;
+    runs_locked.retain(|_, run| run.touched.elapsed() < FORGET_IDLE_CHATS_AFTER);
+    let run = runs_locked.entry(chat_id.to_string()).or_insert_with(ChatToolRun::new);
+    if run.cancel_token.is_cancelled() {
+        run.cancel_token = CancellationToken::new();
+    }
+    run.touched = Instant::now();
+    run.cancel_token.clone()
+}
+
+pub async fn chat_cancel(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str) -> bool {
+    let chat_tool_runs = gcx.read().await.chat_tool_runs.clone();
+    let runs_locked = chat_tool_runs.lock().await;
+    match runs_locked.get(chat_id) {
+        Some(run) if !run.cancel_token.is_cancelled() => {
+            run.cancel_token.cancel();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// (spent_seconds, spent_tokens) so far
+pub async fn chat_tool_spent(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str) -> (f64, usize) {
+    let chat_tool_runs = gcx.read().await.chat_tool_runs.clone();
+    let runs_locked = chat_tool_runs.lock().await;
+    runs_locked.get(chat_id).map(|run| (run.spent_seconds, run.spent_tokens)).unwrap_or((0.0, 0))
+}
+
+pub async fn chat_tool_spend(gcx: Arc<ARwLock<GlobalContext>>, chat_id: &str, seconds: f64, tokens: usize) {
+    let chat_tool_runs = gcx.read().await.chat_tool_runs.clone();
+    let mut runs_locked = chat_tool_runs.lock().await;
+    let run = runs_locked.entry(chat_id.to_string()).or_insert_with(ChatToolRun::new);
+    run.spent_seconds += seconds;
+    run.spent_tokens += tokens;
+    run.touched = Instant::now();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_timeout_and_budget() {
+        let timeouts = IndexMap::from([
+            ("default".to_string(), 60),
+            ("deep_analysis".to_string(), 600),
+            ("web".to_string(), 0),
+        ]);
+        assert_eq!(tool_timeout(&timeouts, "cat", None), Some(Duration::from_secs(60)));
+        assert_eq!(tool_timeout(&timeouts, "deep_analysis", None), Some(Duration::from_secs(600)));
+        assert_eq!(tool_timeout(&timeouts, "web", None), None);
+        assert_eq!(tool_timeout(&IndexMap::new(), "cat", None), Some(Duration::from_secs(DEFAULT_TOOL_TIMEOUT_SECS)));
+        // cmdline_build configured with timeout: "1800" isn't cut at the 60s default, a short own timeout doesn't shorten the default
+        assert_eq!(tool_timeout(&timeouts, "cmdline_build", Some(Duration::from_secs(1800))), Some(Duration::from_secs(1805)));
+        assert_eq!(tool_timeout(&timeouts, "cmdline_build", Some(Duration::from_secs(10))), Some(Duration::from_secs(60)));
+        assert_eq!(tool_timeout(&timeouts, "web", Some(Duration::from_secs(10))), None);
+
+        let budget = ToolBudget { wall_clock_seconds: 100, tokens: 1000 };
+        assert!(budget.exhausted(40.0, 999).is_none());
+        assert_eq!(budget.remaining_seconds(40.0), Some(60.0));
+        assert_eq!(budget.remaining_seconds(140.0), Some(0.0));
+        assert!(budget.exhausted(40.0, 1000).unwrap().contains("1000 tokens"));
+        assert!(budget.exhausted(100.0, 0).unwrap().contains("100s"));
+        assert!(ToolBudget::default().exhausted(1e6, 1_000_000).is_none());
+        assert_eq!(ToolBudget::default().remaining_seconds(40.0), None);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use glob::Pattern;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex as AMutex;
+use tokio::sync::RwLock as ARwLock;
+use tokio::time::Duration;
+use tracing::error;
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::ChatMode;
+use crate::files_correction::{canonical_path, get_project_dirs};
+use crate::global_context::GlobalContext;
+use crate::tools::tools_description::{MatchConfirmDenyResult, Tool};
+
+// tool_policy.yaml is a list of rules, the first rule where all the conditions hold decides what happens to a tool call:
+//
+// rules:
+//   - name: "rm only inside the workspace"
+//     tools: ["rm", "mv"]                      -- globs on the tool name
+//     path: { outside_workspace: true }        -- any path argument is outside of the workspace folders
+//     action: deny                             -- allow, ask_user or deny
+//
+// If no rule matches, the tool's own `ask_user` and `deny` lists decide (see integration configs).
+// The `deny` lists of integrations always win, rules can't make a denied command run.
+
+const POLICY_TOO_OLD: Duration = Duration::from_secs(3);
+const DEFAULT_PATH_ARGS: [&str; 6] = ["path", "paths", "file_path", "source", "destination", "workdir"];
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Allow,
+    AskUser,
+    Deny,
+}
+
+/// All the conditions that are set should hold for at least one path argument
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PathCondition {
+    pub outside_workspace: Option<bool>,
+    pub matching: Vec<String>,
+    pub not_matching: Vec<String>,
+    pub args: Vec<String>,  // arguments that hold paths, DEFAULT_PATH_ARGS if empty
+}
+
+/// Empty conditions match anything
+#[derive(Debug, Deserialize, Clone)]
+pub struct PolicyRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub integrations: Vec<String>,  // globs on the integration config name, like "cmdline_*" or "mcp_*"
+    #[serde(default)]
+    pub chat_modes: Vec<ChatMode>,
+    #[serde(default)]
+    pub command: Vec<String>,  // globs on the same command string that `ask_user` and `deny` lists see
+    #[serde(default)]
+    pub args: IndexMap<String, String>,  // argument name => glob on its value
+    #[serde(default)]
+    pub path: Option<PathCondition>,
+    pub action: PolicyAction,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ToolPolicy {
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    #[serde(skip)]
+    pub loaded_ts: u64,
+}
+
+/// What the rules can look at
+pub struct ToolCallFacts<'a> {
+    pub tool_name: &'a str,
+    pub integration: Option<String>,
+    pub chat_mode: ChatMode,
+    pub command: &'a str,
+    pub args: &'a HashMap<String, Value>,
+    pub workspace_dirs: &'a [PathBuf],
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyDecision {
+    pub result: MatchConfirmDenyResult,
+    pub command: String,
+    pub rule: String,
+    pub explanation: String,
+}
+
+fn glob_matches(glob: &str, text: &str) -> bool {
+    match Pattern::new(glob) {
+        Ok(pattern) => pattern.matches(text),
+        Err(e) => {
+            error!("tool_policy.yaml: bad glob {:?}: {}", glob, e);
+            false
+        }
+    }
+}
+
+fn arg_as_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn any_glob_matches(globs: &[String], path: &Path) -> bool {
+    globs.iter().any(|glob| glob_matches(glob, &path.to_string_lossy()))
+}
+
+fn path_args(facts: &ToolCallFacts, arg_names: &[String]) -> Vec<PathBuf> {
+    let default_names: Vec<String> = DEFAULT_PATH_ARGS.iter().map(|s| s.to_string()).collect();
+    let arg_names = if arg_names.is_empty() { &default_names[..] } else { arg_names };
+    let mut paths = vec![];
+    for name in arg_names {
+        let Some(Value::String(value)) = facts.args.get(name) else { continue };
+        for p in value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let p = PathBuf::from(p);
+            let p = match facts.workspace_dirs.first() {
+                Some(workspace) if p.is_relative() => workspace.join(p),
+                _ => p,
+            };
+            paths.push(canonical_path(p.to_string_lossy().to_string()));  // resolves .. even if the file doesn't exist
+        }
+    }
+    paths
+}
+
+impl PathCondition {
+    fn matches(&self, facts: &ToolCallFacts) -> Option<String> {
+        for p in path_args(facts, &self.args) {
+            let outside = !facts.workspace_dirs.iter().any(|dir| p.starts_with(dir));
+            if self.outside_workspace.is_some_and(|want_outside| want_outside != outside) {
+                continue;
+            }
+            if !self.matching.is_empty() && !any_glob_matches(&self.matching, &p) {
+                continue;
+            }
+            if !self.not_matching.is_empty() && any_glob_matches(&self.not_matching, &p) {
+                continue;
+            }
+            let mut reason = format!("path {:?}", p);
+            if let Some(want_outside) = self.outside_workspace {
+                reason.push_str(if want_outside { " is outside the workspace" } else { " is inside the workspace" });
+            }
+            if !self.matching.is_empty() {
+                reason.push_str(&format!(" matches {:?}", self.matching));
+            }
+            if !self.not_matching.is_empty() {
+                reason.push_str(&format!(" doesn't match {:?}", self.not_matching));
+            }
+            return Some(reason);
+        }
+        None
+    }
+}
+
+impl PolicyRule {
+    /// Reasons why the rule matches, None if it doesn't
+    pub fn matches(&self, facts: &ToolCallFacts) -> Option<Vec<String>> {
+        let mut reasons = vec![];
+        if !self.tools.is_empty() {
+            let glob = self.tools.iter().find(|g| glob_matches(g, facts.tool_name))?;
+            reasons.push(format!("tool `{}` matches `{}`", facts.tool_name, glob));
+        }
+        if !self.integrations.is_empty() {
+            let integration = facts.integration.as_ref()?;
+            let glob = self.integrations.iter().find(|g| glob_matches(g, integration))?;
+            reasons.push(format!("integration `{}` matches `{}`", integration, glob));
+        }
+        if !self.chat_modes.is_empty() {
+            if !self.chat_modes.contains(&facts.chat_mode) {
+                return None;
+            }
+            reasons.push(format!("chat mode is {:?}", facts.chat_mode));
+        }
+        if !self.command.is_empty() {
+            let glob = self.command.iter().find(|g| glob_matches(g, facts.command))?;
+            reasons.push(format!("command `{}` matches `{}`", facts.command, glob));
+        }
+        for (arg_name, glob) in self.args.iter() {
+            let value = arg_as_string(facts.args.get(arg_name)?);
+            if !glob_matches(glob, &value) {
+                return None;
+            }
+            reasons.push(format!("argument `{}` matches `{}`", arg_name, glob));
+        }
+        if let Some(path_condition) = &self.path {
+            reasons.push(path_condition.matches(facts)?);
+        }
+        if reasons.is_empty() {
+            reasons.push("the rule has no conditions".to_string());
+        }
+        Some(reasons)
+    }
+}
+
+impl ToolPolicy {
+    /// The first matching rule, with its index and reasons
+    pub fn evaluate(&self, facts: &ToolCallFacts) -> Option<(usize, &PolicyRule, Vec<String>)> {
+        self.rules.iter().enumerate()
+            .find_map(|(idx, rule)| rule.matches(facts).map(|reasons| (idx, rule, reasons)))
+    }
+}
+
+/// `tool_result` comes from the tool's own `ask_user` and `deny` lists
+pub fn combine_with_policy(
+    policy: &ToolPolicy,
+    facts: &ToolCallFacts,
+    tool_result: MatchConfirmDenyResult,
+    tool_rule: &str,
+) -> PolicyDecision {
+    let where_from = match &facts.integration {
+        Some(integration) => format!("{} config", integration),
+        None => format!("`{}` tool defaults", facts.tool_name),
+    };
+    if let MatchConfirmDenyResult::DENY = tool_result {
+        return PolicyDecision {
+            result: MatchConfirmDenyResult::DENY,
+            command: facts.command.to_string(),
+            rule: tool_rule.to_string(),
+            explanation: format!("`{}` matches deny rule `{}` in the {}, policy rules can't override it", facts.command, tool_rule, where_from),
+        };
+    }
+    if let Some((idx, rule, reasons)) = policy.evaluate(facts) {
+        let rule_name = if rule.name.is_empty() { format!("#{}", idx + 1) } else { rule.name.clone() };
+        let result = match rule.action {
+            PolicyAction::Allow => MatchConfirmDenyResult::PASS,
+            PolicyAction::AskUser => MatchConfirmDenyResult::CONFIRMATION,
+            PolicyAction::Deny => MatchConfirmDenyResult::DENY,
+        };
+        return PolicyDecision {
+            result,
+            command: if facts.command.is_empty() { facts.tool_name.to_string() } else { facts.command.to_string() },
+            explanation: format!("tool_policy.yaml rule {:?} says {:?}: {}", rule_name, rule.action, reasons.join(", ")),
+            rule: rule_name,
+        };
+    }
+    let explanation = match tool_result {
+        MatchConfirmDenyResult::CONFIRMATION => format!("`{}` matches ask_user rule `{}` in the {}", facts.command, tool_rule, where_from),
+        _ => "no rule in tool_policy.yaml matches, and nothing in ask_user or deny lists".to_string(),
+    };
+    PolicyDecision {
+        result: tool_result,
+        command: facts.command.to_string(),
+        rule: tool_rule.to_string(),
+        explanation,
+    }
+}
+
+async fn read_tool_policy_yaml(path: &Path) -> ToolPolicy {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => serde_yaml::from_str::<ToolPolicy>(&content).unwrap_or_else(|e| {
+            error!("parsing {} failed, no policy rules apply\n{}", path.display(), e);
+            ToolPolicy::default()
+        }),
+        Err(_) => ToolPolicy::default(),  // no file, no rules
+    }
+}
+
+pub async fn load_tool_policy_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> Arc<ToolPolicy> {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    let config_dir = {
+        let gcx_locked = gcx.read().await;
+        if gcx_locked.tool_policy.loaded_ts + POLICY_TOO_OLD.as_secs() > current_time {
+            return gcx_locked.tool_policy.clone();
+        }
+        gcx_locked.config_dir.clone()
+    };
+    let mut policy = read_tool_policy_yaml(&config_dir.join("tool_policy.yaml")).await;
+    policy.loaded_ts = current_time;
+    let mut gcx_locked = gcx.write().await;
+    gcx_locked.tool_policy = Arc::new(policy);
+    gcx_locked.tool_policy.clone()
+}
+
+/// The single place that decides whether a tool call runs, needs confirmation, or is denied
+pub async fn check_tool_call(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    tool: &dyn Tool,
+    tool_name: &str,
+    args: &HashMap<String, Value>,
+    chat_mode: ChatMode,
+) -> Result<PolicyDecision, String> {
+    let tool_result = tool.match_against_confirm_deny(ccx.clone(), args).await?;
+    let gcx = ccx.lock().await.global_context.clone();
+    let policy = load_tool_policy_if_needed(gcx.clone()).await;
+    let workspace_dirs = get_project_dirs(gcx.clone()).await;
+    let integration = tool.has_config_path()
+        .and_then(|p| Path::new(&p).file_stem().map(|s| s.to_string_lossy().to_string()));
+    let facts = ToolCallFacts {
+        tool_name,
+        integration,
+        chat_mode,
+        command: &tool_result.command,
+        args,
+        workspace_dirs: &workspace_dirs,
+    };
+    Ok(combine_with_policy(&policy, &facts, tool_result.result.clone(), &tool_result.rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const POLICY_YAML: &str = r#"
+rules:
+  - name: "rm only inside the workspace"
+    tools: ["rm"]
+    path:
+      outside_workspace: true
+    action: deny
+  - tools: ["rm"]
+    action: allow
+  - name: "edits outside src"
+    tools: ["update_textdoc"]
+    path:
+      not_matching: ["*/src/*"]
+    action: ask_user
+  - name: "no curl when exploring"
+    integrations: ["shell"]
+    chat_modes: [EXPLORE]
+    command: ["*curl*"]
+    action: deny
+"#;
+
+    fn decide(policy: &ToolPolicy, tool_name: &str, integration: Option<&str>, chat_mode: ChatMode, command: &str, args: serde_json::Value, tool_result: MatchConfirmDenyResult) -> PolicyDecision {
+        let args: HashMap<String, Value> = serde_json::from_value(args).unwrap();
+        let workspace_dirs = vec![PathBuf::from("/home/user/project")];
+        let facts = ToolCallFacts {
+            tool_name,
+            integration: integration.map(|s| s.to_string()),
+            chat_mode,
+            command,
+            args: &args,
+            workspace_dirs: &workspace_dirs,
+        };
+        combine_with_policy(policy, &facts, tool_result, "*")
+    }
+
+    #[test]
+    fn test_tool_policy_rules() {
+        let policy: ToolPolicy = serde_yaml::from_str(POLICY_YAML).unwrap();
+        use MatchConfirmDenyResult::*;
+
+        let d = decide(&policy, "rm", None, ChatMode::AGENT, "rm /etc/passwd", json!({"path": "/etc/passwd"}), CONFIRMATION);
+        assert!(matches!(d.result, DENY), "{:?}", d);
+        assert_eq!(d.rule, "rm only inside the workspace");
+        assert!(d.explanation.contains("outside the workspace"), "{}", d.explanation);
+
+        let d = decide(&policy, "rm", None, ChatMode::AGENT, "rm src/../tmp.txt", json!({"path": "src/../tmp.txt"}), CONFIRMATION);
+        assert!(matches!(d.result, PASS), "{:?}", d);
+        let d = decide(&policy, "rm", None, ChatMode::AGENT, "rm ../escape.txt", json!({"path": "../escape.txt"}), CONFIRMATION);
+        assert!(matches!(d.result, DENY), "{:?}", d);
+
+        let d = decide(&policy, "update_textdoc", None, ChatMode::AGENT, "", json!({"path": "/home/user/project/README.md"}), PASS);
+        assert!(matches!(d.result, CONFIRMATION), "{:?}", d);
+        let d = decide(&policy, "update_textdoc", None, ChatMode::AGENT, "", json!({"path": "/home/user/project/src/main.rs"}), PASS);
+        assert!(matches!(d.result, PASS), "{:?}", d);
+
+        let d = decide(&policy, "shell", Some("shell"), ChatMode::EXPLORE, "curl example.com", json!({"command": "curl example.com"}), CONFIRMATION);
+        assert!(matches!(d.result, DENY), "{:?}", d);
+        assert!(d.explanation.contains("chat mode is EXPLORE"), "{}", d.explanation);
+        let d = decide(&policy, "shell", Some("shell"), ChatMode::AGENT, "curl example.com", json!({"command": "curl example.com"}), CONFIRMATION);
+        assert!(matches!(d.result, CONFIRMATION), "{:?}", d);
+
+        // integration deny lists always win
+        let d = decide(&policy, "rm", None, ChatMode::AGENT, "rm a.txt", json!({"path": "a.txt"}), DENY);
+        assert!(matches!(d.result, DENY), "{:?}", d);
+    }
+}
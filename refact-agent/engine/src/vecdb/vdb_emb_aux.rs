@@ -81,6 +81,7 @@ pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: u
                     table.name, table.creation_time
                 );
                 conn.execute(&format!("DROP TABLE {}", table.name), [])?;
+                conn.execute(&format!("DROP TABLE IF EXISTS {}_fts", table.name), [])?;  // lexical index lives next to it
             }
             for table in tables.iter().skip(tables.len().saturating_sub(max_count)) {
                 if table.creation_time < cutoff {
@@ -89,6 +90,7 @@ pub async fn cleanup_old_emb_tables(conn: &Connection, days: usize, max_count: u
                         table.name, table.creation_time
                     );
                     conn.execute(&format!("DROP TABLE {}", table.name), [])?;
+                    conn.execute(&format!("DROP TABLE IF EXISTS {}_fts", table.name), [])?;
                 }
             }
             Ok(())
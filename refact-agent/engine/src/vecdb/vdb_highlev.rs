@@ -4,7 +4,8 @@ use std::sync::atomic::AtomicBool;
 use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use tokio::task::JoinHandle;
 use async_trait::async_trait;
-use tracing::{error, info};
+use indexmap::IndexMap;
+use tracing::{error, info, warn};
 
 use crate::background_tasks::BackgroundTasksHolder;
 use crate::caps::get_custom_embedding_api_key;
@@ -12,8 +13,9 @@ use crate::fetch_embedding;
 use crate::global_context::{CommandLine, GlobalContext};
 use crate::knowledge::{MemdbSubEvent, MemoriesDatabase};
 use crate::trajectories::try_to_download_trajectories;
+use crate::vecdb::vdb_remote::VecDbRemote;
 use crate::vecdb::vdb_sqlite::VecDBSqlite;
-use crate::vecdb::vdb_structs::{MemoRecord, MemoSearchResult, SearchResult, VecDbStatus, VecdbConstants, VecdbSearch};
+use crate::vecdb::vdb_structs::{MemoRecord, MemoSearchResult, SearchMode, SearchResult, VecDbStatus, VecdbBackend, VecdbConstants, VecdbRecord, VecdbSearch};
 use crate::vecdb::vdb_thread::{vecdb_start_background_tasks, vectorizer_enqueue_dirty_memory, vectorizer_enqueue_files, FileVectorizerService};
 
 
@@ -29,7 +31,7 @@ fn model_to_rejection_threshold(embedding_model: &str) -> f32 {
 pub struct VecDb {
     pub memdb: Arc<AMutex<MemoriesDatabase>>,
     vecdb_emb_client: Arc<AMutex<reqwest::Client>>,
-    vecdb_handler: Arc<AMutex<VecDBSqlite>>,
+    vecdb_handler: Arc<AMutex<Box<dyn VecdbBackend>>>,
     pub vectorizer_service: Arc<AMutex<FileVectorizerService>>,
     // cmdline: CommandLine,  // TODO: take from command line what's needed, don't store a copy
     constants: VecdbConstants,
@@ -168,8 +170,32 @@ impl VecDb {
         constants: VecdbConstants,
         api_key: &String
     ) -> Result<VecDb, String> {
-        let emb_table_name = crate::vecdb::vdb_emb_aux::create_emb_table_name(&vec![cmdline.workspace_folder]);
-        let handler = VecDBSqlite::init(cache_dir, &constants.embedding_model, constants.embedding_size, &emb_table_name).await?;
+        let mut http_client_builder = reqwest::Client::builder();
+        if cmdline.insecure {
+            http_client_builder = http_client_builder.danger_accept_invalid_certs(true)
+        }
+        let http_client = http_client_builder.build().unwrap();
+
+        let emb_table_name = crate::vecdb::vdb_emb_aux::create_emb_table_name(&vec![cmdline.workspace_folder.clone()]);
+        let handler: Box<dyn VecdbBackend> = if cmdline.vecdb_remote_url.is_empty() {
+            Box::new(VecDBSqlite::init(cache_dir, &constants.embedding_model, constants.embedding_size, &emb_table_name).await?)
+        } else {
+            info!("vecdb: using remote index {}, collection {:?}", cmdline.vecdb_remote_url, cmdline.vecdb_remote_collection);
+            let mut remote = VecDbRemote::new(
+                http_client.clone(),
+                &cmdline.vecdb_remote_url,
+                &cmdline.vecdb_remote_api_key,
+                &cmdline.vecdb_remote_collection,
+                &constants.embedding_model,
+                PathBuf::from(&cmdline.workspace_folder),
+            );
+            if !cmdline.vecdb_remote_lexical {
+                // code stays on this machine, so the keyword half of hybrid search needs a local index
+                let local = VecDBSqlite::init(cache_dir, &constants.embedding_model, constants.embedding_size, &emb_table_name).await?;
+                remote = remote.with_local_lexical(Box::new(local));
+            }
+            Box::new(remote)
+        };
         let vecdb_handler = Arc::new(AMutex::new(handler));
         let memdb = Arc::new(AMutex::new(MemoriesDatabase::init(config_dir, &constants, &emb_table_name, cmdline.reset_memory).await?));
 
@@ -180,11 +206,7 @@ impl VecDb {
             memdb.clone(),
         ).await));
 
-        let mut http_client_builder = reqwest::Client::builder();
-        if cmdline.insecure {
-            http_client_builder = http_client_builder.danger_accept_invalid_certs(true)
-        }
-        let vecdb_emb_client = Arc::new(AMutex::new(http_client_builder.build().unwrap()));
+        let vecdb_emb_client = Arc::new(AMutex::new(http_client));
 
         Ok(VecDb {
             memdb: memdb.clone(),
@@ -505,16 +527,37 @@ pub async fn memdb_pubsub_trigerred(
 }
 
 
-#[async_trait]
-impl VecdbSearch for VecDb {
-    async fn vecdb_search(
+// Reciprocal-rank fusion: a record scores sum(1 / (k + rank)) over the lists it appears in. Only ranks count,
+// so BM25 scores and cosine distances never need to be on the same scale. Ties keep the order of the lists.
+pub fn reciprocal_rank_fusion(lists: Vec<Vec<VecdbRecord>>, k: f32, top_n: usize) -> Vec<VecdbRecord> {
+    let mut fused: IndexMap<(PathBuf, u64, u64), (VecdbRecord, f32)> = IndexMap::new();
+    for list in lists {
+        for (rank, rec) in list.into_iter().enumerate() {
+            let score = 1.0 / (k + rank as f32 + 1.0);
+            fused.entry((rec.file_path.clone(), rec.start_line, rec.end_line))
+                .and_modify(|(_, total)| *total += score)
+                .or_insert((rec, score));
+        }
+    }
+    let mut fused = fused.into_values().collect::<Vec<_>>();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let best_score = fused.first().map(|(_, score)| *score).unwrap_or(1.0);
+    fused.into_iter().take(top_n).map(|(mut rec, score)| {
+        rec.usefulness = 25.0 + 75.0 * score / best_score;
+        rec
+    }).collect()
+}
+
+const RRF_K: f32 = 60.0;
+
+impl VecDb {
+    async fn semantic_search(
         &self,
-        query: String,
+        query: &String,
         top_n: usize,
         vecdb_scope_filter_mb: Option<String>,
         api_key: &String,
-    ) -> Result<SearchResult, String> {
-        // TODO: move out of struct, replace self with Arc
+    ) -> Result<Vec<VecdbRecord>, String> {
         let t0 = std::time::Instant::now();
         let embedding_mb = fetch_embedding::get_embedding_with_retry(
             self.vecdb_emb_client.clone(),
@@ -557,7 +600,60 @@ impl VecdbSearch for VecDb {
                 filtered_results.push(rec.clone());
             }
         }
-        results = filtered_results;
+        Ok(filtered_results)
+    }
+
+    async fn lexical_search(
+        &self,
+        query: &String,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String> {
+        memories_block_until_vectorized_from_vectorizer(self.vectorizer_service.clone(),
+                                                        5_000).await?;
+
+        let t0 = std::time::Instant::now();
+        let mut results = self.vecdb_handler.lock().await.vecdb_lexical_search(query, top_n, vecdb_scope_filter_mb).await?;
+        info!("lexical search {:?} {:.3}s, {} results", query, t0.elapsed().as_secs_f64(), results.len());
+        let results_len = results.len();
+        for (rank, rec) in results.iter_mut().enumerate() {
+            rec.usefulness = 100.0 - 75.0 * rank as f32 / results_len as f32;
+        }
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl VecdbSearch for VecDb {
+    async fn vecdb_search(
+        &self,
+        query: String,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+        api_key: &String,
+        mode: SearchMode,
+    ) -> Result<SearchResult, String> {
+        // TODO: move out of struct, replace self with Arc
+        let results = match mode {
+            SearchMode::Semantic => self.semantic_search(&query, top_n, vecdb_scope_filter_mb, api_key).await?,
+            SearchMode::Lexical => self.lexical_search(&query, top_n, vecdb_scope_filter_mb).await?,
+            SearchMode::Hybrid => {
+                let semantic = self.semantic_search(&query, top_n, vecdb_scope_filter_mb.clone(), api_key).await;
+                let lexical = self.lexical_search(&query, top_n, vecdb_scope_filter_mb).await;
+                match (semantic, lexical) {
+                    (Ok(semantic), Ok(lexical)) => reciprocal_rank_fusion(vec![semantic, lexical], RRF_K, top_n),
+                    (Ok(semantic), Err(err)) => {
+                        warn!("lexical search failed, using semantic results only: {}", err);
+                        semantic
+                    }
+                    (Err(err), Ok(lexical)) => {
+                        warn!("semantic search failed, using lexical results only: {}", err);
+                        lexical
+                    }
+                    (Err(err), Err(_)) => return Err(err),
+                }
+            }
+        };
         Ok(
             SearchResult {
                 query_text: query,
@@ -566,3 +662,36 @@ impl VecdbSearch for VecDb {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(file: &str, line: u64) -> VecdbRecord {
+        VecdbRecord {
+            vector: None,
+            file_path: PathBuf::from(file),
+            start_line: line,
+            end_line: line + 10,
+            distance: 0.0,
+            usefulness: 0.0,
+            window_text: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let semantic = vec![rec("a.rs", 0), rec("b.rs", 0), rec("c.rs", 0)];
+        let lexical = vec![rec("c.rs", 0), rec("a.rs", 0), rec("d.rs", 5)];
+        let fused = reciprocal_rank_fusion(vec![semantic, lexical], RRF_K, 10);
+        let order = fused.iter().map(|r| r.file_path.to_string_lossy().to_string()).collect::<Vec<_>>();
+        // a.rs and c.rs are in both lists, a.rs ranks higher on average
+        assert_eq!(order, vec!["a.rs", "c.rs", "b.rs", "d.rs"]);
+        assert_eq!(fused[0].usefulness, 100.0);
+        assert!(fused[1].usefulness < 100.0 && fused[1].usefulness > fused[2].usefulness);
+
+        let fused = reciprocal_rank_fusion(vec![vec![rec("a.rs", 0), rec("a.rs", 20)], vec![]], RRF_K, 1);
+        assert_eq!(fused.len(), 1);
+        assert_eq!(fused[0].start_line, 0);
+    }
+}
@@ -8,7 +8,7 @@ use tracing::{debug, error, info, warn};
 use crate::caps::get_custom_embedding_api_key;
 use crate::global_context::{CommandLine, GlobalContext};
 use crate::vecdb::vdb_highlev::VecDb;
-use crate::vecdb::vdb_structs::{VecdbConstants, VecdbSearch};
+use crate::vecdb::vdb_structs::{SearchMode, VecdbConstants, VecdbSearch};
 use crate::background_tasks::BackgroundTasksHolder;
 use tokio::sync::RwLock as ARwLock;
 
@@ -109,7 +109,7 @@ async fn vecdb_test_search(vecdb: &VecDb, api_key: &String) -> Result<(), String
     let top_n = 3;
     let filter = None;
     
-    match VecdbSearch::vecdb_search(vecdb, test_query, top_n, filter, api_key).await {
+    match VecdbSearch::vecdb_search(vecdb, test_query, top_n, filter, api_key, SearchMode::Semantic).await {
         Ok(_) => Ok(()),
         Err(e) => Err(format!("Test search failed: {}", e)),
     }
@@ -1,50 +1,341 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use async_trait::async_trait;
-use reqwest::header::CONTENT_TYPE;
-use reqwest::header::HeaderMap;
-use reqwest::header::HeaderValue;
-use serde_json::json;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::time::Duration;
 
-use crate::vecdb::vdb_structs::{SearchResult, VecdbSearch};
+use crate::vecdb::vdb_error::with_retry;
+use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecdbBackend, VecdbRecord};
 
 
-#[derive(Debug)]
-pub struct VecDbRemote {}
+// Remote index protocol, every call is a POST with a JSON body:
+//   /v1/vdb-add          {collection, embedding_model, records: [{embedding, scope, start_line, end_line, window_text?}]}
+//   /v1/vdb-remove       {collection, scopes: [...]}
+//   /v1/vdb-search       {collection, embedding_model, embedding, top_n, scope?} -> {results: [{scope, start_line, end_line, distance}]}
+//   /v1/vdb-lexical-search {collection, query, top_n, scope?} -> {results: [{scope, start_line, end_line, distance}]}, best first
+//   /v1/vdb-size         {collection} -> {size}
+//   /v1/vdb-cache-size   {embedding_model} -> {size}
+//   /v1/vdb-cache-get    {embedding_model, hashes: [...]} -> {vectors: {hash: [...]}}
+//   /v1/vdb-cache-add    {embedding_model, records: [{window_text_hash, vector}]}
+// Scopes are sent relative to the workspace folder, so people with different checkout paths share records.
+// Window texts never leave the machine unless --vecdb-remote-lexical is set, keyword search then runs on a local
+// sqlite index. With the flag, window_text is sent with the records for the server's lexical index. The texts come
+// from read_file_from_disk(), so files that privacy.yaml doesn't allow to send anywhere never get here.
+// The cache only needs hashes.
+
+pub struct VecDbRemote {
+    http_client: reqwest::Client,
+    url: String,
+    api_key: String,
+    collection: String,
+    embedding_model: String,
+    workspace_folder: PathBuf,
+    local_lexical: Option<Box<dyn VecdbBackend>>,  // None means window texts go to the server
+}
+
+#[derive(Deserialize)]
+struct RemoteRecord {
+    scope: String,
+    start_line: u64,
+    end_line: u64,
+    #[serde(default)]
+    distance: f32,
+}
+
+#[derive(Deserialize)]
+struct RemoteSearchResponse {
+    results: Vec<RemoteRecord>,
+}
+
+#[derive(Deserialize)]
+struct RemoteSizeResponse {
+    size: usize,
+}
+
+#[derive(Deserialize)]
+struct RemoteCacheGetResponse {
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl VecDbRemote {
+    pub fn new(
+        http_client: reqwest::Client,
+        url: &str,
+        api_key: &str,
+        collection: &str,
+        embedding_model: &str,
+        workspace_folder: PathBuf,
+    ) -> Self {
+        VecDbRemote {
+            http_client,
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            collection: collection.to_string(),
+            embedding_model: embedding_model.to_string(),
+            workspace_folder,
+            local_lexical: None,
+        }
+    }
+
+    pub fn with_local_lexical(mut self, local_lexical: Box<dyn VecdbBackend>) -> Self {
+        self.local_lexical = Some(local_lexical);
+        self
+    }
+
+    fn records_to_remote(&self, records: &Vec<VecdbRecord>) -> Vec<Value> {
+        records.iter().map(|r| {
+            let mut record = json!({
+                "embedding": r.vector.clone().expect("No embedding is provided"),
+                "scope": self.scope_to_remote(&r.file_path.to_string_lossy()),
+                "start_line": r.start_line,
+                "end_line": r.end_line,
+            });
+            if self.local_lexical.is_none() && !r.window_text.is_empty() {
+                record["window_text"] = Value::String(r.window_text.clone());
+            }
+            record
+        }).collect()
+    }
+
+    fn scope_to_remote(&self, scope: &str) -> String {
+        if self.workspace_folder.as_os_str().is_empty() {
+            return scope.to_string();
+        }
+        match PathBuf::from(scope).strip_prefix(&self.workspace_folder) {
+            Ok(relative) => relative.to_string_lossy().replace("\\", "/"),
+            Err(_) => scope.to_string(),
+        }
+    }
+
+    fn scope_from_remote(&self, scope: &str) -> PathBuf {
+        let path = PathBuf::from(scope);
+        if path.is_absolute() || self.workspace_folder.as_os_str().is_empty() {
+            return path;
+        }
+        self.workspace_folder.join(path)
+    }
+
+    async fn post(&self, endpoint: &str, body: Value) -> Result<Value, String> {
+        let url = format!("{}{}", self.url, endpoint);
+        with_retry(
+            || {
+                let mut request = self.http_client.post(&url)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.to_string());
+                if !self.api_key.is_empty() {
+                    request = request.header(AUTHORIZATION, format!("Bearer {}", self.api_key));
+                }
+                let url = url.clone();
+                async move {
+                    let response = request.send().await.map_err(|e| format!("HTTP error: {}", e))?;
+                    let status = response.status();
+                    let text = response.text().await.map_err(|e| format!("HTTP error: {}", e))?;
+                    if !status.is_success() {
+                        return Err(format!("{} returned {}: {}", url, status, text));
+                    }
+                    if text.trim().is_empty() {
+                        return Ok(Value::Null);
+                    }
+                    serde_json::from_str::<Value>(&text).map_err(|e| format!("vecdb JSON problem: {}", e))
+                }
+            },
+            3,
+            Duration::from_millis(100),
+            endpoint,
+        ).await
+    }
+
+    fn records_from_response(&self, response: Value) -> Result<Vec<VecdbRecord>, String> {
+        let response: RemoteSearchResponse = serde_json::from_value(response)
+            .map_err(|e| format!("vecdb search response problem: {}", e))?;
+        Ok(response.results.into_iter().map(|r| VecdbRecord {
+            vector: None,
+            file_path: self.scope_from_remote(&r.scope),
+            start_line: r.start_line,
+            end_line: r.end_line,
+            distance: r.distance,
+            usefulness: 0.0,
+            window_text: String::new(),
+        }).collect())
+    }
+
+    async fn cache_get(&self, hashes: Vec<String>) -> Result<HashMap<String, Vec<f32>>, String> {
+        if hashes.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let response = self.post("/v1/vdb-cache-get", json!({
+            "embedding_model": self.embedding_model,
+            "hashes": hashes,
+        })).await?;
+        serde_json::from_value::<RemoteCacheGetResponse>(response)
+            .map(|r| r.vectors)
+            .map_err(|e| format!("vecdb cache response problem: {}", e))
+    }
+}
 
 #[async_trait]
-impl VecdbSearch for VecDbRemote {
+impl VecdbBackend for VecDbRemote {
+    async fn vecdb_records_add(&mut self, records: &Vec<VecdbRecord>) -> Result<(), String> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        if let Some(local_lexical) = self.local_lexical.as_mut() {
+            local_lexical.vecdb_records_add(records).await?;
+        }
+        self.post("/v1/vdb-add", json!({
+            "collection": self.collection,
+            "embedding_model": self.embedding_model,
+            "records": self.records_to_remote(records),
+        })).await?;
+        Ok(())
+    }
+
+    async fn vecdb_records_remove(&mut self, scopes_to_remove: Vec<String>) -> Result<(), String> {
+        if scopes_to_remove.is_empty() {
+            return Ok(());
+        }
+        if let Some(local_lexical) = self.local_lexical.as_mut() {
+            local_lexical.vecdb_records_remove(scopes_to_remove.clone()).await?;
+        }
+        let scopes = scopes_to_remove.iter().map(|s| self.scope_to_remote(s)).collect::<Vec<_>>();
+        self.post("/v1/vdb-remove", json!({
+            "collection": self.collection,
+            "scopes": scopes,
+        })).await?;
+        Ok(())
+    }
+
     async fn vecdb_search(
-        &self,
-        query: String,
+        &mut self,
+        embedding: &Vec<f32>,
         top_n: usize,
-        _vecdb_scope_filter_mb: Option<String>,
-        _api_key: &String,
-    ) -> Result<SearchResult, String> {
-        // NOTE: if you're going to use https make sure that you set insecure flag from cmdline
-        let url = "http://127.0.0.1:8008/v1/vdb-search".to_string();
-        let mut headers = HeaderMap::new();
-        // headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", self.token)).unwrap());
-        headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
-        let body = json!({
-            "text": query,
-            "top_n": top_n
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String> {
+        let mut body = json!({
+            "collection": self.collection,
+            "embedding_model": self.embedding_model,
+            "embedding": embedding,
+            "top_n": top_n,
         });
-        let res = reqwest::Client::new()
-            .post(&url)
-            .headers(headers)
-            .body(body.to_string())
-            .send()
-            .await.map_err(|e| format!("Vecdb search HTTP error (1): {}", e))?;
-
-        let body = res.text().await.map_err(|e| format!("Vecdb search HTTP error (2): {}", e))?;
-        // info!("Vecdb search result: {:?}", &body);
-        let result: Vec<SearchResult> = serde_json::from_str(&body).map_err(|e| {
-            format!("vecdb JSON problem: {}", e)
-        })?;
-        if result.len() == 0 {
-            return Err("Vecdb search result is empty".to_string());
-        }
-        let result0 = result[0].clone();
-        // info!("Vecdb search result: {:?}", &result0);
-        Ok(result0)
+        if let Some(scope) = vecdb_scope_filter_mb {
+            body["scope"] = Value::String(self.scope_to_remote(&scope));
+        }
+        let response = self.post("/v1/vdb-search", body).await?;
+        self.records_from_response(response)
+    }
+
+    async fn vecdb_lexical_search(
+        &mut self,
+        query: &str,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String> {
+        if let Some(local_lexical) = self.local_lexical.as_mut() {
+            return local_lexical.vecdb_lexical_search(query, top_n, vecdb_scope_filter_mb).await;
+        }
+        let mut body = json!({
+            "collection": self.collection,
+            "query": query,
+            "top_n": top_n,
+        });
+        if let Some(scope) = vecdb_scope_filter_mb {
+            body["scope"] = Value::String(self.scope_to_remote(&scope));
+        }
+        let response = self.post("/v1/vdb-lexical-search", body).await?;
+        self.records_from_response(response)
+    }
+
+    async fn size(&self) -> Result<usize, String> {
+        let response = self.post("/v1/vdb-size", json!({"collection": self.collection})).await?;
+        serde_json::from_value::<RemoteSizeResponse>(response)
+            .map(|r| r.size)
+            .map_err(|e| format!("vecdb size response problem: {}", e))
+    }
+
+    async fn cache_size(&self) -> Result<usize, String> {
+        let response = self.post("/v1/vdb-cache-size", json!({"embedding_model": self.embedding_model})).await?;
+        serde_json::from_value::<RemoteSizeResponse>(response)
+            .map(|r| r.size)
+            .map_err(|e| format!("vecdb cache size response problem: {}", e))
+    }
+
+    async fn fetch_vectors_from_cache(&mut self, splits: &Vec<SplitResult>) -> Result<Vec<Option<Vec<f32>>>, String> {
+        let hashes = splits.iter().map(|x| x.window_text_hash.clone()).collect::<Vec<_>>();
+        let found = self.cache_get(hashes).await?;
+        Ok(splits.iter().map(|x| found.get(&x.window_text_hash).cloned()).collect())
+    }
+
+    async fn cache_add_new_records(&mut self, records: Vec<SimpleTextHashVector>) -> Result<(), String> {
+        let records_json = records.into_iter()
+            .filter_map(|r| r.vector.map(|vector| json!({
+                "window_text_hash": r.window_text_hash,
+                "vector": vector,
+            })))
+            .collect::<Vec<_>>();
+        if records_json.is_empty() {
+            return Ok(());
+        }
+        self.post("/v1/vdb-cache-add", json!({
+            "embedding_model": self.embedding_model,
+            "records": records_json,
+        })).await?;
+        Ok(())
+    }
+
+    async fn process_simple_hash_text_vector(&mut self, v: &mut Vec<SimpleTextHashVector>) -> Result<(), String> {
+        let hashes = v.iter().map(|x| x.window_text_hash.clone()).collect::<Vec<_>>();
+        let found = self.cache_get(hashes).await?;
+        for save in v.iter_mut() {
+            if let Some(vector) = found.get(&save.window_text_hash) {
+                save.vector = Some(vector.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vecdb::vdb_sqlite::VecDBSqlite;
+
+    #[test]
+    fn test_scopes_are_relative_to_workspace() {
+        let remote = VecDbRemote::new(
+            reqwest::Client::new(), "http://127.0.0.1:8008/", "", "default", "thenlper/gte-base",
+            PathBuf::from("/home/alice/monorepo"),
+        );
+        assert_eq!(remote.url, "http://127.0.0.1:8008");
+        assert_eq!(remote.scope_to_remote("/home/alice/monorepo/svc/main.go"), "svc/main.go");
+        assert_eq!(remote.scope_to_remote("/tmp/outside.py"), "/tmp/outside.py");
+        assert_eq!(remote.scope_from_remote("svc/main.go"), PathBuf::from("/home/alice/monorepo/svc/main.go"));
+        assert_eq!(remote.scope_from_remote("/tmp/outside.py"), PathBuf::from("/tmp/outside.py"));
+    }
+
+    #[tokio::test]
+    async fn test_window_texts_stay_local_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = VecdbRecord {
+            vector: Some(vec![0.0; 4]),
+            file_path: PathBuf::from("/home/alice/monorepo/svc/main.go"),
+            start_line: 1,
+            end_line: 2,
+            distance: 0.0,
+            usefulness: 0.0,
+            window_text: "func main() {}".to_string(),
+        };
+        let remote = VecDbRemote::new(
+            reqwest::Client::new(), "http://127.0.0.1:8008/", "", "default", "thenlper/gte-base",
+            PathBuf::from("/home/alice/monorepo"),
+        );
+        assert_eq!(remote.records_to_remote(&vec![record.clone()])[0]["window_text"], "func main() {}");
+
+        let local = VecDBSqlite::init(&dir.path().to_path_buf(), &"thenlper/gte-base".to_string(), 4, &"emb_test".to_string()).await.unwrap();
+        let remote = remote.with_local_lexical(Box::new(local));
+        let records_json = remote.records_to_remote(&vec![record]);
+        assert!(records_json[0].get("window_text").is_none());
+        assert_eq!(records_json[0]["scope"], "svc/main.go");
     }
 }
@@ -1,3 +1,4 @@
+use async_trait::async_trait;
 use rusqlite::{OpenFlags, Result};
 use std::any::Any;
 use std::collections::HashMap;
@@ -8,7 +9,7 @@ use tokio_rusqlite::Connection;
 use tracing::info;
 use zerocopy::IntoBytes;
 
-use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecdbRecord};
+use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecdbBackend, VecdbRecord};
 
 
 impl Debug for VecDBSqlite {
@@ -112,6 +113,28 @@ async fn migrate_202501(conn: &Connection, embedding_size: i32, emb_table_name:
     }).await
 }
 
+async fn migrate_202507(conn: &Connection, emb_table_name: String) -> tokio_rusqlite::Result<()> {
+    conn.call(move |conn| {
+        conn.execute(&format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {emb_table_name}_fts using fts5(
+              window_text,
+              scope UNINDEXED,
+              start_line UNINDEXED,
+              end_line UNINDEXED
+            );"), [])?;
+        Ok(())
+    }).await
+}
+
+// Every word becomes a quoted phrase, so `foo.bar(` or `E0432` can't be taken for FTS5 syntax,
+// any of them matching is enough, bm25() puts windows that have more of them first
+fn fts5_match_query(query: &str) -> String {
+    query.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace("\"", "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
 impl VecDBSqlite {
     pub async fn init(cache_dir: &PathBuf, model_name: &String, embedding_size: i32, emb_table_name: &String) -> Result<VecDBSqlite, String> {
         let db_path = get_db_path(cache_dir, model_name, embedding_size).await?;
@@ -129,13 +152,17 @@ impl VecDBSqlite {
         }).await.map_err(|e| e.to_string())?;
         migrate_202406(&conn).await.map_err(|e| e.to_string())?;
         migrate_202501(&conn, embedding_size, emb_table_name.clone()).await.map_err(|e| e.to_string())?;
+        migrate_202507(&conn, emb_table_name.clone()).await.map_err(|e| e.to_string())?;
         crate::vecdb::vdb_emb_aux::cleanup_old_emb_tables(&conn, 7, 10).await?;
 
         info!("vecdb initialized");
         Ok(VecDBSqlite { conn, emb_table_name: emb_table_name.clone() })
     }
+}
 
-    pub async fn process_simple_hash_text_vector(
+#[async_trait]
+impl VecdbBackend for VecDBSqlite {
+    async fn process_simple_hash_text_vector(
         &mut self,
         v: &mut Vec<SimpleTextHashVector>,
     ) -> Result<(), String> {
@@ -167,7 +194,7 @@ impl VecDBSqlite {
         Ok(())
     }
 
-    pub async fn fetch_vectors_from_cache(&mut self, splits: &Vec<SplitResult>) -> Result<Vec<Option<Vec<f32>>>, String> {
+    async fn fetch_vectors_from_cache(&mut self, splits: &Vec<SplitResult>) -> Result<Vec<Option<Vec<f32>>>, String> {
         let placeholders: String = splits.iter().map(|_| "?").collect::<Vec<&str>>().join(",");
         let query = format!("SELECT * FROM embeddings_cache WHERE window_text_hash IN ({placeholders})");
         let splits_clone = splits.clone();
@@ -207,7 +234,7 @@ impl VecDBSqlite {
         Ok(records)
     }
 
-    pub async fn cache_add_new_records(&mut self, records: Vec<SimpleTextHashVector>) -> Result<(), String> {
+    async fn cache_add_new_records(&mut self, records: Vec<SimpleTextHashVector>) -> Result<(), String> {
         self.conn.call(|connection| {
             let transaction = connection.transaction()?;
             for record in records {
@@ -243,7 +270,7 @@ impl VecDBSqlite {
         }).await.map_err(|e| e.to_string())
     }
 
-    pub async fn cache_size(&self) -> Result<usize, String> {
+    async fn cache_size(&self) -> Result<usize, String> {
         self.conn.call(move |connection| {
             let mut stmt = connection.prepare(
                 &format!("SELECT COUNT(1) FROM embeddings_cache")
@@ -253,7 +280,7 @@ impl VecDBSqlite {
         }).await.map_err(|e| e.to_string())
     }
 
-    pub async fn size(&self) -> Result<usize, String> {
+    async fn size(&self) -> Result<usize, String> {
         let emb_table_name = self.emb_table_name.clone();
         self.conn.call(move |connection| {
             let mut stmt = connection.prepare(
@@ -264,7 +291,7 @@ impl VecDBSqlite {
         }).await.map_err(|e| e.to_string())
     }
 
-    pub async fn vecdb_records_add(&mut self, records: &Vec<VecdbRecord>) -> Result<(), String> {
+    async fn vecdb_records_add(&mut self, records: &Vec<VecdbRecord>) -> Result<(), String> {
         use crate::vecdb::vdb_error::with_retry;
         use tokio::time::Duration;
         
@@ -285,6 +312,10 @@ impl VecDBSqlite {
                             "INSERT INTO {}(embedding, scope, start_line, end_line) VALUES (?, ?, ?, ?)", emb_table_name
                         ))?;
                         
+                        let mut stmt_fts = tx.prepare(&format!(
+                            "INSERT INTO {}_fts(window_text, scope, start_line, end_line) VALUES (?, ?, ?, ?)", emb_table_name
+                        ))?;
+
                         for item in records_owned.iter() {
                             stmt.execute(rusqlite::params![
                                 item.vector.clone().expect("No embedding is provided").as_bytes(),
@@ -292,6 +323,14 @@ impl VecDBSqlite {
                                 item.start_line,
                                 item.end_line
                             ])?;
+                            if !item.window_text.is_empty() {
+                                stmt_fts.execute(rusqlite::params![
+                                    item.window_text,
+                                    item.file_path.to_string_lossy().to_string(),
+                                    item.start_line,
+                                    item.end_line
+                                ])?;
+                            }
                         }
                     }
                     
@@ -306,7 +345,7 @@ impl VecDBSqlite {
         ).await
     }
 
-    pub async fn vecdb_search(
+    async fn vecdb_search(
         &mut self,
         embedding: &Vec<f32>,
         top_n: usize,
@@ -369,6 +408,7 @@ impl VecDBSqlite {
                                 end_line: row.get(2)?,
                                 distance: row.get(4)?,
                                 usefulness: 0.0,
+                                window_text: String::new(),
                             })
                         },
                     )?;
@@ -387,7 +427,84 @@ impl VecDBSqlite {
         ).await
     }
 
-    pub async fn vecdb_records_remove(
+    async fn vecdb_lexical_search(
+        &mut self,
+        query: &str,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String> {
+        use crate::vecdb::vdb_error::with_retry;
+        use tokio::time::Duration;
+
+        let match_query = fts5_match_query(query);
+        if match_query.is_empty() {
+            return Ok(vec![]);
+        }
+        let scope_condition = vecdb_scope_filter_mb
+            .clone()
+            .map(|_| format!("AND scope = ?"))
+            .unwrap_or_else(String::new);
+        let emb_table_name = self.emb_table_name.clone();
+
+        with_retry(
+            || {
+                let match_query = match_query.clone();
+                let emb_table_name = emb_table_name.clone();
+                let scope_condition = scope_condition.clone();
+                let vecdb_scope_filter_mb = vecdb_scope_filter_mb.clone();
+
+                self.conn.call(move |connection| {
+                    let mut stmt = connection.prepare(&format!(
+                        r#"
+                        SELECT
+                            scope,
+                            start_line,
+                            end_line,
+                            bm25({0}_fts) AS rank
+                        FROM {0}_fts
+                        WHERE {0}_fts MATCH ?
+                            {1}
+                        ORDER BY rank
+                        LIMIT ?
+                        "#,
+                        emb_table_name, scope_condition
+                    ))?;
+
+                    let params = match &vecdb_scope_filter_mb {
+                        Some(scope) => rusqlite::params![match_query, scope.clone(), top_n],
+                        None => rusqlite::params![match_query, top_n],
+                    };
+
+                    let rows = stmt.query_map(
+                        params,
+                        |row| {
+                            Ok(VecdbRecord {
+                                vector: None,
+                                file_path: PathBuf::from(row.get::<_, String>(0)?),
+                                start_line: row.get(1)?,
+                                end_line: row.get(2)?,
+                                distance: row.get::<_, f64>(3)? as f32,
+                                usefulness: 0.0,
+                                window_text: String::new(),
+                            })
+                        },
+                    )?;
+
+                    let mut results = Vec::new();
+                    for row in rows {
+                        results.push(row?);
+                    }
+
+                    Ok(results)
+                })
+            },
+            3, // Max retries
+            Duration::from_millis(100), // Retry delay
+            "lexical search"
+        ).await
+    }
+
+    async fn vecdb_records_remove(
         &mut self,
         scopes_to_remove: Vec<String>,
     ) -> Result<(), String> {
@@ -420,6 +537,11 @@ impl VecDBSqlite {
                         )?;
 
                         stmt.execute(rusqlite::params_from_iter(scopes_to_remove.iter()))?;
+
+                        let mut stmt_fts = tx.prepare(
+                            &format!("DELETE FROM {}_fts WHERE scope IN ({})", emb_table_name, placeholders)
+                        )?;
+                        stmt_fts.execute(rusqlite::params_from_iter(scopes_to_remove.iter()))?;
                     }
                     
                     // Commit the transaction
@@ -433,3 +555,17 @@ impl VecDBSqlite {
         ).await
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts5_match_query() {
+        assert_eq!(fts5_match_query("E0432 unresolved"), "\"E0432\" OR \"unresolved\"");
+        assert_eq!(fts5_match_query("  max_tokens:  "), "\"max_tokens:\"");
+        assert_eq!(fts5_match_query("say \"hi\""), "\"say\" OR \"\"\"hi\"\"\"");
+        assert_eq!(fts5_match_query("   "), "");
+    }
+}
@@ -16,9 +16,69 @@ pub trait VecdbSearch: Send {
         top_n: usize,
         filter_mb: Option<String>,
         api_key: &String,
+        mode: SearchMode,
     ) -> Result<SearchResult, String>;
 }
 
+// Embedding distance misses exact identifiers, error codes and config keys, BM25 over the same windows catches those.
+// Hybrid runs both and fuses the rankings.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    Semantic,
+    Lexical,
+    #[default]
+    Hybrid,
+}
+
+impl std::str::FromStr for SearchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "semantic" => Ok(SearchMode::Semantic),
+            "lexical" => Ok(SearchMode::Lexical),
+            "hybrid" | "" => Ok(SearchMode::Hybrid),
+            _ => Err(format!("unknown search mode {:?}, use \"semantic\", \"lexical\" or \"hybrid\"", s)),
+        }
+    }
+}
+
+// Where the vectors live: a local sqlite-vec file or a remote index shared by the team.
+// Scope is the file path a record came from, removing a file removes its scope.
+#[async_trait]
+pub trait VecdbBackend: Send + Sync {
+    async fn vecdb_records_add(&mut self, records: &Vec<VecdbRecord>) -> Result<(), String>;
+
+    async fn vecdb_records_remove(&mut self, scopes_to_remove: Vec<String>) -> Result<(), String>;
+
+    async fn vecdb_search(
+        &mut self,
+        embedding: &Vec<f32>,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String>;
+
+    // BM25 over window texts, best first, distance is the backend's own score where lower is better
+    async fn vecdb_lexical_search(
+        &mut self,
+        query: &str,
+        top_n: usize,
+        vecdb_scope_filter_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String>;
+
+    async fn size(&self) -> Result<usize, String>;
+
+    // Cache maps window_text_hash -> vector, so unchanged splits are never embedded twice
+    async fn cache_size(&self) -> Result<usize, String>;
+
+    async fn fetch_vectors_from_cache(&mut self, splits: &Vec<SplitResult>) -> Result<Vec<Option<Vec<f32>>>, String>;
+
+    async fn cache_add_new_records(&mut self, records: Vec<SimpleTextHashVector>) -> Result<(), String>;
+
+    async fn process_simple_hash_text_vector(&mut self, v: &mut Vec<SimpleTextHashVector>) -> Result<(), String>;
+}
+
 #[derive(Debug, Clone)]
 pub struct VecdbConstants {
     // constant in a sense it cannot be changed without creating a new db
@@ -56,6 +116,8 @@ pub struct VecdbRecord {
     pub end_line: u64,
     pub distance: f32,
     pub usefulness: f32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub window_text: String,  // only set on the way in, to build the lexical index
 }
 
 #[derive(Debug, Clone)]
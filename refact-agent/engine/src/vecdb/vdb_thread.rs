@@ -15,8 +15,7 @@ use crate::fetch_embedding::get_embedding_with_retry;
 use crate::files_in_workspace::{is_path_to_enqueue_valid, Document};
 use crate::global_context::GlobalContext;
 use crate::knowledge::{vectorize_dirty_memories, MemoriesDatabase};
-use crate::vecdb::vdb_sqlite::VecDBSqlite;
-use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecDbStatus, VecdbConstants, VecdbRecord};
+use crate::vecdb::vdb_structs::{SimpleTextHashVector, SplitResult, VecDbStatus, VecdbBackend, VecdbConstants, VecdbRecord};
 
 const DEBUG_WRITE_VECDB_FILES: bool = false;
 const COOLDOWN_SECONDS: u64 = 10;
@@ -29,7 +28,7 @@ enum MessageToVecdbThread {
 }
 
 pub struct FileVectorizerService {
-    pub vecdb_handler: Arc<AMutex<VecDBSqlite>>,
+    pub vecdb_handler: Arc<AMutex<Box<dyn VecdbBackend>>>,
     pub vstatus: Arc<AMutex<VecDbStatus>>,
     pub vstatus_notify: Arc<ANotify>,   // fun stuff https://docs.rs/tokio/latest/tokio/sync/struct.Notify.html
     constants: VecdbConstants,
@@ -45,7 +44,7 @@ async fn vectorize_batch_from_q(
     client: Arc<AMutex<reqwest::Client>>,
     constants: &VecdbConstants,
     api_key: &String,
-    vecdb_handler_arc: Arc<AMutex<VecDBSqlite>>,
+    vecdb_handler_arc: Arc<AMutex<Box<dyn VecdbBackend>>>,
     #[allow(non_snake_case)]
     B: usize,
 ) -> Result<(), String> {
@@ -93,6 +92,7 @@ async fn vectorize_batch_from_q(
                 end_line: data_res.end_line,
                 distance: -1.0,
                 usefulness: 0.0,
+                window_text: data_res.window_text.clone(),
             }
         );
         send_to_cache.push(
@@ -122,7 +122,7 @@ async fn from_splits_to_vecdb_records_applying_cache(
     splits: &mut Vec<SplitResult>,
     ready_to_vecdb: &mut Vec<VecdbRecord>,
     run_actual_model_on_these: &mut Vec<SplitResult>,
-    vecdb_handler_arc: Arc<AMutex<VecDBSqlite>>,
+    vecdb_handler_arc: Arc<AMutex<Box<dyn VecdbBackend>>>,
     group_size: usize,
 ) {
     while !splits.is_empty() {
@@ -145,6 +145,7 @@ async fn from_splits_to_vecdb_records_applying_cache(
                     end_line: split.end_line,
                     distance: -1.0,
                     usefulness: 0.0,
+                    window_text: split.window_text.clone(),
                 });
             }
         } else if let Err(err) = vectors_maybe {
@@ -394,7 +395,7 @@ async fn vectorize_thread(
 }
 
 async fn _send_to_vecdb(
-    vecdb_handler_arc: Arc<AMutex<VecDBSqlite>>,
+    vecdb_handler_arc: Arc<AMutex<Box<dyn VecdbBackend>>>,
     ready_to_vecdb: &mut Vec<VecdbRecord>,
 ) {
     while !ready_to_vecdb.is_empty() {
@@ -422,7 +423,7 @@ async fn _send_to_vecdb(
 
 impl FileVectorizerService {
     pub async fn new(
-        vecdb_handler: Arc<AMutex<VecDBSqlite>>,
+        vecdb_handler: Arc<AMutex<Box<dyn VecdbBackend>>>,
         constants: VecdbConstants,
         api_key: String,
         memdb: Arc<AMutex<MemoriesDatabase>>,
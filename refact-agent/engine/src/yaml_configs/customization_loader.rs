@@ -6,6 +6,8 @@ use indexmap::IndexMap;
 use tokio::sync::RwLock as ARwLock;
 
 use crate::call_validation::{ChatMessage, SubchatParameters};
+use crate::git::shadow_gc::CheckpointsRetention;
+use crate::tools::tools_limits::ToolBudget;
 use crate::global_context::{GlobalContext, try_load_caps_quickly_if_not_present};
 use crate::integrations::setting_up_integrations::YamlError;
 
@@ -20,6 +22,14 @@ pub struct CustomizationYaml {
     pub toolbox_commands: IndexMap<String, ToolboxCommand>,
     #[serde(default)]
     pub code_lens: IndexMap<String, CodeLensCommand>,
+    #[serde(default)]
+    pub checkpoints_retention: Option<CheckpointsRetention>,
+    #[serde(default)]
+    pub edit_syntax_check: IndexMap<String, bool>,
+    #[serde(default)]
+    pub tool_timeouts: IndexMap<String, u64>,
+    #[serde(default)]
+    pub tool_budget: Option<ToolBudget>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -170,10 +180,27 @@ pub fn load_and_mix_with_users_config(
     work_config.system_prompts.extend(caps_config.system_prompts.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.toolbox_commands.extend(caps_config.toolbox_commands.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.code_lens.extend(caps_config.code_lens.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.edit_syntax_check.extend(caps_config.edit_syntax_check.iter().map(|(k, v)| (k.clone(), *v)));
+    work_config.tool_timeouts.extend(caps_config.tool_timeouts.iter().map(|(k, v)| (k.clone(), *v)));
 
     work_config.system_prompts.extend(user_config.system_prompts.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.toolbox_commands.extend(user_config.toolbox_commands.iter().map(|(k, v)| (k.clone(), v.clone())));
     work_config.code_lens.extend(user_config.code_lens.iter().map(|(k, v)| (k.clone(), v.clone())));
+    work_config.edit_syntax_check.extend(user_config.edit_syntax_check.iter().map(|(k, v)| (k.clone(), *v)));
+    work_config.tool_timeouts.extend(user_config.tool_timeouts.iter().map(|(k, v)| (k.clone(), *v)));
+
+    if caps_config.checkpoints_retention.is_some() {
+        work_config.checkpoints_retention = caps_config.checkpoints_retention.clone();
+    }
+    if user_config.checkpoints_retention.is_some() {
+        work_config.checkpoints_retention = user_config.checkpoints_retention.take();
+    }
+    if caps_config.tool_budget.is_some() {
+        work_config.tool_budget = caps_config.tool_budget.clone();
+    }
+    if user_config.tool_budget.is_some() {
+        work_config.tool_budget = user_config.tool_budget.take();
+    }
 
     let filtered_system_prompts = work_config.system_prompts
         .iter()
@@ -261,4 +288,41 @@ mod tests {
         assert_eq!(config.system_prompts.get("configurator").is_some(), true);
         assert_eq!(config.system_prompts.get("project_summary").is_some(), true);
     }
+
+    #[test]
+    fn user_checkpoints_retention_overrides_default() {
+        let mut error_log = Vec::new();
+        let config = load_and_mix_with_users_config("", "", true, true, &mut error_log);
+        assert_eq!(config.checkpoints_retention.unwrap().max_chats, CheckpointsRetention::default().max_chats);
+
+        let user_yaml = "checkpoints_retention:\n  max_age_days: 7\n";
+        let config = load_and_mix_with_users_config(user_yaml, "", true, true, &mut error_log);
+        assert!(error_log.is_empty());
+        let retention = config.checkpoints_retention.unwrap();
+        assert_eq!(retention.max_age_days, 7);
+        assert_eq!(retention.max_total_size_mb, CheckpointsRetention::default().max_total_size_mb);
+    }
+
+    #[test]
+    fn user_can_turn_off_syntax_check_per_tool() {
+        let mut error_log = Vec::new();
+        let user_yaml = "edit_syntax_check:\n  update_textdoc_regex: false\n";
+        let config = load_and_mix_with_users_config(user_yaml, "", true, true, &mut error_log);
+        assert!(error_log.is_empty());
+        assert_eq!(config.edit_syntax_check.get("update_textdoc_regex"), Some(&false));
+        assert_eq!(config.edit_syntax_check.get("update_textdoc"), Some(&true));
+    }
+
+    #[test]
+    fn user_tool_timeouts_and_budget_override_default() {
+        let mut error_log = Vec::new();
+        let user_yaml = "tool_timeouts:\n  web: 10\ntool_budget:\n  tokens: 50000\n";
+        let config = load_and_mix_with_users_config(user_yaml, "", true, true, &mut error_log);
+        assert!(error_log.is_empty());
+        assert_eq!(config.tool_timeouts.get("web"), Some(&10));
+        assert_eq!(config.tool_timeouts.get("default"), Some(&crate::tools::tools_limits::DEFAULT_TOOL_TIMEOUT_SECS));
+        let budget = config.tool_budget.unwrap();
+        assert_eq!(budget.tokens, 50000);
+        assert_eq!(budget.wall_clock_seconds, 0);
+    }
 }